@@ -0,0 +1,109 @@
+//! A stand-in for Neovim answering RPC requests from a scenario file, to
+//! test clients end to end without installing Neovim
+//!
+//! Listening on a socket, fake-nvim prints `Listening on ADDR` once ready,
+//! so a test can start it on port 0 and read the address it was given.
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::net::TcpListener;
+use std::process::exit;
+use std::sync::Arc;
+use std::thread;
+
+mod scenario;
+mod server;
+
+use scenario::Scenario;
+use server::Server;
+
+const USAGE: &str = "\
+Usage: fake-nvim [--listen ADDR | --embed] [--scenario FILE]
+
+Answers MessagePack-RPC requests like Neovim, with the responses scripted
+by the scenario FILE. See fake-nvim/src/scenario.rs for its format.
+
+--listen ADDR    listen on ADDR, a host:port or, on Unix, a socket path
+                 (by default 127.0.0.1:0, an unused port)
+--embed          serve a single client over stdin and stdout
+--scenario FILE  read the scripted calls from FILE";
+
+fn main() {
+    let mut listen = "127.0.0.1:0".to_string();
+    let mut embed = false;
+    let mut scenario = Scenario::default();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listen" => listen = args.next().unwrap_or_else(|| usage()),
+            "--embed" => embed = true,
+            "--scenario" => {
+                let path = args.next().unwrap_or_else(|| usage());
+                let text = fs::read_to_string(&path)
+                    .unwrap_or_else(|error| fail(&format!("Couldn't read {}: {}", path, error)));
+                scenario = Scenario::parse(&text)
+                    .unwrap_or_else(|error| fail(&format!("{}: {}", path, error)));
+            }
+            _ => usage(),
+        }
+    }
+
+    let server = Arc::new(Server::new(scenario));
+    if embed {
+        if let Err(error) = server.serve(io::stdin(), io::stdout()) {
+            fail(&error.to_string());
+        }
+        return;
+    }
+    // Neovim treats an address with a slash as a socket path
+    #[cfg(unix)]
+    if listen.contains('/') {
+        let listener = std::os::unix::net::UnixListener::bind(&listen)
+            .unwrap_or_else(|error| fail(&format!("Couldn't listen on {}: {}", listen, error)));
+        announce(&listen);
+        for stream in listener.incoming().flatten() {
+            spawn(&server, stream.try_clone(), stream);
+        }
+        return;
+    }
+    let listener = TcpListener::bind(&listen)
+        .unwrap_or_else(|error| fail(&format!("Couldn't listen on {}: {}", listen, error)));
+    let addr = listener.local_addr().map(|addr| addr.to_string());
+    announce(&addr.unwrap_or(listen));
+    for stream in listener.incoming().flatten() {
+        spawn(&server, stream.try_clone(), stream);
+    }
+}
+
+/// Print the address clients can connect to
+fn announce(addr: &str) {
+    println!("Listening on {}", addr);
+    let _ = io::stdout().flush();
+}
+
+/// Serve a connection on its own thread
+fn spawn<S>(server: &Arc<Server>, reader: io::Result<S>, writer: S)
+where
+    S: io::Read + Write + Send + 'static,
+{
+    let reader = match reader {
+        Ok(reader) => reader,
+        Err(error) => return eprintln!("Couldn't accept a connection: {}", error),
+    };
+    let server = server.clone();
+    thread::spawn(move || {
+        if let Err(error) = server.serve(reader, writer) {
+            eprintln!("{}", error);
+        }
+    });
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    exit(2);
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("fake-nvim: {}", message);
+    exit(1);
+}
@@ -0,0 +1,181 @@
+//! Scenario files scripting the responses of fake-nvim
+//!
+//! Scenarios are TOML files with a `[server]` table and a `[[call]]` table
+//! for each scripted call. Arguments, results and notification parameters
+//! may be any TOML value, and are sent as the equivalent MessagePack.
+//!
+//! ```toml
+//! [server]
+//! channel = 3
+//! version = [0, 10, 4]
+//! api_level = 12
+//!
+//! # Answer `nvim_eval("1 + 1")` with 2
+//! [[call]]
+//! method = "nvim_eval"
+//! args = ["1 + 1"]
+//! result = 2
+//!
+//! # Fail the first `nvim_command` after 100ms, then close the connection
+//! [[call]]
+//! method = "nvim_command"
+//! arity = 1
+//! error = "E492: Not an editor command"
+//! delay_ms = 100
+//! times = 1
+//! disconnect = true
+//!
+//! # Notify the client after answering
+//! [[call]]
+//! method = "nvim_subscribe"
+//! args = ["tick"]
+//! notify = "tick"
+//! notify_params = [1, { source = "fake" }]
+//! ```
+//!
+//! Scripted calls are matched in order, before the built-in methods. A
+//! call without `args` matches any arguments, and one without `times`
+//! matches any number of times.
+use rmpv::Value;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// A scenario read from a file
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scenario {
+    /// The channel ID reported by `nvim_get_api_info`
+    pub channel: i64,
+    /// The major, minor and patch version reported by `nvim_get_api_info`
+    pub version: [u64; 3],
+    pub api_level: u64,
+    pub calls: Vec<Call>,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Scenario::from(File::default())
+    }
+}
+
+/// A scripted response to a method
+#[derive(Clone, Debug, PartialEq)]
+pub struct Call {
+    pub method: String,
+    /// The arguments to match, or any if `None`
+    pub args: Option<Vec<Value>>,
+    /// The number of parameters reported by `nvim_get_api_info`
+    pub arity: usize,
+    pub result: Value,
+    pub error: Option<String>,
+    pub delay: Duration,
+    /// The number of calls left to match, or unlimited if `None`
+    pub times: Option<u64>,
+    /// A notification sent after the response
+    pub notify: Option<(String, Vec<Value>)>,
+    /// Close the connection after the response
+    pub disconnect: bool,
+}
+
+impl Scenario {
+    /// Parse the text of a scenario file
+    pub fn parse(text: &str) -> Result<Self, String> {
+        toml::from_str::<File>(text)
+            .map(Scenario::from)
+            .map_err(|error| error.to_string())
+    }
+}
+
+impl Call {
+    /// Whether the call matches a request for `method` with `args`
+    pub fn matches(&self, method: &str, args: &[Value]) -> bool {
+        let args_match = match &self.args {
+            Some(expected) => expected == args,
+            None => true,
+        };
+        self.method == method && self.times != Some(0) && args_match
+    }
+}
+
+/// A scenario file as written
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+    #[serde(default)]
+    server: Server,
+    #[serde(default)]
+    call: Vec<CallEntry>,
+}
+
+/// The `[server]` table
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Server {
+    channel: i64,
+    version: [u64; 3],
+    api_level: u64,
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Server {
+            channel: 1,
+            version: [0, 10, 4],
+            api_level: 12,
+        }
+    }
+}
+
+/// A `[[call]]` table
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CallEntry {
+    method: String,
+    args: Option<Vec<Value>>,
+    /// Defaults to the length of `args`
+    arity: Option<usize>,
+    #[serde(default = "nil")]
+    result: Value,
+    error: Option<String>,
+    #[serde(default)]
+    delay_ms: u64,
+    times: Option<u64>,
+    notify: Option<String>,
+    #[serde(default)]
+    notify_params: Vec<Value>,
+    #[serde(default)]
+    disconnect: bool,
+}
+
+fn nil() -> Value {
+    Value::Nil
+}
+
+impl From<File> for Scenario {
+    fn from(file: File) -> Self {
+        Scenario {
+            channel: file.server.channel,
+            version: file.server.version,
+            api_level: file.server.api_level,
+            calls: file.call.into_iter().map(Call::from).collect(),
+        }
+    }
+}
+
+impl From<CallEntry> for Call {
+    fn from(entry: CallEntry) -> Self {
+        let arity = entry
+            .arity
+            .unwrap_or_else(|| entry.args.as_ref().map_or(0, Vec::len));
+        Call {
+            method: entry.method,
+            args: entry.args,
+            arity,
+            result: entry.result,
+            error: entry.error,
+            delay: Duration::from_millis(entry.delay_ms),
+            times: entry.times,
+            notify: entry.notify.map(|method| (method, entry.notify_params)),
+            disconnect: entry.disconnect,
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! The RPC server answering each connection
+//!
+//! Scripted calls from the scenario are tried first, then the few methods
+//! built in to keep a client's bookkeeping working: the API metadata,
+//! global variables, the current line and the mode. Any other request
+//! fails with an error naming the method.
+use rmpv::Value;
+use std::collections::HashMap;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::sync::Mutex;
+use std::thread;
+
+use rsnvim::error::Error;
+use rsnvim::rpc::{self, RpcMessage};
+
+use crate::scenario::{Call, Scenario};
+
+/// The type and name of each parameter of a method
+type Parameters = &'static [(&'static str, &'static str)];
+
+/// The built-in methods, with their parameters and return type, as
+/// reported by `nvim_get_api_info`
+const BUILTINS: &[(&str, Parameters, &str)] = &[
+    ("nvim_get_api_info", &[], "Array"),
+    (
+        "nvim_set_client_info",
+        &[
+            ("String", "name"),
+            ("Dictionary", "version"),
+            ("String", "type"),
+            ("Dictionary", "methods"),
+            ("Dictionary", "attributes"),
+        ],
+        "void",
+    ),
+    ("nvim_get_mode", &[], "Dictionary"),
+    ("nvim_get_var", &[("String", "name")], "Object"),
+    (
+        "nvim_set_var",
+        &[("String", "name"), ("Object", "value")],
+        "void",
+    ),
+    ("nvim_del_var", &[("String", "name")], "void"),
+    ("nvim_get_current_line", &[], "String"),
+    ("nvim_set_current_line", &[("String", "line")], "void"),
+    ("nvim_del_current_line", &[], "void"),
+    ("nvim_subscribe", &[("String", "event")], "void"),
+    ("nvim_unsubscribe", &[("String", "event")], "void"),
+    ("nvim_input", &[("String", "keys")], "Integer"),
+];
+
+/// The state shared by every connection to the server
+pub struct Server {
+    scenario: Mutex<Scenario>,
+    /// The channel ID of the next connection
+    next_channel: Mutex<i64>,
+    vars: Mutex<HashMap<String, Value>>,
+    current_line: Mutex<String>,
+}
+
+/// The outcome of a request
+struct Reply {
+    result: Result<Value, String>,
+    notify: Option<(String, Vec<Value>)>,
+    disconnect: bool,
+}
+
+impl Reply {
+    fn ok(result: Value) -> Self {
+        Reply {
+            result: Ok(result),
+            notify: None,
+            disconnect: false,
+        }
+    }
+
+    fn error(message: String) -> Self {
+        Reply {
+            result: Err(message),
+            notify: None,
+            disconnect: false,
+        }
+    }
+}
+
+impl Server {
+    pub fn new(scenario: Scenario) -> Self {
+        Server {
+            next_channel: Mutex::new(scenario.channel),
+            scenario: Mutex::new(scenario),
+            vars: Mutex::new(HashMap::new()),
+            current_line: Mutex::new(String::new()),
+        }
+    }
+
+    /// Answer requests read from `reader` until it closes or a scripted
+    /// call disconnects
+    pub fn serve<R: Read, W: Write>(&self, reader: R, writer: W) -> Result<(), Error> {
+        let channel = {
+            let mut next_channel = self.next_channel.lock().unwrap();
+            *next_channel += 1;
+            *next_channel - 1
+        };
+        let mut reader = BufReader::new(reader);
+        let mut writer = BufWriter::new(writer);
+        loop {
+            let (msgid, method, params) = match rpc::decode(&mut reader) {
+                Ok(RpcMessage::RpcRequest {
+                    msgid,
+                    method,
+                    params,
+                }) => (msgid, method, params),
+                // Notifications and responses need no answer
+                Ok(_) => continue,
+                Err(error) if error.is_disconnected() => return Ok(()),
+                Err(error) => return Err(error),
+            };
+            let reply = self.handle(channel, &method, params);
+            let (error, result) = match reply.result {
+                Ok(result) => (Value::Nil, result),
+                Err(message) => (
+                    Value::Array(vec![Value::from(0), Value::from(message)]),
+                    Value::Nil,
+                ),
+            };
+            rpc::encode(
+                &mut writer,
+                RpcMessage::RpcResponse {
+                    msgid,
+                    error,
+                    result,
+                },
+            )?;
+            if let Some((method, params)) = reply.notify {
+                rpc::encode(&mut writer, RpcMessage::RpcNotification { method, params })?;
+            }
+            writer.flush()?;
+            if reply.disconnect {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Answer a request for `method`, from a scripted call if one matches
+    fn handle(&self, channel: i64, method: &str, params: Vec<Value>) -> Reply {
+        let call = {
+            let mut scenario = self.scenario.lock().unwrap();
+            scenario
+                .calls
+                .iter_mut()
+                .find(|call| call.matches(method, &params))
+                .map(|call| {
+                    if let Some(times) = &mut call.times {
+                        *times -= 1;
+                    }
+                    call.clone()
+                })
+        };
+        match call {
+            Some(call) => {
+                thread::sleep(call.delay);
+                Reply {
+                    result: match call.error {
+                        Some(message) => Err(message),
+                        None => Ok(call.result),
+                    },
+                    notify: call.notify,
+                    disconnect: call.disconnect,
+                }
+            }
+            None => self.builtin(channel, method, params),
+        }
+    }
+
+    /// Answer a request for a built-in method
+    fn builtin(&self, channel: i64, method: &str, params: Vec<Value>) -> Reply {
+        let string = |index: usize| params.get(index).and_then(Value::as_str).unwrap_or("");
+        match method {
+            "nvim_get_api_info" => {
+                Reply::ok(Value::Array(vec![Value::from(channel), self.metadata()]))
+            }
+            "nvim_get_mode" => Reply::ok(Value::Map(vec![
+                (Value::from("mode"), Value::from("n")),
+                (Value::from("blocking"), Value::from(false)),
+            ])),
+            "nvim_get_var" => match self.vars.lock().unwrap().get(string(0)) {
+                Some(value) => Reply::ok(value.clone()),
+                None => Reply::error(format!("Key not found: {}", string(0))),
+            },
+            "nvim_set_var" => {
+                let value = params.get(1).cloned().unwrap_or(Value::Nil);
+                self.vars
+                    .lock()
+                    .unwrap()
+                    .insert(string(0).to_string(), value);
+                Reply::ok(Value::Nil)
+            }
+            "nvim_del_var" => match self.vars.lock().unwrap().remove(string(0)) {
+                Some(_) => Reply::ok(Value::Nil),
+                None => Reply::error(format!("Key not found: {}", string(0))),
+            },
+            "nvim_get_current_line" => {
+                Reply::ok(Value::from(self.current_line.lock().unwrap().as_str()))
+            }
+            "nvim_set_current_line" => {
+                *self.current_line.lock().unwrap() = string(0).to_string();
+                Reply::ok(Value::Nil)
+            }
+            "nvim_del_current_line" => {
+                self.current_line.lock().unwrap().clear();
+                Reply::ok(Value::Nil)
+            }
+            "nvim_input" => Reply::ok(Value::from(string(0).len())),
+            "nvim_set_client_info" | "nvim_subscribe" | "nvim_unsubscribe" => Reply::ok(Value::Nil),
+            _ => Reply::error(format!("fake-nvim doesn't implement {}", method)),
+        }
+    }
+
+    /// The metadata returned by `nvim_get_api_info`, listing the built-in
+    /// methods and those in the scenario
+    fn metadata(&self) -> Value {
+        let scenario = self.scenario.lock().unwrap();
+        let [major, minor, patch] = scenario.version;
+        let version = Value::Map(vec![
+            (Value::from("major"), Value::from(major)),
+            (Value::from("minor"), Value::from(minor)),
+            (Value::from("patch"), Value::from(patch)),
+            (Value::from("api_level"), Value::from(scenario.api_level)),
+            (Value::from("api_compatible"), Value::from(0)),
+            (Value::from("api_prerelease"), Value::from(false)),
+        ]);
+        let mut functions: Vec<Value> = BUILTINS
+            .iter()
+            .filter(|(name, _, _)| !scenario.calls.iter().any(|call| call.method == *name))
+            .map(|(name, parameters, return_type)| function(name, parameters.to_vec(), return_type))
+            .collect();
+        let mut scripted: Vec<&Call> = Vec::new();
+        for call in &scenario.calls {
+            if !scripted.iter().any(|other| other.method == call.method) {
+                scripted.push(call);
+            }
+        }
+        functions.extend(scripted.into_iter().map(|call| {
+            let parameters = vec![("Object", "arg"); call.arity];
+            function(&call.method, parameters, "Object")
+        }));
+        Value::Map(vec![
+            (Value::from("version"), version),
+            (Value::from("functions"), Value::Array(functions)),
+        ])
+    }
+}
+
+/// The metadata of an API function
+fn function(name: &str, parameters: Vec<(&str, &str)>, return_type: &str) -> Value {
+    let parameters = parameters
+        .into_iter()
+        .map(|(kind, name)| Value::Array(vec![Value::from(kind), Value::from(name)]))
+        .collect();
+    Value::Map(vec![
+        (Value::from("name"), Value::from(name)),
+        (Value::from("since"), Value::from(1)),
+        (Value::from("method"), Value::from(false)),
+        (Value::from("parameters"), Value::Array(parameters)),
+        (Value::from("return_type"), Value::from(return_type)),
+    ])
+}
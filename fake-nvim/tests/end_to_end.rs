@@ -0,0 +1,205 @@
+//! Tests running rsnvim against the fake-nvim binary
+use rmpv::Value;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use rsnvim::api::Nvim;
+use rsnvim::error::Error;
+use rsnvim::session::Session;
+
+/// A fake-nvim process which is killed when dropped
+struct FakeNvim {
+    child: Child,
+    addr: String,
+}
+
+impl Drop for FakeNvim {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Start fake-nvim with the scenario `scenario` on a free TCP port
+fn spawn(name: &str, scenario: &str) -> FakeNvim {
+    let path = std::env::temp_dir().join(format!("fake-nvim-{}-{}.toml", name, std::process::id()));
+    std::fs::write(&path, scenario).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_fake-nvim"))
+        .args(["--listen", "127.0.0.1:0", "--scenario"])
+        .arg(&path)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut line = String::new();
+    BufReader::new(child.stdout.take().unwrap())
+        .read_line(&mut line)
+        .unwrap();
+    let _ = std::fs::remove_file(&path);
+    let addr = line
+        .trim()
+        .strip_prefix("Listening on ")
+        .unwrap_or_else(|| panic!("Unexpected output '{}'", line))
+        .to_string();
+    FakeNvim { child, addr }
+}
+
+fn connect(fake: &FakeNvim) -> Nvim {
+    let mut nvim = Nvim::from_tcp(&fake.addr).unwrap();
+    nvim.start_event_loop(None, None);
+    nvim
+}
+
+#[test]
+fn scripted_calls() {
+    let fake = spawn(
+        "scripted",
+        r#"
+[server]
+channel = 7
+version = [0, 10, 4]
+
+[[call]]
+method = "nvim_eval"
+args = ["1 + 1"]
+result = 2
+
+[[call]]
+method = "nvim_eval"
+args = ["g:config"]
+result = { width = 80, "tab size" = 4, names = ["a", "b"] }
+
+# Matches once, then falls through to the next call
+[[call]]
+method = "nvim_command"
+arity = 1
+error = "E492: Not an editor command"
+times = 1
+
+[[call]]
+method = "nvim_command"
+arity = 1
+delay_ms = 50
+"#,
+    );
+    let mut nvim = connect(&fake);
+
+    assert_eq!(nvim.eval("1 + 1").unwrap(), Value::from(2));
+    assert_eq!(
+        nvim.eval("g:config").unwrap(),
+        Value::Map(vec![
+            (Value::from("width"), Value::from(80)),
+            (Value::from("tab size"), Value::from(4)),
+            (
+                Value::from("names"),
+                Value::Array(vec![Value::from("a"), Value::from("b")])
+            ),
+        ])
+    );
+
+    // Neovim's error message is passed on
+    assert!(matches!(
+        nvim.command("frobnicate"),
+        Err(Error::RpcError(message)) if message == "E492: Not an editor command"
+    ));
+    let start = Instant::now();
+    nvim.command("frobnicate").unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(50));
+
+    // Unscripted arguments fall through to the built-ins
+    assert!(matches!(
+        nvim.eval("2 + 2"),
+        Err(Error::RpcError(message)) if message == "fake-nvim doesn't implement nvim_eval"
+    ));
+
+    // The scripted methods are reported with the built-in ones
+    let info = nvim.api_info().unwrap();
+    assert_eq!(info.channel, 7);
+    assert_eq!(info.version.to_string(), "0.10.4");
+    assert_eq!(info.function("nvim_command").unwrap().parameters.len(), 1);
+    assert_eq!(info.function("nvim_set_var").unwrap().parameters.len(), 2);
+}
+
+#[test]
+fn builtin_state() {
+    let fake = spawn("builtin", "");
+    let nvim = connect(&fake);
+
+    nvim.set_var("answer", Value::from(42)).unwrap();
+    assert_eq!(nvim.get_var("answer").unwrap(), Value::from(42));
+    assert!(nvim.get_var("question").is_err());
+
+    nvim.set_current_line("hello").unwrap();
+    assert_eq!(nvim.get_current_line().unwrap(), "hello");
+    assert_eq!(nvim.get_mode().unwrap().get_str("mode"), Some("n"));
+
+    // Variables are shared by every connection
+    let other = connect(&fake);
+    assert_eq!(other.get_var("answer").unwrap(), Value::from(42));
+}
+
+#[test]
+fn notify_and_disconnect() {
+    let fake = spawn(
+        "notify",
+        r#"
+[[call]]
+method = "nvim_subscribe"
+args = ["tick"]
+notify = "tick"
+notify_params = [1, "fake"]
+
+[[call]]
+method = "nvim_input"
+args = ["ZZ"]
+result = 2
+disconnect = true
+times = 1
+"#,
+    );
+    let mut nvim = connect(&fake);
+
+    let ticks = nvim.subscribe_notifications("tick");
+    nvim.subscribe("tick").unwrap();
+    assert_eq!(
+        ticks.recv_timeout(Duration::from_secs(5)).unwrap(),
+        vec![Value::from(1), Value::from("fake")]
+    );
+
+    assert_eq!(nvim.input("ZZ").unwrap(), 2);
+    assert!(nvim.get_current_line().is_err());
+
+    nvim.reconnect().unwrap();
+    assert_eq!(nvim.get_current_line().unwrap(), "");
+}
+
+#[test]
+fn invalid_scenario() {
+    let path = std::env::temp_dir().join(format!("fake-nvim-invalid-{}.toml", std::process::id()));
+    std::fs::write(&path, "[[call]]\nmethod = \"nvim_eval\"\nresult = tru\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_fake-nvim"))
+        .arg("--scenario")
+        .arg(&path)
+        .output()
+        .unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("line 3"), "{}", stderr);
+}
+
+#[test]
+fn connect_auto() {
+    let fake = spawn("auto", "");
+    std::env::set_var("NVIM", &fake.addr);
+    let session = Session::connect_auto().unwrap();
+    session.start_event_loop(None, None);
+
+    // Unknown methods fail on the server until the metadata is cached
+    assert!(session.call("nvim_frobnicate", vec![]).is_err());
+    assert_eq!(session.channel_id().unwrap(), 1);
+    assert!(matches!(
+        session.call("nvim_frobnicate", vec![]),
+        Err(Error::UnsupportedVersion(_))
+    ));
+}
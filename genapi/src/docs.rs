@@ -0,0 +1,406 @@
+//! Function documentation parsed from Neovim's `api.txt` help file
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::error::Error;
+
+/// The width doc comments are wrapped to, excluding the indent and `/// `
+const WRAP_WIDTH: usize = 72;
+
+/// The documentation of an API function
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Docs {
+    /// The paragraphs describing the function, as Markdown lines
+    description: Vec<String>,
+    /// Each parameter's name and description
+    parameters: Vec<(String, String)>,
+    /// The description of the return value
+    returns: String,
+}
+
+impl Docs {
+    /// Render the documentation as the lines of a `///` doc comment
+    ///
+    /// Parameters are given in the order they are passed, skipping any in
+    /// `skip`, like the `buffer` of a `Buffer` method.
+    pub fn render(&self, parameters: &[String], skip: &str) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::new();
+        let mut in_code = false;
+        for line in &self.description {
+            if line.starts_with("```") {
+                in_code = !in_code;
+                lines.push(line.clone());
+            } else if in_code || line.is_empty() {
+                lines.push(line.clone());
+            } else {
+                lines.extend(wrap(line, ""));
+            }
+        }
+
+        let parameters: Vec<String> = parameters
+            .iter()
+            .filter(|name| *name != skip)
+            .filter_map(|name| {
+                let (_, doc) = self.parameters.iter().find(|(n, _)| n == name)?;
+                Some(wrap(&format!("- `{}`: {}", name, doc), "  "))
+            })
+            .flatten()
+            .collect();
+        if !parameters.is_empty() {
+            lines.extend(["".to_string(), "# Parameters".to_string(), "".to_string()]);
+            lines.extend(parameters);
+        }
+        if !self.returns.is_empty() {
+            lines.extend(["".to_string(), "# Returns".to_string(), "".to_string()]);
+            lines.extend(wrap(&self.returns, ""));
+        }
+        if !lines.is_empty() {
+            lines.push(String::new());
+        }
+
+        lines
+            .into_iter()
+            .map(|line| {
+                if line.is_empty() {
+                    "///".to_string()
+                } else {
+                    format!("/// {}", line)
+                }
+            })
+            .collect()
+    }
+}
+
+/// Wrap `text` to fit a doc comment, indenting continuation lines with
+/// `indent`
+fn wrap(text: &str, indent: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.len() + word.len() + 1 > WRAP_WIDTH {
+            lines.push(std::mem::replace(&mut line, indent.to_string()));
+        }
+        if !line.trim().is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// Find `api.txt` in the runtime directory of the `nvim` on the PATH
+pub fn find_api_txt() -> Option<PathBuf> {
+    let output = Command::new("nvim")
+        .args([
+            "--clean",
+            "--headless",
+            "--cmd",
+            "lua io.stdout:write(vim.env.VIMRUNTIME)",
+            "--cmd",
+            "quit",
+        ])
+        .output()
+        .ok()?;
+    let runtime = String::from_utf8(output.stdout).ok()?;
+    let path = PathBuf::from(runtime.trim()).join("doc").join("api.txt");
+    path.is_file().then_some(path)
+}
+
+/// Load the documentation of each function in the help file at `path`
+pub fn load_docs(path: &PathBuf) -> Result<HashMap<String, Docs>, Error> {
+    Ok(parse_help(&fs::read_to_string(path)?))
+}
+
+/// A section of a function's help, like `Parameters: ~`
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    Description,
+    Parameters,
+    Returns,
+    Other,
+}
+
+/// Parse the help entries of the API functions, keyed by function name
+///
+/// Each entry starts after a `*nvim_foo()*` tag and continues until the
+/// next unindented line.
+fn parse_help(help: &str) -> HashMap<String, Docs> {
+    let tag = Regex::new(r"\*(nvim_\w+)\(\)\*").expect("This is a valid regex");
+    let section = Regex::new(r"^\s+(\w[\w ]*): ~$").expect("This is a valid regex");
+    let parameter = Regex::new(r"^\s+(?:•\s+)?\{(\w+)\}\s+(.*)$").expect("This is a valid regex");
+    // Code blocks start with a line ending in `>`, optionally followed by
+    // the language, and end with a line starting with `<`
+    let code = Regex::new(r"^(?:(.*)\s)?>(\w*)$").expect("This is a valid regex");
+
+    let mut docs = HashMap::new();
+    let mut lines = help.lines().peekable();
+    while let Some(line) = lines.next() {
+        let name = match tag.captures(line) {
+            Some(captures) => captures[1].to_string(),
+            None => continue,
+        };
+
+        // Skip the rest of a signature whose tag is on the line before it
+        let signature = format!("{}(", name);
+        while lines.next_if(|l| l.starts_with(&signature)).is_some() {}
+
+        let mut entry = Docs::default();
+        let mut current = Section::Description;
+        let mut paragraph: Vec<String> = Vec::new();
+        let mut in_code = false;
+        // The `<` ending a code block may be unindented
+        while let Some(line) = lines.next_if(|l| {
+            l.is_empty() || l.starts_with(char::is_whitespace) || (in_code && l.starts_with('<'))
+        }) {
+            if in_code {
+                if line.trim_start().starts_with('<') {
+                    in_code = false;
+                    if current == Section::Description {
+                        entry.description.push("```".to_string());
+                    }
+                } else if current == Section::Description {
+                    // Code is indented within the entry's text
+                    let line = line.trim_end();
+                    let indent = line.len() - line.trim_start().len();
+                    entry.description.push(line[indent.min(8)..].to_string());
+                }
+                continue;
+            }
+            if let Some(captures) = section.captures(line) {
+                flush(&mut entry, current, &mut paragraph);
+                current = match &captures[1] {
+                    "Parameters" => Section::Parameters,
+                    "Return" => Section::Returns,
+                    _ => Section::Other,
+                };
+                continue;
+            }
+
+            let mut text = line.trim();
+            let mut language = None;
+            if let Some(captures) = code.captures(text) {
+                language = Some(match captures.get(2).map(|m| m.as_str()) {
+                    Some("") | None => "text",
+                    Some(language) => language,
+                });
+                text = captures.get(1).map_or("", |m| m.as_str().trim());
+            }
+
+            if text.is_empty() {
+                flush(&mut entry, current, &mut paragraph);
+            } else if current == Section::Parameters {
+                match parameter.captures(line) {
+                    Some(captures) => {
+                        flush(&mut entry, current, &mut paragraph);
+                        paragraph.push(captures[1].to_string());
+                        paragraph.push(captures[2].to_string());
+                    }
+                    // A continuation of the previous parameter
+                    None if !paragraph.is_empty() => paragraph.push(text.to_string()),
+                    None => (),
+                }
+            } else {
+                paragraph.push(text.to_string());
+            }
+
+            if let Some(language) = language {
+                flush(&mut entry, current, &mut paragraph);
+                in_code = true;
+                if current == Section::Description {
+                    if !entry.description.is_empty() {
+                        entry.description.push(String::new());
+                    }
+                    entry.description.push(format!("```{}", language));
+                }
+            }
+        }
+        if in_code && current == Section::Description {
+            entry.description.push("```".to_string());
+        }
+        flush(&mut entry, current, &mut paragraph);
+        docs.insert(name, entry);
+    }
+
+    docs
+}
+
+/// Add the paragraph being parsed to its section of `entry`
+fn flush(entry: &mut Docs, section: Section, paragraph: &mut Vec<String>) {
+    if paragraph.is_empty() {
+        return;
+    }
+    let lines = std::mem::take(paragraph);
+    match section {
+        Section::Description => {
+            if !entry.description.is_empty() {
+                entry.description.push(String::new());
+            }
+            entry.description.push(markdown(&lines.join(" ")));
+        }
+        Section::Parameters => {
+            let name = lines[0].clone();
+            entry
+                .parameters
+                .push((name, markdown(&lines[1..].join(" "))));
+        }
+        Section::Returns => {
+            if !entry.returns.is_empty() {
+                entry.returns.push(' ');
+            }
+            entry.returns.push_str(&markdown(&lines.join(" ")));
+        }
+        Section::Other => (),
+    }
+}
+
+/// Convert help markup to Markdown
+///
+/// Links like `|nvim_get_mode()|`, arguments like `{opts}`, options like
+/// `'tabstop'` and keys like `<CR>` become code spans.
+fn markdown(text: &str) -> String {
+    let markup = Regex::new(r"\|([^|\s]+)\||\{(\w+)\}|('[a-z]+')|(<[A-Za-z][\w-]*>)|`([^`]+)`")
+        .expect("This is a valid regex");
+    markup
+        .replace_all(text, |captures: &regex::Captures| {
+            let code = (1..=5).find_map(|i| captures.get(i)).unwrap().as_str();
+            format!("`{}`", code)
+        })
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELP: &str = "\
+==============================================================================
+Buffer Functions                                                  *api-buffer*
+
+                                                        *nvim_buf_get_lines()*
+nvim_buf_get_lines({buffer}, {start}, {end}, {strict_indexing})
+    Gets a line-range from the buffer.
+
+    Indexing is zero-based, end-exclusive. Out-of-bounds indices are
+    clamped unless {strict_indexing} is set, see |api-indexing|.
+
+    Example: >lua
+        vim.api.nvim_buf_get_lines(0, 0, -1, false)
+<
+
+    Attributes: ~
+        Since: 0.1.0
+
+    Parameters: ~
+      • {buffer}           Buffer handle, or 0 for current buffer
+      • {start}            First line index
+      • {strict_indexing}  Whether out-of-bounds should be an
+                           error.
+
+    Return: ~
+        Array of lines.
+
+nvim_get_mode()                                              *nvim_get_mode()*
+    Gets the current mode, like 'showmode' or <C-o>.
+
+vim:tw=78:ts=8:sw=4:sts=4:et:ft=help:norl:
+";
+
+    fn strings(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn entries() {
+        let docs = parse_help(HELP);
+        assert_eq!(docs.len(), 2);
+
+        let lines = &docs["nvim_buf_get_lines"];
+        assert_eq!(
+            lines.description,
+            strings(&[
+                "Gets a line-range from the buffer.",
+                "",
+                "Indexing is zero-based, end-exclusive. Out-of-bounds indices are \
+                 clamped unless `strict_indexing` is set, see `api-indexing`.",
+                "",
+                "Example:",
+                "",
+                "```lua",
+                "vim.api.nvim_buf_get_lines(0, 0, -1, false)",
+                "```",
+            ])
+        );
+        assert_eq!(
+            lines.parameters,
+            vec![
+                (
+                    "buffer".to_string(),
+                    "Buffer handle, or 0 for current buffer".to_string()
+                ),
+                ("start".to_string(), "First line index".to_string()),
+                (
+                    "strict_indexing".to_string(),
+                    "Whether out-of-bounds should be an error.".to_string()
+                ),
+            ]
+        );
+        assert_eq!(lines.returns, "Array of lines.");
+
+        let mode = &docs["nvim_get_mode"];
+        assert_eq!(
+            mode.description,
+            strings(&["Gets the current mode, like `'showmode'` or `<C-o>`."])
+        );
+        assert!(mode.parameters.is_empty());
+        assert!(mode.returns.is_empty());
+    }
+
+    #[test]
+    fn rendering() {
+        let docs = parse_help(HELP);
+        let parameters = strings(&["buffer", "start", "end", "strict_indexing"]);
+        let rendered = docs["nvim_buf_get_lines"].render(&parameters, "buffer");
+        assert_eq!(
+            rendered,
+            strings(&[
+                "/// Gets a line-range from the buffer.",
+                "///",
+                "/// Indexing is zero-based, end-exclusive. Out-of-bounds indices are clamped",
+                "/// unless `strict_indexing` is set, see `api-indexing`.",
+                "///",
+                "/// Example:",
+                "///",
+                "/// ```lua",
+                "/// vim.api.nvim_buf_get_lines(0, 0, -1, false)",
+                "/// ```",
+                "///",
+                "/// # Parameters",
+                "///",
+                "/// - `start`: First line index",
+                "/// - `strict_indexing`: Whether out-of-bounds should be an error.",
+                "///",
+                "/// # Returns",
+                "///",
+                "/// Array of lines.",
+                "///",
+            ])
+        );
+        assert!(Docs::default().render(&parameters, "").is_empty());
+    }
+
+    #[test]
+    fn wrapping() {
+        let text = "word ".repeat(20);
+        let lines = wrap(&text, "  ");
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.len() <= WRAP_WIDTH));
+        assert!(lines[1].starts_with("  word"));
+    }
+}
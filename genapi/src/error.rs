@@ -1,10 +1,15 @@
 /// Error types for genapi
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
     /// An error when saving the generated API
     IoError(String),
     /// An error when rendering the templates
     RenderError(String),
+    /// An error when parsing the type overlay
+    InvalidOverlay(String),
+    /// An error when the generated code isn't valid Rust
+    InvalidCode(String),
 }
 
 impl From<std::io::Error> for Error {
@@ -19,6 +24,12 @@ impl From<handlebars::RenderError> for Error {
     }
 }
 
+impl From<serde_json::Error> for Error {
+    fn from(value: serde_json::Error) -> Self {
+        Self::InvalidOverlay(value.to_string())
+    }
+}
+
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -27,6 +38,8 @@ impl std::fmt::Display for Error {
             match &self {
                 Error::IoError(err) => err,
                 Error::RenderError(err) => err,
+                Error::InvalidOverlay(err) => err,
+                Error::InvalidCode(err) => err,
             }
         )
     }
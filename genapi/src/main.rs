@@ -2,10 +2,15 @@ use handlebars::{Handlebars, handlebars_helper};
 use rmpv::{decode, Value};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
+use std::path::PathBuf;
 use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+mod docs;
 mod error;
+use docs::Docs;
 use error::Error;
 
 /// The name of a struct to impl for and all associated functions
@@ -13,9 +18,56 @@ use error::Error;
 pub struct Impl<'a> {
     name: &'a str,
     prefix: &'a str,
+    stamp: &'a Stamp,
     functions: &'a Vec<Function>,
 }
 
+/// Where the generated bindings came from, exposed by `rsnvim::build_info`
+#[derive(Clone, Debug, Serialize)]
+pub struct Stamp {
+    api_level: u64,
+    /// The commit genapi was built from, recorded by `build.rs`
+    git_hash: String,
+    generated_at: String,
+}
+
+impl Stamp {
+    fn new(api_level: u64) -> Self {
+        Stamp {
+            api_level,
+            git_hash: env!("GENAPI_GIT_HASH").to_string(),
+            generated_at: timestamp(),
+        }
+    }
+}
+
+/// The time of generation as a UTC timestamp, e.g. `2024-06-01T12:00:00Z`
+///
+/// `SOURCE_DATE_EPOCH` is used instead of the current time if set, so the
+/// output can be reproduced.
+fn timestamp() -> String {
+    let secs = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs());
+    let (days, secs) = ((secs / 86400) as i64, secs % 86400);
+    // The days since 1970-01-01 as a date, from
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, secs / 3600, secs / 60 % 60, secs % 60
+    )
+}
+
 /// The name and type of a function/struct parameter
 #[derive(Clone, Debug, PartialEq, Serialize)]
 pub struct Parameter {
@@ -34,6 +86,10 @@ pub enum Type {
     VALUE,
     VEC(Box<Type>),
     TUPLE(Vec<Type>),
+    /// A position in a buffer or on the screen, sent as `[row, col]`
+    POINT,
+    DICTIONARY,
+    OPTION(Box<Type>),
     BUFFER,
     TABPAGE,
     WINDOW
@@ -54,34 +110,106 @@ impl Type {
             Type::TUPLE(a) => {
                 format!("({})", a.iter().map(|x| Type::render_type(x.clone())).collect::<Vec<String>>().join(", "))
             },
+            Type::POINT => "Point".to_string(),
+            Type::DICTIONARY => "Dict".to_string(),
+            Type::OPTION(a) => {
+                format!("Option<{}>", Type::render_type(*a))
+            },
             Type::BUFFER => "Buffer".to_string(),
             Type::TABPAGE => "Tabpage".to_string(),
             Type::WINDOW => "Window".to_string()
         }
     }
 
-    pub fn generate_return(var: &str, t: Type) -> String {
+    /// Render a parameter `Type`, accepting any type convertible to it where possible
+    /// and borrowing handles
+    pub fn render_param_type(t: Type) -> String {
         match t {
-            Type::I64 => format!("{}.as_i64().unwrap()", var),
-            Type::F64 => format!("{}.as_f64().unwrap()", var),
-            Type::BOOL => format!("{}.as_bool().unwrap()", var),
-            Type::STRING => format!("{}.as_str().unwrap().to_string()", var),
-            Type::VALUE => format!("{}.to_owned()", var),
-            Type::VEC(a) => {
-                format!("{}.as_array().unwrap().iter().map(|x| {}).collect()", var, Type::generate_return("x", *a))
+            Type::STRING => "impl Into<String>".to_string(),
+            Type::VEC(a) if *a == Type::STRING => {
+                "impl IntoIterator<Item = impl Into<String>>".to_string()
             },
+            Type::VEC(a) if *a == Type::VALUE => "impl IntoIterator<Item = Value>".to_string(),
+            Type::DICTIONARY => "impl Into<Dict>".to_string(),
+            Type::POINT => "impl Into<Point>".to_string(),
+            Type::BUFFER | Type::TABPAGE | Type::WINDOW => format!("&{}", Type::render_type(t)),
+            other => Type::render_type(other),
+        }
+    }
+
+    /// Convert a parameter rendered by `render_param_type` back to its concrete type
+    pub fn generate_param_conversion(name: &str, t: Type) -> String {
+        match t {
+            Type::STRING => format!("let {}: String = {}.into();", name, name),
+            Type::VEC(a) if *a == Type::STRING => {
+                format!("let {}: Vec<String> = {}.into_iter().map(Into::into).collect();", name, name)
+            },
+            Type::VEC(a) if *a == Type::VALUE => {
+                format!("let {}: Vec<Value> = {}.into_iter().collect();", name, name)
+            },
+            Type::DICTIONARY => format!("let {}: Dict = {}.into();", name, name),
+            Type::POINT => format!("let {}: Point = {}.into();", name, name),
+            _ => String::new(),
+        }
+    }
+
+    /// Replace a pair of integers with `Type::POINT`, for the overlay's
+    /// `points` of `function`
+    fn as_point(t: &Type, function: &str, name: &str) -> Type {
+        match t {
+            Type::TUPLE(v) if *v == [Type::I64, Type::I64] => Type::POINT,
+            Type::OPTION(a) => Type::OPTION(Box::new(Type::as_point(a, function, name))),
+            other => panic!("Overlay point `{}` of {} isn't a pair of integers: {:?}", name, function, other),
+        }
+    }
+
+    /// Whether the type contains a handle, which needs the session to construct
+    fn contains_handle(t: &Type) -> bool {
+        match t {
+            Type::BUFFER | Type::TABPAGE | Type::WINDOW => true,
+            Type::VEC(a) | Type::OPTION(a) => Type::contains_handle(a),
+            Type::TUPLE(v) => v.iter().any(Type::contains_handle),
+            _ => false,
+        }
+    }
+
+    /// Convert the `Value` in `var` to the type `t`, returning early with
+    /// `Error::DecodingError` if it has an unexpected type
+    pub fn generate_return(var: &str, t: Type) -> String {
+        match t {
             Type::UNIT => "()".to_string(),
-            Type::BUFFER => format!("Buffer {{ data: {}.clone(), session: self.session.clone() }}", var),
-            Type::TABPAGE => format!("Tabpage {{ data: {}.clone(), session: self.session.clone() }}", var),
-            Type::WINDOW => format!("Window {{ data: {}.clone(), session: self.session.clone() }}", var),
-            Type::TUPLE(v) => {
-                format!("{{let v = {}.as_array().unwrap();\n\t\t({})}}",
+            other => format!("{}?", Type::generate_result(var, other)),
+        }
+    }
+
+    /// Convert the `Value` in `var` to a `Result` of the type `t`, using the
+    /// session to construct handles
+    fn generate_result(var: &str, t: Type) -> String {
+        match t {
+            Type::BUFFER | Type::TABPAGE | Type::WINDOW => {
+                format!("self.handle::<{}>({})", Type::render_type(t), var)
+            },
+            Type::VEC(a) if Type::contains_handle(&a) => {
+                format!(
+                    "Vec::<Value>::from_value({})?.into_iter().map(|x| {}).collect::<Result<_, Error>>()",
                     var,
-                    v.iter().enumerate().map(
-                        |(n, x)| Type::generate_return(format!("v[{}]", n).as_str(), x.to_owned())
-                    ).collect::<Vec<String>>().join(", ")
+                    Type::generate_result("x", *a)
                 )
-            }
+            },
+            Type::OPTION(a) if Type::contains_handle(&a) => {
+                format!("Ok(if {}.is_nil() {{ None }} else {{ Some({}) }})", var, Type::generate_return(var, *a))
+            },
+            other => Type::generate_from_value(var, other),
+        }
+    }
+
+    /// Convert the `Value` in `var` to the type `t` using `FromValue`
+    fn generate_from_value(var: &str, t: Type) -> String {
+        let rendered = Type::render_type(t);
+        if rendered.contains(['<', '(']) {
+            format!("<{}>::from_value({})", rendered, var)
+        } else {
+            format!("{}::from_value({})", rendered, var)
         }
     }
 }
@@ -89,19 +217,94 @@ impl Type {
 // A helper to render a `Type` in valid Rust syntax
 handlebars_helper!(as_type: |t: Type| Type::render_type(t));
 
-handlebars_helper!(generate_return: |t: Type| {
-    match t {
-        Type::TUPLE(v) => {
-            format!("let v = ret.as_array().unwrap();\n\t\tOk(({}))",
-                v.iter().enumerate().map(
-                    |(n, x)| Type::generate_return(format!("v[{}]", n).as_str(), x.to_owned())
-                ).collect::<Vec<String>>().join(", ")
-            )
+// A helper to render a parameter `Type` in valid Rust syntax
+handlebars_helper!(as_param_type: |t: Type| Type::render_param_type(t));
+
+// A helper to convert a parameter to the concrete type sent to Neovim
+handlebars_helper!(convert_param: |name: str, t: Type| Type::generate_param_conversion(name, t));
+
+// A helper to convert the returned `Value` in `ret` to the return type
+handlebars_helper!(generate_return: |t: Type| Type::generate_result("ret", t));
+
+// Check if the return type needs the session to construct
+handlebars_helper!(has_handle: |t: Type| Type::contains_handle(&t));
+
+
+/// Extra type information for a function which api-info doesn't express
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Overlay {
+    /// The function may return Nil
+    optional_return: bool,
+    /// The named parameters accept Nil
+    optional_parameters: Vec<String>,
+    /// The indexing of `ArrayOf(Integer, 2)` positions to generate as
+    /// `Point`, keyed by parameter name or `return`
+    points: HashMap<String, Indexing>,
+    /// Mark the generated function `#[deprecated]` with this note, e.g. in
+    /// favour of a handwritten wrapper
+    deprecated: Option<String>,
+}
+
+/// Where the rows and columns of a `Point` start counting from
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum Indexing {
+    #[serde(rename = "(0,0)")]
+    ZeroZero,
+    #[serde(rename = "(1,0)")]
+    OneZero,
+}
+
+impl Indexing {
+    /// Document a `Point` with this indexing, e.g. `Returns a (1,0)-indexed ...`
+    fn doc(self, prefix: &str) -> String {
+        let (text, variant) = match self {
+            Indexing::ZeroZero => ("(0,0)", "ZeroZero"),
+            Indexing::OneZero => ("(1,0)", "OneZero"),
+        };
+        format!("/// {} a {}-indexed `Point`, see `Indexing::{}`", prefix, text, variant)
+    }
+}
+
+impl Overlay {
+    /// Wrap the Nil-able return type and parameters of `f` in `Type::OPTION`
+    fn apply(&self, f: &Function) -> Function {
+        let mut f_mod = f.clone();
+        if self.optional_return && f_mod.return_type != Type::UNIT {
+            f_mod.return_type = Type::OPTION(Box::new(f_mod.return_type));
+        }
+        for param in f_mod.parameters.iter_mut() {
+            if self.optional_parameters.contains(&param.name) {
+                param.parameter_type = Type::OPTION(Box::new(param.parameter_type.clone()));
+            }
+        }
+
+        // Points are documented after the docs from `api.txt`
+        let mut point_docs = Vec::new();
+        if let Some(indexing) = self.points.get("return") {
+            f_mod.return_type = Type::as_point(&f_mod.return_type, &f.name, "return");
+            point_docs.push(indexing.doc("Returns"));
         }
-        other => format!("Ok({})", Type::generate_return("ret", other))
+        for param in f_mod.parameters.iter_mut() {
+            if let Some(indexing) = self.points.get(&param.name) {
+                param.parameter_type = Type::as_point(&param.parameter_type, &f.name, &param.name);
+                point_docs.push(indexing.doc(&format!("`{}` is", param.name)));
+            }
+        }
+        if !point_docs.is_empty() {
+            f_mod.docs.extend(point_docs);
+            f_mod.docs.push("///".to_string());
+        }
+        f_mod.deprecation = self.deprecated.clone();
+
+        f_mod
     }
-});
+}
 
+/// Load the overlay of Nil-able types, keyed by function name
+fn load_overlay(path: &str) -> Result<HashMap<String, Overlay>, Error> {
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
 
 /// The attributes needed to construct a Rust function signature
 #[derive(Clone, Debug, Serialize)]
@@ -112,6 +315,10 @@ pub struct Function {
     parameters: Vec<Parameter>,
     return_type: Type,
     method: bool,
+    /// The lines of the doc comment, from Neovim's help
+    docs: Vec<String>,
+    /// The note of the function's `#[deprecated]` attribute, from the overlay
+    deprecation: Option<String>,
 }
 
 impl Function {
@@ -164,13 +371,42 @@ impl Function {
             parameters,
             return_type,
             method,
+            docs: Vec::new(),
+            deprecation: None,
+        }
+    }
+
+    /// Render the documentation of this function from `docs`, if any
+    fn with_docs(&self, docs: &HashMap<String, Docs>) -> Function {
+        let mut f_mod = self.clone();
+        // Methods don't take the object they are called on as a parameter
+        let skip = match &self.name {
+            name if name.starts_with("nvim_buf_") => "buffer",
+            name if name.starts_with("nvim_tabpage_") => "tabpage",
+            name if name.starts_with("nvim_win_") => "window",
+            _ => "",
+        };
+        if let Some(doc) = docs.get(&self.name) {
+            let names: Vec<String> = self.parameters.iter().map(|p| p.name.clone()).collect();
+            f_mod.docs = doc.render(&names, skip);
         }
+
+        f_mod
     }
 }
 
 
-// Check if function returns ()
-handlebars_helper!(no_ret: |a: Type| a != Type::UNIT);
+/// The first API level of each Neovim release with a cargo feature, newest
+/// last. Functions added in earlier levels are always available.
+const VERSION_FEATURES: &[(u64, &str)] = &[(12, "nvim-0-10"), (13, "nvim-0-11")];
+
+// Get the feature gating a function added in API level `since`, if any
+handlebars_helper!(since_feature: |since: u64| {
+    match VERSION_FEATURES.iter().rev().find(|(level, _)| since >= *level) {
+        Some((_, feature)) => feature.to_string(),
+        None => String::new(),
+    }
+});
 
 /// Map MessagePack types to Rust
 fn value_to_type(value: &str) -> Type {
@@ -195,7 +431,7 @@ fn value_to_type(value: &str) -> Type {
                 )
             }
         },
-        "Dictionary" => Type::VEC(Box::new(Type::TUPLE(vec![Type::VALUE, Type::VALUE]))),
+        "Dictionary" => Type::DICTIONARY,
         "String" => Type::STRING,
         "Buffer" => Type::BUFFER,
         "Tabpage" => Type::TABPAGE,
@@ -264,6 +500,7 @@ fn change_keywords(f: &Function) -> Function {
 }
 
 /// Save the generated functions to a Rust file
+#[allow(clippy::too_many_arguments)]
 fn save_functions(
     registry: &Handlebars,
     template: &str,
@@ -271,34 +508,61 @@ fn save_functions(
     structname: &str,
     prefix: &str,
     param: &str,
+    stamp: &Stamp,
     functions: &[Function],
 ) -> Result<(), Error> {
-    fs::write(
-        format!("build/{}.rs", filename),
-        registry.render(
-            template,
-            &Impl {
-                name: structname,
-                prefix,
-                functions: &functions
-                    .iter()
-                    .map(|x| strip_prefix(x, prefix, param))
-                    .map(|x| change_keywords(&x))
-                    .collect(),
-            },
-        )?,
+    let path = format!("build/{}.rs", filename);
+    let code = registry.render(
+        template,
+        &Impl {
+            name: structname,
+            prefix,
+            stamp,
+            functions: &functions
+                .iter()
+                .map(|x| strip_prefix(x, prefix, param))
+                .map(|x| change_keywords(&x))
+                .collect(),
+        },
     )?;
-
-    Command::new("rustfmt")
-        .arg(format!("build/{}.rs", filename))
-        .output()
-        .expect("Failed to format generated file");
+    let file = parse_generated(&code, &path)?;
+    fs::write(&path, &code)?;
+
+    // rustfmt keeps comments and matches the rest of rsnvim, so is preferred
+    // to formatting in-process
+    let formatted = Command::new("rustfmt")
+        .arg(&path)
+        .status()
+        .is_ok_and(|status| status.success());
+    if !formatted {
+        fs::write(&path, prettyplease::unparse(&file))?;
+    }
 
     Ok(())
 }
 
+/// Parse the generated code, so a template bug is reported with the function
+/// it broke rather than when rsnvim fails to compile
+fn parse_generated(code: &str, path: &str) -> Result<syn::File, Error> {
+    syn::parse_file(code).map_err(|error| {
+        let line = error.span().start().line;
+        let lines: Vec<&str> = code.lines().take(line).collect();
+        let function = lines.iter().rev().find_map(|l| {
+            l.trim_start()
+                .strip_prefix("pub fn ")
+                .and_then(|rest| rest.split(['(', '<']).next())
+        });
+        Error::InvalidCode(match function {
+            Some(function) => format!("{}:{}: {} in `{}`", path, line, error, function),
+            None => format!("{}:{}: {}", path, line, error),
+        })
+    })
+}
+
 /// Generate Rust files containing the Neovim API
-fn generate_api(functions: Option<Vec<Function>>) -> Result<(), Error> {
+fn generate_api(functions: Option<Vec<Function>>, docs: &HashMap<String, Docs>) -> Result<(), Error> {
+    let overlay = load_overlay("genapi/overlay.json")?;
+
     let mut registry = Handlebars::new();
     registry
         .register_template_file("nvim", "genapi/templates/nvim.hbs")
@@ -308,18 +572,31 @@ fn generate_api(functions: Option<Vec<Function>>) -> Result<(), Error> {
         .unwrap();
     registry
         .register_helper("as_type", Box::new(as_type));
+    registry
+        .register_helper("as_param_type", Box::new(as_param_type));
+    registry
+        .register_helper("convert_param", Box::new(convert_param));
     registry
         .register_helper("generate_return", Box::new(generate_return));
     registry
-        .register_helper("no_ret", Box::new(no_ret));
+        .register_helper("has_handle", Box::new(has_handle));
+    registry
+        .register_helper("since_feature", Box::new(since_feature));
 
 
     let mut buffer_functions: Vec<Function> = Vec::new();
     let mut nvim_functions: Vec<Function> = Vec::new();
     let mut tabpage_functions: Vec<Function> = Vec::new();
     let mut window_functions: Vec<Function> = Vec::new();
+    let mut api_level = 0;
     if let Some(functions) = functions {
         for f in functions {
+            let f = f.with_docs(docs);
+            let f = match overlay.get(&f.name) {
+                Some(o) => o.apply(&f),
+                None => f,
+            };
+            api_level = api_level.max(f.since.unwrap_or(0));
             if f.deprecated_since.is_none() {
                 match &f {
                     f if f.name.starts_with("nvim_buf_") => {
@@ -340,6 +617,7 @@ fn generate_api(functions: Option<Vec<Function>>) -> Result<(), Error> {
     }
 
     fs::create_dir_all("build").expect("Unable to create folder");
+    let stamp = Stamp::new(api_level);
 
     save_functions(
         &registry,
@@ -348,6 +626,7 @@ fn generate_api(functions: Option<Vec<Function>>) -> Result<(), Error> {
         "Buffer",
         "nvim_buf_",
         "buffer",
+        &stamp,
         &buffer_functions,
     )?;
     save_functions(
@@ -357,6 +636,7 @@ fn generate_api(functions: Option<Vec<Function>>) -> Result<(), Error> {
         "Nvim",
         "nvim_",
         "",
+        &stamp,
         &nvim_functions
     )?;
     save_functions(
@@ -366,6 +646,7 @@ fn generate_api(functions: Option<Vec<Function>>) -> Result<(), Error> {
         "Tabpage",
         "nvim_tabpage_",
         "tabpage",
+        &stamp,
         &tabpage_functions,
     )?;
     save_functions(
@@ -375,20 +656,64 @@ fn generate_api(functions: Option<Vec<Function>>) -> Result<(), Error> {
         "Window",
         "nvim_win_",
         "window",
+        &stamp,
         &window_functions,
     )?;
 
     Ok(())
 }
 
-fn main() {
-    let output = Command::new("nvim")
-        .args(["--api-info"])
-        .output()
-        .expect("Failed to retrieve Neovim API");
-    let mut stdout = &output.stdout[..];
+/// Load the function documentation from the file given with `--docs`, or
+/// the `api.txt` of the installed Neovim
+fn load_docs() -> HashMap<String, Docs> {
+    let args: Vec<String> = std::env::args().collect();
+    let path = match args.iter().position(|arg| arg == "--docs") {
+        Some(i) => args.get(i + 1).map(PathBuf::from),
+        None => docs::find_api_txt(),
+    };
+    let path = match path {
+        Some(path) => path,
+        None => {
+            println!("Neovim's api.txt wasn't found, so no documentation will be generated");
+            return HashMap::new();
+        }
+    };
+    match docs::load_docs(&path) {
+        Ok(docs) => docs,
+        Err(e) => {
+            println!("Error loading documentation from {}: {}", path.display(), e);
+            HashMap::new()
+        }
+    }
+}
+
+/// Read the API metadata from the file given with `--api-info`, as saved by
+/// `nvim --api-info > api-info.mpack`, or from the `nvim` on PATH
+fn load_api_info() -> Result<Value, String> {
+    let args: Vec<String> = std::env::args().collect();
+    let bytes = match args.iter().position(|arg| arg == "--api-info") {
+        Some(i) => {
+            let path = args.get(i + 1).ok_or("--api-info needs the path of a file")?;
+            fs::read(path).map_err(|e| format!("Couldn't read {}: {}", path, e))?
+        }
+        None => {
+            let output = Command::new("nvim").arg("--api-info").output().map_err(|e| {
+                format!("Couldn't run `nvim --api-info`, pass a saved file with --api-info: {}", e)
+            })?;
+            output.stdout
+        }
+    };
+    decode::read_value(&mut &bytes[..]).map_err(|e| format!("Invalid API metadata: {}", e))
+}
 
-    let api = decode::read_value(&mut stdout).unwrap();
+fn main() {
+    let api = match load_api_info() {
+        Ok(api) => api,
+        Err(e) => {
+            println!("Error loading the Neovim API: {}", e);
+            std::process::exit(1);
+        }
+    };
 
     let mut functions: Option<Vec<Function>> = None;
     if let Value::Map(map) = api {
@@ -402,8 +727,12 @@ fn main() {
         }
     }
 
-    match generate_api(functions) {
+    let docs = load_docs();
+    match generate_api(functions, &docs) {
         Ok(_) => (),
-        Err(e) => println!("Error generating API: {}", e)
+        Err(e) => {
+            println!("Error generating API: {}", e);
+            std::process::exit(1);
+        }
     };
 }
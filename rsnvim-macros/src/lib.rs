@@ -0,0 +1,158 @@
+//! # rsnvim-macros
+//!
+//! Procedural macros for `rsnvim`, re-exported from the `rsnvim` crate.
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, FnArg, ItemFn, LitStr, ReturnType, Type};
+
+/// The options given to `#[export(...)]`
+#[derive(Default)]
+struct ExportArgs {
+    name: Option<LitStr>,
+    notification: bool,
+}
+
+/// Export a function as an RPC method, to be registered with
+/// `HandlerRouter::export`
+#[proc_macro_attribute]
+pub fn export(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut args = ExportArgs::default();
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("name") {
+            args.name = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("notification") {
+            args.notification = true;
+            Ok(())
+        } else {
+            Err(meta.error("expected `name = \"...\"` or `notification`"))
+        }
+    });
+    parse_macro_input!(attr with parser);
+    let function = parse_macro_input!(item as ItemFn);
+
+    match expand(args, function) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+/// Whether `ty` is `&mut Nvim`, which is passed the handler's `Nvim` handle
+fn is_nvim(ty: &Type) -> bool {
+    match ty {
+        Type::Reference(reference) if reference.mutability.is_some() => match &*reference.elem {
+            Type::Path(path) => path
+                .path
+                .segments
+                .last()
+                .is_some_and(|segment| segment.ident == "Nvim"),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Whether the function returns a `Result`, rather than a plain value
+fn returns_result(output: &ReturnType) -> bool {
+    match output {
+        ReturnType::Type(_, ty) => match &**ty {
+            Type::Path(path) => path
+                .path
+                .segments
+                .last()
+                .is_some_and(|segment| segment.ident == "Result"),
+            _ => false,
+        },
+        ReturnType::Default => false,
+    }
+}
+
+fn expand(args: ExportArgs, function: ItemFn) -> syn::Result<proc_macro2::TokenStream> {
+    let sig = &function.sig;
+    if !sig.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &sig.generics,
+            "exported functions can't be generic",
+        ));
+    }
+    if let Some(asyncness) = &sig.asyncness {
+        return Err(syn::Error::new_spanned(
+            asyncness,
+            "exported functions can't be async",
+        ));
+    }
+
+    // Build the call, passing the `Nvim` handle to a `&mut Nvim` parameter
+    // and decoding the rest from the RPC parameters in order
+    let mut nvim = quote!(_nvim);
+    let mut call_args = Vec::new();
+    let mut param_names = Vec::new();
+    let mut param_types = Vec::new();
+    for input in &sig.inputs {
+        let ty = match input {
+            FnArg::Typed(pat) => &pat.ty,
+            FnArg::Receiver(receiver) => {
+                return Err(syn::Error::new_spanned(
+                    receiver,
+                    "exported functions can't take `self`",
+                ))
+            }
+        };
+        if is_nvim(ty) {
+            nvim = quote!(nvim);
+            call_args.push(quote!(nvim));
+        } else {
+            let name = format_ident!("arg{}", param_names.len());
+            call_args.push(quote!(#name));
+            param_names.push(name);
+            param_types.push(ty.clone());
+        }
+    }
+    if param_names.len() > 6 {
+        return Err(syn::Error::new_spanned(
+            &sig.inputs,
+            "exported functions can take at most 6 RPC parameters",
+        ));
+    }
+
+    let ident = &sig.ident;
+    let vis = &function.vis;
+    let method = args
+        .name
+        .unwrap_or_else(|| LitStr::new(&ident.to_string(), Span::call_site()));
+    let call = quote!(#ident(#(#call_args),*));
+    let result = if returns_result(&sig.output) {
+        quote!(#call.map_err(::std::convert::Into::into))
+    } else {
+        quote!(::std::result::Result::Ok(#call))
+    };
+    let register = if args.notification {
+        quote!(on_notification)
+    } else {
+        quote!(on_request)
+    };
+
+    // A braced struct only occupies the type namespace, so it can share the
+    // function's name
+    Ok(quote! {
+        #function
+
+        #[allow(non_camel_case_types)]
+        #[doc(hidden)]
+        #vis struct #ident {}
+
+        impl ::rsnvim::handler::Export for #ident {
+            const METHOD: &'static str = #method;
+
+            fn register(router: &mut ::rsnvim::handler::HandlerRouter) {
+                router.#register(
+                    #method,
+                    |(#(#param_names,)*): (#(#param_types,)*), #nvim: &mut ::rsnvim::api::Nvim| {
+                        #result
+                    },
+                );
+            }
+        }
+    })
+}
@@ -0,0 +1,179 @@
+use rmpv::Value;
+use std::marker::PhantomData;
+
+use crate::api::convert::FromValue;
+use crate::api::*;
+use crate::error::Error;
+use crate::value_vec;
+
+/// A queue of API calls submitted together using `nvim_call_atomic`
+///
+/// Each queued call returns a `BatchSlot` used to retrieve its typed result
+/// once the batch has been submitted with `Nvim::call_batch`. All of the calls
+/// are made in a single round-trip, and Neovim stops at the first call which
+/// fails.
+///
+/// # Example
+///
+/// ```no_run
+/// use rmpv::Value;
+/// use rsnvim::api::{CallBatch, Nvim};
+///
+/// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+/// nvim.start_event_loop(None, None);
+///
+/// let mut batch = CallBatch::new();
+/// batch.call::<()>("nvim_set_option_value", vec![
+///     Value::from("number"),
+///     Value::from(true),
+///     Value::Map(Vec::new()),
+/// ]);
+/// let line = batch.call::<String>("nvim_get_current_line", Vec::new());
+///
+/// let results = nvim.call_batch(batch).unwrap();
+/// println!("{}", results.get(&line).unwrap());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CallBatch {
+    calls: Vec<Value>,
+}
+
+/// The position of a call within a `CallBatch`, typed by its result
+pub struct BatchSlot<T> {
+    index: usize,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> BatchSlot<T> {
+    /// The position of the call within the batch
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl CallBatch {
+    pub fn new() -> Self {
+        CallBatch { calls: Vec::new() }
+    }
+
+    /// Queue a call to the API method `method`
+    ///
+    /// The returned slot retrieves the result as a `T`.
+    pub fn call<T: FromValue>(&mut self, method: &str, args: Vec<Value>) -> BatchSlot<T> {
+        self.calls
+            .push(Value::Array(vec![Value::from(method), Value::from(args)]));
+        BatchSlot {
+            index: self.calls.len() - 1,
+            marker: PhantomData,
+        }
+    }
+
+    /// The number of queued calls
+    pub fn len(&self) -> usize {
+        self.calls.len()
+    }
+
+    /// Whether no calls have been queued
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+}
+
+/// The call within a batch which failed
+#[derive(Clone, Debug, PartialEq)]
+pub struct BatchError {
+    /// The position of the failed call within the batch
+    pub index: usize,
+    /// The error message reported by Neovim
+    pub message: String,
+}
+
+/// The results of a submitted `CallBatch`
+#[derive(Clone, Debug, PartialEq)]
+pub struct BatchResults {
+    results: Vec<Value>,
+    error: Option<BatchError>,
+}
+
+impl BatchResults {
+    /// Get the result of the call at `slot`
+    ///
+    /// Returns an `RpcError` if the call failed or wasn't made because an
+    /// earlier call failed.
+    pub fn get<T: FromValue>(&self, slot: &BatchSlot<T>) -> Result<T, Error> {
+        T::from_value(self.result(slot.index)?)
+    }
+
+    /// Get the untyped result of the call at `index`
+    pub(crate) fn result(&self, index: usize) -> Result<Value, Error> {
+        if let Some(value) = self.results.get(index) {
+            return Ok(value.clone());
+        }
+        match &self.error {
+            Some(error) if error.index == index => Err(Error::RpcError(error.message.clone())),
+            Some(error) => Err(Error::RpcError(format!(
+                "Call {} was not made as call {} failed",
+                index, error.index
+            ))),
+            None => Err(Error::DecodingError(format!(
+                "Missing result for call {}",
+                index
+            ))),
+        }
+    }
+
+    /// Decode the response to `nvim_call_atomic`
+    pub(crate) fn from_response(ret: Value) -> Result<Self, Error> {
+        let invalid = || Error::DecodingError("Invalid nvim_call_atomic response".to_string());
+        let (results, error) = match ret {
+            Value::Array(mut v) if v.len() == 2 => {
+                let error = v.pop().unwrap();
+                (v.pop().unwrap(), error)
+            }
+            _ => return Err(invalid()),
+        };
+        let results = match results {
+            Value::Array(results) => results,
+            _ => return Err(invalid()),
+        };
+        // Errors have the form [index, error type, message]
+        let error = match error {
+            Value::Nil => None,
+            Value::Array(e) if e.len() == 3 => Some(BatchError {
+                index: e[0].as_u64().ok_or_else(invalid)? as usize,
+                message: e[2].as_str().ok_or_else(invalid)?.to_string(),
+            }),
+            _ => return Err(invalid()),
+        };
+
+        Ok(BatchResults { results, error })
+    }
+
+    /// The call which failed, if any
+    pub fn error(&self) -> Option<&BatchError> {
+        self.error.as_ref()
+    }
+
+    /// Whether every call in the batch succeeded
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// The raw results of the calls made before any failure
+    pub fn values(&self) -> &[Value] {
+        &self.results
+    }
+}
+
+impl Nvim {
+    /// Submit the calls queued in `batch` using a single `nvim_call_atomic`
+    ///
+    /// A failing call doesn't make this return an error; instead it is
+    /// reported by `BatchResults::error` and by `BatchResults::get` for that
+    /// call and those after it.
+    pub fn call_batch(&mut self, batch: CallBatch) -> Result<BatchResults, Error> {
+        let session = self.session.lock().unwrap().clone();
+        let ret = session.call("nvim_call_atomic", value_vec!(batch.calls))?;
+        BatchResults::from_response(ret)
+    }
+}
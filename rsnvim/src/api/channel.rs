@@ -0,0 +1,240 @@
+use rmpv::Value;
+use std::sync::{Arc, Mutex};
+
+use crate::api::convert::FromValue;
+use crate::api::*;
+use crate::error::Error;
+use crate::session::Session;
+
+/// What a channel is connected to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelStream {
+    /// Neovim's own stdin and stdout, e.g. for `nvim --embed`
+    Stdio,
+    /// Neovim's stderr
+    Stderr,
+    /// A socket, e.g. a client connected to `nvim --listen`
+    Socket,
+    /// A process started with `jobstart()`
+    Job,
+}
+
+impl FromValue for ChannelStream {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        match value.as_str() {
+            Some("stdio") => Ok(ChannelStream::Stdio),
+            Some("stderr") => Ok(ChannelStream::Stderr),
+            Some("socket") => Ok(ChannelStream::Socket),
+            Some("job") => Ok(ChannelStream::Job),
+            _ => Err(Error::DecodingError(format!(
+                "Unknown channel stream {}",
+                value
+            ))),
+        }
+    }
+}
+
+/// How the data sent over a channel is interpreted
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelMode {
+    /// Raw bytes, passed to callbacks
+    Bytes,
+    /// Output of a terminal buffer
+    Terminal,
+    /// MessagePack-RPC, as used by API clients
+    Rpc,
+}
+
+impl FromValue for ChannelMode {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        match value.as_str() {
+            Some("bytes") => Ok(ChannelMode::Bytes),
+            Some("terminal") => Ok(ChannelMode::Terminal),
+            Some("rpc") => Ok(ChannelMode::Rpc),
+            _ => Err(Error::DecodingError(format!(
+                "Unknown channel mode {}",
+                value
+            ))),
+        }
+    }
+}
+
+impl FromValue for ClientType {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        match value.as_str() {
+            Some("remote") => Ok(ClientType::Remote),
+            Some("msgpack-rpc") => Ok(ClientType::MsgpackRpc),
+            Some("ui") => Ok(ClientType::Ui),
+            Some("embedder") => Ok(ClientType::Embedder),
+            Some("host") => Ok(ClientType::Host),
+            Some("plugin") => Ok(ClientType::Plugin),
+            _ => Err(Error::DecodingError(format!(
+                "Unknown client type {}",
+                value
+            ))),
+        }
+    }
+}
+
+/// The version a client announced with `nvim_set_client_info`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClientVersion {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    pub prerelease: Option<String>,
+    pub commit: Option<String>,
+}
+
+/// The details a client announced with `nvim_set_client_info`
+///
+/// See `ClientInfoBuilder` for announcing them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelClient {
+    pub name: String,
+    pub version: ClientVersion,
+    /// The kind of client, or `None` if it announced one Neovim doesn't
+    /// document
+    pub client_type: Option<ClientType>,
+    /// The methods the client handles, sorted
+    pub methods: Vec<String>,
+    /// Other attributes, e.g. `website` or `license`
+    pub attributes: Vec<(String, Value)>,
+}
+
+impl FromValue for ChannelClient {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        let name = value["name"]
+            .as_str()
+            .ok_or_else(|| Error::DecodingError(format!("Invalid client info {}", value)))?;
+        let version = &value["version"];
+        let string = |key: &str| version[key].as_str().map(str::to_string);
+        let mut methods: Vec<String> = match value["methods"].as_map() {
+            Some(methods) => methods
+                .iter()
+                .filter_map(|(method, _)| method.as_str().map(str::to_string))
+                .collect(),
+            None => Vec::new(),
+        };
+        methods.sort_unstable();
+        let attributes = match value["attributes"].as_map() {
+            Some(attributes) => attributes
+                .iter()
+                .filter_map(|(key, value)| Some((key.as_str()?.to_string(), value.clone())))
+                .collect(),
+            None => Vec::new(),
+        };
+        Ok(ChannelClient {
+            name: name.to_string(),
+            version: ClientVersion {
+                major: version["major"].as_u64().unwrap_or(0),
+                minor: version["minor"].as_u64().unwrap_or(0),
+                patch: version["patch"].as_u64().unwrap_or(0),
+                prerelease: string("prerelease"),
+                commit: string("commit"),
+            },
+            client_type: ClientType::from_value(value["type"].clone()).ok(),
+            methods,
+            attributes,
+        })
+    }
+}
+
+/// A channel of the connected Neovim, as returned by `nvim_get_chan_info`
+///
+/// # Example
+///
+/// ```no_run
+/// use rsnvim::api::{ChannelMode, Nvim};
+///
+/// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+/// nvim.start_event_loop(None, None);
+///
+/// let own = nvim.channel_id().unwrap();
+/// for channel in nvim.channels().unwrap() {
+///     if channel.mode == ChannelMode::Rpc && channel.id != own {
+///         let name = channel.client.as_ref().map_or("unknown", |client| &client.name);
+///         println!("Channel {} is used by {}", channel.id, name);
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelInfo {
+    pub id: i64,
+    pub stream: ChannelStream,
+    pub mode: ChannelMode,
+    /// The command line of a job
+    pub argv: Vec<String>,
+    /// The pseudoterminal of a job started with `pty`
+    pub pty: Option<String>,
+    /// The terminal buffer of a channel in `Terminal` mode
+    pub buffer: Option<Buffer>,
+    /// The client's details, if it announced them
+    pub client: Option<ChannelClient>,
+}
+
+impl ChannelInfo {
+    fn from_value(value: Value, session: &Arc<Mutex<Session>>) -> Result<Self, Error> {
+        let id = value["id"]
+            .as_i64()
+            .ok_or_else(|| Error::DecodingError(format!("Invalid channel info {}", value)))?;
+        let argv = match &value["argv"] {
+            Value::Nil => Vec::new(),
+            argv => Vec::from_value(argv.clone())?,
+        };
+        let client = match &value["client"] {
+            Value::Map(map) if !map.is_empty() => {
+                Some(ChannelClient::from_value(value["client"].clone())?)
+            }
+            _ => None,
+        };
+        Ok(ChannelInfo {
+            id,
+            stream: ChannelStream::from_value(value["stream"].clone())?,
+            mode: ChannelMode::from_value(value["mode"].clone())?,
+            argv,
+            pty: value["pty"].as_str().map(str::to_string),
+            buffer: value["buffer"]
+                .as_i64()
+                .map(|id| Buffer::new(id, session.clone())),
+            client,
+        })
+    }
+}
+
+impl Nvim {
+    /// Get every open channel, including this client's own
+    pub fn channels(&self) -> Result<Vec<ChannelInfo>, Error> {
+        let channels: Vec<Value> = self.call_typed("nvim_list_chans", Vec::new())?;
+        channels
+            .into_iter()
+            .map(|channel| ChannelInfo::from_value(channel, &self.session))
+            .collect()
+    }
+
+    /// Get the channel with ID `id`, or `None` if it isn't open
+    pub fn channel(&self, id: i64) -> Result<Option<ChannelInfo>, Error> {
+        let channel: Value = self.call_typed("nvim_get_chan_info", vec![Value::from(id)])?;
+        match channel.as_map() {
+            Some(map) if !map.is_empty() => {
+                ChannelInfo::from_value(channel, &self.session).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Close the channel with ID `id` using `chanclose()`, disconnecting
+    /// its client or stopping its job
+    ///
+    /// Returns an error if the channel isn't open.
+    pub fn close_channel(&self, id: i64) -> Result<(), Error> {
+        let closed: i64 = self.call_typed(
+            "nvim_call_function",
+            vec![Value::from("chanclose"), Value::from(vec![Value::from(id)])],
+        )?;
+        if closed == 0 {
+            return Err(Error::InvalidArgument(format!("Channel {} isn't open", id)));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+use rmpv::Value;
+
+use crate::api::convert::AsValue;
+use crate::api::*;
+use crate::error::Error;
+use crate::handler::HandlerRouter;
+
+/// The details a client announces about itself with `Nvim::announce`
+///
+/// These are shown by `nvim_get_chan_info`, `nvim_list_chans` and
+/// `:checkhealth`.
+///
+/// # Example
+///
+/// ```no_run
+/// use rsnvim::api::{ClientInfoBuilder, ClientType, Nvim};
+/// use rsnvim::handler::HandlerRouter;
+///
+/// let mut router = HandlerRouter::new();
+/// router.on_request("add", |(a, b): (i64, i64), _nvim| Ok(a + b));
+///
+/// let info = ClientInfoBuilder::new("my-tool")
+///     .version(0, 3, 1)
+///     .client_type(ClientType::Host)
+///     .methods_from(&router)
+///     .attribute("website", "https://example.com");
+///
+/// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+/// let (requests, notifications) = router.into_handlers();
+/// nvim.start_event_loop(requests, notifications);
+/// nvim.announce(&info).unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClientInfoBuilder {
+    name: String,
+    version: Vec<(Value, Value)>,
+    client_type: ClientType,
+    methods: Vec<(Value, Value)>,
+    attributes: Vec<(Value, Value)>,
+}
+
+impl ClientInfoBuilder {
+    /// Start describing the client `name`, e.g. the name of the crate
+    pub fn new(name: &str) -> Self {
+        ClientInfoBuilder {
+            name: name.to_string(),
+            version: Vec::new(),
+            client_type: ClientType::Remote,
+            methods: Vec::new(),
+            attributes: Vec::new(),
+        }
+    }
+
+    pub fn version(mut self, major: u64, minor: u64, patch: u64) -> Self {
+        for (key, value) in [("major", major), ("minor", minor), ("patch", patch)] {
+            set(&mut self.version, key, Value::from(value));
+        }
+        self
+    }
+
+    /// Set a prerelease label, e.g. `dev`
+    pub fn prerelease(mut self, prerelease: &str) -> Self {
+        set(&mut self.version, "prerelease", Value::from(prerelease));
+        self
+    }
+
+    /// Set the commit hash the client was built from
+    pub fn commit(mut self, commit: &str) -> Self {
+        set(&mut self.version, "commit", Value::from(commit));
+        self
+    }
+
+    pub fn client_type(mut self, client_type: ClientType) -> Self {
+        self.client_type = client_type;
+        self
+    }
+
+    /// Declare that the client handles requests for `method`
+    pub fn method(mut self, method: &str) -> Self {
+        set(&mut self.methods, method, Value::Map(Vec::new()));
+        self
+    }
+
+    /// Declare that the client handles notifications for `method`
+    pub fn notification_method(mut self, method: &str) -> Self {
+        let spec = vec![(Value::from("async"), Value::from(true))];
+        set(&mut self.methods, method, Value::Map(spec));
+        self
+    }
+
+    /// Declare each method handled by `router`
+    pub fn methods_from(mut self, router: &HandlerRouter) -> Self {
+        let mut requests: Vec<&str> = router.request_methods().collect();
+        let mut notifications: Vec<&str> = router.notification_methods().collect();
+        requests.sort_unstable();
+        notifications.sort_unstable();
+        for method in notifications {
+            self = self.notification_method(method);
+        }
+        for method in requests {
+            self = self.method(method);
+        }
+        self
+    }
+
+    /// Set an arbitrary attribute, e.g. `website`, `license` or `logo`
+    pub fn attribute(mut self, key: &str, value: &str) -> Self {
+        set(&mut self.attributes, key, Value::from(value));
+        self
+    }
+
+    /// The arguments to `nvim_set_client_info`
+    pub(crate) fn args(&self) -> Vec<Value> {
+        vec![
+            Value::from(self.name.as_str()),
+            Value::Map(self.version.clone()),
+            self.client_type.convert(),
+            Value::Map(self.methods.clone()),
+            Value::Map(self.attributes.clone()),
+        ]
+    }
+}
+
+/// Set `key` of a dictionary to `value`, replacing any previous value
+fn set(map: &mut Vec<(Value, Value)>, key: &str, value: Value) {
+    map.retain(|(k, _)| k.as_str() != Some(key));
+    map.push((Value::from(key), value));
+}
+
+impl Nvim {
+    /// Describe this client to Neovim using `nvim_set_client_info`
+    ///
+    /// The details are sent again whenever the session reconnects.
+    pub fn announce(&mut self, info: &ClientInfoBuilder) -> Result<(), Error> {
+        let session = self.session.lock().unwrap().clone();
+        session.announce(info.args())
+    }
+}
@@ -0,0 +1,44 @@
+use rmpv::Value;
+use std::sync::{Arc, Mutex};
+
+use crate::api::convert::{from_handle, FromValue};
+use crate::error::Error;
+use crate::session::Session;
+
+/// A buffer, tabpage or window, which Neovim sends as an EXT value
+pub(crate) trait Handle: Sized {
+    /// The EXT type code of the handle
+    const EXT: i8;
+    /// The handle's description in errors, e.g. `a buffer`
+    const DESCRIPTION: &'static str;
+
+    fn from_id(id: i64, session: Arc<Mutex<Session>>) -> Self;
+}
+
+/// An object whose API functions are called over a session
+///
+/// The generated API calls Neovim through these methods, so changes to how
+/// every call is made only need to be made here.
+pub(crate) trait Remote {
+    fn session(&self) -> &Arc<Mutex<Session>>;
+
+    /// Call `method`, returning the result as it was received
+    ///
+    /// The lock is only held to clone the session, so other threads sharing
+    /// it can make calls while this one waits for its response.
+    fn call_raw(&self, method: &str, args: Vec<Value>) -> Result<Value, Error> {
+        let session = self.session().lock().unwrap().clone();
+        session.call(method, args)
+    }
+
+    /// Call `method`, converting the result to `T`
+    fn call_typed<T: FromValue>(&self, method: &str, args: Vec<Value>) -> Result<T, Error> {
+        T::from_value(self.call_raw(method, args)?)
+    }
+
+    /// Convert a handle returned by Neovim to `H`, sharing this session
+    fn handle<H: Handle>(&self, value: Value) -> Result<H, Error> {
+        let id = from_handle(value, H::EXT, H::DESCRIPTION)?;
+        Ok(H::from_id(id, self.session().clone()))
+    }
+}
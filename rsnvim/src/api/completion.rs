@@ -0,0 +1,210 @@
+use rmpv::Value;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::api::convert::AsValue;
+use crate::api::*;
+use crate::blocking::check_blocking;
+use crate::error::Error;
+
+/// Lua run by `Nvim::prompt_input`, notifying this client with the text
+/// entered, or nil if the prompt was cancelled
+///
+/// The default `vim.ui.input` blocks until the user answers, so it is
+/// scheduled to let `nvim_exec_lua` return straight away.
+const PROMPT_INPUT_LUA: &str = r#"
+local channel, method, opts = ...
+vim.schedule(function()
+    vim.ui.input(opts, function(text)
+        vim.rpcnotify(channel, method, text)
+    end)
+end)
+"#;
+
+/// Counter used to give each prompt a unique notification method
+static PROMPT_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// What to complete, as accepted by `getcompletion()` and the `-complete`
+/// attribute of user commands
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CompletionKind {
+    Buffer,
+    Color,
+    Command,
+    /// A whole command line, e.g. `edit fi` completes file names
+    Cmdline,
+    Dir,
+    Environment,
+    Event,
+    File,
+    Filetype,
+    Function,
+    Help,
+    Highlight,
+    Lua,
+    Mapping,
+    Option,
+    Shellcmd,
+    Var,
+    /// Any other kind, e.g. `customlist,MyComplete`
+    Other(String),
+}
+
+impl CompletionKind {
+    fn as_str(&self) -> &str {
+        match self {
+            CompletionKind::Buffer => "buffer",
+            CompletionKind::Color => "color",
+            CompletionKind::Command => "command",
+            CompletionKind::Cmdline => "cmdline",
+            CompletionKind::Dir => "dir",
+            CompletionKind::Environment => "environment",
+            CompletionKind::Event => "event",
+            CompletionKind::File => "file",
+            CompletionKind::Filetype => "filetype",
+            CompletionKind::Function => "function",
+            CompletionKind::Help => "help",
+            CompletionKind::Highlight => "highlight",
+            CompletionKind::Lua => "lua",
+            CompletionKind::Mapping => "mapping",
+            CompletionKind::Option => "option",
+            CompletionKind::Shellcmd => "shellcmd",
+            CompletionKind::Var => "var",
+            CompletionKind::Other(kind) => kind,
+        }
+    }
+}
+
+impl AsValue for CompletionKind {
+    fn convert(&self) -> Value {
+        Value::from(self.as_str())
+    }
+}
+
+/// Options for `Nvim::prompt_input`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InputOpts {
+    prompt: Option<String>,
+    default: Option<String>,
+    completion: Option<CompletionKind>,
+}
+
+impl InputOpts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The text shown before the input
+    pub fn prompt(mut self, prompt: &str) -> Self {
+        self.prompt = Some(prompt.to_string());
+        self
+    }
+
+    /// The text the input starts with
+    pub fn default_text(mut self, default: &str) -> Self {
+        self.default = Some(default.to_string());
+        self
+    }
+
+    /// Complete the input with `<Tab>` as Neovim would for `kind`
+    pub fn completion(mut self, kind: CompletionKind) -> Self {
+        self.completion = Some(kind);
+        self
+    }
+}
+
+impl From<InputOpts> for Dict {
+    fn from(opts: InputOpts) -> Self {
+        let mut dict = Dict::new();
+        if let Some(prompt) = opts.prompt {
+            dict.insert("prompt", prompt);
+        }
+        if let Some(default) = opts.default {
+            dict.insert("default", default);
+        }
+        if let Some(kind) = opts.completion {
+            dict.insert("completion", kind.as_str());
+        }
+        dict
+    }
+}
+
+impl Nvim {
+    /// Get the completions of `pattern` Neovim would offer for `kind`,
+    /// using `getcompletion()`
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::api::{CompletionKind, Nvim};
+    ///
+    /// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+    /// nvim.start_event_loop(None, None);
+    ///
+    /// for tag in nvim.get_completion("nvim_buf_", CompletionKind::Help).unwrap() {
+    ///     println!("{}", tag);
+    /// }
+    /// ```
+    pub fn get_completion(
+        &self,
+        pattern: &str,
+        kind: CompletionKind,
+    ) -> Result<Vec<String>, Error> {
+        let args = vec![Value::from(pattern), kind.convert()];
+        self.call_typed(
+            "nvim_call_function",
+            vec![Value::from("getcompletion"), Value::from(args)],
+        )
+    }
+
+    /// Prompt the user for input with `vim.ui.input`, blocking until they
+    /// answer
+    ///
+    /// Returns `None` if the prompt was cancelled. Plugins replacing
+    /// `vim.ui.input`, e.g. with a floating window, are used too. The event
+    /// loop must be running.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::api::{CompletionKind, InputOpts, Nvim};
+    ///
+    /// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+    /// nvim.start_event_loop(None, None);
+    ///
+    /// let opts = InputOpts::new()
+    ///     .prompt("Open file: ")
+    ///     .completion(CompletionKind::File);
+    /// if let Some(path) = nvim.prompt_input(opts).unwrap() {
+    ///     nvim.command(format!("edit {}", path)).unwrap();
+    /// }
+    /// ```
+    pub fn prompt_input(&mut self, opts: InputOpts) -> Result<Option<String>, Error> {
+        check_blocking("Waiting for input")?;
+        let channel = self.channel_id()?;
+        let method = format!(
+            "rsnvim_input:{}",
+            PROMPT_COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+
+        let answers = self.subscribe_notifications(&method);
+        let result = self.exec_lua(
+            PROMPT_INPUT_LUA,
+            vec![
+                Value::from(channel),
+                Value::from(method.as_str()),
+                Dict::from(opts).into(),
+            ],
+        );
+        let answer = result.and_then(|_| {
+            answers.recv().map_err(|_| {
+                Error::ConnectionError("The connection closed while prompting".to_string())
+            })
+        });
+        self.unsubscribe_notifications(&method);
+
+        match answer?.into_iter().next() {
+            Some(Value::String(text)) => Ok(text.into_str()),
+            _ => Ok(None),
+        }
+    }
+}
@@ -3,8 +3,13 @@ use rmpv::Value;
 use crate::api::{
     Buffer,
     Tabpage,
-    Window
+    Window,
+    BUFFER_EXT,
+    TABPAGE_EXT,
+    WINDOW_EXT
 };
+use crate::error::Error;
+use std::num::Wrapping;
 
 /// Trait to convert any type to rmpv::Value
 pub trait AsValue {
@@ -22,12 +27,13 @@ macro_rules! impl_asvalue {
     };
 }
 
-/// Macro to implement AsValue trait for a tuple of builtin types
+/// Macro to implement AsValue trait for a tuple of builtin types, sent as
+/// an array of its elements
 macro_rules! impl_asvalue_tuple {
-    ($($arg:ty), +) => {
+    ($($index:tt: $arg:ty), +) => {
         impl AsValue for ($($arg), +) {
             fn convert(&self) -> Value {
-                Value::from(0)
+                Value::Array(vec![$(self.$index.convert()), +])
             }
         }
     };
@@ -41,7 +47,26 @@ impl_asvalue!(bool);
 
 
 // Implement AsValue for builtin tuples
-impl_asvalue_tuple!(i64, i64);
+impl_asvalue_tuple!(0: i64, 1: i64);
+
+/// Sent as the i64 with the same bits, as Neovim's integers are i64
+impl AsValue for Wrapping<u64> {
+    fn convert(&self) -> Value {
+        Value::from(self.0 as i64)
+    }
+}
+
+impl AsValue for Wrapping<i64> {
+    fn convert(&self) -> Value {
+        Value::from(self.0)
+    }
+}
+
+impl AsValue for () {
+    fn convert(&self) -> Value {
+        Value::Nil
+    }
+}
 
 impl AsValue for Value {
     fn convert(&self) -> Value {
@@ -70,37 +95,37 @@ impl AsValue for Vec<String> {
 
 impl AsValue for Buffer {
     fn convert(&self) -> Value {
-        self.data.clone()
+        to_handle(BUFFER_EXT, self.id)
     }
 }
 
 impl AsValue for &Buffer {
     fn convert(&self) -> Value {
-        self.data.clone()
+        to_handle(BUFFER_EXT, self.id)
     }
 }
 
 impl AsValue for Tabpage {
     fn convert(&self) -> Value {
-        self.data.clone()
+        to_handle(TABPAGE_EXT, self.id)
     }
 }
 
 impl AsValue for &Tabpage {
     fn convert(&self) -> Value {
-        self.data.clone()
+        to_handle(TABPAGE_EXT, self.id)
     }
 }
 
 impl AsValue for Window {
     fn convert(&self) -> Value {
-        self.data.clone()
+        to_handle(WINDOW_EXT, self.id)
     }
 }
 
 impl AsValue for &Window {
     fn convert(&self) -> Value {
-        self.data.clone()
+        to_handle(WINDOW_EXT, self.id)
     }
 }
 
@@ -109,3 +134,215 @@ impl AsValue for Vec<(Value, Value)> {
         Value::Map(self.clone())
     }
 }
+
+impl<T: AsValue> AsValue for Option<T> {
+    fn convert(&self) -> Value {
+        match self {
+            Some(v) => v.convert(),
+            None => Value::Nil,
+        }
+    }
+}
+
+/// Trait to convert a rmpv::Value returned by Neovim to a Rust type
+pub trait FromValue: Sized {
+    fn from_value(value: Value) -> Result<Self, Error>;
+}
+
+/// Build the error returned when a value has an unexpected type
+fn type_mismatch(expected: &str, value: &Value) -> Error {
+    Error::DecodingError(format!("Expected {} but received {}", expected, value))
+}
+
+/// Create an error for an integer which doesn't fit in `ty`
+fn out_of_range(ty: &str, value: &Value) -> Error {
+    Error::DecodingError(format!("Integer {} is out of range for {}", value, ty))
+}
+
+/// Encode the integer `id` of a buffer, window or tabpage as the EXT value
+/// with type code `ext`
+pub(crate) fn to_handle(ext: i8, id: i64) -> Value {
+    let mut bytes = Vec::new();
+    // Writing to a Vec can't fail
+    rmpv::encode::write_value(&mut bytes, &Value::from(id)).unwrap();
+    Value::Ext(ext, bytes)
+}
+
+/// Decode the integer ID of a buffer, window or tabpage from the EXT value
+/// with type code `ext`
+pub(crate) fn from_handle(value: Value, ext: i8, expected: &str) -> Result<i64, Error> {
+    match &value {
+        Value::Ext(code, bytes) if *code == ext => rmpv::decode::read_value(&mut bytes.as_slice())
+            .ok()
+            .and_then(|id| id.as_i64())
+            .ok_or_else(|| type_mismatch(expected, &value)),
+        _ => Err(type_mismatch(expected, &value)),
+    }
+}
+
+/// Macro to implement FromValue trait for a builtin type
+macro_rules! impl_fromvalue {
+    ($arg:ty, $as:ident, $name:expr) => {
+        impl FromValue for $arg {
+            fn from_value(value: Value) -> Result<Self, Error> {
+                value.$as().ok_or_else(|| type_mismatch($name, &value))
+            }
+        }
+    };
+}
+
+// Implement FromValue for builtin types
+impl_fromvalue!(f64, as_f64, "a float");
+impl_fromvalue!(bool, as_bool, "a boolean");
+
+// Neovim's integers are i64, but MessagePack also encodes u64 values above
+// `i64::MAX`. Integers outside the range of the requested type are an error;
+// use `Wrapping` to reinterpret them instead.
+
+impl FromValue for i64 {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        match &value {
+            Value::Integer(n) => n.as_i64().ok_or_else(|| out_of_range("i64", &value)),
+            _ => Err(type_mismatch("an integer", &value)),
+        }
+    }
+}
+
+impl FromValue for u64 {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        match &value {
+            Value::Integer(n) => n.as_u64().ok_or_else(|| out_of_range("u64", &value)),
+            _ => Err(type_mismatch("an unsigned integer", &value)),
+        }
+    }
+}
+
+/// Accepts any integer, reinterpreting a u64 above `i64::MAX` as negative
+impl FromValue for Wrapping<i64> {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        match &value {
+            Value::Integer(n) => Ok(Wrapping(
+                n.as_i64().unwrap_or_else(|| n.as_u64().unwrap() as i64),
+            )),
+            _ => Err(type_mismatch("an integer", &value)),
+        }
+    }
+}
+
+/// Accepts any integer, reinterpreting a negative i64 as above `i64::MAX`
+impl FromValue for Wrapping<u64> {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        match &value {
+            Value::Integer(n) => Ok(Wrapping(
+                n.as_u64().unwrap_or_else(|| n.as_i64().unwrap() as u64),
+            )),
+            _ => Err(type_mismatch("an unsigned integer", &value)),
+        }
+    }
+}
+
+impl FromValue for () {
+    fn from_value(_value: Value) -> Result<Self, Error> {
+        Ok(())
+    }
+}
+
+impl FromValue for Value {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        Ok(value)
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        match value {
+            Value::String(s) => s.into_str().ok_or_else(|| {
+                Error::DecodingError(
+                    "String isn't valid UTF-8, decode it as an NvimString to keep its bytes"
+                        .to_string(),
+                )
+            }),
+            other => Err(type_mismatch("a string", &other)),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        match value {
+            Value::Array(v) => v.into_iter().map(T::from_value).collect(),
+            other => Err(type_mismatch("an array", &other)),
+        }
+    }
+}
+
+impl FromValue for Vec<(Value, Value)> {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        match value {
+            Value::Map(m) => Ok(m),
+            other => Err(type_mismatch("a dictionary", &other)),
+        }
+    }
+}
+
+impl FromValue for (i64, i64) {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        match value.as_array().map(|v| v.as_slice()) {
+            Some([a, b]) => Ok((i64::from_value(a.clone())?, i64::from_value(b.clone())?)),
+            _ => Err(type_mismatch("an array of two integers", &value)),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        match value {
+            Value::Nil => Ok(None),
+            other => Ok(Some(T::from_value(other)?)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tuple_as_array() {
+        assert_eq!(
+            (3, -4).convert(),
+            Value::Array(vec![Value::from(3), Value::from(-4)])
+        );
+        assert_eq!(
+            <(i64, i64)>::from_value((3, -4).convert()).unwrap(),
+            (3, -4)
+        );
+    }
+
+    #[test]
+    fn integer_boundaries() {
+        let above_i64 = Value::from(i64::MAX as u64 + 1);
+        assert!(matches!(
+            i64::from_value(above_i64.clone()),
+            Err(Error::DecodingError(_))
+        ));
+        assert_eq!(u64::from_value(above_i64.clone()).unwrap(), 1 << 63);
+        assert!(matches!(
+            u64::from_value(Value::from(-1)),
+            Err(Error::DecodingError(_))
+        ));
+        assert_eq!(i64::from_value(i64::MAX.convert()).unwrap(), i64::MAX);
+        assert_eq!(i64::from_value(i64::MIN.convert()).unwrap(), i64::MIN);
+        assert_eq!(u64::from_value(u64::MAX.convert()).unwrap(), u64::MAX);
+
+        // Wrapping reinterprets the bits instead
+        let Wrapping(bits) = Wrapping::<u64>::from_value(Value::from(-1)).unwrap();
+        assert_eq!(bits, u64::MAX);
+        let Wrapping(n) = Wrapping::<i64>::from_value(above_i64).unwrap();
+        assert_eq!(n, i64::MIN);
+        assert_eq!(Wrapping(u64::MAX).convert(), Value::from(-1));
+        let Wrapping(round_trip) =
+            Wrapping::<u64>::from_value(Wrapping(u64::MAX).convert()).unwrap();
+        assert_eq!(round_trip, u64::MAX);
+    }
+}
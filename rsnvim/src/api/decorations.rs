@@ -0,0 +1,223 @@
+use rmpv::Value;
+
+use crate::api::convert::FromValue;
+use crate::api::*;
+use crate::error::Error;
+
+/// Virtual text displayed alongside a line, made of highlighted chunks
+///
+/// # Example
+///
+/// ```no_run
+/// use rsnvim::api::{Nvim, VirtTextPos, VirtualText};
+///
+/// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+/// nvim.start_event_loop(None, None);
+///
+/// let ns = nvim.create_namespace("diagnostics").unwrap();
+/// let text = VirtualText::new()
+///     .chunk("● ", "DiagnosticError")
+///     .chunk("unused variable", "Comment")
+///     .pos(VirtTextPos::RightAlign);
+/// let mut buffer = nvim.get_current_buf().unwrap();
+/// buffer.set_virtual_text(ns, 0, text).unwrap();
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VirtualText {
+    /// The chunks of text and the highlight group of each
+    pub chunks: Vec<(String, String)>,
+    pub pos: Option<VirtTextPos>,
+    pub hl_mode: Option<HlMode>,
+}
+
+impl VirtualText {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append `text` highlighted with `hl_group`
+    pub fn chunk(mut self, text: &str, hl_group: &str) -> Self {
+        self.chunks.push((text.to_string(), hl_group.to_string()));
+        self
+    }
+
+    /// Set where the text is displayed, by default at the end of the line
+    pub fn pos(mut self, pos: VirtTextPos) -> Self {
+        self.pos = Some(pos);
+        self
+    }
+
+    /// Set how the text is combined with the highlights beneath it
+    pub fn hl_mode(mut self, hl_mode: HlMode) -> Self {
+        self.hl_mode = Some(hl_mode);
+        self
+    }
+
+    /// The options for `nvim_buf_set_extmark`
+    fn opts(&self) -> ExtmarkOpts {
+        let chunks: Vec<(&str, &str)> = self
+            .chunks
+            .iter()
+            .map(|(text, hl_group)| (text.as_str(), hl_group.as_str()))
+            .collect();
+        let mut opts = ExtmarkOpts::new().virt_text(&chunks);
+        if let Some(pos) = self.pos {
+            opts = opts.virt_text_pos(pos);
+        }
+        if let Some(hl_mode) = self.hl_mode {
+            opts = opts.hl_mode(hl_mode);
+        }
+        opts
+    }
+}
+
+impl<const N: usize> From<&[(&str, &str); N]> for VirtualText {
+    fn from(chunks: &[(&str, &str); N]) -> Self {
+        VirtualText::from(&chunks[..])
+    }
+}
+
+impl From<&[(&str, &str)]> for VirtualText {
+    fn from(chunks: &[(&str, &str)]) -> Self {
+        chunks
+            .iter()
+            .fold(VirtualText::new(), |text, (chunk, hl_group)| {
+                text.chunk(chunk, hl_group)
+            })
+    }
+}
+
+/// A highlighted region of a buffer
+///
+/// Positions are zero-indexed, and the end column is exclusive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HighlightRange {
+    pub hl_group: String,
+    pub start: Point,
+    pub end: Point,
+    /// The priority used when highlights overlap
+    pub priority: Option<i64>,
+    /// Continue the highlight to the end of the screen line
+    pub hl_eol: bool,
+}
+
+impl HighlightRange {
+    /// Highlight from `start` up to `end` with `hl_group`, e.g. `(row, col)`
+    pub fn new(hl_group: &str, start: impl Into<Point>, end: impl Into<Point>) -> Self {
+        HighlightRange {
+            hl_group: hl_group.to_string(),
+            start: start.into(),
+            end: end.into(),
+            priority: None,
+            hl_eol: false,
+        }
+    }
+
+    /// Highlight the whole of line `row`, up to the edge of the window
+    pub fn line(hl_group: &str, row: i64) -> Self {
+        HighlightRange {
+            hl_eol: true,
+            ..HighlightRange::new(hl_group, (row, 0), (row + 1, 0))
+        }
+    }
+
+    pub fn priority(mut self, priority: i64) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// The options for `nvim_buf_set_extmark`
+    fn opts(&self) -> ExtmarkOpts {
+        let mut opts = ExtmarkOpts::new()
+            .hl_group(&self.hl_group)
+            .end_row(self.end.row)
+            .end_col(self.end.col)
+            .hl_eol(self.hl_eol);
+        if let Some(priority) = self.priority {
+            opts = opts.priority(priority);
+        }
+        opts
+    }
+}
+
+/// An extmark, as returned by `Buffer::extmarks`
+///
+/// Positions are zero-indexed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Extmark {
+    pub id: i64,
+    pub row: i64,
+    pub col: i64,
+    /// The end of the extmark's range, if it has one
+    pub end: Option<Point>,
+    pub hl_group: Option<String>,
+    /// The extmark's virtual text, empty if it has none
+    pub virt_text: Vec<(String, String)>,
+    /// Every detail of the extmark, as returned by `nvim_buf_get_extmarks`
+    pub details: Vec<(Value, Value)>,
+}
+
+impl FromValue for Extmark {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        // Returned as [id, row, col, details]
+        let invalid = || Error::DecodingError(format!("Invalid extmark {}", value));
+        let (id, row, col) = match (value[0].as_i64(), value[1].as_i64(), value[2].as_i64()) {
+            (Some(id), Some(row), Some(col)) => (id, row, col),
+            _ => return Err(invalid()),
+        };
+        let details = &value[3];
+        let end = match (details["end_row"].as_i64(), details["end_col"].as_i64()) {
+            (Some(end_row), Some(end_col)) => Some(Point::new(end_row, end_col)),
+            _ => None,
+        };
+        // Chunks have the form [text, hl_group], with the group optional
+        let virt_text = match details["virt_text"].as_array() {
+            Some(chunks) => chunks
+                .iter()
+                .map(|chunk| {
+                    let text = chunk[0].as_str().ok_or_else(invalid)?;
+                    let hl_group = chunk[1].as_str().unwrap_or("");
+                    Ok((text.to_string(), hl_group.to_string()))
+                })
+                .collect::<Result<_, Error>>()?,
+            None => Vec::new(),
+        };
+        Ok(Extmark {
+            id,
+            row,
+            col,
+            end,
+            hl_group: details["hl_group"].as_str().map(|s| s.to_string()),
+            virt_text,
+            details: details.as_map().cloned().unwrap_or_default(),
+        })
+    }
+}
+
+impl Buffer {
+    /// Display virtual text on the (zero-indexed) line `line`, returning the
+    /// ID of the extmark
+    pub fn set_virtual_text(
+        &mut self,
+        ns_id: i64,
+        line: i64,
+        text: impl Into<VirtualText>,
+    ) -> Result<i64, Error> {
+        self.set_extmark(ns_id, line, 0, text.into().opts())
+    }
+
+    /// Highlight `range`, returning the ID of the extmark
+    ///
+    /// Unlike `add_highlight`, the highlight can span several lines and moves
+    /// with the text as it is edited.
+    pub fn highlight_region(&mut self, ns_id: i64, range: &HighlightRange) -> Result<i64, Error> {
+        self.set_extmark(ns_id, range.start.row, range.start.col, range.opts())
+    }
+
+    /// Get every extmark in the namespace `ns_id`, in order of position
+    pub fn extmarks(&mut self, ns_id: i64) -> Result<Vec<Extmark>, Error> {
+        let opts = vec![(Value::from("details"), Value::from(true))];
+        let extmarks = self.get_extmarks(ns_id, Value::from(0), Value::from(-1), opts)?;
+        extmarks.into_iter().map(Extmark::from_value).collect()
+    }
+}
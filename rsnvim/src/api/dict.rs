@@ -0,0 +1,184 @@
+use rmpv::Value;
+use std::ops::Index;
+
+use crate::api::convert::{AsValue, FromValue};
+use crate::error::Error;
+
+/// A Neovim `Dictionary`, keeping its entries in the order received
+///
+/// Keys are looked up by string, and the typed getters return `None` both
+/// for missing keys and for values of another type.
+///
+/// # Example
+///
+/// ```no_run
+/// use rsnvim::api::{Dict, Nvim};
+///
+/// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+/// nvim.start_event_loop(None, None);
+///
+/// let mode = nvim.get_mode().unwrap();
+/// println!("{:?}", mode.get_str("mode"));
+///
+/// let opts = Dict::new().with("scope", "local");
+/// let number = nvim.get_option_value("number", opts).unwrap();
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Dict(Vec<(Value, Value)>);
+
+impl Dict {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the value of `key`
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.0
+            .iter()
+            .find(|(k, _)| k.as_str() == Some(key))
+            .map(|(_, v)| v)
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key)?.as_str()
+    }
+
+    pub fn get_i64(&self, key: &str) -> Option<i64> {
+        self.get(key)?.as_i64()
+    }
+
+    pub fn get_f64(&self, key: &str) -> Option<f64> {
+        self.get(key)?.as_f64()
+    }
+
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        self.get(key)?.as_bool()
+    }
+
+    /// Get the value of `key` as a nested `Dict`
+    pub fn get_dict(&self, key: &str) -> Option<Dict> {
+        match self.get(key)? {
+            Value::Map(map) => Some(Dict(map.clone())),
+            _ => None,
+        }
+    }
+
+    /// Decode the value of `key` as a `T`, returning `None` if it is missing
+    pub fn get_as<T: FromValue>(&self, key: &str) -> Result<Option<T>, Error> {
+        self.get(key).cloned().map(T::from_value).transpose()
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Set `key` to `value`, replacing any existing value in place
+    pub fn insert(&mut self, key: &str, value: impl Into<Value>) {
+        let value = value.into();
+        match self.0.iter_mut().find(|(k, _)| k.as_str() == Some(key)) {
+            Some((_, v)) => *v = value,
+            None => self.0.push((Value::from(key), value)),
+        }
+    }
+
+    /// Set `key` to `value`, returning the dictionary
+    pub fn with(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.insert(key, value);
+        self
+    }
+
+    /// Remove `key`, returning its value
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        let index = self.0.iter().position(|(k, _)| k.as_str() == Some(key))?;
+        Some(self.0.remove(index).1)
+    }
+
+    /// The keys which are strings, in order
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().filter_map(|(k, _)| k.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(Value, Value)> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The entries of the dictionary, in order
+    pub fn into_inner(self) -> Vec<(Value, Value)> {
+        self.0
+    }
+}
+
+/// Returns `Value::Nil` for missing keys, like indexing a `Value`
+impl Index<&str> for Dict {
+    type Output = Value;
+
+    fn index(&self, key: &str) -> &Value {
+        static NIL: Value = Value::Nil;
+        self.get(key).unwrap_or(&NIL)
+    }
+}
+
+impl From<Vec<(Value, Value)>> for Dict {
+    fn from(entries: Vec<(Value, Value)>) -> Self {
+        Dict(entries)
+    }
+}
+
+impl From<Dict> for Vec<(Value, Value)> {
+    fn from(dict: Dict) -> Self {
+        dict.0
+    }
+}
+
+impl From<Dict> for Value {
+    fn from(dict: Dict) -> Self {
+        Value::Map(dict.0)
+    }
+}
+
+impl TryFrom<Value> for Dict {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Error> {
+        Dict::from_value(value)
+    }
+}
+
+impl<K: Into<Value>, V: Into<Value>> FromIterator<(K, V)> for Dict {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Dict(
+            iter.into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        )
+    }
+}
+
+impl IntoIterator for Dict {
+    type Item = (Value, Value);
+    type IntoIter = std::vec::IntoIter<(Value, Value)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl FromValue for Dict {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        Vec::<(Value, Value)>::from_value(value).map(Dict)
+    }
+}
+
+impl AsValue for Dict {
+    fn convert(&self) -> Value {
+        Value::Map(self.0.clone())
+    }
+}
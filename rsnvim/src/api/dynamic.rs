@@ -0,0 +1,94 @@
+use rmpv::Value;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use crate::api::*;
+use crate::error::Error;
+use crate::session::Session;
+
+/// Untyped access to the API functions of the connected Neovim
+///
+/// Calls are validated against the metadata returned by `Nvim::api_info`,
+/// so the wrong number or type of arguments is reported before anything is
+/// sent. Useful for REPLs and scripting bridges which only know the function
+/// to call at runtime.
+///
+/// # Example
+///
+/// ```no_run
+/// use rmpv::Value;
+/// use rsnvim::api::{DynamicApi, Nvim};
+///
+/// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+/// nvim.start_event_loop(None, None);
+///
+/// let api = DynamicApi::new(&mut nvim)
+///     .unwrap()
+///     .allow(["nvim_get_current_line", "nvim_set_current_line"]);
+/// api.invoke("nvim_set_current_line", vec![Value::from("hello")])
+///     .unwrap();
+/// assert!(api.invoke("nvim_command", vec![Value::from("quit")]).is_err());
+/// ```
+#[derive(Clone)]
+pub struct DynamicApi {
+    session: Arc<Mutex<Session>>,
+    info: Arc<ApiInfo>,
+    allowlist: Option<HashSet<String>>,
+}
+
+impl DynamicApi {
+    /// Create a facade over every API function of the connected Neovim
+    pub fn new(nvim: &mut Nvim) -> Result<Self, Error> {
+        Ok(DynamicApi {
+            info: nvim.api_info()?,
+            session: nvim.session.clone(),
+            allowlist: None,
+        })
+    }
+
+    /// Only allow the functions in `names` to be invoked
+    ///
+    /// Calling this again replaces the previous allowlist.
+    pub fn allow<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.allowlist = Some(names.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Get the function `name`, if it exists and is allowed
+    pub fn function(&self, name: &str) -> Option<&FunctionInfo> {
+        match &self.allowlist {
+            Some(allowlist) if !allowlist.contains(name) => None,
+            _ => self.info.function(name),
+        }
+    }
+
+    /// Iterate over the functions which may be invoked
+    pub fn functions(&self) -> impl Iterator<Item = &FunctionInfo> {
+        self.info
+            .functions
+            .iter()
+            .filter(move |function| match &self.allowlist {
+                Some(allowlist) => allowlist.contains(&function.name),
+                None => true,
+            })
+    }
+
+    /// Call the function `name` with `args`
+    ///
+    /// Returns `Error::NotImplemented` if the function doesn't exist or isn't
+    /// allowed, and `Error::InvalidArgs` if the arguments don't match its
+    /// parameters.
+    pub fn invoke(&self, name: &str, args: Vec<Value>) -> Result<Value, Error> {
+        let function = self
+            .function(name)
+            .ok_or_else(|| Error::NotImplemented(name.to_string()))?;
+        function.check_args(&args)?;
+
+        let session = self.session.lock().unwrap().clone();
+        session.call(name, args)
+    }
+}
@@ -0,0 +1,39 @@
+use crate::api::*;
+use crate::error::Error;
+use crate::value_vec;
+
+/// The result of running Vimscript with `Nvim::exec`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExecResult {
+    /// The output of the script, e.g. from `:echo`, or empty unless output
+    /// was captured
+    pub output: String,
+}
+
+impl Nvim {
+    /// Run the Vimscript `src`, which may span several lines, using
+    /// `nvim_exec2`
+    ///
+    /// With `capture_output`, anything the script would display is returned
+    /// in `ExecResult::output` rather than shown in Neovim.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::api::Nvim;
+    ///
+    /// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+    /// nvim.start_event_loop(None, None);
+    ///
+    /// let result = nvim.exec("set tabstop?", true).unwrap();
+    /// assert_eq!(result.output.trim(), "tabstop=8");
+    /// ```
+    pub fn exec(&self, src: impl Into<String>, capture_output: bool) -> Result<ExecResult, Error> {
+        let src: String = src.into();
+        let opts = Dict::new().with("output", capture_output);
+        let ret: Dict = self.call_typed("nvim_exec2", value_vec!(src, opts))?;
+        Ok(ExecResult {
+            output: ret.get_str("output").unwrap_or_default().to_string(),
+        })
+    }
+}
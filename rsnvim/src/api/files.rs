@@ -0,0 +1,208 @@
+use rmpv::Value;
+use std::path::Path;
+
+use crate::api::convert::{AsValue, FromValue};
+use crate::api::*;
+use crate::error::Error;
+
+/// Lua run by `Nvim::edit_file`, returning the buffer edited
+///
+/// Empty strings leave `++enc` and `++ff` unset.
+const EDIT_FILE_LUA: &str = r#"
+local path, force, encoding, format = ...
+local cmd = force and 'edit!' or 'edit'
+if encoding ~= '' then
+    cmd = cmd .. ' ++enc=' .. encoding
+end
+if format ~= '' then
+    cmd = cmd .. ' ++ff=' .. format
+end
+vim.cmd(cmd .. ' ' .. vim.fn.fnameescape(path))
+return vim.api.nvim_get_current_buf()
+"#;
+
+/// Lua run by `Buffer::load_file`
+const LOAD_FILE_LUA: &str = r#"
+local buf, path = ...
+vim.api.nvim_buf_set_name(buf, path)
+vim.api.nvim_buf_call(buf, function()
+    vim.cmd('edit!')
+end)
+"#;
+
+/// Lua run by `Buffer::file_info`, returning [encoding, format, bomb]
+const FILE_INFO_LUA: &str = r#"
+local buf = ...
+return { vim.bo[buf].fileencoding, vim.bo[buf].fileformat, vim.bo[buf].bomb }
+"#;
+
+/// How lines are separated in a file, Neovim's `fileformat` option
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileFormat {
+    /// Lines end with `\n`
+    Unix,
+    /// Lines end with `\r\n`
+    Dos,
+    /// Lines end with `\r`
+    Mac,
+}
+
+impl AsValue for FileFormat {
+    fn convert(&self) -> Value {
+        Value::from(self.as_str())
+    }
+}
+
+impl FromValue for FileFormat {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        match value.as_str() {
+            Some("unix") => Ok(FileFormat::Unix),
+            Some("dos") => Ok(FileFormat::Dos),
+            Some("mac") => Ok(FileFormat::Mac),
+            _ => Err(Error::DecodingError(format!(
+                "Unknown file format {}",
+                value
+            ))),
+        }
+    }
+}
+
+impl FileFormat {
+    fn as_str(&self) -> &'static str {
+        match self {
+            FileFormat::Unix => "unix",
+            FileFormat::Dos => "dos",
+            FileFormat::Mac => "mac",
+        }
+    }
+}
+
+/// How Neovim interpreted a buffer's file, returned by `Buffer::file_info`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileInfo {
+    /// The file's encoding, e.g. `utf-8` or `latin1`
+    pub encoding: String,
+    pub format: FileFormat,
+    /// Whether the file starts with a byte order mark
+    pub bom: bool,
+}
+
+/// Options for `Nvim::edit_file`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EditOpts {
+    force: bool,
+    encoding: Option<String>,
+    format: Option<FileFormat>,
+}
+
+impl EditOpts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Discard changes to the current buffer, like `:edit!`
+    pub fn force(mut self) -> Self {
+        self.force = true;
+        self
+    }
+
+    /// Read the file with `encoding` rather than detecting it, like `++enc`
+    pub fn encoding(mut self, encoding: &str) -> Self {
+        self.encoding = Some(encoding.to_string());
+        self
+    }
+
+    /// Read the file with `format` rather than detecting it, like `++ff`
+    pub fn format(mut self, format: FileFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+}
+
+/// Get a path as a string to send to Neovim
+pub(crate) fn path_str(path: &Path) -> Result<&str, Error> {
+    path.to_str().ok_or_else(|| {
+        Error::InvalidArgument(format!("Path {} is not valid UTF-8", path.display()))
+    })
+}
+
+impl Nvim {
+    /// Edit the file at `path` in the current window, returning its buffer
+    ///
+    /// The path is escaped for `:edit`, and the file has been read by the
+    /// time this returns. Fails if the current buffer has unsaved changes,
+    /// unless `EditOpts::force` is used.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::api::{EditOpts, FileFormat, Nvim};
+    ///
+    /// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+    /// nvim.start_event_loop(None, None);
+    ///
+    /// let mut buffer = nvim
+    ///     .edit_file("notes/todo list.txt", EditOpts::new().force())
+    ///     .unwrap();
+    /// let info = buffer.file_info().unwrap();
+    /// if info.format == FileFormat::Dos {
+    ///     println!("{} has Windows line endings", info.encoding);
+    /// }
+    /// ```
+    pub fn edit_file(&mut self, path: impl AsRef<Path>, opts: EditOpts) -> Result<Buffer, Error> {
+        let path = path_str(path.as_ref())?;
+        let id: i64 = self.exec_lua_typed(
+            EDIT_FILE_LUA,
+            vec![
+                Value::from(path),
+                Value::from(opts.force),
+                Value::from(opts.encoding.as_deref().unwrap_or("")),
+                Value::from(opts.format.map_or("", |format| format.as_str())),
+            ],
+        )?;
+        Ok(Buffer::new(id, self.session.clone()))
+    }
+}
+
+impl Buffer {
+    /// Replace the buffer's contents with the file at `path`, which becomes
+    /// the buffer's name
+    ///
+    /// Like `:edit!`, unsaved changes are discarded and the file's encoding
+    /// and format are detected. The buffer doesn't need to be displayed.
+    pub fn load_file(&mut self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path_str(path.as_ref())?;
+        let session = self.session.lock().unwrap().clone();
+        session.call(
+            "nvim_exec_lua",
+            vec![
+                Value::from(LOAD_FILE_LUA),
+                Value::from(vec![self.convert(), Value::from(path)]),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Get how Neovim interpreted the buffer's file
+    pub fn file_info(&mut self) -> Result<FileInfo, Error> {
+        let session = self.session.lock().unwrap().clone();
+        let ret = session.call(
+            "nvim_exec_lua",
+            vec![
+                Value::from(FILE_INFO_LUA),
+                Value::from(vec![self.convert()]),
+            ],
+        )?;
+        let invalid = || Error::DecodingError(format!("Invalid file info {}", ret));
+        let encoding = ret[0].as_str().ok_or_else(invalid)?;
+        Ok(FileInfo {
+            // An empty 'fileencoding' means the file uses 'encoding'
+            encoding: match encoding {
+                "" => "utf-8".to_string(),
+                encoding => encoding.to_string(),
+            },
+            format: FileFormat::from_value(ret[1].clone())?,
+            bom: ret[2].as_bool().ok_or_else(invalid)?,
+        })
+    }
+}
@@ -0,0 +1,236 @@
+use rmpv::Value;
+
+use crate::api::*;
+use crate::error::Error;
+
+/// The configuration of a floating window opened with `Nvim::open_float`
+///
+/// Unlike a bare `WinConfig`, the keys a float needs are always set: it is
+/// positioned relative to the editor at the top-left corner unless moved,
+/// and a title or footer gets a single border if none is chosen, as Neovim
+/// only shows them in a border.
+///
+/// # Example
+///
+/// ```no_run
+/// use rsnvim::api::{Border, FloatConfigBuilder, Nvim};
+///
+/// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+/// nvim.start_event_loop(None, None);
+///
+/// let buffer = nvim.create_buf(false, true).unwrap();
+/// let config = FloatConfigBuilder::new(60, 10)
+///     .centered()
+///     .border(Border::Rounded)
+///     .title("Preview")
+///     .enter(true);
+/// let window = nvim.open_float(&buffer, config).unwrap();
+/// window.close(true).unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct FloatConfigBuilder {
+    config: WinConfig,
+    width: i64,
+    height: i64,
+    centered: bool,
+    has_border: bool,
+    has_text: bool,
+    enter: bool,
+}
+
+impl FloatConfigBuilder {
+    /// A float `width` columns wide and `height` rows high
+    pub fn new(width: i64, height: i64) -> Self {
+        FloatConfigBuilder {
+            config: WinConfig::new()
+                .relative(Relative::Editor)
+                .row(0.0)
+                .col(0.0)
+                .width(width)
+                .height(height),
+            width,
+            height,
+            centered: false,
+            has_border: false,
+            has_text: false,
+            enter: false,
+        }
+    }
+
+    /// Position the float relative to this layout element
+    pub fn relative(mut self, relative: Relative) -> Self {
+        self.config = self.config.relative(relative);
+        self
+    }
+
+    /// Position the float relative to `window`
+    pub fn win(mut self, window: &Window) -> Self {
+        self.config = self.config.relative(Relative::Win).win(window);
+        self
+    }
+
+    /// Place the anchor at `row`, `col`, in screen cells
+    pub fn position(mut self, row: f64, col: f64) -> Self {
+        self.centered = false;
+        self.config = self.config.row(row).col(col);
+        self
+    }
+
+    /// Center the float in the editor when it is opened
+    pub fn centered(mut self) -> Self {
+        self.centered = true;
+        self.relative(Relative::Editor).anchor(Anchor::NW)
+    }
+
+    /// The corner of the float placed at its position
+    pub fn anchor(mut self, anchor: Anchor) -> Self {
+        self.config = self.config.anchor(anchor);
+        self
+    }
+
+    pub fn border(mut self, border: Border) -> Self {
+        self.has_border = true;
+        self.config = self.config.border(border);
+        self
+    }
+
+    /// Show `title` in the border
+    pub fn title(mut self, title: &str) -> Self {
+        self.has_text = true;
+        self.config = self.config.title(title);
+        self
+    }
+
+    pub fn title_pos(mut self, pos: TextPos) -> Self {
+        self.config = self.config.title_pos(pos);
+        self
+    }
+
+    /// Show `footer` in the border
+    pub fn footer(mut self, footer: &str) -> Self {
+        self.has_text = true;
+        self.config = self.config.footer(footer);
+        self
+    }
+
+    pub fn footer_pos(mut self, pos: TextPos) -> Self {
+        self.config = self.config.footer_pos(pos);
+        self
+    }
+
+    /// The stacking order of the float, higher floats being drawn on top
+    pub fn zindex(mut self, zindex: i64) -> Self {
+        self.config = self.config.zindex(zindex);
+        self
+    }
+
+    /// Whether the float can be focused by wincmds or mouse events
+    pub fn focusable(mut self, focusable: bool) -> Self {
+        self.config = self.config.focusable(focusable);
+        self
+    }
+
+    /// Use the "minimal" style, disabling most UI elements
+    pub fn minimal(mut self) -> Self {
+        self.config = self.config.minimal();
+        self
+    }
+
+    /// Make the float the current window once opened
+    pub fn enter(mut self, enter: bool) -> Self {
+        self.enter = enter;
+        self
+    }
+
+    /// The `WinConfig` of the float, with its position resolved against
+    /// the size of `nvim`'s editor if it is centered
+    pub fn build(&self, nvim: &Nvim) -> Result<WinConfig, Error> {
+        if self.width <= 0 || self.height <= 0 {
+            return Err(Error::InvalidArgument(format!(
+                "A float must be at least 1x1, not {}x{}",
+                self.width, self.height
+            )));
+        }
+        let mut config = self.config.clone();
+        if self.has_text && !self.has_border {
+            config = config.border(Border::Single);
+        }
+        if self.centered {
+            let size: Vec<i64> = nvim.call_typed(
+                "nvim_eval",
+                vec![Value::from("[&lines - &cmdheight, &columns]")],
+            )?;
+            // Leave room for the border, which is drawn outside the float
+            let border = if self.has_border || self.has_text {
+                2
+            } else {
+                0
+            };
+            let row = (size[0] - self.height - border).max(0) / 2;
+            let col = (size[1] - self.width - border).max(0) / 2;
+            config = config.row(row as f64).col(col as f64);
+        }
+        Ok(config)
+    }
+}
+
+/// The configuration without a centered position resolved, for which use
+/// `FloatConfigBuilder::build`
+impl From<FloatConfigBuilder> for Dict {
+    fn from(builder: FloatConfigBuilder) -> Self {
+        builder.config.into()
+    }
+}
+
+impl Nvim {
+    /// Open a floating window showing `buffer`
+    ///
+    /// Returns `Error::InvalidArgument` if the float is smaller than one
+    /// cell. See `FloatConfigBuilder` for an example.
+    pub fn open_float(&self, buffer: &Buffer, config: FloatConfigBuilder) -> Result<Window, Error> {
+        let enter = config.enter;
+        self.open_win(buffer, enter, config.build(self)?)
+    }
+}
+
+impl Window {
+    /// Change only the keys of the window's configuration set in `config`,
+    /// keeping the rest
+    ///
+    /// `Window::set_config` needs the position of a float to be given in
+    /// full, e.g. `row` and `col` with `relative`, so the current
+    /// configuration of a float is merged with `config` first.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::api::{FloatConfigBuilder, Nvim, WinConfig};
+    ///
+    /// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+    /// nvim.start_event_loop(None, None);
+    ///
+    /// let buffer = nvim.create_buf(false, true).unwrap();
+    /// let window = nvim.open_float(&buffer, FloatConfigBuilder::new(40, 5)).unwrap();
+    /// window.update_config(WinConfig::new().row(3.0).width(60)).unwrap();
+    /// ```
+    pub fn update_config(&self, config: impl Into<Dict>) -> Result<(), Error> {
+        let config: Dict = config.into();
+        let mut current = self.get_config()?.unwrap_or_default();
+        // Split windows have no position to keep
+        if current.get_str("relative").unwrap_or_default().is_empty() {
+            return self.set_config(config);
+        }
+        // A new position replaces the window or buffer position it was
+        // relative to
+        if config.contains_key("relative") {
+            current.remove("win");
+            current.remove("bufpos");
+        }
+        for (key, value) in config {
+            if let Some(key) = key.as_str() {
+                current.insert(key, value);
+            }
+        }
+        self.set_config(current)
+    }
+}
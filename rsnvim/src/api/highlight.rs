@@ -0,0 +1,439 @@
+use rmpv::Value;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::api::*;
+use crate::error::Error;
+use crate::value_vec;
+
+/// Restores a window's previous highlight namespace when dropped
+///
+/// Created by `Window::with_hl_namespace`.
+pub struct HlNamespaceGuard {
+    window: Option<Window>,
+    previous: i64,
+}
+
+impl HlNamespaceGuard {
+    /// The highlight namespace that will be restored
+    pub fn previous(&self) -> i64 {
+        self.previous
+    }
+
+    /// Restore the previous highlight namespace, returning any error
+    ///
+    /// Dropping the guard restores the namespace too, but ignores errors.
+    pub fn restore(mut self) -> Result<(), Error> {
+        match self.window.take() {
+            Some(window) => window.set_hl_ns(self.previous),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for HlNamespaceGuard {
+    fn drop(&mut self) {
+        if let Some(window) = self.window.take() {
+            let _ = window.set_hl_ns(self.previous);
+        }
+    }
+}
+
+impl Window {
+    /// Set the highlight namespace used by this window
+    ///
+    /// Passing `0` switches the window back to the global namespace.
+    pub fn set_hl_namespace(&mut self, ns_id: i64) -> Result<(), Error> {
+        self.set_hl_ns(ns_id)
+    }
+
+    /// Get the highlight namespace used by this window
+    ///
+    /// Returns `None` if the window uses the global namespace.
+    pub fn hl_namespace(&mut self) -> Result<Option<i64>, Error> {
+        let opts = vec![(Value::from("winid"), Value::from(self.id))];
+        let session = self.session.lock().unwrap().clone();
+        let ret = session.call("nvim_get_hl_ns", value_vec!(opts))?;
+        match ret.as_i64() {
+            Some(ns_id) if ns_id > 0 => Ok(Some(ns_id)),
+            Some(_) => Ok(None),
+            None => Err(Error::DecodingError(
+                "Highlight namespace is not an integer".to_string(),
+            )),
+        }
+    }
+
+    /// Create a new highlight namespace named `name` and use it for this window
+    ///
+    /// Highlight groups defined in the returned namespace with `nvim_set_hl`
+    /// only apply to this window, allowing window-scoped theming such as
+    /// dimming inactive windows.
+    pub fn create_hl_namespace(&mut self, name: impl Into<String>) -> Result<i64, Error> {
+        let name: String = name.into();
+        let ns_id = {
+            let session = self.session.lock().unwrap().clone();
+            let ret = session.call("nvim_create_namespace", value_vec!(name))?;
+            ret.as_i64()
+                .ok_or_else(|| Error::DecodingError("Namespace id is not an integer".to_string()))?
+        };
+        self.set_hl_ns(ns_id)?;
+        Ok(ns_id)
+    }
+
+    /// Temporarily use the highlight namespace `ns_id` for this window
+    ///
+    /// The previous namespace is restored when the returned guard is dropped.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::api::Nvim;
+    ///
+    /// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+    /// nvim.start_event_loop(None, None);
+    ///
+    /// let mut window = nvim.get_current_win().unwrap();
+    /// let dim = nvim.create_namespace("dim").unwrap();
+    /// {
+    ///     let _guard = window.with_hl_namespace(dim).unwrap();
+    ///     // `window` is drawn using the `dim` namespace here
+    /// }
+    /// ```
+    pub fn with_hl_namespace(&mut self, ns_id: i64) -> Result<HlNamespaceGuard, Error> {
+        let previous = self.hl_namespace()?.unwrap_or(0);
+        self.set_hl_ns(ns_id)?;
+        Ok(HlNamespaceGuard {
+            window: Some(self.clone()),
+            previous,
+        })
+    }
+}
+
+/// A 24-bit RGB color
+///
+/// Parsed from `#rrggbb` strings and from integers, the two forms Neovim
+/// uses, and displayed as `#rrggbb`.
+///
+/// # Example
+///
+/// ```
+/// use rsnvim::api::Color;
+///
+/// let color: Color = "#ff8000".parse().unwrap();
+/// assert_eq!(color, Color::rgb(255, 128, 0));
+/// assert_eq!(Color::try_from(0xff8000).unwrap(), color);
+/// assert_eq!(color.to_string(), "#ff8000");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Color(u32);
+
+impl Color {
+    pub fn rgb(red: u8, green: u8, blue: u8) -> Self {
+        Color(u32::from_be_bytes([0, red, green, blue]))
+    }
+
+    pub fn red(&self) -> u8 {
+        self.0.to_be_bytes()[1]
+    }
+
+    pub fn green(&self) -> u8 {
+        self.0.to_be_bytes()[2]
+    }
+
+    pub fn blue(&self) -> u8 {
+        self.0.to_be_bytes()[3]
+    }
+
+    /// The color as the integer `0xrrggbb`
+    pub fn as_u32(&self) -> u32 {
+        self.0
+    }
+}
+
+impl std::fmt::Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{:06x}", self.0)
+    }
+}
+
+impl FromStr for Color {
+    type Err = Error;
+
+    /// Parse a `#rrggbb` color
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let hex = s
+            .strip_prefix('#')
+            .filter(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()));
+        match hex.and_then(|hex| u32::from_str_radix(hex, 16).ok()) {
+            Some(rgb) => Ok(Color(rgb)),
+            None => Err(Error::InvalidArgument(format!(
+                "Expected a color like #rrggbb but received '{}'",
+                s
+            ))),
+        }
+    }
+}
+
+impl TryFrom<i64> for Color {
+    type Error = Error;
+
+    /// Convert the integer `0xrrggbb`
+    fn try_from(rgb: i64) -> Result<Self, Error> {
+        match u32::try_from(rgb) {
+            Ok(rgb) if rgb <= 0xffffff => Ok(Color(rgb)),
+            _ => Err(Error::InvalidArgument(format!(
+                "{} is not a 24-bit RGB color",
+                rgb
+            ))),
+        }
+    }
+}
+
+impl FromValue for Color {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        let color = match &value {
+            Value::String(s) => s.as_str().map(str::parse),
+            other => other.as_i64().map(Color::try_from),
+        };
+        match color {
+            Some(Ok(color)) => Ok(color),
+            _ => Err(Error::DecodingError(format!(
+                "Expected a color but received {}",
+                value
+            ))),
+        }
+    }
+}
+
+impl From<Color> for Value {
+    fn from(color: Color) -> Self {
+        Value::from(color.0)
+    }
+}
+
+/// The attributes of a highlight group, as set by `nvim_set_hl` and
+/// returned by `nvim_get_hl`
+///
+/// Colors and attributes left unset keep the terminal's defaults, and a
+/// group with `link` set is drawn as the linked group instead.
+///
+/// # Example
+///
+/// ```no_run
+/// use rsnvim::api::{Color, HighlightAttrs, Nvim};
+///
+/// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+/// nvim.start_event_loop(None, None);
+///
+/// let attrs = HighlightAttrs {
+///     fg: Some("#e0def4".parse().unwrap()),
+///     bg: Some(Color::rgb(25, 23, 36)),
+///     bold: true,
+///     ..Default::default()
+/// };
+/// nvim.set_highlight(0, "Title", &attrs).unwrap();
+/// assert_eq!(nvim.get_highlight(0, "Title").unwrap(), attrs);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HighlightAttrs {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    /// The color of underlines and undercurls
+    pub sp: Option<Color>,
+    /// The foreground in terminals without true color, from `0` to `255`
+    pub ctermfg: Option<u8>,
+    /// The background in terminals without true color, from `0` to `255`
+    pub ctermbg: Option<u8>,
+    /// Transparency from `0` to `100`
+    pub blend: Option<u8>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub undercurl: bool,
+    pub underdouble: bool,
+    pub underdotted: bool,
+    pub underdashed: bool,
+    pub strikethrough: bool,
+    pub reverse: bool,
+    pub standout: bool,
+    /// Override the attributes of highlights combined with this one
+    pub nocombine: bool,
+    /// The group this group links to
+    pub link: Option<String>,
+    /// Only set the group if it isn't already defined, as `:hi default`
+    pub default: bool,
+}
+
+/// The boolean attributes of `HighlightAttrs` and their keys
+fn flags(attrs: &mut HighlightAttrs) -> [(&'static str, &mut bool); 12] {
+    [
+        ("bold", &mut attrs.bold),
+        ("italic", &mut attrs.italic),
+        ("underline", &mut attrs.underline),
+        ("undercurl", &mut attrs.undercurl),
+        ("underdouble", &mut attrs.underdouble),
+        ("underdotted", &mut attrs.underdotted),
+        ("underdashed", &mut attrs.underdashed),
+        ("strikethrough", &mut attrs.strikethrough),
+        ("reverse", &mut attrs.reverse),
+        ("standout", &mut attrs.standout),
+        ("nocombine", &mut attrs.nocombine),
+        ("default", &mut attrs.default),
+    ]
+}
+
+impl HighlightAttrs {
+    /// A group linked to `group`
+    pub fn link(group: &str) -> Self {
+        HighlightAttrs {
+            link: Some(group.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// Whether no attribute is set, as for a cleared group
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl From<&HighlightAttrs> for Dict {
+    fn from(attrs: &HighlightAttrs) -> Self {
+        let mut dict = Dict::new();
+        let colors = [("fg", attrs.fg), ("bg", attrs.bg), ("sp", attrs.sp)];
+        for (key, color) in colors {
+            if let Some(color) = color {
+                dict.insert(key, color);
+            }
+        }
+        let numbers = [
+            ("ctermfg", attrs.ctermfg),
+            ("ctermbg", attrs.ctermbg),
+            ("blend", attrs.blend),
+        ];
+        for (key, number) in numbers {
+            if let Some(number) = number {
+                dict.insert(key, number);
+            }
+        }
+        for (key, flag) in flags(&mut attrs.clone()) {
+            if *flag {
+                dict.insert(key, true);
+            }
+        }
+        if let Some(link) = &attrs.link {
+            dict.insert("link", link.as_str());
+        }
+        dict
+    }
+}
+
+impl From<HighlightAttrs> for Dict {
+    fn from(attrs: HighlightAttrs) -> Self {
+        Dict::from(&attrs)
+    }
+}
+
+impl FromValue for HighlightAttrs {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        let dict = Dict::from_value(value)?;
+        let number = |key: &str| match dict.get(key) {
+            Some(value) => match value.as_u64().and_then(|n| u8::try_from(n).ok()) {
+                Some(number) => Ok(Some(number)),
+                None => Err(Error::DecodingError(format!(
+                    "Expected {} to be from 0 to 255 but received {}",
+                    key, value
+                ))),
+            },
+            None => Ok(None),
+        };
+        let mut attrs = HighlightAttrs {
+            fg: dict.get_as("fg")?,
+            bg: dict.get_as("bg")?,
+            sp: dict.get_as("sp")?,
+            ctermfg: number("ctermfg")?,
+            ctermbg: number("ctermbg")?,
+            blend: number("blend")?,
+            link: dict.get_str("link").map(str::to_string),
+            ..Default::default()
+        };
+        for (key, flag) in flags(&mut attrs) {
+            *flag = dict.get_bool(key).unwrap_or(false);
+        }
+        Ok(attrs)
+    }
+}
+
+impl Nvim {
+    /// Define the highlight group `name` in the namespace `ns_id`, or the
+    /// global namespace if `0`, replacing its attributes
+    pub fn set_highlight(
+        &self,
+        ns_id: i64,
+        name: &str,
+        attrs: &HighlightAttrs,
+    ) -> Result<(), Error> {
+        self.set_hl(ns_id, name, attrs)
+    }
+
+    /// Get the attributes of the highlight group `name` in the namespace
+    /// `ns_id`, or the global namespace if `0`
+    ///
+    /// A linked group's own attributes are returned with `link` set, rather
+    /// than those of the group it links to. A group which isn't defined has
+    /// no attributes set.
+    pub fn get_highlight(&self, ns_id: i64, name: &str) -> Result<HighlightAttrs, Error> {
+        let opts = Dict::new().with("name", name).with("link", true);
+        HighlightAttrs::from_value(self.get_hl(ns_id, opts)?.into())
+    }
+
+    /// Get every highlight group defined in the namespace `ns_id`, or the
+    /// global namespace if `0`
+    pub fn get_highlights(&self, ns_id: i64) -> Result<HashMap<String, HighlightAttrs>, Error> {
+        self.get_hl(ns_id, Dict::new())?
+            .into_iter()
+            .map(|(name, attrs)| {
+                Ok((
+                    String::from_value(name)?,
+                    HighlightAttrs::from_value(attrs)?,
+                ))
+            })
+            .collect()
+    }
+
+    /// Create the highlight namespace `name` defining `groups`, returning
+    /// its ID
+    ///
+    /// The groups only apply where the namespace is active, globally with
+    /// `Nvim::set_hl_ns` or in one window with `Window::set_hl_namespace`,
+    /// e.g. to theme a single window.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::api::{Color, HighlightAttrs, Nvim};
+    ///
+    /// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+    /// nvim.start_event_loop(None, None);
+    ///
+    /// let dim = HighlightAttrs {
+    ///     fg: Some(Color::rgb(110, 106, 134)),
+    ///     ..Default::default()
+    /// };
+    /// let groups = [("Normal", dim), ("Comment", HighlightAttrs::link("Normal"))];
+    /// let ns_id = nvim.define_hl_namespace("dim", &groups).unwrap();
+    /// let mut window = nvim.get_current_win().unwrap();
+    /// window.set_hl_namespace(ns_id).unwrap();
+    /// ```
+    pub fn define_hl_namespace(
+        &self,
+        name: &str,
+        groups: &[(&str, HighlightAttrs)],
+    ) -> Result<i64, Error> {
+        let ns_id = self.create_namespace(name)?;
+        for (group, attrs) in groups {
+            self.set_highlight(ns_id, group, attrs)?;
+        }
+        Ok(ns_id)
+    }
+}
@@ -0,0 +1,248 @@
+use rmpv::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::api::convert::FromValue;
+use crate::api::*;
+use crate::error::Error;
+
+/// The version of the connected Neovim
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    /// The API level provided by this version
+    pub api_level: u64,
+    /// The oldest API level this version is compatible with
+    pub api_compatible: u64,
+    /// Whether the API of this version may still change
+    pub api_prerelease: bool,
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// An API function provided by the connected Neovim
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionInfo {
+    pub name: String,
+    /// The API level the function was added in
+    pub since: u64,
+    /// The API level the function was deprecated in, if any
+    pub deprecated_since: Option<u64>,
+    /// The type and name of each parameter
+    pub parameters: Vec<(String, String)>,
+    pub return_type: String,
+}
+
+impl FunctionInfo {
+    /// Check that `args` match the function's parameters
+    ///
+    /// Returns `Error::InvalidArgs` naming the function, the expected
+    /// parameter and the argument received.
+    pub fn check_args(&self, args: &[Value]) -> Result<(), Error> {
+        if args.len() != self.parameters.len() {
+            return Err(Error::InvalidArgs {
+                method: self.name.clone(),
+                expected: format!("{} arguments", self.parameters.len()),
+                got: args.len().to_string(),
+            });
+        }
+        for ((param_type, param_name), arg) in self.parameters.iter().zip(args) {
+            if !matches_type(param_type, arg) {
+                return Err(Error::InvalidArgs {
+                    method: self.name.clone(),
+                    expected: format!("{} to be {}", param_name, param_type),
+                    got: arg.to_string(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether `value` is accepted by Neovim for a parameter of type `api_type`
+fn matches_type(api_type: &str, value: &Value) -> bool {
+    // Strip parameters like `ArrayOf(Integer, 2)` or `Dict(option)`
+    let base = api_type.split('(').next().unwrap_or(api_type);
+    match base {
+        "Integer" => value.is_i64() || value.is_u64(),
+        // Integers are converted to floats by Neovim
+        "Float" => value.is_f64() || value.is_f32() || value.is_i64() || value.is_u64(),
+        "Boolean" => value.is_bool(),
+        "String" => value.is_str(),
+        "Array" | "ArrayOf" => value.is_array(),
+        // An empty array is accepted as an empty dictionary
+        "Dictionary" | "Dict" => {
+            value.is_map() || value.as_array().is_some_and(|array| array.is_empty())
+        }
+        // Handles may also be given as their integer ID
+        "Buffer" => is_handle(value, BUFFER_EXT),
+        "Window" => is_handle(value, WINDOW_EXT),
+        "Tabpage" => is_handle(value, TABPAGE_EXT),
+        // Object, LuaRef and any types newer than these bindings
+        _ => true,
+    }
+}
+
+/// Whether `value` is a handle EXT with type code `ext`, or an integer ID
+fn is_handle(value: &Value, ext: i8) -> bool {
+    match value {
+        Value::Ext(code, _) => *code == ext,
+        other => other.as_i64().is_some_and(|id| id >= 0),
+    }
+}
+
+/// The API metadata of the connected Neovim, returned by `Nvim::api_info`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApiInfo {
+    /// The ID of this client's channel
+    pub channel: i64,
+    pub version: Version,
+    pub functions: Vec<FunctionInfo>,
+    /// The position of each function in `functions`, by name
+    index: HashMap<String, usize>,
+}
+
+impl ApiInfo {
+    /// Whether the API function `name` is available
+    pub fn supports(&self, name: &str) -> bool {
+        self.function(name).is_some()
+    }
+
+    /// Get the API function `name`, if available
+    pub fn function(&self, name: &str) -> Option<&FunctionInfo> {
+        self.index.get(name).map(|&i| &self.functions[i])
+    }
+}
+
+/// Get the unsigned integer `key` of a metadata map
+fn get_u64(map: &Value, key: &str) -> Result<u64, Error> {
+    map[key]
+        .as_u64()
+        .ok_or_else(|| Error::DecodingError(format!("API info is missing '{}'", key)))
+}
+
+/// Get the string `key` of a metadata map
+fn get_str(map: &Value, key: &str) -> Result<String, Error> {
+    map[key]
+        .as_str()
+        .map(|s| s.to_string())
+        .ok_or_else(|| Error::DecodingError(format!("API info is missing '{}'", key)))
+}
+
+impl FromValue for Version {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        Ok(Version {
+            major: get_u64(&value, "major")?,
+            minor: get_u64(&value, "minor")?,
+            patch: get_u64(&value, "patch")?,
+            api_level: get_u64(&value, "api_level")?,
+            api_compatible: get_u64(&value, "api_compatible")?,
+            api_prerelease: value["api_prerelease"].as_bool().unwrap_or(false),
+        })
+    }
+}
+
+impl FromValue for FunctionInfo {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        let parameters = match value["parameters"].as_array() {
+            Some(parameters) => parameters
+                .iter()
+                .map(|p| <(String, String)>::from_value(p.clone()))
+                .collect::<Result<_, _>>()?,
+            None => Vec::new(),
+        };
+        Ok(FunctionInfo {
+            name: get_str(&value, "name")?,
+            since: get_u64(&value, "since")?,
+            deprecated_since: value["deprecated_since"].as_u64(),
+            parameters,
+            return_type: get_str(&value, "return_type")?,
+        })
+    }
+}
+
+impl FromValue for (String, String) {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        match value {
+            Value::Array(v) if v.len() == 2 => {
+                let mut v = v.into_iter();
+                Ok((
+                    String::from_value(v.next().unwrap())?,
+                    String::from_value(v.next().unwrap())?,
+                ))
+            }
+            other => Err(Error::DecodingError(format!(
+                "Expected an array of two strings but received {}",
+                other
+            ))),
+        }
+    }
+}
+
+impl FromValue for ApiInfo {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        // Returned as [channel, metadata]
+        let (channel, metadata) = match value {
+            Value::Array(v) if v.len() == 2 => {
+                let mut v = v.into_iter();
+                (v.next().unwrap(), v.next().unwrap())
+            }
+            _ => return Err(Error::DecodingError("API info is invalid".to_string())),
+        };
+        let functions: Vec<FunctionInfo> = Vec::from_value(metadata["functions"].clone())?;
+        let index = functions
+            .iter()
+            .enumerate()
+            .map(|(i, function)| (function.name.clone(), i))
+            .collect();
+        Ok(ApiInfo {
+            channel: i64::from_value(channel)?,
+            version: Version::from_value(metadata["version"].clone())?,
+            functions,
+            index,
+        })
+    }
+}
+
+impl Nvim {
+    /// Get the API metadata of the connected Neovim
+    ///
+    /// The metadata is fetched once per connection and then cached.
+    pub fn api_info(&mut self) -> Result<Arc<ApiInfo>, Error> {
+        let session = self.session.lock().unwrap().clone();
+        session.api_info()
+    }
+
+    /// Get the ID of this client's channel, e.g. for `rpcnotify`
+    ///
+    /// Fetched with the rest of the API metadata and then cached.
+    pub fn channel_id(&mut self) -> Result<i64, Error> {
+        let session = self.session.lock().unwrap().clone();
+        session.channel_id()
+    }
+
+    /// Whether the connected Neovim provides the API function `name`
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::api::Nvim;
+    ///
+    /// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+    /// nvim.start_event_loop(None, None);
+    ///
+    /// if nvim.supports("nvim_win_text_height").unwrap() {
+    ///     // Use the newer API
+    /// }
+    /// ```
+    pub fn supports(&mut self, name: &str) -> Result<bool, Error> {
+        Ok(self.api_info()?.supports(name))
+    }
+}
@@ -0,0 +1,87 @@
+use rmpv::Value;
+use std::thread;
+
+use crate::api::*;
+use crate::error::Error;
+
+/// Lua run to create a mapping which notifies this client
+const SET_KEYMAP_LUA: &str = r#"
+local channel, method, mode, lhs, opts = ...
+opts.callback = function()
+    vim.rpcnotify(channel, method)
+end
+vim.api.nvim_set_keymap(mode, lhs, '', opts)
+"#;
+
+/// The notification method sent by the mapping of `lhs` in `mode`
+fn keymap_method(mode: &str, lhs: &str) -> String {
+    format!("rsnvim_keymap:{}:{}", mode, lhs)
+}
+
+impl Nvim {
+    /// Map `lhs` in `mode` to run `callback`
+    ///
+    /// The mapping notifies this client, and `callback` runs on a background
+    /// thread with an `Nvim` handle sharing the connection, so the event
+    /// loop must be running. Mapping the same keys again replaces the
+    /// callback.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::api::{KeymapOpts, Nvim};
+    ///
+    /// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+    /// nvim.start_event_loop(None, None);
+    ///
+    /// nvim.set_keymap_rpc("n", "<leader>h", KeymapOpts::new().silent(true), |nvim| {
+    ///     let _ = nvim.command("echo 'Hello from Rust'");
+    /// })
+    /// .unwrap();
+    /// ```
+    pub fn set_keymap_rpc<F>(
+        &mut self,
+        mode: &str,
+        lhs: &str,
+        opts: KeymapOpts,
+        mut callback: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(&mut Nvim) + Send + 'static,
+    {
+        let channel = self.channel_id()?;
+        let method = keymap_method(mode, lhs);
+
+        // Stop the thread running any previous callback
+        self.unsubscribe_notifications(&method);
+        let invocations = self.subscribe_notifications(&method);
+        let mut handle = self.clone();
+        thread::spawn(move || {
+            for _ in invocations {
+                callback(&mut handle);
+            }
+        });
+
+        let opts: Vec<(Value, Value)> = opts.into();
+        let result = self.exec_lua(
+            SET_KEYMAP_LUA,
+            vec![
+                Value::from(channel),
+                Value::from(method.as_str()),
+                Value::from(mode),
+                Value::from(lhs),
+                Value::Map(opts),
+            ],
+        );
+        if result.is_err() {
+            self.unsubscribe_notifications(&method);
+        }
+        result.map(|_| ())
+    }
+
+    /// Delete a mapping created with `set_keymap_rpc`, dropping its callback
+    pub fn del_keymap_rpc(&mut self, mode: &str, lhs: &str) -> Result<(), Error> {
+        self.unsubscribe_notifications(&keymap_method(mode, lhs));
+        self.del_keymap(mode, lhs)
+    }
+}
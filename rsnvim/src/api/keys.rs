@@ -0,0 +1,371 @@
+use rmpv::Value;
+use std::fmt;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::api::*;
+use crate::error::Error;
+
+/// A sequence of keys in Neovim's key notation, e.g. `<Esc>` or `<C-w>`
+///
+/// # Example
+///
+/// ```
+/// use rsnvim::api::Keys;
+///
+/// let keys = Keys::new().text("ihello").key("Esc").ctrl('w').text("j");
+/// assert_eq!(keys.to_string(), "ihello<Esc><C-w>j");
+/// assert_eq!(keys.len(), 9);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Keys {
+    keys: Vec<String>,
+}
+
+impl Keys {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse keys written in key notation, like `dd<C-o>`
+    pub fn from_notation(notation: &str) -> Self {
+        let mut keys = Vec::new();
+        let mut rest = notation;
+        while let Some(c) = rest.chars().next() {
+            let len = match c {
+                '<' => special_key_len(rest).unwrap_or(1),
+                c => c.len_utf8(),
+            };
+            keys.push(rest[..len].to_string());
+            rest = &rest[len..];
+        }
+        Keys { keys }
+    }
+
+    /// Type `text` literally
+    pub fn text(mut self, text: &str) -> Self {
+        for c in text.chars() {
+            match c {
+                '<' => self.keys.push("<LT>".to_string()),
+                c => self.keys.push(c.to_string()),
+            }
+        }
+        self
+    }
+
+    /// Press the special key `name`, e.g. "Esc", "CR" or "S-Tab"
+    pub fn key(mut self, name: &str) -> Self {
+        self.keys.push(format!("<{}>", name));
+        self
+    }
+
+    /// Press `key` while holding control
+    pub fn ctrl(self, key: char) -> Self {
+        self.key(&format!("C-{}", key))
+    }
+
+    /// Append the keys of `other`
+    pub fn then(mut self, other: Keys) -> Self {
+        self.keys.extend(other.keys);
+        self
+    }
+
+    /// Iterate over the keys, each in key notation
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.keys.iter().map(String::as_str)
+    }
+
+    /// The number of keys
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+impl fmt::Display for Keys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for key in &self.keys {
+            f.write_str(key)?;
+        }
+        Ok(())
+    }
+}
+
+/// Get the length of the special key like `<C-w>` at the start of `notation`
+fn special_key_len(notation: &str) -> Option<usize> {
+    let end = notation[1..].find(['<', '>', ' '])? + 1;
+    // `<>>` is not a key, but `<C->>` is, so a `>` directly after a `-` is
+    // part of the name
+    let end = if notation[end..].starts_with(">>") && notation[..end].ends_with('-') {
+        end + 1
+    } else {
+        end
+    };
+    (end > 1 && notation[end..].starts_with('>')).then_some(end + 1)
+}
+
+/// Options for `Macro::play`
+///
+/// By default the keys are sent with `nvim_input` all at once, and playback
+/// waits up to a second for Neovim to process them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlaybackOpts {
+    delay: Option<Duration>,
+    count: usize,
+    feedkeys: Option<String>,
+    timeout: Option<Duration>,
+}
+
+impl Default for PlaybackOpts {
+    fn default() -> Self {
+        PlaybackOpts {
+            delay: None,
+            count: 1,
+            feedkeys: None,
+            timeout: Some(Duration::from_secs(1)),
+        }
+    }
+}
+
+impl PlaybackOpts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wait `delay` between each key, e.g. to make a demo watchable
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    /// Play the macro `count` times
+    pub fn count(mut self, count: usize) -> Self {
+        self.count = count;
+        self
+    }
+
+    /// Send the keys with `nvim_feedkeys` using `mode`, e.g. "m" to remap
+    /// them or "n" not to, rather than as typed input with `nvim_input`
+    pub fn feedkeys(mut self, mode: &str) -> Self {
+        self.feedkeys = Some(mode.to_string());
+        self
+    }
+
+    /// Wait up to `timeout` for Neovim to process the keys, or return as
+    /// soon as they're sent with `None`
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// A recorded sequence of keys which can be played back
+///
+/// # Example
+///
+/// ```no_run
+/// use rsnvim::api::{Keys, Macro, Nvim, PlaybackOpts};
+/// use std::time::Duration;
+///
+/// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+/// nvim.start_event_loop(None, None);
+///
+/// let demo = Macro::new(Keys::new().text("oHello, world!").key("Esc"));
+/// let opts = PlaybackOpts::new().delay(Duration::from_millis(50));
+/// demo.play(&mut nvim, &opts).unwrap();
+///
+/// // Replay whatever the user recorded with `qa`
+/// let recorded = Macro::from_register(&mut nvim, 'a').unwrap();
+/// recorded.play(&mut nvim, &PlaybackOpts::new().count(3)).unwrap();
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Macro {
+    keys: Keys,
+}
+
+impl Macro {
+    pub fn new(keys: Keys) -> Self {
+        Macro { keys }
+    }
+
+    /// Create a macro from the keys stored in `register`, e.g. by `qa`
+    pub fn from_register(nvim: &mut Nvim, register: char) -> Result<Self, Error> {
+        let notation: String = nvim.exec_lua_typed(
+            "return vim.fn.keytrans(vim.fn.getreg(...))",
+            vec![Value::from(register.to_string())],
+        )?;
+        Ok(Macro::new(Keys::from_notation(&notation)))
+    }
+
+    pub fn keys(&self) -> &Keys {
+        &self.keys
+    }
+
+    /// Send the macro's keys to Neovim
+    ///
+    /// With a timeout, this returns once Neovim has processed every key,
+    /// or `Error::TimeoutError` if it hasn't by then, e.g. because it is
+    /// waiting at a prompt.
+    pub fn play(&self, nvim: &mut Nvim, opts: &PlaybackOpts) -> Result<(), Error> {
+        let notation = self.keys.to_string();
+        for _ in 0..opts.count {
+            match opts.delay {
+                Some(delay) => {
+                    for key in self.keys.iter() {
+                        send(nvim, key, opts)?;
+                        nvim.flush_notifications()?;
+                        thread::sleep(delay);
+                    }
+                }
+                None => send(nvim, &notation, opts)?,
+            }
+        }
+
+        if let Some(timeout) = opts.timeout {
+            // Requests are handled after pending input, so the response
+            // arrives once the keys have been processed
+            nvim.call_async("nvim_eval", vec![Value::from("1")])?
+                .wait_timeout(timeout)
+                .map_err(|error| match error {
+                    Error::TimeoutError(_) => {
+                        Error::TimeoutError("Neovim didn't finish processing the macro".to_string())
+                    }
+                    error => error,
+                })?;
+        }
+        Ok(())
+    }
+}
+
+/// Send `keys`, written in key notation
+fn send(nvim: &mut Nvim, keys: &str, opts: &PlaybackOpts) -> Result<(), Error> {
+    match &opts.feedkeys {
+        Some(mode) => {
+            let keys = nvim.replace_termcodes(keys, true, true, true)?;
+            nvim.feedkeys(keys, mode.as_str(), false)
+        }
+        None => nvim.send_notification("nvim_input", vec![Value::from(keys)]),
+    }
+}
+
+/// How long `Nvim::type_text` waits for Neovim to accept more input before
+/// giving up
+const INPUT_STALL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How `Nvim::type_text` splits and paces text
+///
+/// By default text is sent in chunks of up to 1024 bytes, without waiting
+/// between them. Keys are never split across chunks, so a chunk size of `1`
+/// sends one key at a time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pacing {
+    chunk_size: usize,
+    delay: Option<Duration>,
+}
+
+impl Default for Pacing {
+    fn default() -> Self {
+        Pacing {
+            chunk_size: 1024,
+            delay: None,
+        }
+    }
+}
+
+impl Pacing {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send at most `bytes` of escaped text with each `nvim_input` call
+    pub fn chunk_size(mut self, bytes: usize) -> Self {
+        self.chunk_size = bytes.max(1);
+        self
+    }
+
+    /// Wait `delay` after each chunk, e.g. to type at a watchable speed in a
+    /// screencast
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+}
+
+/// Escape `text` to be typed literally, as keys in key notation
+///
+/// Line breaks, including `\r\n`, are typed as `<CR>`.
+fn escape_input(text: &str) -> Vec<String> {
+    let mut keys = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '<' => keys.push("<LT>".to_string()),
+            '\r' if chars.peek() == Some(&'\n') => (),
+            '\r' | '\n' => keys.push("<CR>".to_string()),
+            c => keys.push(c.to_string()),
+        }
+    }
+    keys
+}
+
+impl Nvim {
+    /// Type `text` as if the user typed it, with `nvim_input`
+    ///
+    /// The text is escaped so it is typed literally rather than read as key
+    /// notation, and sent in chunks as set by `pacing`. Neovim may accept
+    /// only part of a chunk when its input buffer is full, in which case
+    /// the rest is sent again. Returns the number of bytes Neovim accepted
+    /// from each call, or `Error::TimeoutError` if it stops accepting input.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::api::{Nvim, Pacing};
+    /// use std::time::Duration;
+    ///
+    /// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+    /// nvim.start_event_loop(None, None);
+    ///
+    /// nvim.input("i").unwrap();
+    /// let pacing = Pacing::new().chunk_size(1).delay(Duration::from_millis(30));
+    /// nvim.type_text("fn main() {}\n", &pacing).unwrap();
+    /// ```
+    pub fn type_text(&self, text: &str, pacing: &Pacing) -> Result<Vec<usize>, Error> {
+        let mut chunks: Vec<String> = Vec::new();
+        for key in escape_input(text) {
+            match chunks.last_mut() {
+                Some(chunk) if chunk.len() + key.len() <= pacing.chunk_size => chunk.push_str(&key),
+                _ => chunks.push(key),
+            }
+        }
+
+        let mut accepted = Vec::new();
+        for chunk in chunks {
+            let mut rest = chunk.as_str();
+            let mut stalled = Instant::now();
+            while !rest.is_empty() {
+                let written = self.input(rest)?.clamp(0, rest.len() as i64) as usize;
+                accepted.push(written);
+                if written > 0 {
+                    rest = &rest[written..];
+                    stalled = Instant::now();
+                } else if stalled.elapsed() >= INPUT_STALL_TIMEOUT {
+                    return Err(Error::TimeoutError(format!(
+                        "Neovim stopped accepting input with {} bytes left",
+                        rest.len()
+                    )));
+                } else {
+                    // Give Neovim time to process the input it has
+                    thread::sleep(Duration::from_millis(10));
+                }
+            }
+            if let Some(delay) = pacing.delay {
+                thread::sleep(delay);
+            }
+        }
+        Ok(accepted)
+    }
+}
@@ -0,0 +1,335 @@
+use rmpv::Value;
+
+use crate::api::convert::AsValue;
+use crate::api::*;
+use crate::error::Error;
+
+/// Lua run by `LayoutSnapshot::restore`, replacing every tabpage and window
+///
+/// Each split node's first child reuses the node's window, and the others
+/// are split off to its right or below in turn, before the children are
+/// built themselves. Sizes are applied twice, as resizing a window also
+/// resizes its neighbours.
+const RESTORE_LAYOUT_LUA: &str = r#"
+local tabs, current = ...
+vim.cmd('silent! tabonly!')
+vim.cmd('silent! only!')
+
+local function buffer(leaf)
+    if leaf.buffer ~= '' then
+        local buf = vim.fn.bufadd(leaf.buffer)
+        vim.bo[buf].buflisted = true
+        return buf
+    end
+    if vim.api.nvim_buf_is_valid(leaf.bufnr) and vim.api.nvim_buf_get_name(leaf.bufnr) == '' then
+        return leaf.bufnr
+    end
+    return vim.api.nvim_create_buf(true, false)
+end
+
+local sizes = {}
+local function build(node, win, wins)
+    if node.kind == 'leaf' then
+        vim.api.nvim_win_set_buf(win, buffer(node))
+        pcall(vim.api.nvim_win_set_cursor, win, node.cursor)
+        table.insert(sizes, { win, node.width, node.height })
+        table.insert(wins, win)
+        return
+    end
+    local split = node.kind == 'row' and 'rightbelow vsplit' or 'rightbelow split'
+    local children = { win }
+    for i = 2, #node.children do
+        children[i] = vim.api.nvim_win_call(children[i - 1], function()
+            vim.cmd(split)
+            return vim.api.nvim_get_current_win()
+        end)
+    end
+    for i, child in ipairs(node.children) do
+        build(child, children[i], wins)
+    end
+end
+
+local tab_wins = {}
+for i, tab in ipairs(tabs) do
+    if i > 1 then
+        vim.cmd('tab split')
+    end
+    local wins = {}
+    build(tab.root, vim.api.nvim_get_current_win(), wins)
+    tab_wins[i] = wins
+end
+for _ = 1, 2 do
+    for _, size in ipairs(sizes) do
+        vim.api.nvim_win_set_width(size[1], size[2])
+        vim.api.nvim_win_set_height(size[1], size[3])
+    end
+end
+for i, tab in ipairs(tabs) do
+    vim.api.nvim_set_current_win(tab_wins[i][tab.current + 1])
+end
+vim.api.nvim_set_current_win(tab_wins[current + 1][tabs[current + 1].current + 1])
+"#;
+
+/// Captures the arrangement of tabpages and windows, see `Layout::capture`
+pub struct Layout;
+
+/// The tabpages and windows captured by `Layout::capture`
+///
+/// With the `serde` feature the snapshot can be saved to disk, e.g. as a
+/// session file, and restored when Neovim is next started.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LayoutSnapshot {
+    /// Every tabpage, in order
+    pub tabpages: Vec<TabpageLayout>,
+    /// The index of the current tabpage
+    pub current: usize,
+}
+
+/// The windows of one tabpage in a `LayoutSnapshot`
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TabpageLayout {
+    pub root: LayoutNode,
+    /// The index of the tabpage's current window, in the order of
+    /// `LayoutNode::windows`
+    pub current: usize,
+}
+
+/// A window, or a group of windows split in one direction
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LayoutNode {
+    Window(WindowLayout),
+    /// Windows side by side, from left to right
+    Row(Vec<LayoutNode>),
+    /// Windows stacked from top to bottom
+    Column(Vec<LayoutNode>),
+}
+
+/// A window in a `LayoutSnapshot`
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WindowLayout {
+    /// The full name of the displayed buffer, empty if it has none
+    pub buffer: String,
+    /// The handle of the displayed buffer, only used to restore unnamed
+    /// buffers to the same Neovim instance
+    pub bufnr: i64,
+    pub width: i64,
+    pub height: i64,
+    /// The (1,0)-indexed cursor position
+    pub cursor: Point,
+}
+
+impl LayoutNode {
+    /// The windows of the node, from left to right and top to bottom
+    pub fn windows(&self) -> Vec<&WindowLayout> {
+        match self {
+            LayoutNode::Window(window) => vec![window],
+            LayoutNode::Row(children) | LayoutNode::Column(children) => {
+                children.iter().flat_map(LayoutNode::windows).collect()
+            }
+        }
+    }
+
+    /// Decode the result of `winlayout()`, with the windows' IDs in place of
+    /// their layouts
+    fn from_winlayout(value: &Value, ids: &mut Vec<i64>) -> Result<Self, Error> {
+        let invalid = || Error::DecodingError(format!("Invalid window layout {}", value));
+        let children = |ids: &mut Vec<i64>| -> Result<Vec<LayoutNode>, Error> {
+            value[1]
+                .as_array()
+                .ok_or_else(invalid)?
+                .iter()
+                .map(|child| LayoutNode::from_winlayout(child, ids))
+                .collect()
+        };
+        match value[0].as_str() {
+            Some("leaf") => {
+                ids.push(value[1].as_i64().ok_or_else(invalid)?);
+                Ok(LayoutNode::Window(WindowLayout {
+                    buffer: String::new(),
+                    bufnr: 0,
+                    width: 0,
+                    height: 0,
+                    cursor: Point::new(1, 0),
+                }))
+            }
+            Some("row") => Ok(LayoutNode::Row(children(ids)?)),
+            Some("col") => Ok(LayoutNode::Column(children(ids)?)),
+            _ => Err(invalid()),
+        }
+    }
+
+    fn windows_mut(&mut self) -> Vec<&mut WindowLayout> {
+        match self {
+            LayoutNode::Window(window) => vec![window],
+            LayoutNode::Row(children) | LayoutNode::Column(children) => children
+                .iter_mut()
+                .flat_map(LayoutNode::windows_mut)
+                .collect(),
+        }
+    }
+}
+
+impl AsValue for LayoutNode {
+    fn convert(&self) -> Value {
+        let entry = |key: &str, value: Value| (Value::from(key), value);
+        match self {
+            LayoutNode::Window(window) => Value::Map(vec![
+                entry("kind", Value::from("leaf")),
+                entry("buffer", Value::from(window.buffer.as_str())),
+                entry("bufnr", Value::from(window.bufnr)),
+                entry("width", Value::from(window.width)),
+                entry("height", Value::from(window.height)),
+                entry("cursor", window.cursor.convert()),
+            ]),
+            LayoutNode::Row(children) | LayoutNode::Column(children) => {
+                let kind = match self {
+                    LayoutNode::Row(_) => "row",
+                    _ => "col",
+                };
+                Value::Map(vec![
+                    entry("kind", Value::from(kind)),
+                    entry(
+                        "children",
+                        Value::from(children.iter().map(|c| c.convert()).collect::<Vec<_>>()),
+                    ),
+                ])
+            }
+        }
+    }
+}
+
+impl Layout {
+    /// Capture every tabpage's windows, with their sizes, buffers and cursors
+    ///
+    /// The window trees of the tabpages are read in one atomic batch, and the
+    /// details of their windows in a second. Floating windows aren't
+    /// captured.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::api::{Layout, Nvim};
+    ///
+    /// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+    /// nvim.start_event_loop(None, None);
+    ///
+    /// let layout = Layout::capture(&nvim).unwrap();
+    /// nvim.command("tabonly | only | enew").unwrap();
+    /// layout.restore(&nvim).unwrap();
+    /// ```
+    pub fn capture(nvim: &Nvim) -> Result<LayoutSnapshot, Error> {
+        let mut nvim = nvim.clone();
+
+        let mut batch = CallBatch::new();
+        let layouts = batch.call::<Vec<Value>>(
+            "nvim_eval",
+            vec![Value::from(
+                "map(range(1, tabpagenr('$')), 'winlayout(v:val)')",
+            )],
+        );
+        let current_wins = batch.call::<Vec<i64>>(
+            "nvim_eval",
+            vec![Value::from(
+                "map(range(1, tabpagenr('$')), 'win_getid(tabpagewinnr(v:val), v:val)')",
+            )],
+        );
+        let current_tab = batch.call::<i64>("nvim_eval", vec![Value::from("tabpagenr()")]);
+        let results = nvim.call_batch(batch)?;
+        // Tabpage numbers start from 1
+        let current = usize::try_from(results.get(&current_tab)? - 1).unwrap_or(0);
+
+        let mut ids = Vec::new();
+        let mut tabpages = Vec::new();
+        for (layout, current_win) in results
+            .get(&layouts)?
+            .iter()
+            .zip(results.get(&current_wins)?)
+        {
+            let start = ids.len();
+            let root = LayoutNode::from_winlayout(layout, &mut ids)?;
+            let current = ids[start..]
+                .iter()
+                .position(|&id| id == current_win)
+                .unwrap_or(0);
+            tabpages.push(TabpageLayout { root, current });
+        }
+
+        let mut batch = CallBatch::new();
+        let details: Vec<_> = ids
+            .iter()
+            .map(|&id| {
+                let win = Value::from(id);
+                (
+                    batch.call::<Vec<Value>>(
+                        "nvim_exec_lua",
+                        vec![
+                            Value::from(
+                                "local buf = vim.api.nvim_win_get_buf(...) \
+                                 return { buf, vim.api.nvim_buf_get_name(buf) }",
+                            ),
+                            Value::from(vec![win.clone()]),
+                        ],
+                    ),
+                    batch.call::<i64>("nvim_win_get_width", vec![win.clone()]),
+                    batch.call::<i64>("nvim_win_get_height", vec![win.clone()]),
+                    batch.call::<Point>("nvim_win_get_cursor", vec![win]),
+                )
+            })
+            .collect();
+        let results = nvim.call_batch(batch)?;
+
+        let windows = tabpages
+            .iter_mut()
+            .flat_map(|tabpage| tabpage.root.windows_mut());
+        for (window, (buffer, width, height, cursor)) in windows.zip(details) {
+            let buffer = results.get(&buffer)?;
+            let invalid = || Error::DecodingError(format!("Invalid buffer {:?}", buffer));
+            window.bufnr = buffer[0].as_i64().ok_or_else(invalid)?;
+            window.buffer = buffer[1].as_str().ok_or_else(invalid)?.to_string();
+            window.width = results.get(&width)?;
+            window.height = results.get(&height)?;
+            window.cursor = results.get(&cursor)?;
+        }
+
+        Ok(LayoutSnapshot { tabpages, current })
+    }
+}
+
+impl LayoutSnapshot {
+    /// Replace every tabpage and window with those of the snapshot
+    ///
+    /// Buffers are found, or added, by name, so a snapshot saved to disk can
+    /// be restored in a new Neovim instance; unnamed buffers are replaced by
+    /// empty ones unless they still exist. Windows are closed even if their
+    /// buffers have unsaved changes, which are kept in the hidden buffers.
+    /// The layout is rebuilt by a single Lua chunk, so nothing is redrawn
+    /// until it is complete.
+    pub fn restore(&self, nvim: &Nvim) -> Result<(), Error> {
+        if self.tabpages.is_empty() || self.current >= self.tabpages.len() {
+            return Err(Error::InvalidArgument(format!(
+                "The current tabpage {} isn't one of the {} tabpages",
+                self.current,
+                self.tabpages.len()
+            )));
+        }
+        let tabpages = self
+            .tabpages
+            .iter()
+            .map(|tabpage| {
+                Value::Map(vec![
+                    (Value::from("root"), tabpage.root.convert()),
+                    (Value::from("current"), Value::from(tabpage.current)),
+                ])
+            })
+            .collect::<Vec<_>>();
+        nvim.exec_lua(
+            RESTORE_LAYOUT_LUA,
+            vec![Value::from(tabpages), Value::from(self.current)],
+        )?;
+        Ok(())
+    }
+}
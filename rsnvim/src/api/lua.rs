@@ -0,0 +1,62 @@
+use rmpv::Value;
+
+use crate::api::*;
+use crate::error::Error;
+
+/// Execute Lua code with the given arguments, decoding its result
+///
+/// `lua!(nvim, code, args...)` converts each argument into a `Value` and
+/// calls `Nvim::exec_lua_typed`, so the result type is usually inferred.
+/// Inside the code the arguments are available as `...`.
+///
+/// # Example
+///
+/// ```no_run
+/// use rsnvim::api::Nvim;
+/// use rsnvim::lua;
+///
+/// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+/// nvim.start_event_loop(None, None);
+///
+/// let sum: i64 = lua!(nvim, "local a, b = ...; return a + b", 1, 2).unwrap();
+/// let name: String = lua!(nvim, "return vim.api.nvim_buf_get_name(...)", 0).unwrap();
+/// ```
+#[macro_export]
+macro_rules! lua {
+    ($nvim:expr, $code:expr $(, $arg:expr)* $(,)?) => {
+        $nvim.exec_lua_typed(
+            $code,
+            ::std::vec![$($crate::rmpv::Value::from($arg)),*],
+        )
+    };
+}
+
+impl Nvim {
+    /// Execute Lua code, decoding its result as a `T`
+    ///
+    /// Returns `Error::DecodingError` if the result can't be decoded.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rmpv::Value;
+    /// use rsnvim::api::Nvim;
+    ///
+    /// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+    /// nvim.start_event_loop(None, None);
+    ///
+    /// let lines: Vec<String> = nvim
+    ///     .exec_lua_typed(
+    ///         "return vim.api.nvim_buf_get_lines(0, 0, ..., false)",
+    ///         vec![Value::from(10)],
+    ///     )
+    ///     .unwrap();
+    /// ```
+    pub fn exec_lua_typed<T: FromValue>(
+        &mut self,
+        code: impl Into<String>,
+        args: impl IntoIterator<Item = Value>,
+    ) -> Result<T, Error> {
+        T::from_value(self.exec_lua(code, args)?)
+    }
+}
@@ -0,0 +1,341 @@
+use rmpv::Value;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use crate::api::convert::FromValue;
+use crate::api::*;
+use crate::error::Error;
+
+/// A mark, as returned by `getmarklist()`
+///
+/// Positions are (1,0)-indexed, matching `Window::get_cursor`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mark {
+    /// The mark's name, e.g. `'a` or `'A`
+    pub name: String,
+    /// The buffer number the mark is in
+    pub buf: i64,
+    pub row: i64,
+    pub col: i64,
+    /// The file the mark is in, only set for global marks
+    pub file: Option<String>,
+}
+
+/// An entry in the jumplist
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JumpEntry {
+    /// The buffer number of the jump
+    pub buf: i64,
+    pub row: i64,
+    pub col: i64,
+    /// The file of the jump, if known
+    pub file: Option<String>,
+}
+
+/// An entry in a buffer's changelist
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChangeEntry {
+    pub row: i64,
+    pub col: i64,
+}
+
+/// A jumplist or changelist and the current position within it
+///
+/// `position` is equal to `entries.len()` when not navigating the list.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PositionList<T> {
+    pub entries: Vec<T>,
+    pub position: usize,
+}
+
+/// Every mark, jump and change of the current window and buffer
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Navigation {
+    /// The global (file) marks, e.g. `'A`
+    pub global_marks: Vec<Mark>,
+    /// The marks local to the current buffer, e.g. `'a`
+    pub buffer_marks: Vec<Mark>,
+    /// The jumplist of the current window
+    pub jumps: PositionList<JumpEntry>,
+    /// The changelist of the current buffer
+    pub changes: PositionList<ChangeEntry>,
+}
+
+/// A change to a global mark, reported by a `MarkWatcher`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MarkChange {
+    /// The mark's name, e.g. `'A`
+    pub name: String,
+    /// The mark before the change, `None` if it was unset
+    pub previous: Option<Mark>,
+    /// The mark after the change, `None` if it was deleted
+    pub current: Option<Mark>,
+}
+
+/// Reports changes to the global marks, created by `Nvim::watch_global_marks`
+///
+/// Neovim has no event for mark changes, so the marks are polled. A mark
+/// changed and then restored between polls isn't reported. Polling stops
+/// when the watcher is dropped or the connection is closed.
+pub struct MarkWatcher {
+    changes: mpsc::Receiver<MarkChange>,
+    // Dropping the sender stops the polling thread
+    _stop: mpsc::Sender<()>,
+}
+
+impl MarkWatcher {
+    /// The changes, in the order they were seen
+    ///
+    /// The channel disconnects once the connection is closed.
+    pub fn changes(&self) -> &mpsc::Receiver<MarkChange> {
+        &self.changes
+    }
+}
+
+/// Find the changes between two lists of marks
+fn diff_marks(previous: &[Mark], current: &[Mark]) -> Vec<MarkChange> {
+    let find = |marks: &[Mark], name: &str| marks.iter().find(|m| m.name == name).cloned();
+    let mut changes: Vec<MarkChange> = current
+        .iter()
+        .filter(|mark| !previous.contains(mark))
+        .map(|mark| MarkChange {
+            name: mark.name.clone(),
+            previous: find(previous, &mark.name),
+            current: Some(mark.clone()),
+        })
+        .collect();
+    changes.extend(
+        previous
+            .iter()
+            .filter(|mark| find(current, &mark.name).is_none())
+            .map(|mark| MarkChange {
+                name: mark.name.clone(),
+                previous: Some(mark.clone()),
+                current: None,
+            }),
+    );
+    changes
+}
+
+/// Get the integer `key` of a dictionary returned by a Vimscript function
+fn get_i64(dict: &Value, key: &str) -> Result<i64, Error> {
+    dict[key]
+        .as_i64()
+        .ok_or_else(|| Error::DecodingError(format!("Missing integer '{}' in {}", key, dict)))
+}
+
+/// Get the optional string `key`, treating empty strings as missing
+fn get_file(dict: &Value, key: &str) -> Option<String> {
+    dict[key]
+        .as_str()
+        .filter(|file| !file.is_empty())
+        .map(|file| file.to_string())
+}
+
+impl FromValue for Mark {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        // `pos` has the form [bufnum, lnum, col, off] with a one-indexed col
+        let pos = &value["pos"];
+        let (buf, row, col) = match (pos[0].as_i64(), pos[1].as_i64(), pos[2].as_i64()) {
+            (Some(buf), Some(row), Some(col)) => (buf, row, (col - 1).max(0)),
+            _ => return Err(Error::DecodingError(format!("Invalid mark {}", value))),
+        };
+        Ok(Mark {
+            name: String::from_value(value["mark"].clone())?,
+            buf,
+            row,
+            col,
+            file: get_file(&value, "file"),
+        })
+    }
+}
+
+impl FromValue for JumpEntry {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        Ok(JumpEntry {
+            buf: get_i64(&value, "bufnr")?,
+            row: get_i64(&value, "lnum")?,
+            col: get_i64(&value, "col")?,
+            file: get_file(&value, "filename"),
+        })
+    }
+}
+
+impl FromValue for ChangeEntry {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        Ok(ChangeEntry {
+            row: get_i64(&value, "lnum")?,
+            col: get_i64(&value, "col")?,
+        })
+    }
+}
+
+impl<T: FromValue> FromValue for PositionList<T> {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        // Returned as [entries, position]
+        match value {
+            Value::Array(mut v) if v.len() == 2 => {
+                let position = v.pop().unwrap();
+                Ok(PositionList {
+                    entries: Vec::from_value(v.pop().unwrap())?,
+                    position: u64::from_value(position)? as usize,
+                })
+            }
+            other => Err(Error::DecodingError(format!("Invalid list {}", other))),
+        }
+    }
+}
+
+/// Queue a call to the Vimscript function `function`
+fn call_function<T: FromValue>(
+    batch: &mut CallBatch,
+    function: &str,
+    args: Vec<Value>,
+) -> BatchSlot<T> {
+    batch.call(
+        "nvim_call_function",
+        vec![Value::from(function), Value::from(args)],
+    )
+}
+
+impl Nvim {
+    /// Get every mark, jump and change in a single atomic batch
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::api::Nvim;
+    ///
+    /// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+    /// nvim.start_event_loop(None, None);
+    ///
+    /// let navigation = nvim.get_navigation().unwrap();
+    /// for mark in navigation.global_marks {
+    ///     println!("{} {}:{}", mark.name, mark.file.unwrap_or_default(), mark.row);
+    /// }
+    /// ```
+    pub fn get_navigation(&mut self) -> Result<Navigation, Error> {
+        let mut batch = CallBatch::new();
+        let global_marks = call_function(&mut batch, "getmarklist", Vec::new());
+        let buffer_marks = call_function(&mut batch, "getmarklist", vec![Value::from("%")]);
+        let jumps = call_function(&mut batch, "getjumplist", Vec::new());
+        let changes = call_function(&mut batch, "getchangelist", Vec::new());
+
+        let results = self.call_batch(batch)?;
+        Ok(Navigation {
+            global_marks: results.get(&global_marks)?,
+            buffer_marks: results.get(&buffer_marks)?,
+            jumps: results.get(&jumps)?,
+            changes: results.get(&changes)?,
+        })
+    }
+
+    /// Get the global marks and the marks local to the current buffer
+    pub fn get_marks(&mut self) -> Result<Vec<Mark>, Error> {
+        let mut batch = CallBatch::new();
+        let global_marks = call_function::<Vec<Mark>>(&mut batch, "getmarklist", Vec::new());
+        let buffer_marks =
+            call_function::<Vec<Mark>>(&mut batch, "getmarklist", vec![Value::from("%")]);
+
+        let results = self.call_batch(batch)?;
+        let mut marks = results.get(&global_marks)?;
+        marks.extend(results.get(&buffer_marks)?);
+        Ok(marks)
+    }
+
+    /// Get the jumplist of the current window
+    pub fn get_jumplist(&mut self) -> Result<PositionList<JumpEntry>, Error> {
+        PositionList::from_value(self.call_function("getjumplist", Vec::new())?)
+    }
+
+    /// Get the changelist of the current buffer
+    pub fn get_changelist(&mut self) -> Result<PositionList<ChangeEntry>, Error> {
+        PositionList::from_value(self.call_function("getchangelist", Vec::new())?)
+    }
+
+    /// Get the global mark `name`, e.g. `'A'` or `'0'`, or `None` if unset
+    ///
+    /// A typed form of `get_mark`. Only uppercase letters and digits name
+    /// global marks.
+    pub fn get_global_mark(&mut self, name: char) -> Result<Option<Mark>, Error> {
+        // Returned as [row, col, buffer, buffername], all zero if unset
+        let mark = self.get_mark(name.to_string(), Vec::new())?;
+        let invalid = || Error::DecodingError(format!("Invalid mark {:?}", mark));
+        let (row, col, buf) = match mark.as_slice() {
+            [row, col, buf, _] => (
+                row.as_i64().ok_or_else(invalid)?,
+                col.as_i64().ok_or_else(invalid)?,
+                buf.as_i64().ok_or_else(invalid)?,
+            ),
+            _ => return Err(invalid()),
+        };
+        if row == 0 {
+            return Ok(None);
+        }
+        Ok(Some(Mark {
+            name: format!("'{}", name),
+            buf,
+            row,
+            col,
+            file: mark[3]
+                .as_str()
+                .filter(|file| !file.is_empty())
+                .map(|file| file.to_string()),
+        }))
+    }
+
+    /// Watch the global marks for changes, checking every `interval`
+    ///
+    /// Only changes made after this returns are reported.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::api::Nvim;
+    /// use std::time::Duration;
+    ///
+    /// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+    /// nvim.start_event_loop(None, None);
+    ///
+    /// let watcher = nvim.watch_global_marks(Duration::from_millis(500)).unwrap();
+    /// for change in watcher.changes() {
+    ///     match change.current {
+    ///         Some(mark) => println!("{} set to line {}", change.name, mark.row),
+    ///         None => println!("{} deleted", change.name),
+    ///     }
+    /// }
+    /// ```
+    pub fn watch_global_marks(&mut self, interval: Duration) -> Result<MarkWatcher, Error> {
+        let mut marks: Vec<Mark> = Vec::from_value(self.call_function("getmarklist", Vec::new())?)?;
+        let (sender, changes) = mpsc::channel();
+        let (stop, stopped) = mpsc::channel::<()>();
+        let nvim = self.clone();
+        thread::spawn(move || loop {
+            match stopped.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => (),
+                _ => return,
+            }
+            let current = nvim
+                .call_function("getmarklist", Vec::new())
+                .and_then(Vec::<Mark>::from_value);
+            match current {
+                Ok(current) => {
+                    for change in diff_marks(&marks, &current) {
+                        if sender.send(change).is_err() {
+                            return;
+                        }
+                    }
+                    marks = current;
+                }
+                Err(_) if !nvim.is_alive() => return,
+                // Try again at the next poll
+                Err(_) => (),
+            }
+        });
+        Ok(MarkWatcher {
+            changes,
+            _stop: stop,
+        })
+    }
+}
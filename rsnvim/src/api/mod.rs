@@ -1,27 +1,149 @@
+mod batch;
 mod buffer;
+mod channel;
+mod client_info;
+mod common;
+mod completion;
 mod convert;
+mod decorations;
+mod dict;
+mod dynamic;
+mod exec;
+mod files;
+mod float;
+mod highlight;
+mod info;
+mod keymap;
+mod keys;
+mod layout;
+mod lua;
+mod marks;
 mod nvim;
+mod nvim_string;
+mod opts;
+mod redraw;
+#[cfg(feature = "ropey")]
+mod rope;
+mod runtime;
+mod sandbox;
+mod scan;
+mod scoped;
+mod search;
+mod snapshot;
 mod tabpage;
+mod text;
+mod ui;
 mod window;
+mod workspace_edit;
 
-use crate::error::Error;
-use crate::handler::{NotificationHandler, RequestHandler};
-use crate::session::Session;
+pub use batch::{BatchError, BatchResults, BatchSlot, CallBatch};
+pub use channel::{ChannelClient, ChannelInfo, ChannelMode, ChannelStream, ClientVersion};
+pub use client_info::ClientInfoBuilder;
+pub(crate) use common::{Handle, Remote};
+pub use completion::{CompletionKind, InputOpts};
+pub use convert::{AsValue, FromValue};
+pub use decorations::{Extmark, HighlightRange, VirtualText};
+pub use dict::Dict;
+pub use dynamic::DynamicApi;
+pub use exec::ExecResult;
+pub use files::{EditOpts, FileFormat, FileInfo};
+pub use float::FloatConfigBuilder;
+pub use highlight::{Color, HighlightAttrs, HlNamespaceGuard};
+pub use info::{ApiInfo, FunctionInfo, Version};
+pub use keys::{Keys, Macro, Pacing, PlaybackOpts};
+pub use layout::{Layout, LayoutNode, LayoutSnapshot, TabpageLayout, WindowLayout};
+pub use marks::{ChangeEntry, JumpEntry, Mark, MarkChange, MarkWatcher, Navigation, PositionList};
+pub use nvim::{API_LEVEL, GENERATED_AT, GIT_HASH};
+pub use nvim_string::NvimString;
+pub use opts::{
+    Anchor, AutocmdOpts, Border, ClientType, ExtmarkOpts, HlMode, KeymapOpts, Relative, Split,
+    TextPos, UiOptions, VirtTextPos, WinConfig,
+};
+pub(crate) use redraw::RedrawSender;
+pub use redraw::{
+    Chunk, CmdlinePos, CmdlineShow, HlAttrDefine, HlAttrTable, HlAttrs, MsgHistoryEntry, MsgShow,
+    RedrawBatch, RedrawEvent, RedrawMode, RedrawReceiver,
+};
+#[cfg(feature = "ropey")]
+pub use rope::RopeSync;
+pub use sandbox::SandboxOpts;
+pub use scan::BufferScan;
+pub use search::{MatchRange, SearchOpts};
+pub use snapshot::{Snapshot, SnapshotHighlight, SnapshotSpec, UPDATE_SNAPSHOTS_VAR};
+pub use text::{Indexing, Point, Position, Range};
+pub use ui::Ui;
+pub use workspace_edit::{EditTarget, FileEditResult, TextEdit, WorkspaceEdit};
+
+use crate::client::{ClientStats, ResponseHandle};
+use crate::deprecation::DeprecationPolicy;
+use crate::error::{AsyncError, Error};
+use crate::handler::{Notification, NotificationHandler, RequestHandler};
+use crate::metrics::MethodMetrics;
+use crate::rpc::DecodeLimits;
+use crate::session::{Endpoint, NotificationWaiter, RetrySpec, Session};
 
 use rmpv::Value;
-use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+/// Verify the connected Neovim supports at least the given API level
+///
+/// The level must be a constant and is checked at compile time against the
+/// `API_LEVEL` of these bindings, while the connected Neovim is checked at
+/// runtime using `Nvim::require_api_level`.
+///
+/// # Example
+///
+/// ```no_run
+/// use rsnvim::api::Nvim;
+/// use rsnvim::assert_api_level;
+///
+/// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+/// nvim.start_event_loop(None, None);
+///
+/// if let Err(error) = assert_api_level!(nvim, 10) {
+///     panic!("{}", error);
+/// }
+/// ```
+#[macro_export]
+macro_rules! assert_api_level {
+    ($nvim:expr, $level:expr) => {{
+        const _: () = assert!(
+            $level <= $crate::api::API_LEVEL,
+            "Requested API level is newer than these bindings"
+        );
+        $nvim.require_api_level($level)
+    }};
+}
 
 /// The Neovim connection
 ///
 /// This struct exposes each way a user can connect to Neovim's RPC
 /// socket, alongside Rust functions for each API method.
+///
+/// Clones share the connection and may be used from other threads, as may
+/// the `Buffer`, `Window` and `Tabpage` handles it returns. The connection
+/// is only locked while a request is written, so calls from several
+/// threads are in flight at once, each matched to its response however
+/// Neovim orders them. Wrapping an `Nvim` in another `Arc` isn't needed.
+#[derive(Clone)]
 pub struct Nvim {
     session: Arc<Mutex<Session>>,
 }
 
+impl Remote for Nvim {
+    fn session(&self) -> &Arc<Mutex<Session>> {
+        &self.session
+    }
+}
+
 impl Nvim {
     pub fn from_session(session: Session) -> Self {
-        Nvim { session: Arc::new(Mutex::new(session)) }
+        Nvim {
+            session: Arc::new(Mutex::new(session)),
+        }
     }
 
     /// Create a Neovim connection using a TCP socket
@@ -99,6 +221,74 @@ impl Nvim {
         })
     }
 
+    /// Create a Neovim connection over any transport
+    ///
+    /// See `Session::from_transport` for details.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::api::Nvim;
+    /// use std::process::{Command, Stdio};
+    ///
+    /// let mut child = Command::new("nvim")
+    ///     .args(["--embed", "--headless"])
+    ///     .stdin(Stdio::piped())
+    ///     .stdout(Stdio::piped())
+    ///     .spawn()
+    ///     .unwrap();
+    /// let reader = child.stdout.take().unwrap();
+    /// let writer = child.stdin.take().unwrap();
+    /// let mut nvim = Nvim::from_transport(reader, writer);
+    /// ```
+    pub fn from_transport<R, W>(reader: R, writer: W) -> Self
+    where
+        R: Read + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        Nvim::from_session(Session::from_transport(reader, writer))
+    }
+
+    /// Connect to the Neovim this process was started from, using the
+    /// address in `$NVIM` or `$NVIM_LISTEN_ADDRESS`
+    ///
+    /// See `Session::connect_auto` for details.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::api::Nvim;
+    ///
+    /// let mut nvim = match Nvim::connect_auto() {
+    ///     Ok(nvim) => nvim,
+    ///     Err(error) => panic!("Couldn't connect to $NVIM: {}", error)
+    /// };
+    /// ```
+    pub fn connect_auto() -> Result<Self, Error> {
+        Ok(Nvim {
+            session: Arc::new(Mutex::new(Session::connect_auto()?)),
+        })
+    }
+
+    /// Connect to `endpoint`, retrying until its socket accepts connections
+    ///
+    /// See `Session::connect_with_retry` for details.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::api::Nvim;
+    /// use rsnvim::session::{Endpoint, RetrySpec};
+    ///
+    /// let endpoint = Endpoint::Tcp("127.0.0.1:6666".to_string());
+    /// let mut nvim = Nvim::connect_with_retry(endpoint, RetrySpec::new()).unwrap();
+    /// ```
+    pub fn connect_with_retry(endpoint: Endpoint, spec: RetrySpec) -> Result<Self, Error> {
+        Ok(Nvim {
+            session: Arc::new(Mutex::new(Session::connect_with_retry(endpoint, spec)?)),
+        })
+    }
+
     /// Begin the RPC event loop
     ///
     /// This function must be called before RPC messages can be sent as it
@@ -122,9 +312,215 @@ impl Nvim {
         request_handler: Option<Box<dyn RequestHandler + Send>>,
         notification_handler: Option<Box<dyn NotificationHandler + Send>>,
     ) {
-        let mut session = self.session.lock().unwrap();
-        session
-            .start_event_loop(request_handler, notification_handler)
+        let session = self.session.lock().unwrap().clone();
+        session.start_event_loop(request_handler, notification_handler)
+    }
+
+    /// Verify the connected Neovim supports at least API level `level`
+    ///
+    /// Returns an `UnsupportedVersion` error describing the server's version
+    /// otherwise, allowing tools to fail fast with an actionable message.
+    pub fn require_api_level(&mut self, level: u64) -> Result<(), Error> {
+        let version = &self.api_info()?.version;
+        if version.api_level < level {
+            return Err(Error::UnsupportedVersion(format!(
+                "Neovim {} provides API level {} but level {} is required",
+                version, version.api_level, level
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Stop the event loop, closing the connection to Neovim
+    ///
+    /// See `Session::stop_event_loop` for details.
+    pub fn stop_event_loop(&mut self) -> Result<(), Error> {
+        let session = self.session.lock().unwrap().clone();
+        session.stop_event_loop()
+    }
+
+    /// Wait for the event loop to exit
+    ///
+    /// See `Session::join` for details.
+    pub fn join(&mut self) -> Result<(), Error> {
+        // Don't hold the lock while waiting, so clones can still be used
+        let session = self.session.lock().unwrap().clone();
+        session.join()
+    }
+
+    /// Whether the event loop is running and the connection is open
+    pub fn is_alive(&self) -> bool {
+        let session = self.session.lock().unwrap().clone();
+        session.is_alive()
+    }
+
+    /// Register a callback for when Neovim closes the connection
+    ///
+    /// See `Session::on_disconnect` for details.
+    pub fn on_disconnect<F>(&mut self, callback: F)
+    where
+        F: Fn() + Send + 'static,
+    {
+        let session = self.session.lock().unwrap().clone();
+        session.on_disconnect(callback)
+    }
+
+    /// Register a cleanup hook run by `shutdown`
+    ///
+    /// See `Session::on_shutdown` for details.
+    pub fn on_shutdown<F>(&self, hook: F)
+    where
+        F: FnOnce() -> Result<(), Error> + Send + 'static,
+    {
+        let session = self.session.lock().unwrap().clone();
+        session.on_shutdown(hook)
+    }
+
+    /// Shut the session down gracefully, closing the connection
+    ///
+    /// See `Session::shutdown` for details.
+    pub fn shutdown(&self, deadline: Duration) -> Result<(), Error> {
+        let session = self.session.lock().unwrap().clone();
+        session.shutdown(deadline)
+    }
+
+    /// Re-establish a closed connection to the same address
+    ///
+    /// See `Session::reconnect` for details.
+    pub fn reconnect(&mut self) -> Result<(), Error> {
+        let session = self.session.lock().unwrap().clone();
+        session.reconnect()
+    }
+
+    /// Set the limits applied when decoding incoming RPC messages
+    ///
+    /// See `Session::set_decode_limits` for details.
+    pub fn set_decode_limits(&mut self, limits: DecodeLimits) {
+        let session = self.session.lock().unwrap().clone();
+        session.set_decode_limits(limits)
+    }
+
+    /// Set how many notifications can be queued before sending blocks
+    ///
+    /// See `Session::set_write_queue_capacity` for details.
+    pub fn set_write_queue_capacity(&mut self, capacity: usize) {
+        let session = self.session.lock().unwrap().clone();
+        session.set_write_queue_capacity(capacity)
+    }
+
+    /// Set how many requests from Neovim can be handled at once
+    ///
+    /// See `Session::set_max_pending_requests` for details.
+    pub fn set_max_pending_requests(&mut self, max: usize) {
+        let session = self.session.lock().unwrap().clone();
+        session.set_max_pending_requests(max)
+    }
+
+    /// Register a callback for errors encountered by the event loop
+    ///
+    /// See `Session::on_error` for details.
+    pub fn on_error<F>(&mut self, callback: F)
+    where
+        F: Fn(Error) + Send + 'static,
+    {
+        let session = self.session.lock().unwrap().clone();
+        session.on_error(callback)
+    }
+
+    /// Register a callback for asynchronous errors reported by Neovim
+    ///
+    /// See `Session::on_async_error` for details.
+    pub fn on_async_error<F>(&mut self, callback: F)
+    where
+        F: Fn(AsyncError) + Send + 'static,
+    {
+        let session = self.session.lock().unwrap().clone();
+        session.on_async_error(callback)
+    }
+
+    /// A snapshot of the connection's pending requests and error counts
+    ///
+    /// See `Session::stats` for details.
+    pub fn stats(&self) -> ClientStats {
+        let session = self.session.lock().unwrap().clone();
+        session.stats()
+    }
+
+    /// The latency and error metrics of the calls made so far, by method
+    ///
+    /// See `Session::metrics` for details.
+    pub fn metrics(&self) -> HashMap<String, MethodMetrics> {
+        let session = self.session.lock().unwrap().clone();
+        session.metrics()
+    }
+
+    /// Set what happens when a function the connected Neovim has deprecated
+    /// is called
+    ///
+    /// See `Session::set_deprecation_policy` for details.
+    pub fn set_deprecation_policy(&self, policy: DeprecationPolicy) {
+        let session = self.session.lock().unwrap().clone();
+        session.set_deprecation_policy(policy)
+    }
+
+    /// Subscribe to notifications for the given methods
+    ///
+    /// Not to be confused with `Nvim::subscribe`, which asks Neovim to
+    /// broadcast an event to this channel. See `Session::subscribe` for
+    /// details.
+    pub fn subscribe_events(&mut self, methods: &[&str]) -> mpsc::Receiver<Notification> {
+        let session = self.session.lock().unwrap().clone();
+        session.subscribe(methods)
+    }
+
+    /// Subscribe to the parameters of notifications for `method`
+    ///
+    /// See `Session::subscribe_notifications` for details.
+    pub fn subscribe_notifications(&mut self, method: &str) -> mpsc::Receiver<Vec<Value>> {
+        let session = self.session.lock().unwrap().clone();
+        session.subscribe_notifications(method)
+    }
+
+    /// Receive the `redraw` notifications of an attached UI a batch at a
+    /// time
+    ///
+    /// See `RedrawReceiver` for details.
+    pub fn subscribe_redraw(&mut self, mode: RedrawMode) -> RedrawReceiver {
+        let session = self.session.lock().unwrap().clone();
+        session.subscribe_redraw(mode)
+    }
+
+    /// Remove every subscription made with `subscribe_notifications` for
+    /// `method`
+    pub fn unsubscribe_notifications(&mut self, method: &str) {
+        let session = self.session.lock().unwrap().clone();
+        session.unsubscribe_notifications(method)
+    }
+
+    /// Wait for the first notification for `method` whose parameters match
+    /// `predicate`
+    ///
+    /// See `Session::wait_for_notification` for details.
+    pub fn wait_for_notification<P>(
+        &self,
+        method: &str,
+        predicate: P,
+        timeout: Duration,
+    ) -> Result<Vec<Value>, Error>
+    where
+        P: FnMut(&[Value]) -> bool,
+    {
+        let session = self.session.lock().unwrap().clone();
+        session.wait_for_notification(method, predicate, timeout)
+    }
+
+    /// Start receiving notifications for `method`, to wait for one
+    ///
+    /// See `Session::expect_notification` for details.
+    pub fn expect_notification(&self, method: &str) -> NotificationWaiter {
+        let session = self.session.lock().unwrap().clone();
+        session.expect_notification(method)
     }
 
     /// Call a RPC function
@@ -132,71 +528,176 @@ impl Nvim {
     /// This function allows for arbitrary Neovim function calls though should
     /// not be necessary as the API is exposed within this struct.
     pub fn call(&mut self, method: &str, args: Vec<Value>) {
-        let mut session = self.session.lock().unwrap();
+        let session = self.session.lock().unwrap().clone();
         session.call(method, args).unwrap();
     }
+
+    /// Coalesce asynchronous extmark updates in the namespace `ns_id`
+    ///
+    /// See `Session::coalesce_extmarks` for details.
+    pub fn coalesce_extmarks(&mut self, ns_id: i64, window: Option<Duration>) {
+        let session = self.session.lock().unwrap().clone();
+        session.coalesce_extmarks(ns_id, window)
+    }
+
+    /// Stop coalescing extmark updates in the namespace `ns_id`, sending any
+    /// which are queued
+    pub fn stop_coalescing_extmarks(&mut self, ns_id: i64) -> Result<(), Error> {
+        let session = self.session.lock().unwrap().clone();
+        session.stop_coalescing_extmarks(ns_id)
+    }
+
+    /// Send any queued extmark updates now
+    pub fn flush_extmarks(&mut self) -> Result<(), Error> {
+        let session = self.session.lock().unwrap().clone();
+        session.flush_extmarks()
+    }
+
+    /// Call a RPC function without waiting for the response
+    ///
+    /// See `Session::call_async` for details.
+    pub fn call_async(&mut self, method: &str, args: Vec<Value>) -> Result<ResponseHandle, Error> {
+        let session = self.session.lock().unwrap().clone();
+        session.call_async(method, args)
+    }
+
+    /// Send a RPC notification, which has no response
+    ///
+    /// Unlike `Nvim::notify`, which displays a message to the user, this
+    /// sends any method. See `Session::notify` for details.
+    pub fn send_notification(&mut self, method: &str, args: Vec<Value>) -> Result<(), Error> {
+        let session = self.session.lock().unwrap().clone();
+        session.notify(method, args)
+    }
+
+    /// Send a RPC notification without blocking
+    ///
+    /// See `Session::try_notify` for details.
+    pub fn try_send_notification(&mut self, method: &str, args: Vec<Value>) -> Result<(), Error> {
+        let session = self.session.lock().unwrap().clone();
+        session.try_notify(method, args)
+    }
+
+    /// Block until every queued notification has been written
+    ///
+    /// See `Session::flush_notifications` for details.
+    pub fn flush_notifications(&mut self) -> Result<(), Error> {
+        let session = self.session.lock().unwrap().clone();
+        session.flush_notifications()
+    }
+}
+
+/// The msgpack EXT type codes Neovim uses for handles, given by the `types`
+/// of `nvim_get_api_info`
+pub(crate) const BUFFER_EXT: i8 = 0;
+pub(crate) const WINDOW_EXT: i8 = 1;
+pub(crate) const TABPAGE_EXT: i8 = 2;
+
+/// Macro to implement the traits shared by Buffer, Tabpage and Window
+///
+/// Handles are compared and hashed by their ID alone, so handles to the same
+/// object from different sessions are equal.
+macro_rules! impl_handle {
+    ($handle:ident, $ext:expr, $description:expr) => {
+        impl $handle {
+            pub fn new(id: i64, session: Arc<Mutex<Session>>) -> Self {
+                $handle { id, session }
+            }
+
+            /// The integer handle Neovim uses to identify this object
+            pub fn id(&self) -> i64 {
+                self.id
+            }
+        }
+
+        impl Handle for $handle {
+            const EXT: i8 = $ext;
+            const DESCRIPTION: &'static str = $description;
+
+            fn from_id(id: i64, session: Arc<Mutex<Session>>) -> Self {
+                $handle { id, session }
+            }
+        }
+
+        impl Remote for $handle {
+            fn session(&self) -> &Arc<Mutex<Session>> {
+                &self.session
+            }
+        }
+
+        impl PartialEq for $handle {
+            fn eq(&self, other: &Self) -> bool {
+                self.id == other.id
+            }
+        }
+
+        impl Eq for $handle {}
+
+        impl std::hash::Hash for $handle {
+            fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+                self.id.hash(state);
+            }
+        }
+
+        impl std::fmt::Debug for $handle {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_tuple(stringify!($handle)).field(&self.id).finish()
+            }
+        }
+
+        impl From<$handle> for Value {
+            fn from(value: $handle) -> Self {
+                convert::to_handle($ext, value.id)
+            }
+        }
+    };
 }
 
 /// A Neovim buffer
 ///
 /// This struct exposes each way a user can create and interact with a buffer.
+/// Like `Nvim`, clones share the connection and may be used from other
+/// threads.
 #[derive(Clone)]
 pub struct Buffer {
-    data: Value,
+    id: i64,
     session: Arc<Mutex<Session>>,
 }
 
-impl Buffer {
-    pub fn new(data: Value, session: Arc<Mutex<Session>>) -> Self {
-        Buffer { data, session }
-    }
-}
-
-impl From<Buffer> for Value {
-    fn from(value: Buffer) -> Self {
-        value.data
-    }
-}
+impl_handle!(Buffer, BUFFER_EXT, "a buffer");
 
 /// A Neovim tabpage
 ///
 /// This struct exposes each way a user can create and interact with a tabpage.
+/// Like `Nvim`, clones share the connection and may be used from other
+/// threads.
 #[derive(Clone)]
 pub struct Tabpage {
-    data: Value,
+    id: i64,
     session: Arc<Mutex<Session>>,
 }
 
-impl Tabpage {
-    pub fn new(data: Value, session: Arc<Mutex<Session>>) -> Self {
-        Tabpage { data, session }
-    }
-}
-
-impl From<Tabpage> for Value {
-    fn from(value: Tabpage) -> Self {
-        value.data
-    }
-}
+impl_handle!(Tabpage, TABPAGE_EXT, "a tabpage");
 
-/// A Neovim buffer
+/// A Neovim window
 ///
 /// This struct exposes each way a user can create and interact with a window.
+/// Like `Nvim`, clones share the connection and may be used from other
+/// threads.
 #[derive(Clone)]
 pub struct Window {
-    data: Value,
+    id: i64,
     session: Arc<Mutex<Session>>,
 }
 
-impl Window {
-    pub fn new(data: Value, session: Arc<Mutex<Session>>) -> Self {
-        Window { data, session }
-    }
-}
-
-impl From<Window> for Value {
-    fn from(value: Window) -> Self {
-        value.data
-    }
-}
+impl_handle!(Window, WINDOW_EXT, "a window");
 
+// Clones of each handle are used from other threads, so the handles must
+// stay `Send` and `Sync` as the session changes
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Nvim>();
+    assert_send_sync::<Buffer>();
+    assert_send_sync::<Window>();
+    assert_send_sync::<Tabpage>();
+};
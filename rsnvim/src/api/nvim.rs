@@ -4,1078 +4,779 @@ use crate::api::*;
 use crate::error::Error;
 use crate::value_vec;
 
+/// The Neovim API level these bindings were generated against
+pub const API_LEVEL: u64 = 12;
+
+/// The git commit of the generator these bindings were generated with
+pub const GIT_HASH: &str = "b5f843b9c7cb";
+
+/// When these bindings were generated, as a UTC timestamp
+pub const GENERATED_AT: &str = "2026-10-16T04:01:53Z";
+
 /// The below implementations are autogenerated using the Neovim API
 impl Nvim {
     /// Since: 9
-    pub fn get_autocmds(&mut self, opts: Vec<(Value, Value)>) -> Result<Vec<Value>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_get_autocmds", value_vec!(opts))?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| x.to_owned())
-            .collect())
+    pub fn get_autocmds(&self, opts: impl Into<Dict>) -> Result<Vec<Value>, Error> {
+        let opts: Dict = opts.into();
+        self.call_typed("nvim_get_autocmds", value_vec!(opts))
     }
 
     /// Since: 9
-    pub fn create_autocmd(
-        &mut self,
-        event: Value,
-        opts: Vec<(Value, Value)>,
-    ) -> Result<i64, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_create_autocmd", value_vec!(event, opts))?;
-        Ok(ret.as_i64().unwrap())
+    pub fn create_autocmd(&self, event: Value, opts: impl Into<Dict>) -> Result<i64, Error> {
+        let opts: Dict = opts.into();
+        self.call_typed("nvim_create_autocmd", value_vec!(event, opts))
     }
 
     /// Since: 9
-    pub fn del_autocmd(&mut self, id: i64) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_del_autocmd", value_vec!(id))?;
-        Ok(())
+    pub fn del_autocmd(&self, id: i64) -> Result<(), Error> {
+        self.call_typed("nvim_del_autocmd", value_vec!(id))
     }
 
     /// Since: 9
-    pub fn clear_autocmds(&mut self, opts: Vec<(Value, Value)>) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_clear_autocmds", value_vec!(opts))?;
-        Ok(())
+    pub fn clear_autocmds(&self, opts: impl Into<Dict>) -> Result<(), Error> {
+        let opts: Dict = opts.into();
+        self.call_typed("nvim_clear_autocmds", value_vec!(opts))
     }
 
     /// Since: 9
     pub fn create_augroup(
-        &mut self,
-        name: String,
-        opts: Vec<(Value, Value)>,
+        &self,
+        name: impl Into<String>,
+        opts: impl Into<Dict>,
     ) -> Result<i64, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_create_augroup", value_vec!(name, opts))?;
-        Ok(ret.as_i64().unwrap())
+        let name: String = name.into();
+        let opts: Dict = opts.into();
+        self.call_typed("nvim_create_augroup", value_vec!(name, opts))
     }
 
     /// Since: 9
-    pub fn del_augroup_by_id(&mut self, id: i64) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_del_augroup_by_id", value_vec!(id))?;
-        Ok(())
+    pub fn del_augroup_by_id(&self, id: i64) -> Result<(), Error> {
+        self.call_typed("nvim_del_augroup_by_id", value_vec!(id))
     }
 
     /// Since: 9
-    pub fn del_augroup_by_name(&mut self, name: String) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_del_augroup_by_name", value_vec!(name))?;
-        Ok(())
+    pub fn del_augroup_by_name(&self, name: impl Into<String>) -> Result<(), Error> {
+        let name: String = name.into();
+        self.call_typed("nvim_del_augroup_by_name", value_vec!(name))
     }
 
     /// Since: 9
-    pub fn exec_autocmds(&mut self, event: Value, opts: Vec<(Value, Value)>) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_exec_autocmds", value_vec!(event, opts))?;
-        Ok(())
+    pub fn exec_autocmds(&self, event: Value, opts: impl Into<Dict>) -> Result<(), Error> {
+        let opts: Dict = opts.into();
+        self.call_typed("nvim_exec_autocmds", value_vec!(event, opts))
     }
 
     /// Since: 10
-    pub fn parse_cmd(
-        &mut self,
-        str: String,
-        opts: Vec<(Value, Value)>,
-    ) -> Result<Vec<(Value, Value)>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_parse_cmd", value_vec!(str, opts))?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| {
-                let v = x.as_array().unwrap();
-                (v[0].to_owned(), v[1].to_owned())
-            })
-            .collect())
+    pub fn parse_cmd(&self, str: impl Into<String>, opts: impl Into<Dict>) -> Result<Dict, Error> {
+        let str: String = str.into();
+        let opts: Dict = opts.into();
+        self.call_typed("nvim_parse_cmd", value_vec!(str, opts))
     }
 
     /// Since: 10
-    pub fn cmd(
-        &mut self,
-        cmd: Vec<(Value, Value)>,
-        opts: Vec<(Value, Value)>,
-    ) -> Result<String, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_cmd", value_vec!(cmd, opts))?;
-        Ok(ret.as_str().unwrap().to_string())
+    pub fn cmd(&self, cmd: impl Into<Dict>, opts: impl Into<Dict>) -> Result<String, Error> {
+        let cmd: Dict = cmd.into();
+        let opts: Dict = opts.into();
+        self.call_typed("nvim_cmd", value_vec!(cmd, opts))
     }
 
     /// Since: 9
     pub fn create_user_command(
-        &mut self,
-        name: String,
+        &self,
+        name: impl Into<String>,
         command: Value,
-        opts: Vec<(Value, Value)>,
+        opts: impl Into<Dict>,
     ) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_create_user_command", value_vec!(name, command, opts))?;
-        Ok(())
+        let name: String = name.into();
+        let opts: Dict = opts.into();
+        self.call_typed("nvim_create_user_command", value_vec!(name, command, opts))
     }
 
     /// Since: 9
-    pub fn del_user_command(&mut self, name: String) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_del_user_command", value_vec!(name))?;
-        Ok(())
+    pub fn del_user_command(&self, name: impl Into<String>) -> Result<(), Error> {
+        let name: String = name.into();
+        self.call_typed("nvim_del_user_command", value_vec!(name))
     }
 
     /// Since: 4
-    pub fn get_commands(
-        &mut self,
-        opts: Vec<(Value, Value)>,
-    ) -> Result<Vec<(Value, Value)>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_get_commands", value_vec!(opts))?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| {
-                let v = x.as_array().unwrap();
-                (v[0].to_owned(), v[1].to_owned())
-            })
-            .collect())
+    pub fn get_commands(&self, opts: impl Into<Dict>) -> Result<Dict, Error> {
+        let opts: Dict = opts.into();
+        self.call_typed("nvim_get_commands", value_vec!(opts))
     }
 
     /// Since: 5
-    pub fn create_namespace(&mut self, name: String) -> Result<i64, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_create_namespace", value_vec!(name))?;
-        Ok(ret.as_i64().unwrap())
+    pub fn create_namespace(&self, name: impl Into<String>) -> Result<i64, Error> {
+        let name: String = name.into();
+        self.call_typed("nvim_create_namespace", value_vec!(name))
     }
 
     /// Since: 5
-    pub fn get_namespaces(&mut self) -> Result<Vec<(Value, Value)>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_get_namespaces", Vec::new())?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| {
-                let v = x.as_array().unwrap();
-                (v[0].to_owned(), v[1].to_owned())
-            })
-            .collect())
+    pub fn get_namespaces(&self) -> Result<Dict, Error> {
+        self.call_typed("nvim_get_namespaces", Vec::new())
     }
 
     /// Since: 7
-    pub fn set_decoration_provider(
-        &mut self,
-        ns_id: i64,
-        opts: Vec<(Value, Value)>,
-    ) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_set_decoration_provider", value_vec!(ns_id, opts))?;
-        Ok(())
+    pub fn set_decoration_provider(&self, ns_id: i64, opts: impl Into<Dict>) -> Result<(), Error> {
+        let opts: Dict = opts.into();
+        self.call_typed("nvim_set_decoration_provider", value_vec!(ns_id, opts))
     }
 
     /// Since: 9
     pub fn get_option_value(
-        &mut self,
-        name: String,
-        opts: Vec<(Value, Value)>,
+        &self,
+        name: impl Into<String>,
+        opts: impl Into<Dict>,
     ) -> Result<Value, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_get_option_value", value_vec!(name, opts))?;
-        Ok(ret.to_owned())
+        let name: String = name.into();
+        let opts: Dict = opts.into();
+        self.call_typed("nvim_get_option_value", value_vec!(name, opts))
     }
 
     /// Since: 9
     pub fn set_option_value(
-        &mut self,
-        name: String,
+        &self,
+        name: impl Into<String>,
         value: Value,
-        opts: Vec<(Value, Value)>,
+        opts: impl Into<Dict>,
     ) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_set_option_value", value_vec!(name, value, opts))?;
-        Ok(())
+        let name: String = name.into();
+        let opts: Dict = opts.into();
+        self.call_typed("nvim_set_option_value", value_vec!(name, value, opts))
     }
 
     /// Since: 7
-    pub fn get_all_options_info(&mut self) -> Result<Vec<(Value, Value)>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_get_all_options_info", Vec::new())?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| {
-                let v = x.as_array().unwrap();
-                (v[0].to_owned(), v[1].to_owned())
-            })
-            .collect())
+    pub fn get_all_options_info(&self) -> Result<Dict, Error> {
+        self.call_typed("nvim_get_all_options_info", Vec::new())
     }
 
     /// Since: 11
     pub fn get_option_info2(
-        &mut self,
-        name: String,
-        opts: Vec<(Value, Value)>,
-    ) -> Result<Vec<(Value, Value)>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_get_option_info2", value_vec!(name, opts))?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| {
-                let v = x.as_array().unwrap();
-                (v[0].to_owned(), v[1].to_owned())
-            })
-            .collect())
+        &self,
+        name: impl Into<String>,
+        opts: impl Into<Dict>,
+    ) -> Result<Dict, Error> {
+        let name: String = name.into();
+        let opts: Dict = opts.into();
+        self.call_typed("nvim_get_option_info2", value_vec!(name, opts))
     }
 
     /// Since: 1
     pub fn ui_attach(
-        &mut self,
+        &self,
         width: i64,
         height: i64,
-        options: Vec<(Value, Value)>,
+        options: impl Into<Dict>,
     ) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_ui_attach", value_vec!(width, height, options))?;
-        Ok(())
+        let options: Dict = options.into();
+        self.call_typed("nvim_ui_attach", value_vec!(width, height, options))
     }
 
     /// Since: 11
-    pub fn ui_set_focus(&mut self, gained: bool) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_ui_set_focus", value_vec!(gained))?;
-        Ok(())
+    pub fn ui_set_focus(&self, gained: bool) -> Result<(), Error> {
+        self.call_typed("nvim_ui_set_focus", value_vec!(gained))
     }
 
     /// Since: 1
-    pub fn ui_detach(&mut self) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_ui_detach", Vec::new())?;
-        Ok(())
+    pub fn ui_detach(&self) -> Result<(), Error> {
+        self.call_typed("nvim_ui_detach", Vec::new())
     }
 
     /// Since: 1
-    pub fn ui_try_resize(&mut self, width: i64, height: i64) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_ui_try_resize", value_vec!(width, height))?;
-        Ok(())
+    pub fn ui_try_resize(&self, width: i64, height: i64) -> Result<(), Error> {
+        self.call_typed("nvim_ui_try_resize", value_vec!(width, height))
     }
 
     /// Since: 1
-    pub fn ui_set_option(&mut self, name: String, value: Value) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_ui_set_option", value_vec!(name, value))?;
-        Ok(())
+    pub fn ui_set_option(&self, name: impl Into<String>, value: Value) -> Result<(), Error> {
+        let name: String = name.into();
+        self.call_typed("nvim_ui_set_option", value_vec!(name, value))
     }
 
     /// Since: 6
-    pub fn ui_try_resize_grid(&mut self, grid: i64, width: i64, height: i64) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_ui_try_resize_grid", value_vec!(grid, width, height))?;
-        Ok(())
+    pub fn ui_try_resize_grid(&self, grid: i64, width: i64, height: i64) -> Result<(), Error> {
+        self.call_typed("nvim_ui_try_resize_grid", value_vec!(grid, width, height))
     }
 
     /// Since: 6
-    pub fn ui_pum_set_height(&mut self, height: i64) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_ui_pum_set_height", value_vec!(height))?;
-        Ok(())
+    pub fn ui_pum_set_height(&self, height: i64) -> Result<(), Error> {
+        self.call_typed("nvim_ui_pum_set_height", value_vec!(height))
     }
 
     /// Since: 7
     pub fn ui_pum_set_bounds(
-        &mut self,
+        &self,
         width: f64,
         height: f64,
         row: f64,
         col: f64,
     ) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call(
+        self.call_typed(
             "nvim_ui_pum_set_bounds",
             value_vec!(width, height, row, col),
-        )?;
-        Ok(())
+        )
     }
 
     /// Since: 12
-    pub fn ui_term_event(&mut self, event: String, value: Value) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_ui_term_event", value_vec!(event, value))?;
-        Ok(())
+    #[cfg(feature = "nvim-0-10")]
+    pub fn ui_term_event(&self, event: impl Into<String>, value: Value) -> Result<(), Error> {
+        let event: String = event.into();
+        self.call_typed("nvim_ui_term_event", value_vec!(event, value))
     }
 
     /// Since: 7
-    pub fn get_hl_id_by_name(&mut self, name: String) -> Result<i64, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_get_hl_id_by_name", value_vec!(name))?;
-        Ok(ret.as_i64().unwrap())
+    pub fn get_hl_id_by_name(&self, name: impl Into<String>) -> Result<i64, Error> {
+        let name: String = name.into();
+        self.call_typed("nvim_get_hl_id_by_name", value_vec!(name))
     }
 
     /// Since: 11
-    pub fn get_hl(
-        &mut self,
-        ns_id: i64,
-        opts: Vec<(Value, Value)>,
-    ) -> Result<Vec<(Value, Value)>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_get_hl", value_vec!(ns_id, opts))?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| {
-                let v = x.as_array().unwrap();
-                (v[0].to_owned(), v[1].to_owned())
-            })
-            .collect())
+    pub fn get_hl(&self, ns_id: i64, opts: impl Into<Dict>) -> Result<Dict, Error> {
+        let opts: Dict = opts.into();
+        self.call_typed("nvim_get_hl", value_vec!(ns_id, opts))
     }
 
     /// Since: 7
     pub fn set_hl(
-        &mut self,
+        &self,
         ns_id: i64,
-        name: String,
-        val: Vec<(Value, Value)>,
+        name: impl Into<String>,
+        val: impl Into<Dict>,
     ) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_set_hl", value_vec!(ns_id, name, val))?;
-        Ok(())
+        let name: String = name.into();
+        let val: Dict = val.into();
+        self.call_typed("nvim_set_hl", value_vec!(ns_id, name, val))
     }
 
     /// Since: 12
-    pub fn get_hl_ns(&mut self, opts: Vec<(Value, Value)>) -> Result<i64, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_get_hl_ns", value_vec!(opts))?;
-        Ok(ret.as_i64().unwrap())
+    #[cfg(feature = "nvim-0-10")]
+    pub fn get_hl_ns(&self, opts: impl Into<Dict>) -> Result<i64, Error> {
+        let opts: Dict = opts.into();
+        self.call_typed("nvim_get_hl_ns", value_vec!(opts))
     }
 
     /// Since: 10
-    pub fn set_hl_ns(&mut self, ns_id: i64) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_set_hl_ns", value_vec!(ns_id))?;
-        Ok(())
+    pub fn set_hl_ns(&self, ns_id: i64) -> Result<(), Error> {
+        self.call_typed("nvim_set_hl_ns", value_vec!(ns_id))
     }
 
     /// Since: 10
-    pub fn set_hl_ns_fast(&mut self, ns_id: i64) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_set_hl_ns_fast", value_vec!(ns_id))?;
-        Ok(())
+    pub fn set_hl_ns_fast(&self, ns_id: i64) -> Result<(), Error> {
+        self.call_typed("nvim_set_hl_ns_fast", value_vec!(ns_id))
     }
 
     /// Since: 1
-    pub fn feedkeys(&mut self, keys: String, mode: String, escape_ks: bool) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_feedkeys", value_vec!(keys, mode, escape_ks))?;
-        Ok(())
+    pub fn feedkeys(
+        &self,
+        keys: impl Into<String>,
+        mode: impl Into<String>,
+        escape_ks: bool,
+    ) -> Result<(), Error> {
+        let keys: String = keys.into();
+        let mode: String = mode.into();
+        self.call_typed("nvim_feedkeys", value_vec!(keys, mode, escape_ks))
     }
 
     /// Since: 1
-    pub fn input(&mut self, keys: String) -> Result<i64, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_input", value_vec!(keys))?;
-        Ok(ret.as_i64().unwrap())
+    pub fn input(&self, keys: impl Into<String>) -> Result<i64, Error> {
+        let keys: String = keys.into();
+        self.call_typed("nvim_input", value_vec!(keys))
     }
 
     /// Since: 6
     pub fn input_mouse(
-        &mut self,
-        button: String,
-        action: String,
-        modifier: String,
+        &self,
+        button: impl Into<String>,
+        action: impl Into<String>,
+        modifier: impl Into<String>,
         grid: i64,
         row: i64,
         col: i64,
     ) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call(
+        let button: String = button.into();
+        let action: String = action.into();
+        let modifier: String = modifier.into();
+        self.call_typed(
             "nvim_input_mouse",
             value_vec!(button, action, modifier, grid, row, col),
-        )?;
-        Ok(())
+        )
     }
 
     /// Since: 1
     pub fn replace_termcodes(
-        &mut self,
-        str: String,
+        &self,
+        str: impl Into<String>,
         from_part: bool,
         do_lt: bool,
         special: bool,
     ) -> Result<String, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call(
+        let str: String = str.into();
+        self.call_typed(
             "nvim_replace_termcodes",
             value_vec!(str, from_part, do_lt, special),
-        )?;
-        Ok(ret.as_str().unwrap().to_string())
+        )
     }
 
     /// Since: 7
-    pub fn exec_lua(&mut self, code: String, args: Vec<Value>) -> Result<Value, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_exec_lua", value_vec!(code, args))?;
-        Ok(ret.to_owned())
+    pub fn exec_lua(
+        &self,
+        code: impl Into<String>,
+        args: impl IntoIterator<Item = Value>,
+    ) -> Result<Value, Error> {
+        let code: String = code.into();
+        let args: Vec<Value> = args.into_iter().collect();
+        self.call_typed("nvim_exec_lua", value_vec!(code, args))
     }
 
     /// Since: 7
     pub fn notify(
-        &mut self,
-        msg: String,
+        &self,
+        msg: impl Into<String>,
         log_level: i64,
-        opts: Vec<(Value, Value)>,
-    ) -> Result<Value, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_notify", value_vec!(msg, log_level, opts))?;
-        Ok(ret.to_owned())
+        opts: impl Into<Dict>,
+    ) -> Result<Option<Value>, Error> {
+        let msg: String = msg.into();
+        let opts: Dict = opts.into();
+        self.call_typed("nvim_notify", value_vec!(msg, log_level, opts))
     }
 
     /// Since: 1
-    pub fn strwidth(&mut self, text: String) -> Result<i64, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_strwidth", value_vec!(text))?;
-        Ok(ret.as_i64().unwrap())
+    pub fn strwidth(&self, text: impl Into<String>) -> Result<i64, Error> {
+        let text: String = text.into();
+        self.call_typed("nvim_strwidth", value_vec!(text))
     }
 
     /// Since: 1
-    pub fn list_runtime_paths(&mut self) -> Result<Vec<String>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_list_runtime_paths", Vec::new())?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| x.as_str().unwrap().to_string())
-            .collect())
+    pub fn list_runtime_paths(&self) -> Result<Vec<String>, Error> {
+        self.call_typed("nvim_list_runtime_paths", Vec::new())
     }
 
     /// Since: 7
-    pub fn get_runtime_file(&mut self, name: String, all: bool) -> Result<Vec<String>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_get_runtime_file", value_vec!(name, all))?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| x.as_str().unwrap().to_string())
-            .collect())
+    pub fn get_runtime_file(
+        &self,
+        name: impl Into<String>,
+        all: bool,
+    ) -> Result<Vec<String>, Error> {
+        let name: String = name.into();
+        self.call_typed("nvim_get_runtime_file", value_vec!(name, all))
     }
 
     /// Since: 1
-    pub fn set_current_dir(&mut self, dir: String) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_set_current_dir", value_vec!(dir))?;
-        Ok(())
+    pub fn set_current_dir(&self, dir: impl Into<String>) -> Result<(), Error> {
+        let dir: String = dir.into();
+        self.call_typed("nvim_set_current_dir", value_vec!(dir))
     }
 
     /// Since: 1
-    pub fn get_current_line(&mut self) -> Result<String, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_get_current_line", Vec::new())?;
-        Ok(ret.as_str().unwrap().to_string())
+    pub fn get_current_line(&self) -> Result<String, Error> {
+        self.call_typed("nvim_get_current_line", Vec::new())
     }
 
     /// Since: 1
-    pub fn set_current_line(&mut self, line: String) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_set_current_line", value_vec!(line))?;
-        Ok(())
+    pub fn set_current_line(&self, line: impl Into<String>) -> Result<(), Error> {
+        let line: String = line.into();
+        self.call_typed("nvim_set_current_line", value_vec!(line))
     }
 
     /// Since: 1
-    pub fn del_current_line(&mut self) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_del_current_line", Vec::new())?;
-        Ok(())
+    pub fn del_current_line(&self) -> Result<(), Error> {
+        self.call_typed("nvim_del_current_line", Vec::new())
     }
 
     /// Since: 1
-    pub fn get_var(&mut self, name: String) -> Result<Value, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_get_var", value_vec!(name))?;
-        Ok(ret.to_owned())
+    pub fn get_var(&self, name: impl Into<String>) -> Result<Value, Error> {
+        let name: String = name.into();
+        self.call_typed("nvim_get_var", value_vec!(name))
     }
 
     /// Since: 1
-    pub fn set_var(&mut self, name: String, value: Value) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_set_var", value_vec!(name, value))?;
-        Ok(())
+    pub fn set_var(&self, name: impl Into<String>, value: Value) -> Result<(), Error> {
+        let name: String = name.into();
+        self.call_typed("nvim_set_var", value_vec!(name, value))
     }
 
     /// Since: 1
-    pub fn del_var(&mut self, name: String) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_del_var", value_vec!(name))?;
-        Ok(())
+    pub fn del_var(&self, name: impl Into<String>) -> Result<(), Error> {
+        let name: String = name.into();
+        self.call_typed("nvim_del_var", value_vec!(name))
     }
 
     /// Since: 1
-    pub fn get_vvar(&mut self, name: String) -> Result<Value, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_get_vvar", value_vec!(name))?;
-        Ok(ret.to_owned())
+    pub fn get_vvar(&self, name: impl Into<String>) -> Result<Value, Error> {
+        let name: String = name.into();
+        self.call_typed("nvim_get_vvar", value_vec!(name))
     }
 
     /// Since: 6
-    pub fn set_vvar(&mut self, name: String, value: Value) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_set_vvar", value_vec!(name, value))?;
-        Ok(())
+    pub fn set_vvar(&self, name: impl Into<String>, value: Value) -> Result<(), Error> {
+        let name: String = name.into();
+        self.call_typed("nvim_set_vvar", value_vec!(name, value))
     }
 
     /// Since: 7
     pub fn echo(
-        &mut self,
-        chunks: Vec<Value>,
+        &self,
+        chunks: impl IntoIterator<Item = Value>,
         history: bool,
-        opts: Vec<(Value, Value)>,
+        opts: impl Into<Dict>,
     ) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_echo", value_vec!(chunks, history, opts))?;
-        Ok(())
+        let chunks: Vec<Value> = chunks.into_iter().collect();
+        let opts: Dict = opts.into();
+        self.call_typed("nvim_echo", value_vec!(chunks, history, opts))
     }
 
     /// Since: 1
-    pub fn out_write(&mut self, str: String) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_out_write", value_vec!(str))?;
-        Ok(())
+    pub fn out_write(&self, str: impl Into<String>) -> Result<(), Error> {
+        let str: String = str.into();
+        self.call_typed("nvim_out_write", value_vec!(str))
     }
 
     /// Since: 1
-    pub fn err_write(&mut self, str: String) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_err_write", value_vec!(str))?;
-        Ok(())
+    pub fn err_write(&self, str: impl Into<String>) -> Result<(), Error> {
+        let str: String = str.into();
+        self.call_typed("nvim_err_write", value_vec!(str))
     }
 
     /// Since: 1
-    pub fn err_writeln(&mut self, str: String) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_err_writeln", value_vec!(str))?;
-        Ok(())
+    pub fn err_writeln(&self, str: impl Into<String>) -> Result<(), Error> {
+        let str: String = str.into();
+        self.call_typed("nvim_err_writeln", value_vec!(str))
     }
 
     /// Since: 1
-    pub fn list_bufs(&mut self) -> Result<Vec<Buffer>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_list_bufs", Vec::new())?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| Buffer {
-                data: x.clone(),
-                session: self.session.clone(),
-            })
-            .collect())
+    pub fn list_bufs(&self) -> Result<Vec<Buffer>, Error> {
+        let ret = self.call_raw("nvim_list_bufs", Vec::new())?;
+        Vec::<Value>::from_value(ret)?
+            .into_iter()
+            .map(|x| self.handle::<Buffer>(x))
+            .collect::<Result<_, Error>>()
     }
 
     /// Since: 1
-    pub fn get_current_buf(&mut self) -> Result<Buffer, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_get_current_buf", Vec::new())?;
-        Ok(Buffer {
-            data: ret.clone(),
-            session: self.session.clone(),
-        })
+    pub fn get_current_buf(&self) -> Result<Buffer, Error> {
+        let ret = self.call_raw("nvim_get_current_buf", Vec::new())?;
+        self.handle::<Buffer>(ret)
     }
 
     /// Since: 1
-    pub fn set_current_buf(&mut self, buffer: Buffer) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_set_current_buf", value_vec!(buffer))?;
-        Ok(())
+    pub fn set_current_buf(&self, buffer: &Buffer) -> Result<(), Error> {
+        self.call_typed("nvim_set_current_buf", value_vec!(buffer))
     }
 
     /// Since: 1
-    pub fn list_wins(&mut self) -> Result<Vec<Window>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_list_wins", Vec::new())?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| Window {
-                data: x.clone(),
-                session: self.session.clone(),
-            })
-            .collect())
+    pub fn list_wins(&self) -> Result<Vec<Window>, Error> {
+        let ret = self.call_raw("nvim_list_wins", Vec::new())?;
+        Vec::<Value>::from_value(ret)?
+            .into_iter()
+            .map(|x| self.handle::<Window>(x))
+            .collect::<Result<_, Error>>()
     }
 
     /// Since: 1
-    pub fn get_current_win(&mut self) -> Result<Window, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_get_current_win", Vec::new())?;
-        Ok(Window {
-            data: ret.clone(),
-            session: self.session.clone(),
-        })
+    pub fn get_current_win(&self) -> Result<Window, Error> {
+        let ret = self.call_raw("nvim_get_current_win", Vec::new())?;
+        self.handle::<Window>(ret)
     }
 
     /// Since: 1
-    pub fn set_current_win(&mut self, window: Window) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_set_current_win", value_vec!(window))?;
-        Ok(())
+    pub fn set_current_win(&self, window: &Window) -> Result<(), Error> {
+        self.call_typed("nvim_set_current_win", value_vec!(window))
     }
 
     /// Since: 6
-    pub fn create_buf(&mut self, listed: bool, scratch: bool) -> Result<Buffer, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_create_buf", value_vec!(listed, scratch))?;
-        Ok(Buffer {
-            data: ret.clone(),
-            session: self.session.clone(),
-        })
+    pub fn create_buf(&self, listed: bool, scratch: bool) -> Result<Buffer, Error> {
+        let ret = self.call_raw("nvim_create_buf", value_vec!(listed, scratch))?;
+        self.handle::<Buffer>(ret)
     }
 
     /// Since: 7
-    pub fn open_term(&mut self, buffer: Buffer, opts: Vec<(Value, Value)>) -> Result<i64, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_open_term", value_vec!(buffer, opts))?;
-        Ok(ret.as_i64().unwrap())
+    pub fn open_term(&self, buffer: &Buffer, opts: impl Into<Dict>) -> Result<i64, Error> {
+        let opts: Dict = opts.into();
+        self.call_typed("nvim_open_term", value_vec!(buffer, opts))
     }
 
     /// Since: 7
-    pub fn chan_send(&mut self, chan: i64, data: String) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_chan_send", value_vec!(chan, data))?;
-        Ok(())
+    pub fn chan_send(&self, chan: i64, data: impl Into<String>) -> Result<(), Error> {
+        let data: String = data.into();
+        self.call_typed("nvim_chan_send", value_vec!(chan, data))
     }
 
     /// Since: 1
-    pub fn list_tabpages(&mut self) -> Result<Vec<Tabpage>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_list_tabpages", Vec::new())?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| Tabpage {
-                data: x.clone(),
-                session: self.session.clone(),
-            })
-            .collect())
+    pub fn list_tabpages(&self) -> Result<Vec<Tabpage>, Error> {
+        let ret = self.call_raw("nvim_list_tabpages", Vec::new())?;
+        Vec::<Value>::from_value(ret)?
+            .into_iter()
+            .map(|x| self.handle::<Tabpage>(x))
+            .collect::<Result<_, Error>>()
     }
 
     /// Since: 1
-    pub fn get_current_tabpage(&mut self) -> Result<Tabpage, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_get_current_tabpage", Vec::new())?;
-        Ok(Tabpage {
-            data: ret.clone(),
-            session: self.session.clone(),
-        })
+    pub fn get_current_tabpage(&self) -> Result<Tabpage, Error> {
+        let ret = self.call_raw("nvim_get_current_tabpage", Vec::new())?;
+        self.handle::<Tabpage>(ret)
     }
 
     /// Since: 1
-    pub fn set_current_tabpage(&mut self, tabpage: Tabpage) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_set_current_tabpage", value_vec!(tabpage))?;
-        Ok(())
+    pub fn set_current_tabpage(&self, tabpage: &Tabpage) -> Result<(), Error> {
+        self.call_typed("nvim_set_current_tabpage", value_vec!(tabpage))
     }
 
     /// Since: 6
-    pub fn paste(&mut self, data: String, crlf: bool, phase: i64) -> Result<bool, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_paste", value_vec!(data, crlf, phase))?;
-        Ok(ret.as_bool().unwrap())
+    pub fn paste(&self, data: impl Into<String>, crlf: bool, phase: i64) -> Result<bool, Error> {
+        let data: String = data.into();
+        self.call_typed("nvim_paste", value_vec!(data, crlf, phase))
     }
 
     /// Since: 6
     pub fn put(
-        &mut self,
-        lines: Vec<String>,
-        r#type: String,
+        &self,
+        lines: impl IntoIterator<Item = impl Into<String>>,
+        r#type: impl Into<String>,
         after: bool,
         follow: bool,
     ) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_put", value_vec!(lines, r#type, after, follow))?;
-        Ok(())
+        let lines: Vec<String> = lines.into_iter().map(Into::into).collect();
+        let r#type: String = r#type.into();
+        self.call_typed("nvim_put", value_vec!(lines, r#type, after, follow))
     }
 
     /// Since: 1
-    pub fn subscribe(&mut self, event: String) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_subscribe", value_vec!(event))?;
-        Ok(())
+    pub fn subscribe(&self, event: impl Into<String>) -> Result<(), Error> {
+        let event: String = event.into();
+        self.call_typed("nvim_subscribe", value_vec!(event))
     }
 
     /// Since: 1
-    pub fn unsubscribe(&mut self, event: String) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_unsubscribe", value_vec!(event))?;
-        Ok(())
+    pub fn unsubscribe(&self, event: impl Into<String>) -> Result<(), Error> {
+        let event: String = event.into();
+        self.call_typed("nvim_unsubscribe", value_vec!(event))
     }
 
     /// Since: 1
-    pub fn get_color_by_name(&mut self, name: String) -> Result<i64, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_get_color_by_name", value_vec!(name))?;
-        Ok(ret.as_i64().unwrap())
+    pub fn get_color_by_name(&self, name: impl Into<String>) -> Result<i64, Error> {
+        let name: String = name.into();
+        self.call_typed("nvim_get_color_by_name", value_vec!(name))
     }
 
     /// Since: 1
-    pub fn get_color_map(&mut self) -> Result<Vec<(Value, Value)>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_get_color_map", Vec::new())?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| {
-                let v = x.as_array().unwrap();
-                (v[0].to_owned(), v[1].to_owned())
-            })
-            .collect())
+    pub fn get_color_map(&self) -> Result<Dict, Error> {
+        self.call_typed("nvim_get_color_map", Vec::new())
     }
 
     /// Since: 6
-    pub fn get_context(&mut self, opts: Vec<(Value, Value)>) -> Result<Vec<(Value, Value)>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_get_context", value_vec!(opts))?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| {
-                let v = x.as_array().unwrap();
-                (v[0].to_owned(), v[1].to_owned())
-            })
-            .collect())
+    pub fn get_context(&self, opts: impl Into<Dict>) -> Result<Dict, Error> {
+        let opts: Dict = opts.into();
+        self.call_typed("nvim_get_context", value_vec!(opts))
     }
 
     /// Since: 6
-    pub fn load_context(&mut self, dict: Vec<(Value, Value)>) -> Result<Value, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_load_context", value_vec!(dict))?;
-        Ok(ret.to_owned())
+    pub fn load_context(&self, dict: impl Into<Dict>) -> Result<Option<Value>, Error> {
+        let dict: Dict = dict.into();
+        self.call_typed("nvim_load_context", value_vec!(dict))
     }
 
     /// Since: 2
-    pub fn get_mode(&mut self) -> Result<Vec<(Value, Value)>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_get_mode", Vec::new())?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| {
-                let v = x.as_array().unwrap();
-                (v[0].to_owned(), v[1].to_owned())
-            })
-            .collect())
+    pub fn get_mode(&self) -> Result<Dict, Error> {
+        self.call_typed("nvim_get_mode", Vec::new())
     }
 
     /// Since: 3
-    pub fn get_keymap(&mut self, mode: String) -> Result<Vec<Vec<(Value, Value)>>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_get_keymap", value_vec!(mode))?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| {
-                x.as_array()
-                    .unwrap()
-                    .iter()
-                    .map(|x| {
-                        let v = x.as_array().unwrap();
-                        (v[0].to_owned(), v[1].to_owned())
-                    })
-                    .collect()
-            })
-            .collect())
+    pub fn get_keymap(&self, mode: impl Into<String>) -> Result<Vec<Dict>, Error> {
+        let mode: String = mode.into();
+        self.call_typed("nvim_get_keymap", value_vec!(mode))
     }
 
     /// Since: 6
     pub fn set_keymap(
-        &mut self,
-        mode: String,
-        lhs: String,
-        rhs: String,
-        opts: Vec<(Value, Value)>,
+        &self,
+        mode: impl Into<String>,
+        lhs: impl Into<String>,
+        rhs: impl Into<String>,
+        opts: impl Into<Dict>,
     ) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_set_keymap", value_vec!(mode, lhs, rhs, opts))?;
-        Ok(())
+        let mode: String = mode.into();
+        let lhs: String = lhs.into();
+        let rhs: String = rhs.into();
+        let opts: Dict = opts.into();
+        self.call_typed("nvim_set_keymap", value_vec!(mode, lhs, rhs, opts))
     }
 
     /// Since: 6
-    pub fn del_keymap(&mut self, mode: String, lhs: String) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_del_keymap", value_vec!(mode, lhs))?;
-        Ok(())
+    pub fn del_keymap(&self, mode: impl Into<String>, lhs: impl Into<String>) -> Result<(), Error> {
+        let mode: String = mode.into();
+        let lhs: String = lhs.into();
+        self.call_typed("nvim_del_keymap", value_vec!(mode, lhs))
     }
 
     /// Since: 1
-    pub fn get_api_info(&mut self) -> Result<Vec<Value>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_get_api_info", Vec::new())?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| x.to_owned())
-            .collect())
+    pub fn get_api_info(&self) -> Result<Vec<Value>, Error> {
+        self.call_typed("nvim_get_api_info", Vec::new())
     }
 
     /// Since: 4
     pub fn set_client_info(
-        &mut self,
-        name: String,
-        version: Vec<(Value, Value)>,
-        r#type: String,
-        methods: Vec<(Value, Value)>,
-        attributes: Vec<(Value, Value)>,
+        &self,
+        name: impl Into<String>,
+        version: impl Into<Dict>,
+        r#type: impl Into<String>,
+        methods: impl Into<Dict>,
+        attributes: impl Into<Dict>,
     ) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call(
+        let name: String = name.into();
+        let version: Dict = version.into();
+        let r#type: String = r#type.into();
+        let methods: Dict = methods.into();
+        let attributes: Dict = attributes.into();
+        self.call_typed(
             "nvim_set_client_info",
             value_vec!(name, version, r#type, methods, attributes),
-        )?;
-        Ok(())
+        )
     }
 
     /// Since: 4
-    pub fn get_chan_info(&mut self, chan: i64) -> Result<Vec<(Value, Value)>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_get_chan_info", value_vec!(chan))?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| {
-                let v = x.as_array().unwrap();
-                (v[0].to_owned(), v[1].to_owned())
-            })
-            .collect())
+    pub fn get_chan_info(&self, chan: i64) -> Result<Dict, Error> {
+        self.call_typed("nvim_get_chan_info", value_vec!(chan))
     }
 
     /// Since: 4
-    pub fn list_chans(&mut self) -> Result<Vec<Value>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_list_chans", Vec::new())?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| x.to_owned())
-            .collect())
+    pub fn list_chans(&self) -> Result<Vec<Value>, Error> {
+        self.call_typed("nvim_list_chans", Vec::new())
     }
 
     /// Since: 1
-    pub fn call_atomic(&mut self, calls: Vec<Value>) -> Result<Vec<Value>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_call_atomic", value_vec!(calls))?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| x.to_owned())
-            .collect())
+    pub fn call_atomic(&self, calls: impl IntoIterator<Item = Value>) -> Result<Vec<Value>, Error> {
+        let calls: Vec<Value> = calls.into_iter().collect();
+        self.call_typed("nvim_call_atomic", value_vec!(calls))
     }
 
     /// Since: 4
-    pub fn list_uis(&mut self) -> Result<Vec<Value>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_list_uis", Vec::new())?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| x.to_owned())
-            .collect())
+    pub fn list_uis(&self) -> Result<Vec<Value>, Error> {
+        self.call_typed("nvim_list_uis", Vec::new())
     }
 
     /// Since: 4
-    pub fn get_proc_children(&mut self, pid: i64) -> Result<Vec<Value>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_get_proc_children", value_vec!(pid))?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| x.to_owned())
-            .collect())
+    pub fn get_proc_children(&self, pid: i64) -> Result<Vec<Value>, Error> {
+        self.call_typed("nvim_get_proc_children", value_vec!(pid))
     }
 
     /// Since: 4
-    pub fn get_proc(&mut self, pid: i64) -> Result<Value, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_get_proc", value_vec!(pid))?;
-        Ok(ret.to_owned())
+    pub fn get_proc(&self, pid: i64) -> Result<Option<Value>, Error> {
+        self.call_typed("nvim_get_proc", value_vec!(pid))
     }
 
     /// Since: 6
     pub fn select_popupmenu_item(
-        &mut self,
+        &self,
         item: i64,
         insert: bool,
         finish: bool,
-        opts: Vec<(Value, Value)>,
+        opts: impl Into<Dict>,
     ) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call(
+        let opts: Dict = opts.into();
+        self.call_typed(
             "nvim_select_popupmenu_item",
             value_vec!(item, insert, finish, opts),
-        )?;
-        Ok(())
+        )
     }
 
     /// Since: 8
-    pub fn del_mark(&mut self, name: String) -> Result<bool, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_del_mark", value_vec!(name))?;
-        Ok(ret.as_bool().unwrap())
+    pub fn del_mark(&self, name: impl Into<String>) -> Result<bool, Error> {
+        let name: String = name.into();
+        self.call_typed("nvim_del_mark", value_vec!(name))
     }
 
     /// Since: 8
     pub fn get_mark(
-        &mut self,
-        name: String,
-        opts: Vec<(Value, Value)>,
+        &self,
+        name: impl Into<String>,
+        opts: impl Into<Dict>,
     ) -> Result<Vec<Value>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_get_mark", value_vec!(name, opts))?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| x.to_owned())
-            .collect())
+        let name: String = name.into();
+        let opts: Dict = opts.into();
+        self.call_typed("nvim_get_mark", value_vec!(name, opts))
     }
 
     /// Since: 8
     pub fn eval_statusline(
-        &mut self,
-        str: String,
-        opts: Vec<(Value, Value)>,
-    ) -> Result<Vec<(Value, Value)>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_eval_statusline", value_vec!(str, opts))?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| {
-                let v = x.as_array().unwrap();
-                (v[0].to_owned(), v[1].to_owned())
-            })
-            .collect())
+        &self,
+        str: impl Into<String>,
+        opts: impl Into<Dict>,
+    ) -> Result<Dict, Error> {
+        let str: String = str.into();
+        let opts: Dict = opts.into();
+        self.call_typed("nvim_eval_statusline", value_vec!(str, opts))
     }
 
     /// Since: 12
-    pub fn complete_set(
-        &mut self,
-        index: i64,
-        opts: Vec<(Value, Value)>,
-    ) -> Result<Vec<(Value, Value)>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_complete_set", value_vec!(index, opts))?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| {
-                let v = x.as_array().unwrap();
-                (v[0].to_owned(), v[1].to_owned())
-            })
-            .collect())
+    #[cfg(feature = "nvim-0-10")]
+    pub fn complete_set(&self, index: i64, opts: impl Into<Dict>) -> Result<Dict, Error> {
+        let opts: Dict = opts.into();
+        self.call_typed("nvim_complete_set", value_vec!(index, opts))
     }
 
     /// Since: 11
-    pub fn exec2(
-        &mut self,
-        src: String,
-        opts: Vec<(Value, Value)>,
-    ) -> Result<Vec<(Value, Value)>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_exec2", value_vec!(src, opts))?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| {
-                let v = x.as_array().unwrap();
-                (v[0].to_owned(), v[1].to_owned())
-            })
-            .collect())
+    #[deprecated(note = "Use Nvim::exec, which returns the output as an ExecResult")]
+    pub fn exec2(&self, src: impl Into<String>, opts: impl Into<Dict>) -> Result<Dict, Error> {
+        let src: String = src.into();
+        let opts: Dict = opts.into();
+        self.call_typed("nvim_exec2", value_vec!(src, opts))
     }
 
     /// Since: 1
-    pub fn command(&mut self, command: String) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_command", value_vec!(command))?;
-        Ok(())
+    pub fn command(&self, command: impl Into<String>) -> Result<(), Error> {
+        let command: String = command.into();
+        self.call_typed("nvim_command", value_vec!(command))
     }
 
     /// Since: 1
-    pub fn eval(&mut self, expr: String) -> Result<Value, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_eval", value_vec!(expr))?;
-        Ok(ret.to_owned())
+    pub fn eval(&self, expr: impl Into<String>) -> Result<Value, Error> {
+        let expr: String = expr.into();
+        self.call_typed("nvim_eval", value_vec!(expr))
     }
 
     /// Since: 1
-    pub fn call_function(&mut self, r#fn: String, args: Vec<Value>) -> Result<Value, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_call_function", value_vec!(r#fn, args))?;
-        Ok(ret.to_owned())
+    pub fn call_function(
+        &self,
+        r#fn: impl Into<String>,
+        args: impl IntoIterator<Item = Value>,
+    ) -> Result<Value, Error> {
+        let r#fn: String = r#fn.into();
+        let args: Vec<Value> = args.into_iter().collect();
+        self.call_typed("nvim_call_function", value_vec!(r#fn, args))
     }
 
     /// Since: 4
     pub fn call_dict_function(
-        &mut self,
+        &self,
         dict: Value,
-        r#fn: String,
-        args: Vec<Value>,
+        r#fn: impl Into<String>,
+        args: impl IntoIterator<Item = Value>,
     ) -> Result<Value, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_call_dict_function", value_vec!(dict, r#fn, args))?;
-        Ok(ret.to_owned())
+        let r#fn: String = r#fn.into();
+        let args: Vec<Value> = args.into_iter().collect();
+        self.call_typed("nvim_call_dict_function", value_vec!(dict, r#fn, args))
     }
 
     /// Since: 4
     pub fn parse_expression(
-        &mut self,
-        expr: String,
-        flags: String,
+        &self,
+        expr: impl Into<String>,
+        flags: impl Into<String>,
         highlight: bool,
-    ) -> Result<Vec<(Value, Value)>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_parse_expression", value_vec!(expr, flags, highlight))?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| {
-                let v = x.as_array().unwrap();
-                (v[0].to_owned(), v[1].to_owned())
-            })
-            .collect())
+    ) -> Result<Dict, Error> {
+        let expr: String = expr.into();
+        let flags: String = flags.into();
+        self.call_typed("nvim_parse_expression", value_vec!(expr, flags, highlight))
     }
 
     /// Since: 6
     pub fn open_win(
-        &mut self,
-        buffer: Buffer,
+        &self,
+        buffer: &Buffer,
         enter: bool,
-        config: Vec<(Value, Value)>,
+        config: impl Into<Dict>,
     ) -> Result<Window, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_open_win", value_vec!(buffer, enter, config))?;
-        Ok(Window {
-            data: ret.clone(),
-            session: self.session.clone(),
-        })
+        let config: Dict = config.into();
+        let ret = self.call_raw("nvim_open_win", value_vec!(buffer, enter, config))?;
+        self.handle::<Window>(ret)
     }
 }
@@ -0,0 +1,204 @@
+use rmpv::Value;
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::api::convert::{AsValue, FromValue};
+use crate::api::*;
+use crate::error::Error;
+use crate::value_vec;
+
+/// A string from Neovim, which may not be valid UTF-8
+///
+/// Buffer lines hold whatever bytes the file contained, e.g. Latin-1 text
+/// or binary data, which can't be read as a `String`. `NvimString` keeps
+/// the bytes as they are, so lines can be read and written back unchanged.
+///
+/// # Example
+///
+/// ```no_run
+/// use rsnvim::api::Nvim;
+///
+/// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+/// nvim.start_event_loop(None, None);
+///
+/// let buffer = nvim.get_current_buf().unwrap();
+/// for line in buffer.get_lines_raw(0, -1, false).unwrap() {
+///     match line.to_str() {
+///         Ok(line) => println!("{}", line),
+///         Err(_) => println!("{} (not UTF-8)", line.to_string_lossy()),
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NvimString(Vec<u8>);
+
+impl NvimString {
+    pub fn from_bytes(bytes: impl Into<Vec<u8>>) -> Self {
+        NvimString(bytes.into())
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+
+    pub fn is_utf8(&self) -> bool {
+        std::str::from_utf8(&self.0).is_ok()
+    }
+
+    /// The string, or `Error::DecodingError` if it isn't valid UTF-8
+    pub fn to_str(&self) -> Result<&str, Error> {
+        std::str::from_utf8(&self.0)
+            .map_err(|error| Error::DecodingError(format!("String isn't valid UTF-8: {}", error)))
+    }
+
+    /// The string, with invalid UTF-8 replaced by `U+FFFD`
+    pub fn to_string_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.0)
+    }
+
+    /// Convert to a `String`, or return `Error::DecodingError` if it isn't
+    /// valid UTF-8
+    pub fn into_string(self) -> Result<String, Error> {
+        String::from_utf8(self.0)
+            .map_err(|error| Error::DecodingError(format!("String isn't valid UTF-8: {}", error)))
+    }
+
+    /// Convert to a `String`, replacing invalid UTF-8 with `U+FFFD`
+    pub fn into_string_lossy(self) -> String {
+        match String::from_utf8(self.0) {
+            Ok(string) => string,
+            Err(error) => String::from_utf8_lossy(error.as_bytes()).into_owned(),
+        }
+    }
+}
+
+impl fmt::Display for NvimString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_string_lossy())
+    }
+}
+
+impl From<String> for NvimString {
+    fn from(string: String) -> Self {
+        NvimString(string.into_bytes())
+    }
+}
+
+impl From<&str> for NvimString {
+    fn from(string: &str) -> Self {
+        NvimString(string.as_bytes().to_vec())
+    }
+}
+
+impl From<Vec<u8>> for NvimString {
+    fn from(bytes: Vec<u8>) -> Self {
+        NvimString(bytes)
+    }
+}
+
+impl From<&[u8]> for NvimString {
+    fn from(bytes: &[u8]) -> Self {
+        NvimString(bytes.to_vec())
+    }
+}
+
+impl FromValue for NvimString {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        match value {
+            Value::String(s) => Ok(NvimString(s.into_bytes())),
+            Value::Binary(bytes) => Ok(NvimString(bytes)),
+            other => Err(Error::DecodingError(format!(
+                "Expected a string but received {}",
+                other
+            ))),
+        }
+    }
+}
+
+impl AsValue for NvimString {
+    /// Sent as a string if it is valid UTF-8, and otherwise as binary, which
+    /// Neovim accepts wherever it expects a string
+    fn convert(&self) -> Value {
+        match std::str::from_utf8(&self.0) {
+            Ok(string) => Value::from(string),
+            Err(_) => Value::Binary(self.0.clone()),
+        }
+    }
+}
+
+impl From<NvimString> for Value {
+    fn from(string: NvimString) -> Self {
+        string.convert()
+    }
+}
+
+impl Buffer {
+    /// Get lines like `get_lines`, keeping lines which aren't valid UTF-8
+    pub fn get_lines_raw(
+        &self,
+        start: i64,
+        end: i64,
+        strict_indexing: bool,
+    ) -> Result<Vec<NvimString>, Error> {
+        self.call_typed(
+            "nvim_buf_get_lines",
+            value_vec!(*self, start, end, strict_indexing),
+        )
+    }
+
+    /// Replace lines like `set_lines`, with lines which needn't be valid
+    /// UTF-8
+    pub fn set_lines_raw(
+        &self,
+        start: i64,
+        end: i64,
+        strict_indexing: bool,
+        replacement: impl IntoIterator<Item = impl Into<NvimString>>,
+    ) -> Result<(), Error> {
+        let replacement: Vec<Value> = replacement
+            .into_iter()
+            .map(|line| Value::from(line.into()))
+            .collect();
+        self.call_typed(
+            "nvim_buf_set_lines",
+            value_vec!(*self, start, end, strict_indexing, replacement),
+        )
+    }
+
+    /// Get text like `get_text`, keeping text which isn't valid UTF-8
+    pub fn get_text_raw(
+        &self,
+        start_row: i64,
+        start_col: i64,
+        end_row: i64,
+        end_col: i64,
+    ) -> Result<Vec<NvimString>, Error> {
+        self.call_typed(
+            "nvim_buf_get_text",
+            value_vec!(*self, start_row, start_col, end_row, end_col, Dict::new()),
+        )
+    }
+
+    /// Replace text like `set_text`, with text which needn't be valid UTF-8
+    pub fn set_text_raw(
+        &self,
+        start_row: i64,
+        start_col: i64,
+        end_row: i64,
+        end_col: i64,
+        replacement: impl IntoIterator<Item = impl Into<NvimString>>,
+    ) -> Result<(), Error> {
+        let replacement: Vec<Value> = replacement
+            .into_iter()
+            .map(|line| Value::from(line.into()))
+            .collect();
+        self.call_typed(
+            "nvim_buf_set_text",
+            value_vec!(*self, start_row, start_col, end_row, end_col, replacement),
+        )
+    }
+}
@@ -0,0 +1,540 @@
+use rmpv::Value;
+
+use crate::api::convert::AsValue;
+use crate::api::*;
+
+/// Macro to implement AsValue trait for an enum of string options
+macro_rules! impl_asvalue_enum {
+    ($enum:ident { $($variant:ident => $name:expr),+ $(,)? }) => {
+        impl AsValue for $enum {
+            fn convert(&self) -> Value {
+                Value::from(match self {
+                    $($enum::$variant => $name,)+
+                })
+            }
+        }
+    };
+}
+
+/// Macro to implement a setter for each key of an options dictionary
+macro_rules! impl_opts {
+    ($opts:ident { $($(#[$doc:meta])* $key:ident: $ty:ty),+ $(,)? }) => {
+        impl $opts {
+            $(
+                $(#[$doc])*
+                pub fn $key(self, $key: $ty) -> Self {
+                    self.set(stringify!($key), $key.convert())
+                }
+            )+
+        }
+
+        impl From<$opts> for Vec<(Value, Value)> {
+            fn from(value: $opts) -> Self {
+                value.opts
+            }
+        }
+
+        impl From<$opts> for Dict {
+            fn from(value: $opts) -> Self {
+                Dict::from(value.opts)
+            }
+        }
+    };
+}
+
+/// Convert highlighted text chunks to the `[[text, hl_group], ...]` form
+fn chunks(chunks: &[(&str, &str)]) -> Value {
+    Value::from(
+        chunks
+            .iter()
+            .map(|(text, hl)| Value::from(vec![Value::from(*text), Value::from(*hl)]))
+            .collect::<Vec<Value>>(),
+    )
+}
+
+/// What a floating window is positioned relative to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Relative {
+    Editor,
+    Win,
+    Cursor,
+    Mouse,
+    Laststatus,
+    Tabline,
+}
+
+impl_asvalue_enum!(Relative {
+    Editor => "editor",
+    Win => "win",
+    Cursor => "cursor",
+    Mouse => "mouse",
+    Laststatus => "laststatus",
+    Tabline => "tabline",
+});
+
+/// Which corner of a floating window is placed at its position
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Anchor {
+    NW,
+    NE,
+    SW,
+    SE,
+}
+
+impl_asvalue_enum!(Anchor {
+    NW => "NW",
+    NE => "NE",
+    SW => "SW",
+    SE => "SE",
+});
+
+/// The border style of a floating window
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Border {
+    None,
+    Single,
+    Double,
+    Rounded,
+    Solid,
+    Shadow,
+    /// Up to eight characters, clockwise from the top-left corner
+    Custom(Vec<String>),
+}
+
+impl AsValue for Border {
+    fn convert(&self) -> Value {
+        match self {
+            Border::None => Value::from("none"),
+            Border::Single => Value::from("single"),
+            Border::Double => Value::from("double"),
+            Border::Rounded => Value::from("rounded"),
+            Border::Solid => Value::from("solid"),
+            Border::Shadow => Value::from("shadow"),
+            Border::Custom(chars) => chars.convert(),
+        }
+    }
+}
+
+/// The alignment of a floating window's title or footer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextPos {
+    Left,
+    Center,
+    Right,
+}
+
+impl_asvalue_enum!(TextPos {
+    Left => "left",
+    Center => "center",
+    Right => "right",
+});
+
+/// Where a split window is opened
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Split {
+    Left,
+    Right,
+    Above,
+    Below,
+}
+
+impl_asvalue_enum!(Split {
+    Left => "left",
+    Right => "right",
+    Above => "above",
+    Below => "below",
+});
+
+/// The kind of client, as reported by `nvim_set_client_info`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClientType {
+    /// A remote client connected over a socket, the default
+    Remote,
+    /// A remote client not using the Neovim API, e.g. another editor
+    MsgpackRpc,
+    /// A graphical frontend
+    Ui,
+    /// An application embedding Neovim
+    Embedder,
+    /// A plugin host, typically started by a Neovim plugin
+    Host,
+    /// A single plugin, started by a plugin host
+    Plugin,
+}
+
+impl_asvalue_enum!(ClientType {
+    Remote => "remote",
+    MsgpackRpc => "msgpack-rpc",
+    Ui => "ui",
+    Embedder => "embedder",
+    Host => "host",
+    Plugin => "plugin",
+});
+
+/// Where an extmark's virtual text is displayed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VirtTextPos {
+    Eol,
+    Overlay,
+    RightAlign,
+    Inline,
+}
+
+impl_asvalue_enum!(VirtTextPos {
+    Eol => "eol",
+    Overlay => "overlay",
+    RightAlign => "right_align",
+    Inline => "inline",
+});
+
+/// How an extmark's highlights are combined with the text's highlights
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HlMode {
+    Replace,
+    Combine,
+    Blend,
+}
+
+impl_asvalue_enum!(HlMode {
+    Replace => "replace",
+    Combine => "combine",
+    Blend => "blend",
+});
+
+/// The configuration of a window, used by `Nvim::open_win` and
+/// `Window::set_config`
+///
+/// # Example
+///
+/// ```no_run
+/// use rsnvim::api::{Border, Nvim, Relative, WinConfig};
+///
+/// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+/// nvim.start_event_loop(None, None);
+///
+/// let buffer = nvim.create_buf(false, true).unwrap();
+/// let config = WinConfig::new()
+///     .relative(Relative::Editor)
+///     .row(5.0)
+///     .col(10.0)
+///     .width(40)
+///     .height(10)
+///     .border(Border::Rounded)
+///     .title("Preview");
+/// nvim.open_win(&buffer, true, config).unwrap();
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WinConfig {
+    opts: Vec<(Value, Value)>,
+}
+
+/// Options for `Nvim::set_keymap`, `Nvim::set_keymap_rpc` and
+/// `Buffer::set_keymap`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct KeymapOpts {
+    opts: Vec<(Value, Value)>,
+}
+
+/// Options for `Buffer::set_extmark`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExtmarkOpts {
+    opts: Vec<(Value, Value)>,
+}
+
+/// Options for `Nvim::attach_ui` and `Ui::set_options`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UiOptions {
+    opts: Vec<(Value, Value)>,
+}
+
+/// Options for `AutocmdManager::on`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AutocmdOpts {
+    opts: Vec<(Value, Value)>,
+}
+
+/// Constructors shared by the options dictionaries
+macro_rules! impl_opts_new {
+    ($($opts:ident),+) => {
+        $(
+            impl $opts {
+                pub fn new() -> Self {
+                    Self::default()
+                }
+
+                /// Set `key` to `value`, replacing any previous value
+                fn set(mut self, key: &str, value: Value) -> Self {
+                    self.opts.retain(|(k, _)| k.as_str() != Some(key));
+                    self.opts.push((Value::from(key), value));
+                    self
+                }
+            }
+        )+
+    };
+}
+
+impl_opts_new!(WinConfig, KeymapOpts, ExtmarkOpts, UiOptions, AutocmdOpts);
+
+impl_opts!(WinConfig {
+    /// Position the window relative to this layout element
+    relative: Relative,
+    /// The window to position relative to, with `Relative::Win`
+    win: &Window,
+    /// The corner of the window placed at `row`/`col`
+    anchor: Anchor,
+    width: i64,
+    height: i64,
+    /// The row of the anchor, in screen cells
+    row: f64,
+    /// The column of the anchor, in screen cells
+    col: f64,
+    /// Whether the window can be focused by wincmds or mouse events
+    focusable: bool,
+    /// Whether to display the window as an external window
+    external: bool,
+    /// The stacking order of floating windows
+    zindex: i64,
+    border: Border,
+    title_pos: TextPos,
+    footer_pos: TextPos,
+    /// Don't run autocommands when opening the window
+    noautocmd: bool,
+    /// Don't move the window when the cursor moves
+    fixed: bool,
+    /// Whether the window is hidden
+    hide: bool,
+    /// Open a vertical split instead of a horizontal one
+    vertical: bool,
+    /// Open a split window in this direction
+    split: Split,
+});
+
+impl WinConfig {
+    /// Use the "minimal" style, disabling most UI elements
+    pub fn minimal(self) -> Self {
+        self.set("style", Value::from("minimal"))
+    }
+
+    /// Set the title shown in the window's border
+    pub fn title(self, title: &str) -> Self {
+        self.set("title", Value::from(title))
+    }
+
+    /// Set the footer shown in the window's border
+    pub fn footer(self, footer: &str) -> Self {
+        self.set("footer", Value::from(footer))
+    }
+
+    /// Position the window relative to the (zero-indexed) buffer position
+    /// `row`, `col` of the window given by `win`
+    pub fn bufpos(self, row: i64, col: i64) -> Self {
+        self.set(
+            "bufpos",
+            Value::from(vec![Value::from(row), Value::from(col)]),
+        )
+    }
+}
+
+impl_opts!(KeymapOpts {
+    /// Don't remap the right-hand side
+    noremap: bool,
+    /// Don't wait for longer mappings to be typed
+    nowait: bool,
+    /// Don't echo the command
+    silent: bool,
+    /// Only remap script-local mappings
+    script: bool,
+    /// The right-hand side is an expression
+    expr: bool,
+    /// Fail if the mapping already exists
+    unique: bool,
+    /// Replace keycodes in the result of an `expr` mapping
+    replace_keycodes: bool,
+});
+
+impl KeymapOpts {
+    /// Set the description shown by `:map` and plugins
+    pub fn desc(self, desc: &str) -> Self {
+        self.set("desc", Value::from(desc))
+    }
+}
+
+impl_opts!(ExtmarkOpts {
+    /// Update the extmark with this ID rather than creating a new one
+    id: i64,
+    /// The (zero-indexed) row the extmark ends at
+    end_row: i64,
+    /// The (zero-indexed, exclusive) column the extmark ends at
+    end_col: i64,
+    /// Continue the highlight to the end of the screen line
+    hl_eol: bool,
+    virt_text_pos: VirtTextPos,
+    /// Display virtual text at this fixed window column
+    virt_text_win_col: i64,
+    /// Hide virtual text when the background text is selected or hidden
+    virt_text_hide: bool,
+    /// Place virtual lines above rather than below the line
+    virt_lines_above: bool,
+    hl_mode: HlMode,
+    /// The priority used when highlights overlap
+    priority: i64,
+    /// Fail if the position is outside of the buffer
+    strict: bool,
+    /// Whether the extmark moves right when text is inserted at its position
+    right_gravity: bool,
+    /// Whether the end moves right when text is inserted at its position
+    end_right_gravity: bool,
+    /// Only draw the extmark for the current redraw
+    ephemeral: bool,
+    /// Restore the extmark's position when undoing a deletion
+    undo_restore: bool,
+    /// Hide the extmark when its range is deleted
+    invalidate: bool,
+    /// Whether to spell check the range
+    spell: bool,
+});
+
+impl ExtmarkOpts {
+    /// Highlight the range with `hl_group`
+    pub fn hl_group(self, hl_group: &str) -> Self {
+        self.set("hl_group", Value::from(hl_group))
+    }
+
+    /// Display chunks of `(text, hl_group)` as virtual text
+    pub fn virt_text(self, virt_text: &[(&str, &str)]) -> Self {
+        self.set("virt_text", chunks(virt_text))
+    }
+
+    /// Display virtual lines, each made of chunks of `(text, hl_group)`
+    pub fn virt_lines(self, virt_lines: &[&[(&str, &str)]]) -> Self {
+        let lines: Vec<Value> = virt_lines.iter().map(|line| chunks(line)).collect();
+        self.set("virt_lines", Value::from(lines))
+    }
+
+    /// Display `text` (up to two cells) in the sign column
+    pub fn sign_text(self, sign_text: &str) -> Self {
+        self.set("sign_text", Value::from(sign_text))
+    }
+
+    /// Highlight the sign column text with `hl_group`
+    pub fn sign_hl_group(self, hl_group: &str) -> Self {
+        self.set("sign_hl_group", Value::from(hl_group))
+    }
+
+    /// Highlight the line number with `hl_group`
+    pub fn number_hl_group(self, hl_group: &str) -> Self {
+        self.set("number_hl_group", Value::from(hl_group))
+    }
+
+    /// Highlight the whole line with `hl_group`
+    pub fn line_hl_group(self, hl_group: &str) -> Self {
+        self.set("line_hl_group", Value::from(hl_group))
+    }
+
+    /// Conceal the range, displaying `conceal` (a single character, or empty)
+    /// in its place
+    pub fn conceal(self, conceal: &str) -> Self {
+        self.set("conceal", Value::from(conceal))
+    }
+}
+
+impl_opts!(UiOptions {
+    /// Use 24-bit RGB colors rather than terminal colors
+    rgb: bool,
+    /// Draw the command line externally
+    ext_cmdline: bool,
+    /// Receive detailed highlight state, which requires `ext_linegrid`
+    ext_hlstate: bool,
+    /// Use line-based grid events
+    ext_linegrid: bool,
+    /// Draw messages externally
+    ext_messages: bool,
+    /// Draw each window in its own grid, which requires `ext_linegrid`
+    ext_multigrid: bool,
+    /// Draw the popupmenu externally
+    ext_popupmenu: bool,
+    /// Draw the tabline externally
+    ext_tabline: bool,
+    /// Use the external default colors
+    ext_termcolors: bool,
+    /// Draw the wildmenu externally
+    ext_wildmenu: bool,
+    /// The number of colors supported by the terminal
+    term_colors: i64,
+    /// Read buffer contents from this file descriptor
+    stdin_fd: i64,
+    /// Whether stdin is a terminal
+    stdin_tty: bool,
+    /// Whether stdout is a terminal
+    stdout_tty: bool,
+});
+
+impl UiOptions {
+    /// Override the other UIs' choices of the `ext_*` options
+    pub fn override_ext(self, enabled: bool) -> Self {
+        self.set("override", Value::from(enabled))
+    }
+
+    /// Set the terminal name, used for `$TERM`
+    pub fn term_name(self, term_name: &str) -> Self {
+        self.set("term_name", Value::from(term_name))
+    }
+
+    /// Set the terminal's background, "light" or "dark"
+    pub fn term_background(self, term_background: &str) -> Self {
+        self.set("term_background", Value::from(term_background))
+    }
+
+    /// Get the value of the option `name`, if set
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.opts
+            .iter()
+            .find(|(k, _)| k.as_str() == Some(name))
+            .map(|(_, v)| v)
+    }
+
+    /// Iterate over the options which are set, as `(name, value)` pairs
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.opts.iter().filter_map(|(k, v)| Some((k.as_str()?, v)))
+    }
+
+    /// Whether the option `name` is enabled
+    pub(crate) fn enabled(&self, name: &str) -> bool {
+        self.get(name).and_then(Value::as_bool).unwrap_or(false)
+    }
+
+    /// Set the option `name` to `value`, replacing any previous value
+    pub(crate) fn with(self, name: &str, value: Value) -> Self {
+        self.set(name, value)
+    }
+
+    /// Set each option of `other`, replacing any previous values
+    pub(crate) fn merge(mut self, other: &UiOptions) -> Self {
+        for (k, v) in other.iter() {
+            self = self.set(k, v.clone());
+        }
+        self
+    }
+}
+
+impl_opts!(AutocmdOpts {
+    /// Only trigger for this buffer, rather than by pattern
+    buffer: i64,
+    /// Delete the autocommand after it first triggers
+    once: bool,
+    /// Allow the autocommand to trigger other autocommands
+    nested: bool,
+});
+
+impl AutocmdOpts {
+    /// Only trigger when `<amatch>` matches one of `patterns`, e.g. "*.rs"
+    pub fn pattern(self, patterns: &[&str]) -> Self {
+        let patterns: Vec<Value> = patterns.iter().map(|p| Value::from(*p)).collect();
+        self.set("pattern", Value::from(patterns))
+    }
+
+    /// Set the description shown by `:autocmd`
+    pub fn desc(self, desc: &str) -> Self {
+        self.set("desc", Value::from(desc))
+    }
+}
@@ -0,0 +1,524 @@
+use rmpv::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::api::*;
+use crate::error::Error;
+
+/// A run of text drawn with one highlight, as sent in message and command
+/// line events
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Chunk {
+    /// The highlight, defined by an earlier `hl_attr_define` event, or `0`
+    /// for the default highlight
+    pub attr_id: i64,
+    pub text: String,
+}
+
+impl FromValue for Chunk {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        // Neovim 0.11 adds the highlight group's id after the text
+        match value.as_array().map(|v| v.as_slice()) {
+            Some([attr_id, text, ..]) => Ok(Chunk {
+                attr_id: i64::from_value(attr_id.clone())?,
+                text: String::from_value(text.clone())?,
+            }),
+            _ => Err(Error::DecodingError(format!(
+                "Expected a [attr_id, text] chunk, got {}",
+                value
+            ))),
+        }
+    }
+}
+
+/// Highlight attributes set by an `hl_attr_define` event
+///
+/// Colors are 24-bit RGB values, or terminal color numbers for the `cterm`
+/// attributes, and are `None` to use the default color.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HlAttrs {
+    pub foreground: Option<i64>,
+    pub background: Option<i64>,
+    pub special: Option<i64>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub undercurl: bool,
+    pub strikethrough: bool,
+    pub reverse: bool,
+    /// Transparency from `0` to `100`
+    pub blend: Option<i64>,
+}
+
+impl FromValue for HlAttrs {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        let mut attrs = HlAttrs::default();
+        for (key, value) in Vec::<(Value, Value)>::from_value(value)? {
+            let flag = || value.as_bool().unwrap_or(false);
+            match key.as_str().unwrap_or("") {
+                "foreground" => attrs.foreground = value.as_i64(),
+                "background" => attrs.background = value.as_i64(),
+                "special" => attrs.special = value.as_i64(),
+                "bold" => attrs.bold = flag(),
+                "italic" => attrs.italic = flag(),
+                "underline" => attrs.underline = flag(),
+                "undercurl" => attrs.undercurl = flag(),
+                "strikethrough" => attrs.strikethrough = flag(),
+                "reverse" => attrs.reverse = flag(),
+                "blend" => attrs.blend = value.as_i64(),
+                _ => (),
+            }
+        }
+        Ok(attrs)
+    }
+}
+
+/// A highlight defined by an `hl_attr_define` event
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HlAttrDefine {
+    pub id: i64,
+    pub rgb: HlAttrs,
+    pub cterm: HlAttrs,
+    /// The highlight groups the highlight was made from, e.g. `ErrorMsg`,
+    /// which are only sent with `ext_hlstate`
+    pub groups: Vec<String>,
+}
+
+/// An `msg_show` event, drawing a message
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MsgShow {
+    /// What made the message, e.g. `echomsg` or `emsg`, or empty if unknown
+    pub kind: String,
+    pub content: Vec<Chunk>,
+    /// Whether the message replaces the last message shown
+    pub replace_last: bool,
+}
+
+/// An entry of an `msg_history_show` event, listing the message history
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MsgHistoryEntry {
+    pub kind: String,
+    pub content: Vec<Chunk>,
+}
+
+/// A `cmdline_show` event, drawing the command line
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CmdlineShow {
+    pub content: Vec<Chunk>,
+    /// The cursor position in bytes
+    pub pos: i64,
+    /// The command type, e.g. `:` or `/`, or empty for `input()`
+    pub firstc: String,
+    /// The prompt given to `input()`
+    pub prompt: String,
+    pub indent: i64,
+    /// The nesting level, increasing for command lines opened from another,
+    /// e.g. with `<C-r>=`
+    pub level: i64,
+}
+
+/// A `cmdline_pos` event, moving the command line's cursor
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CmdlinePos {
+    /// The cursor position in bytes
+    pub pos: i64,
+    pub level: i64,
+}
+
+/// An event sent in a `redraw` notification to an attached UI
+///
+/// # Example
+///
+/// ```no_run
+/// use rsnvim::api::{Nvim, RedrawEvent, UiOptions};
+///
+/// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+/// let redraws = nvim.subscribe_notifications("redraw");
+/// nvim.start_event_loop(None, None);
+///
+/// let options = UiOptions::new().ext_linegrid(true).ext_messages(true);
+/// let _ui = nvim.attach_ui(80, 24, options).unwrap();
+/// for params in redraws {
+///     for event in RedrawEvent::parse(params).unwrap() {
+///         if let RedrawEvent::MsgShow(msg) = event {
+///             let text: String = msg.content.iter().map(|c| c.text.as_str()).collect();
+///             println!("{}: {}", msg.kind, text);
+///         }
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub enum RedrawEvent {
+    HlAttrDefine(HlAttrDefine),
+    MsgShow(MsgShow),
+    MsgHistoryShow(Vec<MsgHistoryEntry>),
+    CmdlineShow(CmdlineShow),
+    CmdlinePos(CmdlinePos),
+    /// An event without a typed form, with its arguments as sent
+    Other {
+        name: String,
+        args: Vec<Value>,
+    },
+}
+
+impl RedrawEvent {
+    /// Decode the parameters of a `redraw` notification
+    ///
+    /// Neovim batches the events, sending the name of each followed by the
+    /// arguments of each time it occurred, which are returned as separate
+    /// events in the order they were sent.
+    pub fn parse(params: Vec<Value>) -> Result<Vec<RedrawEvent>, Error> {
+        let mut events = Vec::new();
+        for batch in params {
+            let mut batch = Vec::<Value>::from_value(batch)?.into_iter();
+            let name = match batch.next() {
+                Some(name) => String::from_value(name)?,
+                None => return Err(Error::DecodingError("Empty redraw batch".to_string())),
+            };
+            for args in batch {
+                let args = Vec::<Value>::from_value(args)?;
+                events.push(RedrawEvent::from_args(&name, args)?);
+            }
+        }
+        Ok(events)
+    }
+
+    /// Decode the arguments of one occurrence of the event `name`
+    fn from_args(name: &str, args: Vec<Value>) -> Result<RedrawEvent, Error> {
+        let invalid = || Error::DecodingError(format!("Invalid {} event {:?}", name, args));
+        let arg = |index: usize| args.get(index).cloned().ok_or_else(invalid);
+        let event = match name {
+            "hl_attr_define" => {
+                let groups = Vec::<Value>::from_value(arg(3)?)?
+                    .iter()
+                    .filter_map(|info| lookup(info, "hi_name")?.as_str().map(String::from))
+                    .collect();
+                RedrawEvent::HlAttrDefine(HlAttrDefine {
+                    id: i64::from_value(arg(0)?)?,
+                    rgb: HlAttrs::from_value(arg(1)?)?,
+                    cterm: HlAttrs::from_value(arg(2)?)?,
+                    groups,
+                })
+            }
+            "msg_show" => RedrawEvent::MsgShow(MsgShow {
+                kind: String::from_value(arg(0)?)?,
+                content: Vec::from_value(arg(1)?)?,
+                replace_last: bool::from_value(arg(2)?)?,
+            }),
+            "msg_history_show" => {
+                let entries = Vec::<Vec<Value>>::from_value(arg(0)?)?
+                    .into_iter()
+                    .map(|entry| match entry.as_slice() {
+                        [kind, content, ..] => Ok(MsgHistoryEntry {
+                            kind: String::from_value(kind.clone())?,
+                            content: Vec::from_value(content.clone())?,
+                        }),
+                        _ => Err(invalid()),
+                    })
+                    .collect::<Result<_, Error>>()?;
+                RedrawEvent::MsgHistoryShow(entries)
+            }
+            "cmdline_show" => RedrawEvent::CmdlineShow(CmdlineShow {
+                content: Vec::from_value(arg(0)?)?,
+                pos: i64::from_value(arg(1)?)?,
+                firstc: String::from_value(arg(2)?)?,
+                prompt: String::from_value(arg(3)?)?,
+                indent: i64::from_value(arg(4)?)?,
+                level: i64::from_value(arg(5)?)?,
+            }),
+            "cmdline_pos" => RedrawEvent::CmdlinePos(CmdlinePos {
+                pos: i64::from_value(arg(0)?)?,
+                level: i64::from_value(arg(1)?)?,
+            }),
+            _ => RedrawEvent::Other {
+                name: name.to_string(),
+                args,
+            },
+        };
+        Ok(event)
+    }
+}
+
+/// Get the value of `key` in a dictionary
+fn lookup<'a>(map: &'a Value, key: &str) -> Option<&'a Value> {
+    map.as_map()?
+        .iter()
+        .find(|(k, _)| k.as_str() == Some(key))
+        .map(|(_, v)| v)
+}
+
+/// The highlights defined by `hl_attr_define` events, used to resolve the
+/// `attr_id` of each chunk
+///
+/// Neovim only defines each highlight once per UI, so every redraw event
+/// must be passed to `update` from when the UI is attached.
+#[derive(Clone, Debug, Default)]
+pub struct HlAttrTable {
+    attrs: HashMap<i64, HlAttrDefine>,
+}
+
+impl HlAttrTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the highlight defined by `event`, if it is an `hl_attr_define`
+    pub fn update(&mut self, event: &RedrawEvent) {
+        if let RedrawEvent::HlAttrDefine(define) = event {
+            self.attrs.insert(define.id, define.clone());
+        }
+    }
+
+    /// Get the highlight with `attr_id`, or `None` for the default highlight
+    /// or one which hasn't been defined
+    pub fn get(&self, attr_id: i64) -> Option<&HlAttrDefine> {
+        self.attrs.get(&attr_id)
+    }
+
+    /// Pair each chunk's text with its highlight
+    pub fn resolve<'a>(&'a self, content: &'a [Chunk]) -> Vec<(&'a str, Option<&'a HlAttrDefine>)> {
+        content
+            .iter()
+            .map(|chunk| (chunk.text.as_str(), self.get(chunk.attr_id)))
+            .collect()
+    }
+}
+
+/// Events which a full redraw, e.g. `:redraw!`, sends again, so may be
+/// dropped from a stale batch
+const REDRAWN_EVENTS: &[&str] = &[
+    "grid_line",
+    "grid_scroll",
+    "grid_clear",
+    "grid_cursor_goto",
+    "win_viewport",
+    "flush",
+];
+
+/// How a `RedrawReceiver` handles batches arriving faster than they are
+/// received
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RedrawMode {
+    /// Queue every batch
+    #[default]
+    Every,
+    /// Keep only the latest complete batch, dropping the grid contents of
+    /// older ones while keeping events like `grid_resize` and
+    /// `hl_attr_define`, which are never sent again
+    DropStale,
+}
+
+/// The events drawn between two `flush` events, received from a
+/// `RedrawReceiver`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RedrawBatch {
+    /// The event batches of each `redraw` notification, in the form taken
+    /// by `RedrawEvent::parse`
+    pub params: Vec<Value>,
+    /// The number of earlier batches which were dropped, merging their
+    /// remaining events into this one
+    pub dropped: usize,
+}
+
+impl RedrawBatch {
+    /// Decode the batch's events
+    pub fn events(&self) -> Result<Vec<RedrawEvent>, Error> {
+        RedrawEvent::parse(self.params.clone())
+    }
+
+    /// Keep only the events of a dropped batch which a full redraw doesn't
+    /// send again
+    fn strip(self) -> Vec<Value> {
+        self.params
+            .into_iter()
+            .filter(|batch| {
+                let name = batch.as_array().and_then(|batch| batch.first()?.as_str());
+                !name.is_some_and(|name| REDRAWN_EVENTS.contains(&name))
+            })
+            .collect()
+    }
+}
+
+/// State shared between a `RedrawReceiver` and its subscription
+struct RedrawState {
+    /// The events received since the last `flush`
+    partial: Vec<Value>,
+    ready: VecDeque<RedrawBatch>,
+    closed: bool,
+}
+
+struct RedrawInner {
+    state: Mutex<RedrawState>,
+    condvar: Condvar,
+    mode: RedrawMode,
+}
+
+/// The sending half of a `RedrawReceiver`, fed the parameters of each
+/// `redraw` notification
+///
+/// Dropping it, e.g. when the connection closes, ends the receiver once its
+/// queued batches have been received.
+pub(crate) struct RedrawSender {
+    inner: Arc<RedrawInner>,
+}
+
+impl RedrawSender {
+    /// Queue the events of a `redraw` notification, returning `false` once
+    /// the receiver has been dropped
+    pub(crate) fn send(&self, params: &[Value]) -> bool {
+        let mut state = self.inner.state.lock().unwrap();
+        if state.closed {
+            return false;
+        }
+        for batch in params {
+            state.partial.push(batch.clone());
+            let is_flush =
+                batch.as_array().and_then(|batch| batch.first()?.as_str()) == Some("flush");
+            if !is_flush {
+                continue;
+            }
+            let mut complete = RedrawBatch {
+                params: std::mem::take(&mut state.partial),
+                dropped: 0,
+            };
+            if self.inner.mode == RedrawMode::DropStale {
+                if let Some(stale) = state.ready.pop_front() {
+                    complete.dropped = stale.dropped + 1;
+                    let mut params = stale.strip();
+                    params.append(&mut complete.params);
+                    complete.params = params;
+                }
+            }
+            state.ready.push_back(complete);
+            self.inner.condvar.notify_one();
+        }
+        true
+    }
+}
+
+impl Drop for RedrawSender {
+    fn drop(&mut self) {
+        self.inner.state.lock().unwrap().closed = true;
+        self.inner.condvar.notify_all();
+    }
+}
+
+/// Receives the redraw events of an attached UI a batch at a time, created
+/// by `Nvim::subscribe_redraw`
+///
+/// Each batch holds the events up to and including a `flush`, so is a
+/// complete update of the screen. With `RedrawMode::DropStale`, a UI which
+/// falls behind skips to the latest batch rather than replaying every
+/// intermediate one, bounding both memory and the time taken to catch up.
+/// The grid contents of the dropped batches are lost, so when
+/// `RedrawBatch::dropped` is non-zero the UI should ask for a full redraw.
+///
+/// # Example
+///
+/// ```no_run
+/// use rsnvim::api::{Nvim, RedrawMode, UiOptions};
+///
+/// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+/// let redraws = nvim.subscribe_redraw(RedrawMode::DropStale);
+/// nvim.start_event_loop(None, None);
+///
+/// let _ui = nvim.attach_ui(80, 24, UiOptions::new().ext_linegrid(true)).unwrap();
+/// for batch in redraws {
+///     if batch.dropped > 0 {
+///         nvim.command("redraw!").unwrap();
+///     }
+///     for event in batch.events().unwrap() {
+///         println!("{:?}", event);
+///     }
+/// }
+/// ```
+pub struct RedrawReceiver {
+    inner: Arc<RedrawInner>,
+}
+
+impl RedrawReceiver {
+    /// Create a receiver and the sender which feeds it
+    pub(crate) fn new(mode: RedrawMode) -> (RedrawSender, RedrawReceiver) {
+        let inner = Arc::new(RedrawInner {
+            state: Mutex::new(RedrawState {
+                partial: Vec::new(),
+                ready: VecDeque::new(),
+                closed: false,
+            }),
+            condvar: Condvar::new(),
+            mode,
+        });
+        (
+            RedrawSender {
+                inner: inner.clone(),
+            },
+            RedrawReceiver { inner },
+        )
+    }
+
+    /// Block until a batch is complete, or return `None` once the
+    /// connection has closed
+    pub fn recv(&self) -> Option<RedrawBatch> {
+        let mut state = self.inner.state.lock().unwrap();
+        loop {
+            if let Some(batch) = state.ready.pop_front() {
+                return Some(batch);
+            }
+            if state.closed {
+                return None;
+            }
+            state = self.inner.condvar.wait(state).unwrap();
+        }
+    }
+
+    /// Like `recv`, but return `None` if no batch is complete within
+    /// `timeout`
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<RedrawBatch> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.inner.state.lock().unwrap();
+        loop {
+            if let Some(batch) = state.ready.pop_front() {
+                return Some(batch);
+            }
+            let now = Instant::now();
+            if state.closed || now >= deadline {
+                return None;
+            }
+            state = self
+                .inner
+                .condvar
+                .wait_timeout(state, deadline - now)
+                .unwrap()
+                .0;
+        }
+    }
+
+    /// Get a complete batch without blocking
+    pub fn try_recv(&self) -> Option<RedrawBatch> {
+        self.inner.state.lock().unwrap().ready.pop_front()
+    }
+
+    /// The number of complete batches waiting to be received
+    pub fn len(&self) -> usize {
+        self.inner.state.lock().unwrap().ready.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Drop for RedrawReceiver {
+    fn drop(&mut self) {
+        self.inner.state.lock().unwrap().closed = true;
+    }
+}
+
+impl Iterator for RedrawReceiver {
+    type Item = RedrawBatch;
+
+    fn next(&mut self) -> Option<RedrawBatch> {
+        self.recv()
+    }
+}
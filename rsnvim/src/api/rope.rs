@@ -0,0 +1,253 @@
+use rmpv::Value;
+use ropey::{Rope, RopeBuilder};
+use std::sync::mpsc::{self, RecvTimeoutError, TryRecvError};
+use std::time::Duration;
+
+use crate::api::convert::from_handle;
+use crate::api::*;
+use crate::error::Error;
+use crate::handler::Notification;
+
+/// The number of lines fetched per request by `Buffer::to_rope`
+const ROPE_CHUNK: usize = 1000;
+
+/// How long `Buffer::attach_rope` waits for the buffer's contents
+const ATTACH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The buffer events applied by a `RopeSync`
+const ROPE_EVENTS: &[&str] = &[
+    "nvim_buf_lines_event",
+    "nvim_buf_changedtick_event",
+    "nvim_buf_detach_event",
+];
+
+/// Replace the lines `first..last` of `rope`, where every line ends with
+/// `\n`, with `lines`
+///
+/// `last` past the end of the rope replaces every line from `first`.
+fn replace_lines(rope: &mut Rope, first: usize, last: usize, lines: &[Value]) {
+    // The rope ends with an empty line after the final `\n`
+    let line_count = rope.len_lines() - 1;
+    let start = rope.line_to_char(first.min(line_count));
+    let end = rope.line_to_char(last.min(line_count));
+    rope.remove(start..end);
+    let mut text = String::new();
+    for line in lines {
+        text.push_str(&line_text(line));
+        text.push('\n');
+    }
+    rope.insert(start, &text);
+}
+
+/// Get the text of a line, replacing invalid UTF-8
+fn line_text(line: &Value) -> String {
+    match line.as_slice() {
+        Some(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+        None => String::new(),
+    }
+}
+
+impl Buffer {
+    /// Get the buffer's contents as a `Rope`, fetching the lines in chunks
+    ///
+    /// Every line ends with `\n`, so the rope has an extra empty line at the
+    /// end and line `n` of the rope is line `n` of the buffer. Invalid UTF-8
+    /// is replaced with U+FFFD.
+    pub fn to_rope(&mut self) -> Result<Rope, Error> {
+        let mut builder = RopeBuilder::new();
+        for chunk in self.get_lines_chunked(0, -1, ROPE_CHUNK) {
+            for line in chunk? {
+                builder.append(&line);
+                builder.append("\n");
+            }
+        }
+        Ok(builder.finish())
+    }
+
+    /// Keep a `Rope` of the buffer's contents up to date as it is edited
+    ///
+    /// The buffer is attached with `nvim_buf_attach`, and the rope is built
+    /// from the buffer's contents sent with the attach, so no edit is missed
+    /// or applied twice. See `to_rope` for the layout of the rope.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::api::Nvim;
+    /// use std::time::Duration;
+    ///
+    /// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+    /// nvim.start_event_loop(None, None);
+    ///
+    /// let mut buffer = nvim.get_current_buf().unwrap();
+    /// let mut sync = buffer.attach_rope().unwrap();
+    /// while sync.wait(Duration::from_secs(1)).unwrap() {
+    ///     println!("{} lines", sync.rope().len_lines() - 1);
+    /// }
+    /// ```
+    pub fn attach_rope(&mut self) -> Result<RopeSync, Error> {
+        // Subscribe first so the initial contents can't be missed
+        let events = self.session.lock().unwrap().subscribe(ROPE_EVENTS);
+        if !self.attach(true, Vec::new())? {
+            return Err(Error::RpcError(format!(
+                "Couldn't attach to buffer {}",
+                self.id
+            )));
+        }
+        let mut sync = RopeSync {
+            buffer: self.clone(),
+            rope: Rope::new(),
+            changedtick: 0,
+            attached: true,
+            events,
+        };
+        // Apply the initial contents, which may be sent in several parts, so
+        // the rope is never partially built
+        let mut initial = false;
+        loop {
+            let event = sync
+                .events
+                .recv_timeout(ATTACH_TIMEOUT)
+                .map_err(|_| Error::TimeoutError("Buffer contents not received".to_string()))?;
+            let (whole, last_part) = if event.method == "nvim_buf_lines_event" {
+                let params = &event.params;
+                (
+                    params.get(3).and_then(Value::as_i64) == Some(-1),
+                    params.get(5).and_then(Value::as_bool) == Some(false),
+                )
+            } else {
+                (false, false)
+            };
+            // Only lines events for this buffer change the rope
+            if sync.apply(event)? {
+                initial |= whole;
+                if initial && last_part {
+                    break;
+                }
+            }
+            if !sync.attached {
+                break;
+            }
+        }
+        Ok(sync)
+    }
+}
+
+/// A `Rope` kept up to date with a buffer, created by `Buffer::attach_rope`
+///
+/// Edits are applied as `update` or `wait` receive them, so the rope only
+/// changes when one of these is called.
+pub struct RopeSync {
+    buffer: Buffer,
+    rope: Rope,
+    changedtick: i64,
+    attached: bool,
+    events: mpsc::Receiver<Notification>,
+}
+
+impl RopeSync {
+    pub fn rope(&self) -> &Rope {
+        &self.rope
+    }
+
+    /// The `b:changedtick` of the last edit applied to the rope
+    pub fn changedtick(&self) -> i64 {
+        self.changedtick
+    }
+
+    /// Whether the rope is still updated, which stops once the buffer is
+    /// detached, e.g. when it is unloaded or reloaded with `:edit!`
+    pub fn is_attached(&self) -> bool {
+        self.attached
+    }
+
+    /// Apply each edit received so far, returning whether the rope changed
+    pub fn update(&mut self) -> Result<bool, Error> {
+        let mut changed = false;
+        loop {
+            match self.events.try_recv() {
+                Ok(event) => changed |= self.apply(event)?,
+                Err(TryRecvError::Empty) => return Ok(changed),
+                Err(TryRecvError::Disconnected) => return self.disconnected(),
+            }
+        }
+    }
+
+    /// Wait up to `timeout` for an edit, then apply each edit received,
+    /// returning whether the rope changed
+    ///
+    /// Returns `false` immediately once the buffer is detached.
+    pub fn wait(&mut self, timeout: Duration) -> Result<bool, Error> {
+        if !self.attached {
+            return Ok(false);
+        }
+        match self.events.recv_timeout(timeout) {
+            Ok(event) => {
+                let changed = self.apply(event)?;
+                Ok(self.update()? || changed)
+            }
+            Err(RecvTimeoutError::Timeout) => Ok(false),
+            Err(RecvTimeoutError::Disconnected) => self.disconnected(),
+        }
+    }
+
+    /// Stop updating the rope, returning it
+    pub fn detach(self) -> Result<Rope, Error> {
+        if self.attached {
+            self.buffer.detach()?;
+        }
+        Ok(self.rope)
+    }
+
+    /// Stop updating once the connection is closed
+    fn disconnected(&mut self) -> Result<bool, Error> {
+        self.attached = false;
+        Err(Error::ConnectionError(format!(
+            "Connection closed while syncing buffer {}",
+            self.buffer.id
+        )))
+    }
+
+    /// Apply `event` if it is for this buffer, returning whether the rope
+    /// changed
+    fn apply(&mut self, event: Notification) -> Result<bool, Error> {
+        let params = &event.params;
+        let buffer = params.first().cloned().unwrap_or(Value::Nil);
+        if from_handle(buffer, BUFFER_EXT, "a buffer")? != self.buffer.id {
+            return Ok(false);
+        }
+        let invalid = || Error::DecodingError(format!("Invalid buffer event {:?}", params));
+        match event.method.as_str() {
+            "nvim_buf_lines_event" => {
+                // [buf, changedtick, firstline, lastline, linedata, more]
+                let first = params.get(2).and_then(Value::as_i64).ok_or_else(invalid)?;
+                // -1 when the whole buffer is sent
+                let last = match params.get(3).and_then(Value::as_i64).ok_or_else(invalid)? {
+                    -1 => usize::MAX,
+                    last => last as usize,
+                };
+                let lines = params
+                    .get(4)
+                    .and_then(Value::as_array)
+                    .ok_or_else(invalid)?;
+                if first == 0 && last == usize::MAX {
+                    self.rope = Rope::new();
+                }
+                replace_lines(&mut self.rope, first as usize, last, lines);
+                // Nil when only the buffer's contents were sent
+                if let Some(changedtick) = params.get(1).ok_or_else(invalid)?.as_i64() {
+                    self.changedtick = changedtick;
+                }
+                Ok(true)
+            }
+            "nvim_buf_changedtick_event" => {
+                self.changedtick = params.get(1).and_then(Value::as_i64).ok_or_else(invalid)?;
+                Ok(false)
+            }
+            _ => {
+                self.attached = false;
+                Ok(false)
+            }
+        }
+    }
+}
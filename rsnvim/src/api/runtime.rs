@@ -0,0 +1,82 @@
+use rmpv::Value;
+use std::path::{Path, PathBuf};
+
+use crate::api::files::path_str;
+use crate::api::*;
+use crate::error::Error;
+
+/// Lua run by `Nvim::append_runtime_path` and `Nvim::prepend_runtime_path`,
+/// returning whether the path was added
+///
+/// Commas in the path are escaped, and a path already in 'runtimepath' isn't
+/// added again.
+const ADD_RUNTIME_PATH_LUA: &str = r#"
+local path, prepend = ...
+local entry = path:gsub(",", "\\,")
+local rtp = vim.o.runtimepath
+if ("," .. rtp .. ","):find("," .. entry .. ",", 1, true) then
+  return false
+end
+if rtp == "" then
+  vim.o.runtimepath = entry
+elseif prepend then
+  vim.o.runtimepath = entry .. "," .. rtp
+else
+  vim.o.runtimepath = rtp .. "," .. entry
+end
+return true
+"#;
+
+impl Nvim {
+    /// Find files matching `pattern` in the runtime directories, e.g.
+    /// `queries/rust/highlights.scm` or `colors/*.vim`
+    ///
+    /// A typed form of `get_runtime_file`. Only the first match is returned
+    /// unless `all` is set.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::api::Nvim;
+    ///
+    /// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+    /// nvim.start_event_loop(None, None);
+    ///
+    /// nvim.prepend_runtime_path("/opt/my-plugin").unwrap();
+    /// for scheme in nvim.runtime_files("colors/*.lua", true).unwrap() {
+    ///     println!("{}", scheme.display());
+    /// }
+    /// ```
+    pub fn runtime_files(&mut self, pattern: &str, all: bool) -> Result<Vec<PathBuf>, Error> {
+        let files = self.get_runtime_file(pattern, all)?;
+        Ok(files.into_iter().map(PathBuf::from).collect())
+    }
+
+    /// Get the directories searched for runtime files
+    ///
+    /// A typed form of `list_runtime_paths`, which unlike 'runtimepath'
+    /// includes the `after` directories of packages.
+    pub fn runtime_paths(&mut self) -> Result<Vec<PathBuf>, Error> {
+        let paths = self.list_runtime_paths()?;
+        Ok(paths.into_iter().map(PathBuf::from).collect())
+    }
+
+    /// Add `path` to the end of 'runtimepath', returning `false` if it is
+    /// already there
+    pub fn append_runtime_path(&mut self, path: impl AsRef<Path>) -> Result<bool, Error> {
+        self.add_runtime_path(path.as_ref(), false)
+    }
+
+    /// Add `path` to the start of 'runtimepath', so its files are found
+    /// first, returning `false` if it is already there
+    pub fn prepend_runtime_path(&mut self, path: impl AsRef<Path>) -> Result<bool, Error> {
+        self.add_runtime_path(path.as_ref(), true)
+    }
+
+    fn add_runtime_path(&mut self, path: &Path, prepend: bool) -> Result<bool, Error> {
+        self.exec_lua_typed(
+            ADD_RUNTIME_PATH_LUA,
+            vec![Value::from(path_str(path)?), Value::from(prepend)],
+        )
+    }
+}
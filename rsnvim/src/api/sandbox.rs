@@ -0,0 +1,191 @@
+use rmpv::Value;
+use std::time::Duration;
+
+use crate::api::convert::FromValue;
+use crate::api::*;
+use crate::error::Error;
+
+/// Lua run by `Nvim::eval_sandboxed`, returning [status, value or message]
+///
+/// The expression only sees the allowed globals and variables. Reading any
+/// other global, assigning a global or running past the deadline raises a
+/// tagged error which is reported as a violation. Hooks don't run inside
+/// JIT-compiled code, so the JIT is disabled for the expression.
+const SANDBOX_LUA: &str = r#"
+local expr, globals, vars, timeout_ms = ...
+local function violation(message)
+    error({ rsnvim_sandbox = 'violation', message = message }, 0)
+end
+
+local env = {}
+for _, name in ipairs(globals) do
+    local value = _G[name]
+    if type(value) == 'table' then
+        -- Copy tables so the expression can't modify the real modules
+        value = vim.deepcopy(value)
+    end
+    env[name] = value
+end
+for name, value in pairs(vars) do
+    env[name] = value
+end
+setmetatable(env, {
+    __index = function(_, name)
+        violation("access to global '" .. tostring(name) .. "' is not allowed")
+    end,
+    __newindex = function(_, name)
+        violation("assignment to global '" .. tostring(name) .. "' is not allowed")
+    end,
+})
+
+local chunk, err = loadstring('return ' .. expr, '=expression')
+if not chunk then
+    return { 'syntax', err }
+end
+setfenv(chunk, env)
+if jit then
+    jit.off(chunk, true)
+end
+
+local hrtime = (vim.uv or vim.loop).hrtime
+local deadline = hrtime() + timeout_ms * 1000000
+local hook, mask, count = debug.gethook()
+debug.sethook(function()
+    if hrtime() > deadline then
+        error({ rsnvim_sandbox = 'timeout', message = 'expression timed out' }, 0)
+    end
+end, '', 1000)
+local ok, result = pcall(chunk)
+debug.sethook(hook, mask, count)
+
+if ok then
+    return { 'ok', result }
+elseif type(result) == 'table' and result.rsnvim_sandbox then
+    return { result.rsnvim_sandbox, result.message }
+end
+return { 'error', tostring(result) }
+"#;
+
+/// The globals an expression may use by default
+const DEFAULT_GLOBALS: &[&str] = &[
+    "ipairs", "math", "next", "pairs", "select", "string", "table", "tonumber", "tostring", "type",
+    "unpack",
+];
+
+/// Options for `Nvim::eval_sandboxed`
+///
+/// By default an expression may only use pure Lua globals like `math`,
+/// `string`, `table` and `tostring`, and is stopped after 100 milliseconds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SandboxOpts {
+    globals: Vec<String>,
+    vars: Vec<(Value, Value)>,
+    timeout: Duration,
+}
+
+impl Default for SandboxOpts {
+    fn default() -> Self {
+        SandboxOpts {
+            globals: DEFAULT_GLOBALS
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            vars: Vec::new(),
+            timeout: Duration::from_millis(100),
+        }
+    }
+}
+
+impl SandboxOpts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow the expression to use the global `name`, e.g. `vim`
+    ///
+    /// Tables are copied into the sandbox, but functions run with full
+    /// access to Neovim, so only allow globals which are safe to call with
+    /// untrusted arguments.
+    pub fn allow(mut self, name: impl Into<String>) -> Self {
+        self.globals.push(name.into());
+        self
+    }
+
+    /// Make `value` available to the expression as the variable `name`
+    pub fn var(mut self, name: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.vars.push((Value::from(name.into()), value.into()));
+        self
+    }
+
+    /// Stop the expression once it has run for `timeout`
+    ///
+    /// Time spent inside a single builtin function, like `string.rep`, isn't
+    /// interrupted.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl Nvim {
+    /// Evaluate the untrusted Lua expression `expr` in a restricted
+    /// environment, decoding its result as a `T`
+    ///
+    /// Returns `Error::SandboxViolation` if the expression uses a global
+    /// which isn't allowed or runs past the timeout, `Error::InvalidArgument`
+    /// if it isn't a valid expression, and `Error::RpcError` if it raises an
+    /// error.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::api::{Nvim, SandboxOpts};
+    /// use rsnvim::error::Error;
+    ///
+    /// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+    /// nvim.start_event_loop(None, None);
+    ///
+    /// let opts = SandboxOpts::new().var("width", 80);
+    /// let half: i64 = nvim.eval_sandboxed("math.floor(width / 2)", &opts).unwrap();
+    /// assert!(matches!(
+    ///     nvim.eval_sandboxed::<i64>("os.exit(1)", &opts),
+    ///     Err(Error::SandboxViolation(_))
+    /// ));
+    /// ```
+    pub fn eval_sandboxed<T: FromValue>(
+        &mut self,
+        expr: &str,
+        opts: &SandboxOpts,
+    ) -> Result<T, Error> {
+        let globals: Vec<Value> = opts
+            .globals
+            .iter()
+            .map(|g| Value::from(g.as_str()))
+            .collect();
+        let args = vec![
+            Value::from(expr),
+            Value::from(globals),
+            Value::Map(opts.vars.clone()),
+            Value::from(opts.timeout.as_millis() as u64),
+        ];
+        let ret = self.exec_lua(SANDBOX_LUA, args)?;
+
+        let mut ret = Vec::<Value>::from_value(ret)?.into_iter();
+        let status = String::from_value(ret.next().unwrap_or(Value::Nil))?;
+        let value = ret.next().unwrap_or(Value::Nil);
+        match status.as_str() {
+            "ok" => T::from_value(value),
+            "violation" | "timeout" => Err(Error::SandboxViolation(message(value))),
+            "syntax" => Err(Error::InvalidArgument(message(value))),
+            _ => Err(Error::RpcError(message(value))),
+        }
+    }
+}
+
+/// Get the error message returned by the sandbox
+fn message(value: Value) -> String {
+    match value.as_str() {
+        Some(message) => message.to_string(),
+        None => value.to_string(),
+    }
+}
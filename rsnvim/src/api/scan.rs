@@ -0,0 +1,145 @@
+use rmpv::Value;
+use std::sync::{Arc, Mutex};
+
+use crate::api::convert::{AsValue, FromValue};
+use crate::api::*;
+use crate::client::ResponseHandle;
+use crate::error::Error;
+use crate::session::{Session, CALL_TIMEOUT};
+
+/// An iterator over chunks of a buffer's lines, created by
+/// `Buffer::scan_lines` and `Buffer::get_lines_chunked`
+///
+/// The request for the next chunk is sent before the current one is
+/// returned, so fetching overlaps with processing and each chunk after the
+/// first is usually ready as soon as it is needed.
+pub struct BufferScan {
+    session: Arc<Mutex<Session>>,
+    data: Value,
+    chunk_size: i64,
+    /// The (exclusive) line the scan ends at
+    end: i64,
+    next_start: i64,
+    pending: Option<Result<ResponseHandle, Error>>,
+}
+
+impl BufferScan {
+    /// Request the next chunk, if there are lines left
+    fn prefetch(&mut self) -> Option<Result<ResponseHandle, Error>> {
+        if self.next_start >= self.end {
+            return None;
+        }
+        let start = self.next_start;
+        let end = (start + self.chunk_size).min(self.end);
+        self.next_start = end;
+
+        let session = self.session.lock().unwrap().clone();
+        Some(session.call_async(
+            "nvim_buf_get_lines",
+            vec![
+                self.data.clone(),
+                Value::from(start),
+                Value::from(end),
+                Value::from(false),
+            ],
+        ))
+    }
+}
+
+impl Iterator for BufferScan {
+    type Item = Result<Vec<String>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = self.pending.take()?;
+        self.pending = self.prefetch();
+
+        let lines = current.and_then(|handle| handle.wait_timeout(CALL_TIMEOUT));
+        Some(lines.and_then(Vec::from_value))
+    }
+}
+
+impl Buffer {
+    /// Iterate over the buffer's lines in chunks of up to `chunk_size` lines
+    ///
+    /// Each chunk is requested while the previous one is being processed,
+    /// roughly halving the time taken to scan large buffers over a slow
+    /// connection. The number of lines is fixed when the scan starts, so
+    /// lines added during the scan aren't returned.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::api::Nvim;
+    ///
+    /// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+    /// nvim.start_event_loop(None, None);
+    ///
+    /// let mut todos = 0;
+    /// for buffer in nvim.list_bufs().unwrap().iter_mut() {
+    ///     for chunk in buffer.scan_lines(1000).unwrap() {
+    ///         todos += chunk.unwrap().iter().filter(|l| l.contains("TODO")).count();
+    ///     }
+    /// }
+    /// ```
+    pub fn scan_lines(&mut self, chunk_size: usize) -> Result<BufferScan, Error> {
+        let line_count = self.line_count()?;
+        Ok(self.scan(0, line_count, chunk_size))
+    }
+
+    /// Iterate over lines `start` to `end` in chunks of up to `chunk_size`
+    /// lines
+    ///
+    /// Indexing is zero-based and end-exclusive, with negative indices
+    /// counting from the end as for `get_lines`, so `get_lines_chunked(0,
+    /// -1, 1000)` reads the whole buffer. Each chunk is requested as it is
+    /// needed, one ahead of the chunk being processed, so memory use stays
+    /// bounded however large the buffer is. An error getting the number of
+    /// lines is returned by the first call to `next`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::api::Nvim;
+    ///
+    /// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+    /// nvim.start_event_loop(None, None);
+    ///
+    /// let mut buffer = nvim.get_current_buf().unwrap();
+    /// let mut longest = 0;
+    /// for chunk in buffer.get_lines_chunked(0, -1, 10_000) {
+    ///     for line in chunk.unwrap() {
+    ///         longest = longest.max(line.len());
+    ///     }
+    /// }
+    /// ```
+    pub fn get_lines_chunked(&mut self, start: i64, end: i64, chunk_size: usize) -> BufferScan {
+        let line_count = match self.line_count() {
+            Ok(line_count) => line_count,
+            Err(error) => {
+                let mut scan = self.scan(0, 0, chunk_size);
+                scan.pending = Some(Err(error));
+                return scan;
+            }
+        };
+        // Negative indices count from one past the last line
+        let resolve = |index: i64| match index {
+            index if index < 0 => (line_count + 1 + index).max(0),
+            index => index.min(line_count),
+        };
+        self.scan(resolve(start), resolve(end), chunk_size)
+    }
+
+    /// Start scanning lines `start` to `end`
+    fn scan(&self, start: i64, end: i64, chunk_size: usize) -> BufferScan {
+        let mut scan = BufferScan {
+            session: self.session.clone(),
+            data: self.convert(),
+            chunk_size: chunk_size.max(1) as i64,
+            end,
+            next_start: start,
+            pending: None,
+        };
+        scan.pending = scan.prefetch();
+        scan
+    }
+}
@@ -0,0 +1,89 @@
+use rmpv::Value;
+
+use crate::api::*;
+use crate::error::Error;
+
+impl Nvim {
+    /// Set `options` while running `f`, then restore their previous values
+    ///
+    /// Options are set as by `:set`, and restored even if `f` returns an
+    /// error. Each of the set and restore phases is a single atomic batch.
+    /// If an option can't be set, those already set are restored and `f`
+    /// isn't run.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::api::Nvim;
+    ///
+    /// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+    /// nvim.start_event_loop(None, None);
+    ///
+    /// nvim.with_options(
+    ///     &[("hidden", true.into()), ("eventignore", "all".into())],
+    ///     |nvim| {
+    ///         for path in ["a.txt", "b.txt"] {
+    ///             nvim.command(format!("edit {}", path))?;
+    ///             nvim.command("%s/foo/bar/ge | write")?;
+    ///         }
+    ///         Ok(())
+    ///     },
+    /// )
+    /// .unwrap();
+    /// ```
+    pub fn with_options<T, F>(&mut self, options: &[(&str, Value)], f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut Nvim) -> Result<T, Error>,
+    {
+        // Get every previous value before setting any option
+        let mut batch = CallBatch::new();
+        let previous: Vec<BatchSlot<Value>> = options
+            .iter()
+            .map(|(name, _)| {
+                batch.call(
+                    "nvim_get_option_value",
+                    vec![Value::from(*name), Value::Map(Vec::new())],
+                )
+            })
+            .collect();
+        for (name, value) in options {
+            batch.call::<()>(
+                "nvim_set_option_value",
+                vec![Value::from(*name), value.clone(), Value::Map(Vec::new())],
+            );
+        }
+        let results = self.call_batch(batch)?;
+        let previous: Vec<(&str, Value)> = options
+            .iter()
+            .zip(&previous)
+            .map(|((name, _), slot)| Ok((*name, results.get(slot)?)))
+            .collect::<Result<_, Error>>()?;
+
+        if let Some(error) = results.error() {
+            // Restore the options set before the failing one
+            let set = error.index - options.len();
+            self.set_options(&previous[..set])?;
+            return Err(Error::RpcError(error.message.clone()));
+        }
+
+        let result = f(self);
+        let restored = self.set_options(&previous);
+        let value = result?;
+        restored.map(|_| value)
+    }
+
+    /// Set each of `options` in a single atomic batch
+    fn set_options(&mut self, options: &[(&str, Value)]) -> Result<(), Error> {
+        let mut batch = CallBatch::new();
+        for (name, value) in options {
+            batch.call::<()>(
+                "nvim_set_option_value",
+                vec![Value::from(*name), value.clone(), Value::Map(Vec::new())],
+            );
+        }
+        match self.call_batch(batch)?.error() {
+            Some(error) => Err(Error::RpcError(error.message.clone())),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use rmpv::Value;
+
+use crate::api::convert::{AsValue, FromValue};
+use crate::api::*;
+use crate::error::Error;
+
+/// Lua run by `Buffer::search`, returning matches as [row, col, end_col, text]
+///
+/// `matchstrpos` is given a count so that earlier matches are skipped
+/// without `^` matching at the start offset.
+const SEARCH_LUA: &str = r#"
+local buf, pattern, first, last, max = ...
+if last < 0 then
+    last = vim.api.nvim_buf_line_count(buf)
+end
+local matches = {}
+local lines = vim.api.nvim_buf_get_lines(buf, first, last, false)
+for i, line in ipairs(lines) do
+    local start = 0
+    while start <= #line do
+        local m = vim.fn.matchstrpos(line, pattern, start, 1)
+        if m[2] < 0 then
+            break
+        end
+        table.insert(matches, { first + i - 1, m[2], m[3], m[1] })
+        if max > 0 and #matches >= max then
+            return matches
+        end
+        start = math.max(m[3], m[2] + 1)
+    end
+end
+return matches
+"#;
+
+/// Options for `Buffer::search`
+///
+/// By default the whole buffer is searched using the `ignorecase` option,
+/// and every match is returned.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchOpts {
+    start: i64,
+    end: i64,
+    max_matches: usize,
+    ignore_case: Option<bool>,
+    literal: bool,
+}
+
+impl Default for SearchOpts {
+    fn default() -> Self {
+        SearchOpts {
+            start: 0,
+            end: -1,
+            max_matches: 0,
+            ignore_case: None,
+            literal: false,
+        }
+    }
+}
+
+impl SearchOpts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only search the zero-indexed rows `start` to `end` (exclusive)
+    ///
+    /// An `end` of -1 searches to the end of the buffer.
+    pub fn range(mut self, start: i64, end: i64) -> Self {
+        self.start = start;
+        self.end = end;
+        self
+    }
+
+    /// Stop after `max_matches` matches, or 0 for no limit
+    pub fn max_matches(mut self, max_matches: usize) -> Self {
+        self.max_matches = max_matches;
+        self
+    }
+
+    /// Ignore or match case, overriding `ignorecase`
+    pub fn ignore_case(mut self, ignore_case: bool) -> Self {
+        self.ignore_case = Some(ignore_case);
+        self
+    }
+
+    /// Match the pattern as literal text rather than a Vim regex
+    pub fn literal(mut self) -> Self {
+        self.literal = true;
+        self
+    }
+
+    /// Add the flags for these options to `pattern`
+    fn pattern(&self, pattern: &str) -> String {
+        let mut flags = String::new();
+        match self.ignore_case {
+            Some(true) => flags.push_str("\\c"),
+            Some(false) => flags.push_str("\\C"),
+            None => {}
+        }
+        if self.literal {
+            // Only backslashes are special in very nomagic mode
+            flags.push_str("\\V");
+            return flags + &pattern.replace('\\', "\\\\");
+        }
+        flags + pattern
+    }
+}
+
+/// A match found by `Buffer::search`
+///
+/// Rows and columns are zero-indexed, with an exclusive end column in bytes.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct MatchRange {
+    pub row: i64,
+    pub col: i64,
+    pub end_col: i64,
+    /// The matched text
+    pub text: String,
+}
+
+impl FromValue for MatchRange {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        match value {
+            Value::Array(v) if v.len() == 4 => {
+                let mut v = v.into_iter();
+                Ok(MatchRange {
+                    row: i64::from_value(v.next().unwrap())?,
+                    col: i64::from_value(v.next().unwrap())?,
+                    end_col: i64::from_value(v.next().unwrap())?,
+                    text: String::from_value(v.next().unwrap())?,
+                })
+            }
+            other => Err(Error::DecodingError(format!("Invalid match {}", other))),
+        }
+    }
+}
+
+impl Buffer {
+    /// Find the matches of the Vim regex `pattern` in the buffer
+    ///
+    /// The search runs inside Neovim, so only the matches are sent back
+    /// rather than every line. Matches never span lines.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::api::{Nvim, SearchOpts};
+    ///
+    /// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+    /// nvim.start_event_loop(None, None);
+    ///
+    /// let mut buffer = nvim.get_current_buf().unwrap();
+    /// let opts = SearchOpts::new().ignore_case(true).max_matches(100);
+    /// for m in buffer.search("todo", &opts).unwrap() {
+    ///     println!("{}:{} {}", m.row + 1, m.col, m.text);
+    /// }
+    /// ```
+    pub fn search(&mut self, pattern: &str, opts: &SearchOpts) -> Result<Vec<MatchRange>, Error> {
+        let args = vec![
+            self.convert(),
+            Value::from(opts.pattern(pattern)),
+            Value::from(opts.start),
+            Value::from(opts.end),
+            Value::from(opts.max_matches as u64),
+        ];
+        let session = self.session.lock().unwrap().clone();
+        let matches = session.call(
+            "nvim_exec_lua",
+            vec![Value::from(SEARCH_LUA), Value::from(args)],
+        )?;
+        Vec::from_value(matches)
+    }
+}
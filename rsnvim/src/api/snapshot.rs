@@ -0,0 +1,277 @@
+use rmpv::Value;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::api::*;
+use crate::error::Error;
+
+/// Environment variable which causes `Snapshot::assert_matches` to overwrite
+/// golden files rather than compare against them
+pub const UPDATE_SNAPSHOTS_VAR: &str = "RSNVIM_UPDATE_SNAPSHOTS";
+
+/// The editor state captured by `Nvim::snapshot`
+///
+/// By default the current buffer and window are used, and nothing is
+/// captured until enabled with the builder methods.
+///
+/// # Example
+///
+/// ```no_run
+/// use rsnvim::api::{Nvim, SnapshotSpec};
+///
+/// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+/// nvim.start_event_loop(None, None);
+///
+/// let spec = SnapshotSpec::new()
+///     .lines()
+///     .cursor()
+///     .option("filetype")
+///     .highlights(0, 10);
+/// nvim.snapshot(&spec)
+///     .unwrap()
+///     .assert_matches("tests/golden/format.snap");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SnapshotSpec {
+    buffer: Option<Value>,
+    window: Option<Value>,
+    lines: bool,
+    cursor: bool,
+    options: Vec<String>,
+    highlights: Option<(i64, i64)>,
+}
+
+impl SnapshotSpec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Capture `buffer` instead of the current buffer
+    pub fn buffer(mut self, buffer: &Buffer) -> Self {
+        self.buffer = Some(buffer.convert());
+        self
+    }
+
+    /// Capture the cursor of `window` instead of the current window
+    pub fn window(mut self, window: &Window) -> Self {
+        self.window = Some(window.convert());
+        self
+    }
+
+    /// Capture the contents of the buffer
+    pub fn lines(mut self) -> Self {
+        self.lines = true;
+        self
+    }
+
+    /// Capture the cursor position of the window
+    pub fn cursor(mut self) -> Self {
+        self.cursor = true;
+        self
+    }
+
+    /// Capture the value of the option `name`
+    ///
+    /// Options are read in the context of the current buffer and window.
+    pub fn option(mut self, name: impl Into<String>) -> Self {
+        self.options.push(name.into());
+        self
+    }
+
+    /// Capture the highlights from all namespaces between the zero-indexed
+    /// rows `start` and `end` (inclusive) of the buffer
+    pub fn highlights(mut self, start: i64, end: i64) -> Self {
+        self.highlights = Some((start, end));
+        self
+    }
+}
+
+/// A highlighted region of a buffer
+///
+/// Rows and columns are zero-indexed, with an exclusive end column.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SnapshotHighlight {
+    pub row: i64,
+    pub col: i64,
+    pub end_row: i64,
+    pub end_col: i64,
+    pub group: String,
+}
+
+/// A capture of editor state, created by `Nvim::snapshot`
+///
+/// The `Display` implementation gives a stable, line-based representation
+/// suitable for golden files.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot {
+    /// The contents of the buffer
+    pub lines: Option<Vec<String>>,
+    /// The (1,0)-indexed cursor position
+    pub cursor: Option<Point>,
+    /// The name and value of each option, in the order requested
+    pub options: Vec<(String, String)>,
+    /// The highlights in the requested range, sorted by position
+    pub highlights: Option<Vec<SnapshotHighlight>>,
+}
+
+impl Snapshot {
+    /// Compare the snapshot against the golden file at `path`
+    ///
+    /// If the file doesn't exist, or `RSNVIM_UPDATE_SNAPSHOTS` is set, it is
+    /// written with the snapshot instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the snapshot differs from the golden file, or the file
+    /// couldn't be read or written.
+    pub fn assert_matches(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+        let actual = self.to_string();
+
+        if std::env::var_os(UPDATE_SNAPSHOTS_VAR).is_some() || !path.exists() {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).unwrap();
+            }
+            fs::write(path, actual)
+                .unwrap_or_else(|err| panic!("Couldn't write {}: {}", path.display(), err));
+            return;
+        }
+
+        let expected = fs::read_to_string(path)
+            .unwrap_or_else(|err| panic!("Couldn't read {}: {}", path.display(), err));
+        if expected != actual {
+            panic!(
+                "Snapshot doesn't match {} (set {} to update)\n--- expected\n{}--- actual\n{}",
+                path.display(),
+                UPDATE_SNAPSHOTS_VAR,
+                expected,
+                actual
+            );
+        }
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(lines) = &self.lines {
+            writeln!(f, "lines:")?;
+            for (i, line) in lines.iter().enumerate() {
+                writeln!(f, "{:>4} | {}", i + 1, line)?;
+            }
+        }
+        if let Some(Point { row, col }) = self.cursor {
+            writeln!(f, "cursor: {}:{}", row, col)?;
+        }
+        if !self.options.is_empty() {
+            writeln!(f, "options:")?;
+            for (name, value) in &self.options {
+                writeln!(f, "  {} = {}", name, value)?;
+            }
+        }
+        if let Some(highlights) = &self.highlights {
+            writeln!(f, "highlights:")?;
+            for hl in highlights {
+                writeln!(
+                    f,
+                    "  {}:{}-{}:{} {}",
+                    hl.row, hl.col, hl.end_row, hl.end_col, hl.group
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Decode an extmark of the form [id, row, col, details], if it highlights
+fn to_highlight(extmark: &Value) -> Option<SnapshotHighlight> {
+    let row = extmark[1].as_i64()?;
+    let col = extmark[2].as_i64()?;
+    let details = &extmark[3];
+    let group = match &details["hl_group"] {
+        Value::String(group) => group.as_str()?.to_string(),
+        _ => return None,
+    };
+    Some(SnapshotHighlight {
+        row,
+        col,
+        end_row: details["end_row"].as_i64().unwrap_or(row),
+        end_col: details["end_col"].as_i64().unwrap_or(col),
+        group,
+    })
+}
+
+impl Nvim {
+    /// Capture the editor state selected by `spec`
+    ///
+    /// All of the state is read in a single atomic batch, so it is consistent
+    /// even if Neovim is being modified concurrently.
+    pub fn snapshot(&mut self, spec: &SnapshotSpec) -> Result<Snapshot, Error> {
+        // Handle 0 refers to the current buffer/window
+        let buffer = spec.buffer.clone().unwrap_or(Value::from(0));
+        let window = spec.window.clone().unwrap_or(Value::from(0));
+
+        let mut batch = CallBatch::new();
+        let lines = spec.lines.then(|| {
+            batch.call::<Vec<String>>(
+                "nvim_buf_get_lines",
+                vec![
+                    buffer.clone(),
+                    Value::from(0),
+                    Value::from(-1),
+                    Value::from(true),
+                ],
+            )
+        });
+        let cursor = spec
+            .cursor
+            .then(|| batch.call::<Point>("nvim_win_get_cursor", vec![window.clone()]));
+        let options: Vec<_> = spec
+            .options
+            .iter()
+            .map(|name| {
+                batch.call::<Value>(
+                    "nvim_get_option_value",
+                    vec![Value::from(name.as_str()), Value::Map(Vec::new())],
+                )
+            })
+            .collect();
+        let highlights = spec.highlights.map(|(start, end)| {
+            batch.call::<Vec<Value>>(
+                "nvim_buf_get_extmarks",
+                vec![
+                    buffer.clone(),
+                    Value::from(-1),
+                    Value::from(vec![Value::from(start), Value::from(0)]),
+                    Value::from(vec![Value::from(end), Value::from(-1)]),
+                    Value::Map(vec![(Value::from("details"), Value::from(true))]),
+                ],
+            )
+        });
+
+        let results = self.call_batch(batch)?;
+        let mut snapshot = Snapshot {
+            lines: lines.map(|slot| results.get(&slot)).transpose()?,
+            cursor: cursor.map(|slot| results.get(&slot)).transpose()?,
+            ..Default::default()
+        };
+        for (name, slot) in spec.options.iter().zip(options) {
+            snapshot
+                .options
+                .push((name.clone(), results.get(&slot)?.to_string()));
+        }
+        if let Some(slot) = highlights {
+            let mut hls: Vec<_> = results
+                .get(&slot)?
+                .iter()
+                .filter_map(to_highlight)
+                .collect();
+            hls.sort();
+            snapshot.highlights = Some(hls);
+        }
+
+        Ok(snapshot)
+    }
+}
@@ -7,62 +7,45 @@ use crate::value_vec;
 /// The below implementations are autogenerated using the Neovim API
 impl Tabpage {
     /// Since: 1
-    pub fn list_wins(&mut self) -> Result<Vec<Window>, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_tabpage_list_wins", value_vec!(self.data))?;
-        Ok(ret
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| Window {
-                data: x.clone(),
-                session: self.session.clone(),
-            })
-            .collect())
+    pub fn list_wins(&self) -> Result<Vec<Window>, Error> {
+        let ret = self.call_raw("nvim_tabpage_list_wins", value_vec!(*self))?;
+        Vec::<Value>::from_value(ret)?
+            .into_iter()
+            .map(|x| self.handle::<Window>(x))
+            .collect::<Result<_, Error>>()
     }
 
     /// Since: 1
-    pub fn get_var(&mut self, name: String) -> Result<Value, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_tabpage_get_var", value_vec!(self.data, name))?;
-        Ok(ret.to_owned())
+    pub fn get_var(&self, name: impl Into<String>) -> Result<Value, Error> {
+        let name: String = name.into();
+        self.call_typed("nvim_tabpage_get_var", value_vec!(*self, name))
     }
 
     /// Since: 1
-    pub fn set_var(&mut self, name: String, value: Value) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_tabpage_set_var", value_vec!(self.data, name, value))?;
-        Ok(())
+    pub fn set_var(&self, name: impl Into<String>, value: Value) -> Result<(), Error> {
+        let name: String = name.into();
+        self.call_typed("nvim_tabpage_set_var", value_vec!(*self, name, value))
     }
 
     /// Since: 1
-    pub fn del_var(&mut self, name: String) -> Result<(), Error> {
-        let mut session = self.session.lock().unwrap();
-        session.call("nvim_tabpage_del_var", value_vec!(self.data, name))?;
-        Ok(())
+    pub fn del_var(&self, name: impl Into<String>) -> Result<(), Error> {
+        let name: String = name.into();
+        self.call_typed("nvim_tabpage_del_var", value_vec!(*self, name))
     }
 
     /// Since: 1
-    pub fn get_win(&mut self) -> Result<Window, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_tabpage_get_win", value_vec!(self.data))?;
-        Ok(Window {
-            data: ret.clone(),
-            session: self.session.clone(),
-        })
+    pub fn get_win(&self) -> Result<Window, Error> {
+        let ret = self.call_raw("nvim_tabpage_get_win", value_vec!(*self))?;
+        self.handle::<Window>(ret)
     }
 
     /// Since: 1
-    pub fn get_number(&mut self) -> Result<i64, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_tabpage_get_number", value_vec!(self.data))?;
-        Ok(ret.as_i64().unwrap())
+    pub fn get_number(&self) -> Result<i64, Error> {
+        self.call_typed("nvim_tabpage_get_number", value_vec!(*self))
     }
 
     /// Since: 1
-    pub fn is_valid(&mut self) -> Result<bool, Error> {
-        let mut session = self.session.lock().unwrap();
-        let ret = session.call("nvim_tabpage_is_valid", value_vec!(self.data))?;
-        Ok(ret.as_bool().unwrap())
+    pub fn is_valid(&self) -> Result<bool, Error> {
+        self.call_typed("nvim_tabpage_is_valid", value_vec!(*self))
     }
 }
@@ -0,0 +1,214 @@
+use rmpv::Value;
+
+use crate::api::*;
+use crate::error::Error;
+
+/// A position in a buffer
+///
+/// Rows and columns are zero-indexed, with columns in bytes. Positions are
+/// ordered by row, then column.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    pub row: i64,
+    pub col: i64,
+}
+
+impl Position {
+    pub fn new(row: i64, col: i64) -> Self {
+        Position { row, col }
+    }
+}
+
+impl From<(i64, i64)> for Position {
+    fn from((row, col): (i64, i64)) -> Self {
+        Position { row, col }
+    }
+}
+
+/// Where the rows and columns of a `Point` start counting from
+///
+/// Neovim isn't consistent, e.g. the cursor and marks have (1,0)-indexed
+/// positions while extmarks and highlights are (0,0)-indexed. The generated
+/// API documents which each function uses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Indexing {
+    /// Rows and columns both start from `0`
+    ZeroZero,
+    /// Rows start from `1` and columns from `0`
+    OneZero,
+}
+
+impl Indexing {
+    /// The first row
+    fn first_row(self) -> i64 {
+        match self {
+            Indexing::ZeroZero => 0,
+            Indexing::OneZero => 1,
+        }
+    }
+}
+
+/// A row and column as sent to and from Neovim, e.g. by `get_cursor`
+///
+/// Unlike `Position`, the indexing depends on where the point came from, so
+/// convert with `to_position` to compare points from different sources.
+/// Points are ordered by row, then column.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Point {
+    pub row: i64,
+    pub col: i64,
+}
+
+impl Point {
+    pub fn new(row: i64, col: i64) -> Self {
+        Point { row, col }
+    }
+
+    /// Convert a point with `indexing` to a zero-indexed `Position`
+    pub fn to_position(self, indexing: Indexing) -> Position {
+        Position::new(self.row - indexing.first_row(), self.col)
+    }
+
+    /// Convert `position` to a point with `indexing`
+    pub fn from_position(position: Position, indexing: Indexing) -> Self {
+        Point::new(position.row + indexing.first_row(), position.col)
+    }
+}
+
+impl From<(i64, i64)> for Point {
+    fn from((row, col): (i64, i64)) -> Self {
+        Point { row, col }
+    }
+}
+
+impl From<Point> for (i64, i64) {
+    fn from(point: Point) -> Self {
+        (point.row, point.col)
+    }
+}
+
+impl FromValue for Point {
+    fn from_value(value: Value) -> Result<Self, Error> {
+        <(i64, i64)>::from_value(value).map(Point::from)
+    }
+}
+
+impl AsValue for Point {
+    fn convert(&self) -> Value {
+        Value::Array(vec![Value::from(self.row), Value::from(self.col)])
+    }
+}
+
+/// A range of text in a buffer, from `start` up to but excluding `end`
+///
+/// # Example
+///
+/// ```no_run
+/// use rsnvim::api::{Nvim, Position, Range};
+///
+/// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+/// nvim.start_event_loop(None, None);
+///
+/// let mut buffer = nvim.get_current_buf().unwrap();
+/// // Rename the identifier at the start of the second line
+/// let range = Range::new((1, 0), (1, 3));
+/// if buffer.get_range(&range).unwrap() == ["foo"] {
+///     buffer.set_range(&range, ["bar"]).unwrap();
+/// }
+/// buffer.insert_text(Position::new(0, 0), ["// renamed foo", ""]).unwrap();
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Range {
+    pub fn new(start: impl Into<Position>, end: impl Into<Position>) -> Self {
+        Range {
+            start: start.into(),
+            end: end.into(),
+        }
+    }
+
+    /// An empty range at `position`, e.g. to insert text
+    pub fn point(position: impl Into<Position>) -> Self {
+        let position = position.into();
+        Range {
+            start: position,
+            end: position,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Whether `position` is within the range
+    pub fn contains(&self, position: Position) -> bool {
+        self.start <= position && position < self.end
+    }
+
+    /// Check the range is valid to send to Neovim
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        let Range { start, end } = self;
+        if start.row < 0 || start.col < 0 || end.row < 0 || end.col < 0 {
+            return Err(Error::InvalidArgument(format!(
+                "Range {:?} has a negative position",
+                self
+            )));
+        }
+        if start > end {
+            return Err(Error::InvalidArgument(format!(
+                "Range {:?} ends before it starts",
+                self
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl From<&MatchRange> for Range {
+    fn from(range: &MatchRange) -> Self {
+        Range::new((range.row, range.col), (range.row, range.end_col))
+    }
+}
+
+impl Buffer {
+    /// Get the text in `range`, with a string for each line it spans
+    ///
+    /// A typed form of `get_text`. Returns `Error::InvalidArgument` if the
+    /// range ends before it starts or has a negative position.
+    pub fn get_range(&mut self, range: &Range) -> Result<Vec<String>, Error> {
+        range.validate()?;
+        let Range { start, end } = range;
+        self.get_text(start.row, start.col, end.row, end.col, Vec::new())
+    }
+
+    /// Replace the text in `range` with `lines`
+    ///
+    /// A typed form of `set_text`, validating `range` like `get_range`.
+    /// Replacing with several lines splits the line at the end of the range.
+    pub fn set_range(
+        &mut self,
+        range: &Range,
+        lines: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<(), Error> {
+        range.validate()?;
+        let Range { start, end } = range;
+        self.set_text(start.row, start.col, end.row, end.col, lines)
+    }
+
+    /// Insert `lines` at `position`
+    ///
+    /// The first line is joined to the text before `position` and the last
+    /// to the text after, so `["a", ""]` inserts `a` and a line break.
+    pub fn insert_text(
+        &mut self,
+        position: Position,
+        lines: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<(), Error> {
+        self.set_range(&Range::point(position), lines)
+    }
+}
@@ -0,0 +1,132 @@
+use rmpv::Value;
+use std::sync::{Arc, Mutex};
+
+use crate::api::*;
+use crate::error::Error;
+use crate::session::Session;
+
+/// Options which can only be enabled alongside another option
+const REQUIRES: &[(&str, &str)] = &[
+    ("ext_multigrid", "ext_linegrid"),
+    ("ext_hlstate", "ext_linegrid"),
+];
+
+/// A UI attached to Neovim using `Nvim::attach_ui`
+///
+/// Keeps track of the UI's size and options so changes can be validated
+/// before they are sent.
+///
+/// # Example
+///
+/// ```no_run
+/// use rsnvim::api::{Nvim, UiOptions};
+///
+/// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+/// nvim.start_event_loop(None, None);
+///
+/// let options = UiOptions::new().rgb(true).ext_linegrid(true);
+/// let mut ui = nvim.attach_ui(80, 24, options).unwrap();
+/// ui.set_options(UiOptions::new().ext_multigrid(true)).unwrap();
+/// ui.try_resize(120, 40).unwrap();
+/// ui.detach().unwrap();
+/// ```
+pub struct Ui {
+    session: Arc<Mutex<Session>>,
+    width: i64,
+    height: i64,
+    options: UiOptions,
+}
+
+impl Nvim {
+    /// Attach this connection as a UI with the given size and options
+    ///
+    /// Returns `Error::InvalidArgument` if the size isn't positive or an
+    /// option is enabled without the option it requires, e.g.
+    /// `ext_multigrid` without `ext_linegrid`.
+    pub fn attach_ui(&mut self, width: i64, height: i64, options: UiOptions) -> Result<Ui, Error> {
+        validate_size(width, height)?;
+        validate_options(&options)?;
+        self.ui_attach(width, height, options.clone())?;
+        Ok(Ui {
+            session: self.session.clone(),
+            width,
+            height,
+            options,
+        })
+    }
+}
+
+impl Ui {
+    pub fn width(&self) -> i64 {
+        self.width
+    }
+
+    pub fn height(&self) -> i64 {
+        self.height
+    }
+
+    /// The options the UI currently has set
+    pub fn options(&self) -> &UiOptions {
+        &self.options
+    }
+
+    /// Ask Neovim to resize the UI
+    pub fn try_resize(&mut self, width: i64, height: i64) -> Result<(), Error> {
+        validate_size(width, height)?;
+        let session = self.session.lock().unwrap().clone();
+        session.call(
+            "nvim_ui_try_resize",
+            vec![Value::from(width), Value::from(height)],
+        )?;
+        self.width = width;
+        self.height = height;
+        Ok(())
+    }
+
+    /// Change the given options, keeping the others as they are
+    ///
+    /// The resulting options are validated before any are sent. Options are
+    /// set one at a time, so if Neovim rejects one the earlier ones remain
+    /// set.
+    pub fn set_options(&mut self, options: UiOptions) -> Result<(), Error> {
+        let merged = self.options.clone().merge(&options);
+        validate_options(&merged)?;
+
+        let session = self.session.lock().unwrap().clone();
+        for (name, value) in options.iter() {
+            session.call("nvim_ui_set_option", vec![Value::from(name), value.clone()])?;
+            self.options = std::mem::take(&mut self.options).with(name, value.clone());
+        }
+        Ok(())
+    }
+
+    /// Detach the UI from Neovim
+    pub fn detach(self) -> Result<(), Error> {
+        let session = self.session.lock().unwrap().clone();
+        session.call("nvim_ui_detach", Vec::new())?;
+        Ok(())
+    }
+}
+
+fn validate_size(width: i64, height: i64) -> Result<(), Error> {
+    if width <= 0 || height <= 0 {
+        return Err(Error::InvalidArgument(format!(
+            "Invalid UI size {}x{}",
+            width, height
+        )));
+    }
+    Ok(())
+}
+
+/// Check that every enabled option's requirements are enabled too
+fn validate_options(options: &UiOptions) -> Result<(), Error> {
+    for (option, required) in REQUIRES {
+        if options.enabled(option) && !options.enabled(required) {
+            return Err(Error::InvalidArgument(format!(
+                "{} requires {}",
+                option, required
+            )));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,321 @@
+use rmpv::Value;
+use std::path::{Path, PathBuf};
+
+use crate::api::convert::AsValue;
+use crate::api::files::path_str;
+use crate::api::*;
+use crate::error::Error;
+
+/// Lua run by `WorkspaceEdit::apply`, returning a report for each file
+///
+/// Every buffer is loaded and every edit checked against its buffer before
+/// any is changed, so the edits are applied to all files or none. The
+/// edits of each file arrive sorted from the bottom of the buffer up, so
+/// applying one doesn't move the text the next replaces.
+const APPLY_LUA: &str = r#"
+local files, save = ...
+local reports, bufs, seen, failed = {}, {}, {}, false
+
+local function resolve(target)
+    if type(target) == 'number' then
+        if not vim.api.nvim_buf_is_valid(target) then
+            error('Invalid buffer ' .. target, 0)
+        end
+        vim.fn.bufload(target)
+        return target
+    end
+    if vim.fn.bufexists(target) == 0 and vim.fn.filereadable(target) == 0 then
+        error('No such file ' .. target, 0)
+    end
+    local buf = vim.fn.bufadd(target)
+    vim.fn.bufload(buf)
+    vim.bo[buf].buflisted = true
+    return buf
+end
+
+local function check(buf, edits)
+    if seen[buf] then
+        error('Buffer ' .. buf .. ' is edited more than once', 0)
+    end
+    seen[buf] = true
+    if not vim.bo[buf].modifiable then
+        error('Buffer ' .. buf .. ' is not modifiable', 0)
+    end
+    local count = vim.api.nvim_buf_line_count(buf)
+    local function check_position(row, col)
+        if row >= count then
+            error(('Row %d is past the end of the buffer'):format(row), 0)
+        end
+        local line = vim.api.nvim_buf_get_lines(buf, row, row + 1, true)[1]
+        if col > #line then
+            error(('Column %d is past the end of row %d'):format(col, row), 0)
+        end
+    end
+    for _, edit in ipairs(edits) do
+        check_position(edit[1], edit[2])
+        check_position(edit[3], edit[4])
+    end
+end
+
+for i, file in ipairs(files) do
+    local ok, buf = pcall(resolve, file[1])
+    local err
+    if ok then
+        bufs[i] = buf
+        ok, err = pcall(check, buf, file[2])
+    else
+        err = buf
+    end
+    reports[i] = { buf = bufs[i], applied = false, saved = false, error = err }
+    failed = failed or not ok
+end
+if failed then
+    return reports
+end
+
+for i, file in ipairs(files) do
+    for _, edit in ipairs(file[2]) do
+        vim.api.nvim_buf_set_text(bufs[i], edit[1], edit[2], edit[3], edit[4], edit[5])
+    end
+    reports[i].applied = true
+end
+
+if save then
+    for i, buf in ipairs(bufs) do
+        local ok, err = pcall(vim.api.nvim_buf_call, buf, function()
+            vim.cmd('silent update')
+        end)
+        reports[i].saved = ok
+        reports[i].error = not ok and err or nil
+    end
+end
+return reports
+"#;
+
+/// The buffer or file changed by a set of `TextEdit`s
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EditTarget {
+    Buffer(Buffer),
+    /// A file, loaded into a buffer if it isn't already
+    Path(PathBuf),
+}
+
+impl From<Buffer> for EditTarget {
+    fn from(buffer: Buffer) -> Self {
+        EditTarget::Buffer(buffer)
+    }
+}
+
+impl From<&Buffer> for EditTarget {
+    fn from(buffer: &Buffer) -> Self {
+        EditTarget::Buffer(buffer.clone())
+    }
+}
+
+impl From<PathBuf> for EditTarget {
+    fn from(path: PathBuf) -> Self {
+        EditTarget::Path(path)
+    }
+}
+
+impl From<&Path> for EditTarget {
+    fn from(path: &Path) -> Self {
+        EditTarget::Path(path.to_path_buf())
+    }
+}
+
+impl From<&str> for EditTarget {
+    fn from(path: &str) -> Self {
+        EditTarget::Path(PathBuf::from(path))
+    }
+}
+
+/// A replacement of the text in `range` with `new_text`
+///
+/// As for `Range`, columns are in bytes rather than the UTF-16 code units
+/// used by the Language Server Protocol.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Range,
+    pub new_text: String,
+}
+
+impl TextEdit {
+    pub fn new(range: Range, new_text: impl Into<String>) -> Self {
+        TextEdit {
+            range,
+            new_text: new_text.into(),
+        }
+    }
+
+    /// Insert `text` at `position`
+    pub fn insert(position: impl Into<Position>, text: impl Into<String>) -> Self {
+        Self::new(Range::point(position), text)
+    }
+
+    /// Delete the text in `range`
+    pub fn delete(range: Range) -> Self {
+        Self::new(range, "")
+    }
+}
+
+/// Edits to several buffers or files, applied together
+///
+/// Like a Language Server Protocol `WorkspaceEdit`, each file's edits are
+/// given as ranges in the text before any of them is applied, so they
+/// can't overlap, and edits inserting at the same position keep their
+/// order.
+///
+/// # Example
+///
+/// ```no_run
+/// use rsnvim::api::{Nvim, Range, TextEdit, WorkspaceEdit};
+///
+/// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+/// nvim.start_event_loop(None, None);
+///
+/// // Rename `parse` to `parse_config` where it is defined and used
+/// let results = WorkspaceEdit::new()
+///     .edit(
+///         "src/config.rs",
+///         vec![TextEdit::new(Range::new((4, 7), (4, 12)), "parse_config")],
+///     )
+///     .edit(
+///         "src/main.rs",
+///         vec![
+///             TextEdit::new(Range::new((2, 12), (2, 17)), "parse_config"),
+///             TextEdit::new(Range::new((9, 18), (9, 23)), "parse_config"),
+///         ],
+///     )
+///     .save(true)
+///     .apply(&nvim)
+///     .unwrap();
+/// for result in results.iter().filter(|result| !result.is_ok()) {
+///     eprintln!("{:?}: {}", result.target, result.error.as_deref().unwrap_or(""));
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WorkspaceEdit {
+    pub changes: Vec<(EditTarget, Vec<TextEdit>)>,
+    save: bool,
+}
+
+/// The outcome of a `WorkspaceEdit` for one of its targets
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileEditResult {
+    pub target: EditTarget,
+    /// The buffer edited, or `None` if the target couldn't be loaded
+    pub buffer: Option<Buffer>,
+    /// Whether the edits were applied, which they are for every target or
+    /// for none
+    pub applied: bool,
+    /// Whether the buffer was saved after its edits were applied
+    pub saved: bool,
+    /// Why the target couldn't be edited or saved
+    pub error: Option<String>,
+}
+
+impl FileEditResult {
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+impl WorkspaceEdit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `edits` to `target`
+    pub fn edit(mut self, target: impl Into<EditTarget>, edits: Vec<TextEdit>) -> Self {
+        self.changes.push((target.into(), edits));
+        self
+    }
+
+    /// Save each modified buffer once the edits are applied
+    pub fn save(mut self, save: bool) -> Self {
+        self.save = save;
+        self
+    }
+
+    /// Apply the edits in `nvim`, returning the outcome for each target in
+    /// the order they were added
+    ///
+    /// Files not already open are loaded into listed buffers. The edits are
+    /// checked before any is applied, and if a target can't be loaded or
+    /// one of its edits is outside the buffer, no target is changed and
+    /// `FileEditResult::error` says why. Neovim handles no other request
+    /// while the edits are applied, so none sees them half done.
+    ///
+    /// Returns `Error::InvalidArgument` without contacting Neovim if a range
+    /// is invalid or two edits to the same target overlap.
+    pub fn apply(&self, nvim: &Nvim) -> Result<Vec<FileEditResult>, Error> {
+        let mut files = Vec::with_capacity(self.changes.len());
+        for (target, edits) in &self.changes {
+            let target = match target {
+                EditTarget::Buffer(buffer) => buffer.convert(),
+                EditTarget::Path(path) => Value::from(path_str(path)?),
+            };
+            let edits = sorted(edits)?
+                .into_iter()
+                .rev()
+                .map(|edit| {
+                    let Range { start, end } = edit.range;
+                    let lines: Vec<Value> = edit.new_text.split('\n').map(Value::from).collect();
+                    Value::Array(vec![
+                        Value::from(start.row),
+                        Value::from(start.col),
+                        Value::from(end.row),
+                        Value::from(end.col),
+                        Value::Array(lines),
+                    ])
+                })
+                .collect();
+            files.push(Value::Array(vec![target, Value::Array(edits)]));
+        }
+
+        let reports: Vec<Value> = nvim.call_typed(
+            "nvim_exec_lua",
+            vec![
+                Value::from(APPLY_LUA),
+                Value::Array(vec![Value::Array(files), Value::from(self.save)]),
+            ],
+        )?;
+        self.changes
+            .iter()
+            .zip(reports)
+            .map(|((target, _), report)| {
+                let buffer = report["buf"]
+                    .as_i64()
+                    .map(|id| Buffer::new(id, nvim.session.clone()));
+                Ok(FileEditResult {
+                    target: target.clone(),
+                    buffer,
+                    applied: report["applied"].as_bool().unwrap_or(false),
+                    saved: report["saved"].as_bool().unwrap_or(false),
+                    error: report["error"].as_str().map(str::to_string),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Sort `edits` by position, keeping the order of insertions at the same
+/// position, after checking their ranges are valid and don't overlap
+fn sorted(edits: &[TextEdit]) -> Result<Vec<&TextEdit>, Error> {
+    let mut sorted: Vec<&TextEdit> = edits.iter().collect();
+    // Insertions before replacements starting at the same position
+    sorted.sort_by_key(|edit| (edit.range.start, edit.range.end));
+    for edit in &sorted {
+        edit.range.validate()?;
+    }
+    for pair in sorted.windows(2) {
+        if pair[0].range.end > pair[1].range.start {
+            return Err(Error::InvalidArgument(format!(
+                "Edits to {:?} and {:?} overlap",
+                pair[0].range, pair[1].range
+            )));
+        }
+    }
+    Ok(sorted)
+}
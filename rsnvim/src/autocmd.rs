@@ -0,0 +1,196 @@
+//! Autocommands whose callbacks are Rust closures
+//!
+//! An `AutocmdManager` creates autocommands with a Lua callback which
+//! notifies this client, and runs the closure registered for the
+//! autocommand when the notification arrives.
+use rmpv::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::api::{AutocmdOpts, Nvim};
+use crate::error::Error;
+
+/// Counter used to give each manager a unique group and notification
+static MANAGER_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Lua run to create an autocommand which notifies this client
+///
+/// Values in `data` which can't be sent over RPC, like functions, cause
+/// the notification to be sent without `data` rather than failing.
+const CREATE_AUTOCMD_LUA: &str = r#"
+local channel, method, event, opts = ...
+opts.callback = function(args)
+    if not pcall(vim.rpcnotify, channel, method, args) then
+        args.data = nil
+        vim.rpcnotify(channel, method, args)
+    end
+end
+return vim.api.nvim_create_autocmd(event, opts)
+"#;
+
+type Callback = Box<dyn FnMut(AutocmdArgs, &mut Nvim) + Send>;
+
+/// The details of a triggered autocommand, as given to a Lua callback
+#[derive(Clone, Debug, PartialEq)]
+pub struct AutocmdArgs {
+    /// The ID of the autocommand
+    pub id: i64,
+    /// The name of the event, e.g. `BufWritePost`
+    pub event: String,
+    /// The ID of the autocommand's group
+    pub group: Option<i64>,
+    /// The expanded value of `<amatch>`
+    pub matched: String,
+    /// The expanded value of `<abuf>`
+    pub buf: i64,
+    /// The expanded value of `<afile>`
+    pub file: String,
+    /// Arbitrary data passed by `nvim_exec_autocmds`
+    pub data: Value,
+}
+
+impl AutocmdArgs {
+    fn from_value(value: &Value) -> Option<Self> {
+        let field = |name: &str| {
+            value
+                .as_map()?
+                .iter()
+                .find(|(k, _)| k.as_str() == Some(name))
+                .map(|(_, v)| v)
+        };
+        let string = |name: &str| {
+            field(name)
+                .and_then(Value::as_str)
+                .unwrap_or("")
+                .to_string()
+        };
+        Some(AutocmdArgs {
+            id: field("id")?.as_i64()?,
+            event: string("event"),
+            group: field("group").and_then(Value::as_i64),
+            matched: string("match"),
+            buf: field("buf").and_then(Value::as_i64).unwrap_or(0),
+            file: string("file"),
+            data: field("data").cloned().unwrap_or(Value::Nil),
+        })
+    }
+}
+
+/// Creates autocommands which run Rust closures
+///
+/// Each manager has its own autocommand group and notification method, so
+/// several can be used at once. Closures run on a background thread, in
+/// the order their autocommands were triggered, and are given an `Nvim`
+/// handle sharing the connection. The event loop must be running.
+///
+/// # Example
+///
+/// ```no_run
+/// use rsnvim::api::{AutocmdOpts, Nvim};
+/// use rsnvim::autocmd::AutocmdManager;
+///
+/// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+/// nvim.start_event_loop(None, None);
+///
+/// let mut autocmds = AutocmdManager::new(&mut nvim).unwrap();
+/// autocmds
+///     .on(
+///         &["BufWritePost"],
+///         AutocmdOpts::new().pattern(&["*.rs"]),
+///         |args, nvim| {
+///             let _ = nvim.command(format!("echo 'Saved {}'", args.file));
+///         },
+///     )
+///     .unwrap();
+/// ```
+pub struct AutocmdManager {
+    nvim: Nvim,
+    channel: i64,
+    method: String,
+    augroup: i64,
+    callbacks: Arc<Mutex<HashMap<i64, Callback>>>,
+}
+
+impl AutocmdManager {
+    pub fn new(nvim: &mut Nvim) -> Result<Self, Error> {
+        let channel = nvim.api_info()?.channel;
+        let method = format!(
+            "rsnvim_autocmd_{}",
+            MANAGER_COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let augroup = nvim.create_augroup(
+            method.as_str(),
+            vec![(Value::from("clear"), Value::from(true))],
+        )?;
+
+        let callbacks: Arc<Mutex<HashMap<i64, Callback>>> = Arc::new(Mutex::new(HashMap::new()));
+        let notifications = nvim.subscribe_notifications(&method);
+        let routes = callbacks.clone();
+        let mut handle = nvim.clone();
+        thread::spawn(move || {
+            for params in notifications {
+                let args = match params.first().and_then(AutocmdArgs::from_value) {
+                    Some(args) => args,
+                    None => continue,
+                };
+                let mut routes = routes.lock().unwrap();
+                if let Some(callback) = routes.get_mut(&args.id) {
+                    callback(args, &mut handle);
+                }
+            }
+        });
+
+        Ok(AutocmdManager {
+            nvim: nvim.clone(),
+            channel,
+            method,
+            augroup,
+            callbacks,
+        })
+    }
+
+    /// Run `callback` when any of `events` is triggered, returning the ID of
+    /// the autocommand
+    pub fn on<F>(&mut self, events: &[&str], opts: AutocmdOpts, callback: F) -> Result<i64, Error>
+    where
+        F: FnMut(AutocmdArgs, &mut Nvim) + Send + 'static,
+    {
+        let mut opts: Vec<(Value, Value)> = opts.into();
+        opts.push((Value::from("group"), Value::from(self.augroup)));
+        let events: Vec<Value> = events.iter().map(|e| Value::from(*e)).collect();
+
+        // Hold the lock until the callback is registered, so an event
+        // triggered straight away isn't dropped
+        let mut callbacks = self.callbacks.lock().unwrap();
+        let id: i64 = self.nvim.exec_lua_typed(
+            CREATE_AUTOCMD_LUA,
+            vec![
+                Value::from(self.channel),
+                Value::from(self.method.as_str()),
+                Value::from(events),
+                Value::Map(opts),
+            ],
+        )?;
+        callbacks.insert(id, Box::new(callback));
+        Ok(id)
+    }
+
+    /// Delete the autocommand `id` and drop its callback
+    pub fn remove(&mut self, id: i64) -> Result<(), Error> {
+        self.callbacks.lock().unwrap().remove(&id);
+        self.nvim.del_autocmd(id)
+    }
+
+    /// The ID of the manager's autocommand group
+    pub fn augroup(&self) -> i64 {
+        self.augroup
+    }
+
+    /// Delete every autocommand and stop the background thread
+    pub fn stop(mut self) -> Result<(), Error> {
+        self.nvim.unsubscribe_notifications(&self.method);
+        self.nvim.del_augroup_by_id(self.augroup)
+    }
+}
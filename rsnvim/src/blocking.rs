@@ -0,0 +1,68 @@
+//! Guards against blocking RPC calls on a UI thread
+//!
+//! A synchronous call blocks until Neovim responds, which freezes a GUI if it
+//! is made from the thread drawing the interface. Marking that thread with
+//! `mark_ui_thread` makes such calls panic or fail instead, so they are
+//! caught during development. Non-blocking methods like `Session::call_async`
+//! and `ResponseHandle::try_recv` are still allowed.
+use std::cell::Cell;
+
+use crate::error::Error;
+
+/// What happens when a blocking call is made from a UI thread
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockingPolicy {
+    /// Panic, pointing at the offending call
+    Panic,
+    /// Return `Error::BlockingCall` from the call
+    Error,
+}
+
+thread_local! {
+    /// The policy of the current thread, if it is marked as a UI thread
+    static UI_THREAD: Cell<Option<BlockingPolicy>> = const { Cell::new(None) };
+}
+
+/// Mark the current thread as a UI thread, guarding it with `policy`
+///
+/// # Example
+///
+/// ```no_run
+/// use rsnvim::api::Nvim;
+/// use rsnvim::blocking::{mark_ui_thread, BlockingPolicy};
+/// use rsnvim::error::Error;
+///
+/// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+/// nvim.start_event_loop(None, None);
+///
+/// mark_ui_thread(BlockingPolicy::Error);
+/// assert!(matches!(
+///     nvim.get_current_line(),
+///     Err(Error::BlockingCall(_))
+/// ));
+/// ```
+pub fn mark_ui_thread(policy: BlockingPolicy) {
+    UI_THREAD.with(|ui_thread| ui_thread.set(Some(policy)));
+}
+
+/// Stop treating the current thread as a UI thread
+pub fn unmark_ui_thread() {
+    UI_THREAD.with(|ui_thread| ui_thread.set(None));
+}
+
+/// Whether the current thread is marked as a UI thread
+pub fn is_ui_thread() -> bool {
+    UI_THREAD.with(|ui_thread| ui_thread.get().is_some())
+}
+
+/// Apply the current thread's policy before the blocking operation `what`
+pub(crate) fn check_blocking(what: &str) -> Result<(), Error> {
+    match UI_THREAD.with(|ui_thread| ui_thread.get()) {
+        None => Ok(()),
+        Some(BlockingPolicy::Panic) => panic!("{} would block the UI thread", what),
+        Some(BlockingPolicy::Error) => Err(Error::BlockingCall(format!(
+            "{} would block the UI thread",
+            what
+        ))),
+    }
+}
@@ -0,0 +1,131 @@
+//! Rust functions callable from Lua
+//!
+//! A `LuaBridge` installs a Lua module in Neovim whose functions call this
+//! client with `vim.rpcrequest`, so Lua code can use Rust functions like any
+//! other Lua function. The arguments are decoded with `FromArgs` and the
+//! result is returned with `AsValue`, while errors are raised in Lua.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rsnvim::api::Nvim;
+//! use rsnvim::bridge::LuaBridge;
+//! use rsnvim::handler::HandlerRouter;
+//!
+//! let mut bridge = LuaBridge::new("my_plugin");
+//! bridge
+//!     .function("add", |(a, b): (i64, i64), _nvim| Ok(a + b))
+//!     .function("line_count", |(): (), nvim| {
+//!         nvim.get_current_buf()?.line_count()
+//!     });
+//!
+//! let mut router = HandlerRouter::new();
+//! bridge.register(&mut router);
+//!
+//! let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+//! let (requests, notifications) = router.into_handlers();
+//! nvim.start_event_loop(requests, notifications);
+//!
+//! // Lua can now call `require("my_plugin").add(1, 2)`
+//! bridge.install(&mut nvim).unwrap();
+//! ```
+use rmpv::Value;
+use std::sync::Arc;
+
+use crate::api::{AsValue, Nvim};
+use crate::error::Error;
+use crate::handler::{FromArgs, HandlerRouter};
+
+type BridgeFn = Arc<dyn Fn(Vec<Value>, &mut Nvim) -> Result<Value, Error> + Send + Sync>;
+
+/// Create a function in the module for each name, calling the method named
+/// after the module and function
+const INSTALL_LUA: &str = r#"
+local module_name, channel, names = ...
+local module = package.loaded[module_name] or {}
+for _, name in ipairs(names) do
+  local method = module_name .. "." .. name
+  module[name] = function(...)
+    return vim.rpcrequest(channel, method, ...)
+  end
+end
+package.loaded[module_name] = module
+"#;
+
+/// A Lua module of Rust functions
+///
+/// See the module documentation for an example.
+pub struct LuaBridge {
+    module: String,
+    functions: Vec<(String, BridgeFn)>,
+}
+
+impl LuaBridge {
+    /// Create a bridge installing the Lua module `module`, which Lua code
+    /// loads with `require(module)`
+    pub fn new(module: &str) -> Self {
+        LuaBridge {
+            module: module.to_string(),
+            functions: Vec::new(),
+        }
+    }
+
+    /// Add the function `name` to the module, calling `handler`
+    ///
+    /// Adding a function again replaces the previous handler.
+    pub fn function<P, T, F>(&mut self, name: &str, handler: F) -> &mut Self
+    where
+        P: FromArgs,
+        T: AsValue,
+        F: Fn(P, &mut Nvim) -> Result<T, Error> + Send + Sync + 'static,
+    {
+        self.functions.retain(|(existing, _)| existing != name);
+        self.functions.push((
+            name.to_string(),
+            Arc::new(move |params, nvim| {
+                let params = P::from_args(params)?;
+                handler(params, nvim).map(|result| result.convert())
+            }),
+        ));
+        self
+    }
+
+    /// The RPC method called by the Lua function `name`
+    pub fn method(&self, name: &str) -> String {
+        format!("{}.{}", self.module, name)
+    }
+
+    /// Handle calls to each function with `router`
+    pub fn register(&self, router: &mut HandlerRouter) {
+        for (name, handler) in &self.functions {
+            let handler = handler.clone();
+            router.on_request(&self.method(name), move |params: Vec<Value>, nvim| {
+                handler(params, nvim)
+            });
+        }
+    }
+
+    /// Install the Lua module in the connected Neovim, calling this client's
+    /// channel
+    ///
+    /// Functions are added to the module if it is already loaded, so several
+    /// bridges can share a module. The bridge must also be registered with
+    /// the client's router, and installed again after reconnecting.
+    pub fn install(&self, nvim: &mut Nvim) -> Result<(), Error> {
+        let channel = nvim.channel_id()?;
+        let names = self
+            .functions
+            .iter()
+            .map(|(name, _)| Value::from(name.as_str()))
+            .collect();
+        nvim.exec_lua(
+            INSTALL_LUA,
+            vec![
+                Value::from(self.module.as_str()),
+                Value::from(channel),
+                Value::Array(names),
+            ],
+        )?;
+        Ok(())
+    }
+}
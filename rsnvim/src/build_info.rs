@@ -0,0 +1,46 @@
+use crate::api::{API_LEVEL, GENERATED_AT, GIT_HASH};
+
+/// Which version of rsnvim is in use, and where its bindings came from
+///
+/// Include this in bug reports, e.g. by logging it on startup, so it's clear
+/// which generated API a plugin was built with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BuildInfo {
+    /// The version of the rsnvim crate
+    pub version: &'static str,
+    /// The Neovim API level the bindings were generated against
+    pub api_level: u64,
+    /// The git commit of the generator the bindings were generated with, or
+    /// `unknown`
+    pub git_hash: &'static str,
+    /// When the bindings were generated, as a UTC timestamp
+    pub generated_at: &'static str,
+}
+
+impl std::fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "rsnvim {} (API level {}, generated {} from {})",
+            self.version, self.api_level, self.generated_at, self.git_hash
+        )
+    }
+}
+
+/// Get the version of rsnvim and its generated bindings
+///
+/// # Example
+///
+/// ```
+/// let info = rsnvim::build_info();
+/// println!("{}", info);
+/// assert!(info.api_level >= 11);
+/// ```
+pub fn build_info() -> BuildInfo {
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        api_level: API_LEVEL,
+        git_hash: GIT_HASH,
+        generated_at: GENERATED_AT,
+    }
+}
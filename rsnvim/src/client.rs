@@ -1,19 +1,341 @@
 use rmpv::Value;
-use std::collections::HashMap;
-use std::io::{BufReader, BufWriter, Read, Stdin, Stdout, Write};
-use std::net::TcpStream;
-#[cfg(unix)]
-use std::os::unix::net::UnixStream;
+use std::collections::{HashMap, HashSet};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{mpsc, Arc, Mutex};
 use std::thread::{self, JoinHandle};
-use std::time;
+use std::time::{Duration, Instant};
 
-use crate::error::Error;
-use crate::handler::{DefaultHandler, NotificationHandler, RequestHandler};
+use crate::api::{Nvim, RedrawMode, RedrawReceiver, RedrawSender};
+use crate::blocking::check_blocking;
+use crate::error::{AsyncError, Error};
+use crate::handler::{DefaultHandler, Notification, NotificationHandler, RequestHandler};
+use crate::metrics::{CallRecord, MethodMetrics, Metrics};
 use crate::rpc;
 
 type Sender = mpsc::Sender<Result<Value, Error>>;
-type Handles = Arc<Mutex<HashMap<u64, Sender>>>;
+type Handles = Arc<Calls>;
+type Interrupt = Arc<dyn Fn() -> Result<(), Error> + Send + Sync>;
+type ErrorCallback = Arc<Mutex<Option<Box<dyn Fn(Error) + Send>>>>;
+type Subscribers = Arc<Mutex<Vec<Subscriber>>>;
+type DisconnectCallback = Arc<Mutex<Option<Box<dyn Fn() + Send>>>>;
+type AsyncErrorCallback = Arc<Mutex<Option<Box<dyn Fn(AsyncError) + Send>>>>;
+type EventLoopThreads = (
+    JoinHandle<Result<(), Error>>,
+    JoinHandle<()>,
+    JoinHandle<()>,
+);
+type Handlers = Arc<
+    Mutex<
+        Option<(
+            Box<dyn RequestHandler + Send>,
+            Box<dyn NotificationHandler + Send>,
+        )>,
+    >,
+>;
+
+/// The default number of notifications which can be queued by
+/// `Client::notify` before it blocks
+pub const DEFAULT_WRITE_QUEUE_CAPACITY: usize = 1024;
+
+/// The default number of requests from Neovim which can be handled or
+/// waiting to be handled before more are rejected
+pub const DEFAULT_MAX_PENDING_REQUESTS: usize = 256;
+
+/// A snapshot of a client's internal state, e.g. to check nothing is leaked
+/// by a long-running plugin
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ClientStats {
+    /// Requests sent to Neovim whose responses haven't arrived
+    pub pending_calls: usize,
+    /// Requests from Neovim received but not yet answered
+    pub pending_requests: usize,
+    /// The number of `nvim_error_event` notifications received
+    pub async_errors: u64,
+    /// The number of handlers which panicked
+    pub handler_panics: u64,
+}
+
+/// Work for the write thread
+enum WriteJob {
+    /// Send a notification
+    Message(rpc::RpcMessage),
+    /// Signal that every earlier job has been done
+    Flush(mpsc::Sender<()>),
+}
+
+/// A receiver of notifications registered with `Client::subscribe` or
+/// `Client::subscribe_notifications`
+struct Subscriber {
+    methods: Vec<String>,
+    sender: SubscriberSender,
+}
+
+/// Where a subscriber's notifications are sent
+enum SubscriberSender {
+    /// The whole notification, for `Client::subscribe`
+    Notification(mpsc::Sender<Notification>),
+    /// Only the parameters, for `Client::subscribe_notifications`
+    Params(mpsc::Sender<Vec<Value>>),
+    /// The parameters of `redraw` notifications, for `Client::subscribe_redraw`
+    Redraw(RedrawSender),
+}
+
+impl Subscriber {
+    /// Whether this subscriber wants notifications for `method`
+    fn matches(&self, method: &str) -> bool {
+        self.methods.is_empty() || self.methods.iter().any(|m| m == method)
+    }
+
+    /// Send a notification, returning `false` if the receiver was dropped
+    fn send(&self, method: &str, params: &[Value]) -> bool {
+        match &self.sender {
+            SubscriberSender::Notification(sender) => sender
+                .send(Notification {
+                    method: method.to_owned(),
+                    params: params.to_vec(),
+                })
+                .is_ok(),
+            SubscriberSender::Params(sender) => sender.send(params.to_vec()).is_ok(),
+            SubscriberSender::Redraw(sender) => sender.send(params),
+        }
+    }
+}
+
+/// What `ResponseHandle::cancel` does besides abandoning the request
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CancelPolicy {
+    /// Only stop waiting; Neovim still runs the request to completion
+    #[default]
+    Abandon,
+    /// Also send `<C-c>` with `nvim_input`, interrupting a long-running
+    /// request like a slow `nvim_eval`
+    ///
+    /// The key is typed even if Neovim has already finished, so may cancel a
+    /// pending operator or leave insert mode.
+    Interrupt,
+}
+
+/// A pending response to a request sent with `Client::call`
+///
+/// Dropping the handle discards the response when it arrives.
+pub struct ResponseHandle {
+    msgid: u64,
+    receiver: mpsc::Receiver<Result<Value, Error>>,
+    /// The client's pending calls and interrupt, unless the request hasn't
+    /// been sent by the client, e.g. while coalesced
+    cancel: Option<(Handles, Interrupt)>,
+}
+
+impl ResponseHandle {
+    /// Create a handle for a response delivered through `receiver`
+    pub(crate) fn new(msgid: u64, receiver: mpsc::Receiver<Result<Value, Error>>) -> Self {
+        ResponseHandle {
+            msgid,
+            receiver,
+            cancel: None,
+        }
+    }
+
+    /// The ID of the request this response belongs to
+    pub fn msgid(&self) -> u64 {
+        self.msgid
+    }
+
+    /// Block until the response arrives
+    pub fn wait(self) -> Result<Value, Error> {
+        check_blocking("Waiting for an RPC response")?;
+        match self.receiver.recv() {
+            Ok(response) => response,
+            Err(mpsc::RecvError) => Err(Self::disconnected()),
+        }
+    }
+
+    /// Block until the response arrives or `timeout` elapses
+    ///
+    /// On timeout the request is abandoned, as if by `cancel`, but Neovim
+    /// isn't interrupted.
+    pub fn wait_timeout(self, timeout: Duration) -> Result<Value, Error> {
+        check_blocking("Waiting for an RPC response")?;
+        self.wait_unchecked(timeout)
+    }
+
+    /// Like `wait_timeout`, but allowed on a thread marked with
+    /// `blocking::mark_ui_thread`, for waits the caller didn't ask for
+    pub(crate) fn wait_unchecked(self, timeout: Duration) -> Result<Value, Error> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(response) => response,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                // Don't keep the request pending forever if Neovim never answers
+                if let Some((calls, _)) = &self.cancel {
+                    calls.cancel(self.msgid);
+                }
+                Err(Error::TimeoutError(
+                    "Timeout when waiting for RPC response".to_string(),
+                ))
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err(Self::disconnected()),
+        }
+    }
+
+    /// Get the response if it has arrived, without blocking
+    ///
+    /// Returns `Ok(None)` if the response is still pending.
+    pub fn try_recv(&self) -> Result<Option<Value>, Error> {
+        match self.receiver.try_recv() {
+            Ok(response) => response.map(Some),
+            Err(mpsc::TryRecvError::Empty) => Ok(None),
+            Err(mpsc::TryRecvError::Disconnected) => Err(Self::disconnected()),
+        }
+    }
+
+    /// Stop waiting for the response, which is discarded when it arrives
+    ///
+    /// The request is no longer counted as pending, and its response isn't
+    /// reported as unexpected. With `CancelPolicy::Interrupt`, Neovim is
+    /// also interrupted if the request was still pending.
+    pub fn cancel(self) -> Result<(), Error> {
+        let (calls, interrupt) = match self.cancel {
+            Some(cancel) => cancel,
+            None => return Ok(()),
+        };
+        if calls.cancel(self.msgid)
+            && *calls.cancel_policy.lock().unwrap() == CancelPolicy::Interrupt
+        {
+            interrupt()?;
+        }
+        Ok(())
+    }
+
+    fn disconnected() -> Error {
+        Error::MpscError("Channel disconnected while waiting for RPC response".to_string())
+    }
+}
+
+/// The state used to report `nvim_error_event` notifications
+struct AsyncErrors {
+    callback: AsyncErrorCallback,
+    count: Arc<AtomicU64>,
+}
+
+/// The number of requests from Neovim received but not yet answered
+#[derive(Clone)]
+struct PendingRequests {
+    count: Arc<AtomicUsize>,
+    max: Arc<AtomicUsize>,
+}
+
+impl PendingRequests {
+    /// Count a new request, returning `false` if the limit is reached
+    fn acquire(&self) -> bool {
+        let max = self.max.load(Ordering::Relaxed);
+        self.count
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                (count < max).then_some(count + 1)
+            })
+            .is_ok()
+    }
+
+    /// Stop counting a request once it has been answered
+    fn release(&self) {
+        self.count.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// The response to a request rejected by `acquire`
+    ///
+    /// The error is `[type, message]` like Neovim's own errors, so Neovim
+    /// shows the message rather than the whole error.
+    fn overloaded(&self, msgid: u64) -> rpc::RpcMessage {
+        let message = format!(
+            "Client overloaded with {} pending requests, retry later",
+            self.max.load(Ordering::Relaxed)
+        );
+        rpc::RpcMessage::RpcResponse {
+            msgid,
+            error: Value::Array(vec![Value::from(0), Value::from(message)]),
+            result: Value::Nil,
+        }
+    }
+}
+
+impl AsyncErrors {
+    /// Decode an `nvim_error_event` and pass it to the relevant callback
+    fn report(&self, on_error: &ErrorCallback, params: &[Value]) {
+        self.count.fetch_add(1, Ordering::Relaxed);
+        let error = match AsyncError::from_params(params) {
+            Some(error) => error,
+            None => {
+                if let Some(callback) = &*on_error.lock().unwrap() {
+                    callback(Error::DecodingError(
+                        "Invalid nvim_error_event notification".to_string(),
+                    ));
+                }
+                return;
+            }
+        };
+        match &*self.callback.lock().unwrap() {
+            Some(callback) => callback(error),
+            None => {
+                if let Some(callback) = &*on_error.lock().unwrap() {
+                    callback(Error::RpcError(error.message));
+                }
+            }
+        }
+    }
+}
+
+/// A request waiting for its response
+struct PendingCall {
+    sender: Sender,
+    method: String,
+    sent: Instant,
+}
+
+/// The requests waiting for a response, and the metrics of completed ones
+#[derive(Default)]
+struct Calls {
+    pending: Mutex<HashMap<u64, PendingCall>>,
+    /// Requests cancelled before their response arrived
+    cancelled: Mutex<HashSet<u64>>,
+    cancel_policy: Mutex<CancelPolicy>,
+    metrics: Metrics,
+}
+
+impl Calls {
+    /// Remove the request with ID `msgid`, recording it as completed
+    fn complete(&self, msgid: u64, error: Option<String>) -> Option<Sender> {
+        let call = self.pending.lock().unwrap().remove(&msgid)?;
+        self.metrics
+            .record(&call.method, msgid, call.sent.elapsed(), error);
+        Some(call.sender)
+    }
+
+    /// Abandon the request with ID `msgid`, returning `false` if it wasn't
+    /// pending
+    fn cancel(&self, msgid: u64) -> bool {
+        let cancelled = self.complete(msgid, Some("Cancelled".to_string()));
+        if cancelled.is_some() {
+            self.cancelled.lock().unwrap().insert(msgid);
+        }
+        cancelled.is_some()
+    }
+
+    /// Remove every pending request, recording each as failed with `reason`
+    fn drain(&self, reason: &str) -> Vec<Sender> {
+        self.cancelled.lock().unwrap().clear();
+        let calls: Vec<_> = self.pending.lock().unwrap().drain().collect();
+        calls
+            .into_iter()
+            .map(|(msgid, call)| {
+                let latency = call.sent.elapsed();
+                self.metrics
+                    .record(&call.method, msgid, latency, Some(reason.to_string()));
+                call.sender
+            })
+            .collect()
+    }
+}
 
 /// The client controls the underlying transport mechanism used
 /// to communicate with a Neovim instance.
@@ -24,10 +346,28 @@ where
     R: Read + Send + 'static,
     W: Write + Send + 'static,
 {
-    reader: Option<BufReader<R>>,
+    reader: Mutex<Option<BufReader<R>>>,
     writer: Arc<Mutex<BufWriter<W>>>,
     handles: Handles,
-    msg_counter: u64,
+    /// Sends `<C-c>` for `CancelPolicy::Interrupt`
+    interrupt: Interrupt,
+    msg_counter: AtomicU64,
+    limits: Arc<Mutex<rpc::DecodeLimits>>,
+    on_error: ErrorCallback,
+    subscribers: Subscribers,
+    handlers: Handlers,
+    alive: Arc<AtomicBool>,
+    on_disconnect: DisconnectCallback,
+    on_async_error: AsyncErrorCallback,
+    async_errors: Arc<AtomicU64>,
+    handler_panics: Arc<AtomicU64>,
+    stopping: Arc<AtomicBool>,
+    threads: Mutex<Option<EventLoopThreads>>,
+    write_queue: Mutex<Option<mpsc::SyncSender<WriteJob>>>,
+    write_queue_capacity: AtomicUsize,
+    /// Notifications queued but not yet written
+    queued: Arc<AtomicUsize>,
+    pending_requests: PendingRequests,
 }
 
 impl<R, W> Client<R, W>
@@ -36,19 +376,272 @@ where
     W: Write + Send + 'static,
 {
     pub fn new(reader: R, writer: W) -> Self {
-        let handles = Arc::new(Mutex::new(HashMap::new()));
+        let handles = Arc::new(Calls::default());
+        let writer = Arc::new(Mutex::new(BufWriter::new(writer)));
+        let interrupt_writer = writer.clone();
         Client {
-            reader: Some(BufReader::new(reader)),
-            writer: Arc::new(Mutex::new(BufWriter::new(writer))),
+            reader: Mutex::new(Some(BufReader::new(reader))),
+            writer,
             handles: handles.clone(),
-            msg_counter: 0,
+            interrupt: Arc::new(move || {
+                let msg = rpc::RpcMessage::RpcNotification {
+                    method: "nvim_input".to_string(),
+                    params: vec![Value::from("<C-c>")],
+                };
+                rpc::encode(&mut *interrupt_writer.lock().unwrap(), msg)
+            }),
+            msg_counter: AtomicU64::new(0),
+            limits: Arc::new(Mutex::new(rpc::DecodeLimits::default())),
+            on_error: Arc::new(Mutex::new(None)),
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+            handlers: Arc::new(Mutex::new(None)),
+            alive: Arc::new(AtomicBool::new(false)),
+            on_disconnect: Arc::new(Mutex::new(None)),
+            on_async_error: Arc::new(Mutex::new(None)),
+            async_errors: Arc::new(AtomicU64::new(0)),
+            handler_panics: Arc::new(AtomicU64::new(0)),
+            stopping: Arc::new(AtomicBool::new(false)),
+            threads: Mutex::new(None),
+            write_queue: Mutex::new(None),
+            write_queue_capacity: AtomicUsize::new(DEFAULT_WRITE_QUEUE_CAPACITY),
+            queued: Arc::new(AtomicUsize::new(0)),
+            pending_requests: PendingRequests {
+                count: Arc::new(AtomicUsize::new(0)),
+                max: Arc::new(AtomicUsize::new(DEFAULT_MAX_PENDING_REQUESTS)),
+            },
+        }
+    }
+
+    /// Whether the event loop is running, or the client has been split, and
+    /// the connection is open
+    pub fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::SeqCst)
+    }
+
+    /// Register a callback for when the connection is closed
+    ///
+    /// Requests still waiting for a response fail when this happens.
+    pub fn on_disconnect<F>(&self, callback: F)
+    where
+        F: Fn() + Send + 'static,
+    {
+        *self.on_disconnect.lock().unwrap() = Some(Box::new(callback));
+    }
+
+    /// Replace the closed transport and restart the event loop
+    ///
+    /// The handlers given to `start_event_loop` are reused, alongside the
+    /// registered callbacks and subscribers.
+    pub fn reconnect(&self, reader: R, writer: W, nvim: Nvim) -> Result<(), Error> {
+        if self.is_alive() {
+            return Err(Error::ConnectionError(
+                "Can't reconnect while the connection is open".to_string(),
+            ));
+        }
+        *self.writer.lock().unwrap() = BufWriter::new(writer);
+        *self.reader.lock().unwrap() = Some(BufReader::new(reader));
+        if self.handlers.lock().unwrap().is_some() {
+            self.spawn_event_loop(nvim);
+        }
+        Ok(())
+    }
+
+    /// Ask the event loop to exit, closing the transport with `close`
+    ///
+    /// Pending output, including queued notifications, is flushed first.
+    /// `close` is given the writer's
+    /// underlying transport and should shut it down so the read thread's
+    /// blocking read returns. Use `join` to wait for the event loop to exit.
+    pub fn stop_event_loop<F>(&self, close: F) -> Result<(), Error>
+    where
+        F: FnOnce(&W) -> std::io::Result<()>,
+    {
+        self.stopping.store(true, Ordering::SeqCst);
+        if !self.is_alive() {
+            return Ok(());
+        }
+        self.drain_write_queue();
+        self.write_queue.lock().unwrap().take();
+        let writer = &mut *self.writer.lock().unwrap();
+        // The connection may already be closing
+        let _ = writer.flush();
+        close(writer.get_ref())?;
+        Ok(())
+    }
+
+    /// Wait for the event loop to exit
+    ///
+    /// Returns the error which ended the event loop, or `Ok(())` if it was
+    /// stopped with `stop_event_loop` or was never started.
+    pub fn join(&self) -> Result<(), Error> {
+        let (read, dispatch, write) = match self.threads.lock().unwrap().take() {
+            Some(threads) => threads,
+            None => return Ok(()),
+        };
+        let result = read.join().unwrap_or_else(|_| {
+            Err(Error::ConnectionError(
+                "The read thread panicked".to_string(),
+            ))
+        });
+        // The dispatch thread exits once the read thread drops its sender
+        if dispatch.join().is_err() && result.is_ok() {
+            return Err(Error::ConnectionError(
+                "A handler panicked on the dispatch thread".to_string(),
+            ));
         }
+        // The write thread exits once its queue is dropped
+        self.write_queue.lock().unwrap().take();
+        let _ = write.join();
+        result
     }
 
-    /// Call a Neovim API method
-    pub fn call(&mut self, method: &str, args: Vec<Value>) -> Result<Value, Error> {
-        let msgid = self.msg_counter;
-        self.msg_counter += 1;
+    /// Register a callback for errors encountered by the event loop
+    ///
+    /// This includes messages which couldn't be decoded or exceeded the
+    /// decoding limits, and responses to unknown requests. These errors are
+    /// otherwise ignored and the event loop keeps running.
+    pub fn on_error<F>(&self, callback: F)
+    where
+        F: Fn(Error) + Send + 'static,
+    {
+        *self.on_error.lock().unwrap() = Some(Box::new(callback));
+    }
+
+    /// Register a callback for `nvim_error_event` notifications
+    ///
+    /// Without a callback these errors are passed to the `on_error` callback
+    /// as an `RpcError`.
+    pub fn on_async_error<F>(&self, callback: F)
+    where
+        F: Fn(AsyncError) + Send + 'static,
+    {
+        *self.on_async_error.lock().unwrap() = Some(Box::new(callback));
+    }
+
+    /// The number of `nvim_error_event` notifications received
+    pub fn async_error_count(&self) -> u64 {
+        self.async_errors.load(Ordering::Relaxed)
+    }
+
+    /// A snapshot of the client's pending requests and error counts
+    pub fn stats(&self) -> ClientStats {
+        ClientStats {
+            pending_calls: self.handles.pending.lock().unwrap().len(),
+            pending_requests: self.pending_requests.count.load(Ordering::SeqCst),
+            async_errors: self.async_error_count(),
+            handler_panics: self.handler_panics.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Fail every call still waiting for a response with
+    /// `Error::ConnectionError`, as if Neovim had answered with `reason`
+    ///
+    /// Responses which arrive later are discarded.
+    pub fn cancel_pending(&self, reason: &str) {
+        for sender in self.handles.drain(reason) {
+            let _ = sender.send(Err(Error::ConnectionError(reason.to_string())));
+        }
+    }
+
+    /// The latency and error metrics of the calls made so far, by method
+    pub fn metrics(&self) -> HashMap<String, MethodMetrics> {
+        self.handles.metrics.snapshot()
+    }
+
+    /// Clear the metrics of the calls made so far
+    pub fn reset_metrics(&self) {
+        self.handles.metrics.reset();
+    }
+
+    /// Set what `ResponseHandle::cancel` does besides abandoning a request
+    pub fn set_cancel_policy(&self, policy: CancelPolicy) {
+        *self.handles.cancel_policy.lock().unwrap() = policy;
+    }
+
+    /// Set how long a call takes before it is logged and counted as slow
+    pub fn set_slow_call_threshold(&self, threshold: Duration) {
+        self.handles.metrics.set_slow_threshold(threshold);
+    }
+
+    /// Set a callback run as each call completes, successfully or not
+    ///
+    /// The callback runs on the thread which read the response, so should
+    /// return quickly and must not make calls itself.
+    pub fn on_call<F>(&self, callback: F)
+    where
+        F: Fn(&CallRecord) + Send + 'static,
+    {
+        self.handles.metrics.on_call(Box::new(callback));
+    }
+
+    /// Subscribe to notifications for the given methods
+    ///
+    /// Every matching notification is sent to the returned receiver, in
+    /// addition to any other subscribers and the notification handler. An
+    /// empty `methods` slice subscribes to all notifications. Dropping the
+    /// receiver unsubscribes.
+    pub fn subscribe(&self, methods: &[&str]) -> mpsc::Receiver<Notification> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(Subscriber {
+            methods: methods.iter().map(|m| m.to_string()).collect(),
+            sender: SubscriberSender::Notification(sender),
+        });
+        receiver
+    }
+
+    /// Subscribe to the parameters of notifications for `method`
+    ///
+    /// The subscription lasts until the receiver is dropped or
+    /// `unsubscribe_notifications` is called.
+    pub fn subscribe_notifications(&self, method: &str) -> mpsc::Receiver<Vec<Value>> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(Subscriber {
+            methods: vec![method.to_string()],
+            sender: SubscriberSender::Params(sender),
+        });
+        receiver
+    }
+
+    /// Subscribe to `redraw` notifications, grouped into batches ending with
+    /// a `flush` event
+    pub fn subscribe_redraw(&self, mode: RedrawMode) -> RedrawReceiver {
+        let (sender, receiver) = RedrawReceiver::new(mode);
+        self.subscribers.lock().unwrap().push(Subscriber {
+            methods: vec!["redraw".to_string()],
+            sender: SubscriberSender::Redraw(sender),
+        });
+        receiver
+    }
+
+    /// Remove every subscription made with `subscribe_notifications` for
+    /// `method`, closing their receivers
+    pub fn unsubscribe_notifications(&self, method: &str) {
+        self.subscribers.lock().unwrap().retain(|subscriber| {
+            let params_only = matches!(subscriber.sender, SubscriberSender::Params(_));
+            !params_only || subscriber.methods != [method]
+        });
+    }
+
+    /// Set the limits applied when decoding incoming RPC messages
+    ///
+    /// This may be called while the event loop is running, e.g. to relax the
+    /// limits for the duration of a bulk operation.
+    pub fn set_decode_limits(&self, limits: rpc::DecodeLimits) {
+        *self.limits.lock().unwrap() = limits;
+    }
+
+    /// Send a request to Neovim without waiting for the response
+    ///
+    /// The returned `ResponseHandle` receives the response once it has been
+    /// read by the event loop, allowing many requests to be in flight at
+    /// once. Locks are only held while the request is being sent, so other
+    /// threads (including handlers running on the dispatch thread) may make
+    /// calls concurrently.
+    ///
+    /// Notifications queued by `notify` are written first, so Neovim
+    /// receives messages in the order they were sent.
+    pub fn call(&self, method: &str, args: Vec<Value>) -> Result<ResponseHandle, Error> {
+        let msgid = self.msg_counter.fetch_add(1, Ordering::Relaxed);
 
         let req = rpc::RpcMessage::RpcRequest {
             msgid,
@@ -58,123 +651,587 @@ where
 
         // Keep track of sender to return the response to the correct receiver
         let (sender, receiver) = mpsc::channel();
-        self.handles.lock().unwrap().insert(msgid, sender);
+        let call = PendingCall {
+            sender,
+            method: method.to_owned(),
+            sent: Instant::now(),
+        };
+        self.handles.pending.lock().unwrap().insert(msgid, call);
+
+        // The event loop has stopped, so the response would never arrive
+        if let Err(error) = self.check_open() {
+            self.handles.pending.lock().unwrap().remove(&msgid);
+            return Err(error);
+        }
 
+        // Keep the request behind any queued notifications
+        if self.queued.load(Ordering::SeqCst) > 0 && !self.drain_write_queue() {
+            self.handles.pending.lock().unwrap().remove(&msgid);
+            return Err(Self::closed());
+        }
         let writer = &mut *self.writer.lock().unwrap();
-        rpc::encode(writer, req)?;
-
-        let dur = time::Duration::from_secs(1);
-        let delay = time::Duration::from_millis(1);
-        let instant = time::Instant::now();
-        loop {
-            match receiver.try_recv() {
-                Err(mpsc::TryRecvError::Empty) => {
-                    thread::sleep(delay);
-                    if instant.elapsed() >= dur {
-                        return Err(Error::TimeoutError(
-                            "Timeout when waiting for RPC response".to_string(),
-                        ));
-                    }
+        if let Err(error) = rpc::encode(writer, req) {
+            self.handles.pending.lock().unwrap().remove(&msgid);
+            return Err(error);
+        }
+
+        Ok(ResponseHandle {
+            msgid,
+            receiver,
+            cancel: Some((self.handles.clone(), self.interrupt.clone())),
+        })
+    }
+
+    /// Send a notification to Neovim
+    ///
+    /// While the event loop is running, notifications are queued and written
+    /// by a dedicated thread, so bulk sends like replaying thousands of
+    /// `nvim_input` calls only hold the writer for one message at a time.
+    /// This blocks while the queue is full. Notifications are written in
+    /// order, and before any request sent with `call` afterwards; use
+    /// `flush_notifications` to wait for them to be written.
+    pub fn notify(&self, method: &str, args: Vec<Value>) -> Result<(), Error> {
+        self.check_open()?;
+        let msg = rpc::RpcMessage::RpcNotification {
+            method: method.to_owned(),
+            params: args,
+        };
+        // The queue is cloned so the lock isn't held while blocking
+        let queue = self.write_queue.lock().unwrap().clone();
+        match queue {
+            Some(queue) => {
+                self.queued.fetch_add(1, Ordering::SeqCst);
+                queue.send(WriteJob::Message(msg)).map_err(|_| {
+                    self.queued.fetch_sub(1, Ordering::SeqCst);
+                    Self::closed()
+                })
+            }
+            None => rpc::encode(&mut *self.writer.lock().unwrap(), msg),
+        }
+    }
+
+    /// Send a notification to Neovim without blocking
+    ///
+    /// Returns `Error::LimitExceeded` if the write queue is full.
+    pub fn try_notify(&self, method: &str, args: Vec<Value>) -> Result<(), Error> {
+        self.check_open()?;
+        let msg = rpc::RpcMessage::RpcNotification {
+            method: method.to_owned(),
+            params: args,
+        };
+        match &*self.write_queue.lock().unwrap() {
+            Some(queue) => {
+                self.queued.fetch_add(1, Ordering::SeqCst);
+                let sent = queue.try_send(WriteJob::Message(msg));
+                if sent.is_err() {
+                    self.queued.fetch_sub(1, Ordering::SeqCst);
                 }
-                Err(mpsc::TryRecvError::Disconnected) => {
-                    return Err(Error::MpscError(
-                        "Channel disconnected while waiting for RPC response".to_string(),
-                    ));
+                match sent {
+                    Ok(()) => Ok(()),
+                    Err(mpsc::TrySendError::Full(_)) => Err(Error::LimitExceeded(
+                        "The notification write queue is full".to_string(),
+                    )),
+                    Err(mpsc::TrySendError::Disconnected(_)) => Err(Self::closed()),
                 }
-                Ok(val) => return val,
-            };
+            }
+            None => rpc::encode(&mut *self.writer.lock().unwrap(), msg),
+        }
+    }
+
+    /// Block until every queued notification has been written
+    pub fn flush_notifications(&self) -> Result<(), Error> {
+        check_blocking("Flushing notifications")?;
+        self.check_open()?;
+        if self.drain_write_queue() {
+            Ok(())
+        } else {
+            Err(Self::closed())
         }
     }
 
-    /// Get the sender responsible for the request with ID `msgid`
-    fn find_sender(handles: &Handles, msgid: u64) -> Sender {
-        let mut handles = handles.lock().unwrap();
+    /// Set how many notifications can be queued before `notify` blocks
+    ///
+    /// This takes effect the next time the event loop is started.
+    pub fn set_write_queue_capacity(&self, capacity: usize) {
+        self.write_queue_capacity.store(capacity, Ordering::Relaxed);
+    }
 
-        handles.remove(&msgid).unwrap()
+    /// Set how many requests from Neovim can be handled or waiting to be
+    /// handled at once
+    ///
+    /// This may be called while the event loop is running.
+    pub fn set_max_pending_requests(&self, max: usize) {
+        self.pending_requests.max.store(max, Ordering::Relaxed);
     }
 
-    /// Spawn a thread to handle incoming RPC messages
+    /// Wait for the write thread to finish the queued jobs, returning
+    /// whether it is still running
+    fn drain_write_queue(&self) -> bool {
+        let queue = self.write_queue.lock().unwrap().clone();
+        let queue = match queue {
+            Some(queue) => queue,
+            None => return true,
+        };
+        let (sender, receiver) = mpsc::channel();
+        queue.send(WriteJob::Flush(sender)).is_ok() && receiver.recv().is_ok()
+    }
+
+    /// Fail if the event loop has stopped, as nothing more can be sent
+    fn check_open(&self) -> Result<(), Error> {
+        if !self.is_alive() && self.reader.lock().unwrap().is_none() {
+            return Err(Self::closed());
+        }
+        Ok(())
+    }
+
+    fn closed() -> Error {
+        Error::ConnectionError("The connection to Neovim has been closed".to_string())
+    }
+
+    /// Reserve a request ID without sending a request
+    pub fn reserve_msgid(&self) -> u64 {
+        self.msg_counter.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Pass an error to the registered error callback, if any
+    fn report_error(on_error: &ErrorCallback, error: Error) {
+        if let Some(callback) = &*on_error.lock().unwrap() {
+            callback(error);
+        }
+    }
+
+    /// Split the client into halves which read and write the transport,
+    /// instead of starting the event loop
+    ///
+    /// The client can still make calls and send notifications, but responses
+    /// only arrive as the `ReadHalf` is read. Returns an error if the event
+    /// loop has been started or the client already split.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rmpv::Value;
+    /// use rsnvim::client::Client;
+    /// use rsnvim::rpc::RpcMessage;
+    /// use std::net::TcpStream;
+    /// use std::thread;
+    ///
+    /// let stream = TcpStream::connect("127.0.0.1:6666").unwrap();
+    /// let client = Client::new(stream.try_clone().unwrap(), stream);
+    /// let (mut reader, writer) = client.split().unwrap();
+    ///
+    /// thread::spawn(move || {
+    ///     while let Ok(msg) = reader.read() {
+    ///         if let Some(RpcMessage::RpcRequest { msgid, .. }) = msg {
+    ///             writer.respond(msgid, Ok(Value::from("pong"))).unwrap();
+    ///         }
+    ///     }
+    /// });
+    /// let handle = client.call("nvim_eval", vec![Value::from("1 + 2")]).unwrap();
+    /// assert_eq!(handle.wait().unwrap(), Value::from(3));
+    /// ```
+    pub fn split(&self) -> Result<(ReadHalf<R>, WriteHalf<W>), Error> {
+        let reader = self.reader.lock().unwrap().take().ok_or_else(|| {
+            Error::ConnectionError("The client's reader is already in use".to_string())
+        })?;
+        self.alive.store(true, Ordering::SeqCst);
+        let read = ReadHalf {
+            reader,
+            handles: self.handles.clone(),
+            limits: self.limits.clone(),
+            subscribers: self.subscribers.clone(),
+            alive: self.alive.clone(),
+            on_disconnect: self.on_disconnect.clone(),
+        };
+        let write = WriteHalf {
+            writer: self.writer.clone(),
+        };
+        Ok((read, write))
+    }
+
+    /// Spawn threads to handle incoming RPC messages
+    ///
+    /// Responses are routed to their callers by the read thread, while
+    /// requests and notifications are passed, in order, to a separate dispatch
+    /// thread which runs the handlers. This allows handlers to call back into
+    /// Neovim using `nvim` without blocking the read thread.
     pub fn start_event_loop(
-        &mut self,
+        &self,
+        nvim: Nvim,
         request_handler: Option<Box<dyn RequestHandler + Send>>,
         notification_handler: Option<Box<dyn NotificationHandler + Send>>,
     ) {
         let r = request_handler.unwrap_or(Box::new(DefaultHandler::new()));
         let n = notification_handler.unwrap_or(Box::new(DefaultHandler::new()));
-        Self::dispatch_read_thread(
-            self.reader.take().unwrap(),
-            self.writer.clone(),
+        *self.handlers.lock().unwrap() = Some((r, n));
+        self.spawn_event_loop(nvim);
+    }
+
+    fn spawn_event_loop(&self, nvim: Nvim) {
+        let (sender, receiver) = mpsc::channel();
+        self.alive.store(true, Ordering::SeqCst);
+        self.stopping.store(false, Ordering::SeqCst);
+        let read = Self::dispatch_read_thread(
+            self.reader.lock().unwrap().take().unwrap(),
             self.handles.clone(),
-            r,
-            n,
+            self.limits.clone(),
+            self.on_error.clone(),
+            self.subscribers.clone(),
+            self.alive.clone(),
+            self.on_disconnect.clone(),
+            AsyncErrors {
+                callback: self.on_async_error.clone(),
+                count: self.async_errors.clone(),
+            },
+            self.stopping.clone(),
+            self.writer.clone(),
+            self.pending_requests.clone(),
+            sender,
+        );
+        let dispatch = Self::dispatch_handler_thread(
+            receiver,
+            self.writer.clone(),
+            nvim,
+            self.handlers.clone(),
+            self.pending_requests.clone(),
+            self.on_error.clone(),
+            self.handler_panics.clone(),
         );
+        let capacity = self.write_queue_capacity.load(Ordering::Relaxed);
+        let (queue, jobs) = mpsc::sync_channel(capacity);
+        let write = Self::dispatch_write_thread(jobs, self.writer.clone(), self.queued.clone());
+        *self.write_queue.lock().unwrap() = Some(queue);
+        *self.threads.lock().unwrap() = Some((read, dispatch, write));
     }
 
     /// Spawn new thread to handle reading the underlying Neovim connection
+    #[allow(clippy::too_many_arguments)]
     fn dispatch_read_thread(
         mut reader: BufReader<R>,
-        writer: Arc<Mutex<BufWriter<W>>>,
         handles: Handles,
-        request_handler: Box<dyn RequestHandler + Send>,
-        notification_handler: Box<dyn NotificationHandler + Send>,
-    ) -> JoinHandle<()> {
+        limits: Arc<Mutex<rpc::DecodeLimits>>,
+        on_error: ErrorCallback,
+        subscribers: Subscribers,
+        alive: Arc<AtomicBool>,
+        on_disconnect: DisconnectCallback,
+        async_errors: AsyncErrors,
+        stopping: Arc<AtomicBool>,
+        writer: Arc<Mutex<BufWriter<W>>>,
+        pending: PendingRequests,
+        dispatcher: mpsc::Sender<rpc::RpcMessage>,
+    ) -> JoinHandle<Result<(), Error>> {
         thread::spawn(move || loop {
-            let msg = match rpc::decode(&mut reader) {
+            let limits = *limits.lock().unwrap();
+            let msg = match rpc::decode_with_limits(&mut reader, &limits) {
                 Ok(msg) => msg,
-                Err(_) => return,
+                // The connection has been closed
+                Err(error @ (Error::ConnectionError(_) | Error::IoError(_))) => {
+                    disconnect(&handles, &alive, &on_disconnect);
+                    if stopping.load(Ordering::SeqCst) {
+                        return Ok(());
+                    }
+                    return Err(error);
+                }
+                // Any later messages can't be trusted, so fail the pending
+                // requests now rather than leaving them to time out
+                Err(Error::StreamCorrupted(reason)) => {
+                    fail_pending(&handles, &reason);
+                    disconnect(&handles, &alive, &on_disconnect);
+                    Self::report_error(&on_error, Error::StreamCorrupted(reason.clone()));
+                    return Err(Error::StreamCorrupted(reason));
+                }
+                // The whole message has been consumed so the stream is still usable
+                Err(error) => {
+                    Self::report_error(&on_error, error);
+                    continue;
+                }
             };
 
             match msg {
-                rpc::RpcMessage::RpcRequest {
-                    msgid,
-                    method,
-                    params,
-                } => {
-                    let response = match request_handler.handle_request(msgid, method, params) {
-                        Ok(result) => rpc::RpcMessage::RpcResponse {
-                            msgid,
-                            error: Value::Nil,
-                            result,
-                        },
-                        Err(error) => rpc::RpcMessage::RpcResponse {
-                            msgid,
-                            error: Value::from(error),
-                            result: Value::Nil,
-                        },
-                    };
-
-                    let writer = &mut *writer.lock().unwrap();
-                    rpc::encode(writer, response).unwrap();
-                }
                 rpc::RpcMessage::RpcResponse {
                     msgid,
                     result,
                     error,
                 } => {
-                    let sender = Self::find_sender(&handles, msgid);
-                    if error != Value::Nil {
-                        sender
-                            .send(Err(Error::MpscError("Error in RPC response".to_string())))
-                            .unwrap();
-                    } else {
-                        sender.send(Ok(result)).unwrap();
+                    if let Err(error) = deliver_response(&handles, msgid, result, error) {
+                        Self::report_error(&on_error, error);
+                    }
+                }
+                rpc::RpcMessage::RpcRequest { msgid, method, .. } if !pending.acquire() => {
+                    // Answer straight away so Neovim isn't left waiting
+                    let response = pending.overloaded(msgid);
+                    if rpc::encode(&mut *writer.lock().unwrap(), response).is_err() {
+                        continue;
                     }
+                    Self::report_error(
+                        &on_error,
+                        Error::LimitExceeded(format!(
+                            "Rejected request {} as too many requests are pending",
+                            method
+                        )),
+                    );
                 }
-                rpc::RpcMessage::RpcNotification { method, params } => {
-                    notification_handler.handle_notification(method, params)
+                msg => {
+                    if let rpc::RpcMessage::RpcNotification { method, params } = &msg {
+                        if method == "nvim_error_event" {
+                            async_errors.report(&on_error, params);
+                        }
+                        broadcast(&subscribers, method, params);
+                    }
+                    // The dispatch thread has exited, e.g. after failing to
+                    // write a response, so the connection can't be used
+                    if dispatcher.send(msg).is_err() {
+                        disconnect(&handles, &alive, &on_disconnect);
+                        return Ok(());
+                    }
+                }
+            };
+        })
+    }
+
+    /// Spawn new thread to write queued notifications
+    ///
+    /// The writer is locked for each message, so requests and responses are
+    /// written between notifications rather than waiting for the queue.
+    fn dispatch_write_thread(
+        jobs: mpsc::Receiver<WriteJob>,
+        writer: Arc<Mutex<BufWriter<W>>>,
+        queued: Arc<AtomicUsize>,
+    ) -> JoinHandle<()> {
+        thread::spawn(move || {
+            for job in jobs {
+                match job {
+                    WriteJob::Message(msg) => {
+                        // A closed connection is reported by the read thread,
+                        // and the queue is still drained so senders don't block
+                        let _ = rpc::encode(&mut *writer.lock().unwrap(), msg);
+                        queued.fetch_sub(1, Ordering::SeqCst);
+                    }
+                    WriteJob::Flush(done) => {
+                        let _ = done.send(());
+                    }
                 }
+            }
+        })
+    }
+
+    /// Spawn new thread to run the handlers for incoming requests and notifications
+    ///
+    /// A handler which panics is reported to `on_error` and, for a request,
+    /// answered with `Error::HandlerPanicked`, so Neovim isn't left waiting.
+    fn dispatch_handler_thread(
+        receiver: mpsc::Receiver<rpc::RpcMessage>,
+        writer: Arc<Mutex<BufWriter<W>>>,
+        mut nvim: Nvim,
+        handlers: Handlers,
+        pending: PendingRequests,
+        on_error: ErrorCallback,
+        handler_panics: Arc<AtomicU64>,
+    ) -> JoinHandle<()> {
+        thread::spawn(move || {
+            let panicked = |method: &str| {
+                handler_panics.fetch_add(1, Ordering::Relaxed);
+                let error = format!("The handler for {} panicked", method);
+                Self::report_error(&on_error, Error::HandlerPanicked(error.clone()));
+                Error::HandlerPanicked(error)
             };
+            for msg in receiver {
+                let handlers = handlers.lock().unwrap();
+                let (request_handler, notification_handler) = handlers.as_ref().unwrap();
+                match msg {
+                    rpc::RpcMessage::RpcRequest {
+                        msgid,
+                        method,
+                        params,
+                    } => {
+                        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                            request_handler.handle_request(&mut nvim, msgid, method.clone(), params)
+                        }));
+                        let response =
+                            response(msgid, result.unwrap_or_else(|_| Err(panicked(&method))));
+
+                        let writer = &mut *writer.lock().unwrap();
+                        pending.release();
+                        if rpc::encode(writer, response).is_err() {
+                            // The connection has been closed
+                            return;
+                        }
+                    }
+                    rpc::RpcMessage::RpcNotification { method, params } => {
+                        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                            notification_handler.handle_notification(
+                                &mut nvim,
+                                method.clone(),
+                                params,
+                            )
+                        }));
+                        if result.is_err() {
+                            panicked(&method);
+                        }
+                    }
+                    rpc::RpcMessage::RpcResponse { .. } => (),
+                }
+            }
         })
     }
 }
 
-/// Method of connecting to Neovim session
-pub enum Connection {
-    /// A TCP socket connection
-    TCP(Client<TcpStream, TcpStream>),
-    /// A stdin/stdout connection
-    STDIO(Client<Stdin, Stdout>),
-    /// A Unix socket connection
-    #[cfg(unix)]
-    UNIX(Client<UnixStream, UnixStream>),
+/// Mark the connection as closed, failing any outstanding requests
+fn disconnect(handles: &Handles, alive: &AtomicBool, on_disconnect: &DisconnectCallback) {
+    alive.store(false, Ordering::SeqCst);
+    // Dropping the senders wakes any callers waiting for a response
+    handles.drain("The connection was closed");
+    if let Some(callback) = &*on_disconnect.lock().unwrap() {
+        callback();
+    }
+}
+
+/// Fail every outstanding request with `Error::StreamCorrupted`
+fn fail_pending(handles: &Handles, reason: &str) {
+    for sender in handles.drain(reason) {
+        let _ = sender.send(Err(Error::StreamCorrupted(reason.to_string())));
+    }
 }
+
+/// Send a notification to every matching subscriber
+///
+/// Subscribers whose receiver has been dropped are removed.
+fn broadcast(subscribers: &Subscribers, method: &str, params: &[Value]) {
+    subscribers
+        .lock()
+        .unwrap()
+        .retain(|subscriber| !subscriber.matches(method) || subscriber.send(method, params));
+}
+
+/// Pass a response to the caller waiting for the request with ID `msgid`
+fn deliver_response(
+    handles: &Handles,
+    msgid: u64,
+    result: Value,
+    error: Value,
+) -> Result<(), Error> {
+    let failure = match error {
+        Value::Nil => None,
+        error => Some(rpc::error_message(error)),
+    };
+    let sender = match handles.complete(msgid, failure.clone()) {
+        Some(sender) => sender,
+        None if handles.cancelled.lock().unwrap().remove(&msgid) => return Ok(()),
+        None => {
+            return Err(Error::UnexpectedResponse(format!(
+                "Received response to unknown request {}",
+                msgid
+            )))
+        }
+    };
+    // The caller may have stopped waiting, e.g. after a timeout
+    let _ = match failure {
+        Some(message) => sender.send(Err(Error::RpcError(message))),
+        None => sender.send(Ok(result)),
+    };
+    Ok(())
+}
+
+/// The response to the request with ID `msgid`, returning `result`
+fn response(msgid: u64, result: Result<Value, Error>) -> rpc::RpcMessage {
+    match result {
+        Ok(result) => rpc::RpcMessage::RpcResponse {
+            msgid,
+            error: Value::Nil,
+            result,
+        },
+        Err(error) => rpc::RpcMessage::RpcResponse {
+            msgid,
+            error: Value::from(error),
+            result: Value::Nil,
+        },
+    }
+}
+
+/// The reading half of a client, created by `Client::split`
+///
+/// Owning the read loop allows messages to be read whenever suits the
+/// caller, e.g. from an async task, rather than by the event loop's threads.
+/// Responses are still routed to the `ResponseHandle` of each request made
+/// with `Client::call`, and notifications to subscribers.
+pub struct ReadHalf<R: Read> {
+    reader: BufReader<R>,
+    handles: Handles,
+    limits: Arc<Mutex<rpc::DecodeLimits>>,
+    subscribers: Subscribers,
+    alive: Arc<AtomicBool>,
+    on_disconnect: DisconnectCallback,
+}
+
+impl<R: Read> ReadHalf<R> {
+    /// Read the next message, blocking until one arrives
+    ///
+    /// Responses are passed to the caller waiting for them and `None` is
+    /// returned, while requests and notifications are returned to be handled.
+    /// Answer requests with `WriteHalf::respond`.
+    ///
+    /// Returns `Error::ConnectionError` or `Error::IoError` once the
+    /// connection is closed, or `Error::StreamCorrupted` once the stream is
+    /// out of sync, any of which fails every pending request. Other errors,
+    /// e.g. a message exceeding the decode limits, leave the stream usable.
+    pub fn read(&mut self) -> Result<Option<rpc::RpcMessage>, Error> {
+        let limits = *self.limits.lock().unwrap();
+        match rpc::decode_with_limits(&mut self.reader, &limits) {
+            Ok(rpc::RpcMessage::RpcResponse {
+                msgid,
+                result,
+                error,
+            }) => deliver_response(&self.handles, msgid, result, error).map(|_| None),
+            Ok(msg) => {
+                if let rpc::RpcMessage::RpcNotification { method, params } = &msg {
+                    broadcast(&self.subscribers, method, params);
+                }
+                Ok(Some(msg))
+            }
+            Err(error @ (Error::ConnectionError(_) | Error::IoError(_))) => {
+                disconnect(&self.handles, &self.alive, &self.on_disconnect);
+                Err(error)
+            }
+            Err(Error::StreamCorrupted(reason)) => {
+                fail_pending(&self.handles, &reason);
+                disconnect(&self.handles, &self.alive, &self.on_disconnect);
+                Err(Error::StreamCorrupted(reason))
+            }
+            Err(error) => Err(error),
+        }
+    }
+}
+
+/// The writing half of a client, created by `Client::split`
+///
+/// The writer is shared with the client, so `Client::call` and
+/// `Client::notify` can still be used alongside it.
+pub struct WriteHalf<W: Write> {
+    writer: Arc<Mutex<BufWriter<W>>>,
+}
+
+impl<W: Write> Clone for WriteHalf<W> {
+    fn clone(&self) -> Self {
+        WriteHalf {
+            writer: self.writer.clone(),
+        }
+    }
+}
+
+impl<W: Write> WriteHalf<W> {
+    /// Write `msg`, flushing it to the transport
+    pub fn send(&self, msg: rpc::RpcMessage) -> Result<(), Error> {
+        rpc::encode(&mut *self.writer.lock().unwrap(), msg)
+    }
+
+    /// Answer the request with ID `msgid` with `result`
+    pub fn respond(&self, msgid: u64, result: Result<Value, Error>) -> Result<(), Error> {
+        self.send(response(msgid, result))
+    }
+}
+
+/// A client over any transport, used by `Session`
+///
+/// The reader and writer are boxed so sockets, stdin/stdout and custom
+/// transports given to `Session::from_transport` share one client type.
+pub type Connection = Client<Box<dyn Read + Send>, Box<dyn Write + Send>>;
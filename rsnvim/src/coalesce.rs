@@ -0,0 +1,181 @@
+//! Coalescing of extmark updates into atomic batches
+//!
+//! Live decorations often set many extmarks in quick succession. For the
+//! namespaces opted in with `Session::coalesce_extmarks`, asynchronous
+//! `nvim_buf_set_extmark` calls are queued and sent together as a single
+//! `nvim_call_atomic` request once the flush window has elapsed.
+use rmpv::Value;
+use std::collections::HashMap;
+use std::sync::{mpsc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::api::BatchResults;
+use crate::client::ResponseHandle;
+use crate::error::Error;
+use crate::session::Session;
+
+/// The method whose calls are coalesced
+pub(crate) const SET_EXTMARK: &str = "nvim_buf_set_extmark";
+
+/// How long the worker waits for calls before checking the connection
+const IDLE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A `nvim_buf_set_extmark` call waiting to be sent
+struct PendingCall {
+    args: Vec<Value>,
+    sender: mpsc::Sender<Result<Value, Error>>,
+}
+
+#[derive(Default)]
+struct State {
+    /// The flush window of each coalesced namespace, where `None` waits for
+    /// an explicit flush
+    namespaces: HashMap<i64, Option<Duration>>,
+    pending: Vec<PendingCall>,
+    /// When the pending calls must be sent
+    deadline: Option<Instant>,
+    /// Whether the worker thread which flushes at the deadline is running
+    worker: bool,
+}
+
+/// The queue of coalesced extmark calls shared by clones of a `Session`
+#[derive(Default)]
+pub(crate) struct ExtmarkCoalescer {
+    state: Mutex<State>,
+    wake: Condvar,
+}
+
+impl ExtmarkCoalescer {
+    /// Coalesce calls for the namespace `ns_id`
+    pub(crate) fn enable(&self, ns_id: i64, window: Option<Duration>) {
+        self.state.lock().unwrap().namespaces.insert(ns_id, window);
+    }
+
+    /// Stop coalescing calls for the namespace `ns_id`
+    pub(crate) fn disable(&self, ns_id: i64) {
+        self.state.lock().unwrap().namespaces.remove(&ns_id);
+    }
+
+    /// Whether any calls are waiting to be sent
+    pub(crate) fn has_pending(&self) -> bool {
+        !self.state.lock().unwrap().pending.is_empty()
+    }
+
+    /// Queue the call if its namespace is coalesced, otherwise give the
+    /// arguments back
+    ///
+    /// The arguments of `nvim_buf_set_extmark` are [buffer, ns_id, line, col,
+    /// opts].
+    pub(crate) fn push(
+        &self,
+        session: &Session,
+        msgid: u64,
+        args: Vec<Value>,
+    ) -> Result<ResponseHandle, Vec<Value>> {
+        let mut state = self.state.lock().unwrap();
+        let window = match args.get(1).and_then(Value::as_i64) {
+            Some(ns_id) => match state.namespaces.get(&ns_id) {
+                Some(window) => *window,
+                None => return Err(args),
+            },
+            None => return Err(args),
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        state.pending.push(PendingCall { args, sender });
+        if let Some(window) = window {
+            let deadline = Instant::now() + window;
+            if state.deadline.map_or(true, |current| deadline < current) {
+                state.deadline = Some(deadline);
+            }
+            if !state.worker {
+                state.worker = true;
+                let session = session.clone();
+                thread::spawn(move || session.extmarks.run_worker(&session));
+            }
+            self.wake.notify_one();
+        }
+
+        Ok(ResponseHandle::new(msgid, receiver))
+    }
+
+    /// Send every pending call in a single atomic batch
+    ///
+    /// `send` makes the `nvim_call_atomic` request. The results are delivered
+    /// to each call's `ResponseHandle` once the response arrives.
+    pub(crate) fn flush<F>(&self, send: F) -> Result<(), Error>
+    where
+        F: FnOnce(Vec<Value>) -> Result<ResponseHandle, Error>,
+    {
+        // The lock is held while sending so batches are sent in order
+        let mut state = self.state.lock().unwrap();
+        state.deadline = None;
+        if state.pending.is_empty() {
+            return Ok(());
+        }
+        let pending = std::mem::take(&mut state.pending);
+
+        let calls = pending
+            .iter()
+            .map(|call| {
+                Value::Array(vec![
+                    Value::from(SET_EXTMARK),
+                    Value::from(call.args.clone()),
+                ])
+            })
+            .collect();
+        let handle = match send(vec![Value::Array(calls)]) {
+            Ok(handle) => handle,
+            Err(error) => {
+                for call in &pending {
+                    let _ = call
+                        .sender
+                        .send(Err(Error::ConnectionError(error.to_string())));
+                }
+                return Err(error);
+            }
+        };
+
+        thread::spawn(move || {
+            let results = handle.wait().and_then(BatchResults::from_response);
+            for (index, call) in pending.into_iter().enumerate() {
+                let result = match &results {
+                    Ok(results) => results.result(index),
+                    Err(error) => Err(Error::RpcError(error.to_string())),
+                };
+                // The caller may have stopped waiting
+                let _ = call.sender.send(result);
+            }
+        });
+        Ok(())
+    }
+
+    /// Flush the pending calls at each deadline until the connection closes
+    fn run_worker(&self, session: &Session) {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            state = match state.deadline {
+                Some(deadline) if Instant::now() >= deadline => {
+                    drop(state);
+                    // Errors are delivered to the queued calls' handles
+                    let _ = session.flush_extmarks();
+                    self.state.lock().unwrap()
+                }
+                Some(deadline) => {
+                    let timeout = deadline.saturating_duration_since(Instant::now());
+                    self.wake.wait_timeout(state, timeout).unwrap().0
+                }
+                None => {
+                    let (state, timeout) = self.wake.wait_timeout(state, IDLE_TIMEOUT).unwrap();
+                    if timeout.timed_out() && state.deadline.is_none() && !session.is_alive() {
+                        let mut state = state;
+                        state.worker = false;
+                        return;
+                    }
+                    state
+                }
+            };
+        }
+    }
+}
@@ -0,0 +1,311 @@
+//! Utilities to coalesce bursts of events, such as those caused by typing
+//!
+//! A `Debouncer` runs its callback once events have stopped arriving for a
+//! given delay, while a throttling `Debouncer` runs it at most once per
+//! interval. `Nvim::debounce_autocmd` drives either from Neovim autocommands
+//! like `CursorHold` or `TextChanged`.
+use rmpv::Value;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::api::Nvim;
+use crate::error::Error;
+
+/// Counter used to give each autocommand debouncer a unique group
+static AUGROUP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// How new events affect a pending callback
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Every event restarts the delay
+    Debounce,
+    /// Events only replace the pending value
+    Throttle,
+}
+
+/// State shared between a `Debouncer` and its worker thread
+struct State<T> {
+    pending: Option<(T, Instant)>,
+    closed: bool,
+}
+
+struct Inner<T> {
+    state: Mutex<State<T>>,
+    condvar: Condvar,
+    delay: Duration,
+    mode: Mode,
+}
+
+impl<T> Inner<T> {
+    /// Record a new event, returning `false` once the debouncer is closed
+    fn trigger(&self, value: T) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return false;
+        }
+        let deadline = match (&state.pending, self.mode) {
+            (Some((_, deadline)), Mode::Throttle) => *deadline,
+            _ => Instant::now() + self.delay,
+        };
+        state.pending = Some((value, deadline));
+        self.condvar.notify_one();
+        true
+    }
+
+    /// Block until a pending value is due, or `None` once closed
+    fn next(&self) -> Option<T> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if state.closed {
+                return None;
+            }
+            match state.pending {
+                None => state = self.condvar.wait(state).unwrap(),
+                Some((_, deadline)) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return state.pending.take().map(|(value, _)| value);
+                    }
+                    state = self.condvar.wait_timeout(state, deadline - now).unwrap().0;
+                }
+            }
+        }
+    }
+}
+
+/// Coalesces events, running a callback on a background thread
+///
+/// Dropping the `Debouncer` discards any pending event and stops the
+/// background thread.
+///
+/// # Example
+///
+/// ```
+/// use rsnvim::debounce::Debouncer;
+/// use std::time::Duration;
+///
+/// let debouncer = Debouncer::new(Duration::from_millis(200), |line: String| {
+///     println!("Analysing {}", line);
+/// });
+/// // Only the last line is analysed, 200ms after it was triggered
+/// debouncer.trigger("fn ma".to_string());
+/// debouncer.trigger("fn main".to_string());
+/// ```
+pub struct Debouncer<T> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T: Send + 'static> Debouncer<T> {
+    /// Run `callback` with the latest event once none have arrived for `delay`
+    pub fn new<F>(delay: Duration, callback: F) -> Self
+    where
+        F: FnMut(T) + Send + 'static,
+    {
+        Self::spawn(delay, Mode::Debounce, callback)
+    }
+
+    /// Run `callback` with the latest event at most once every `interval`
+    ///
+    /// Unlike `Debouncer::new` a steady stream of events doesn't delay the
+    /// callback indefinitely.
+    pub fn throttle<F>(interval: Duration, callback: F) -> Self
+    where
+        F: FnMut(T) + Send + 'static,
+    {
+        Self::spawn(interval, Mode::Throttle, callback)
+    }
+
+    fn spawn<F>(delay: Duration, mode: Mode, mut callback: F) -> Self
+    where
+        F: FnMut(T) + Send + 'static,
+    {
+        let inner = Arc::new(Inner {
+            state: Mutex::new(State {
+                pending: None,
+                closed: false,
+            }),
+            condvar: Condvar::new(),
+            delay,
+            mode,
+        });
+        let worker = inner.clone();
+        thread::spawn(move || {
+            while let Some(value) = worker.next() {
+                callback(value);
+            }
+        });
+
+        Debouncer { inner }
+    }
+}
+
+impl<T> Debouncer<T> {
+    /// Record a new event, replacing any pending one
+    pub fn trigger(&self, value: T) {
+        self.inner.trigger(value);
+    }
+
+    /// Discard the pending event, if any, without running the callback
+    pub fn cancel(&self) {
+        self.inner.state.lock().unwrap().pending = None;
+        self.inner.condvar.notify_one();
+    }
+
+    /// Whether an event is waiting for the callback to run
+    pub fn is_pending(&self) -> bool {
+        self.inner.state.lock().unwrap().pending.is_some()
+    }
+}
+
+impl<T> Drop for Debouncer<T> {
+    fn drop(&mut self) {
+        let mut state = self.inner.state.lock().unwrap();
+        state.closed = true;
+        state.pending = None;
+        self.inner.condvar.notify_one();
+    }
+}
+
+/// An autocommand event received by `Nvim::debounce_autocmd`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AutocmdEvent {
+    /// The name of the event, e.g. `CursorHold`
+    pub event: String,
+    /// The buffer the event was triggered in
+    pub buf: i64,
+}
+
+impl AutocmdEvent {
+    fn from_params(params: &[Value]) -> Option<Self> {
+        Some(AutocmdEvent {
+            event: params.first()?.as_str()?.to_string(),
+            buf: params.get(1)?.as_i64()?,
+        })
+    }
+}
+
+/// A `Debouncer` fed by Neovim autocommands
+///
+/// Created by `Nvim::debounce_autocmd`. The autocommands remain registered
+/// until `stop` is called.
+pub struct AutocmdDebouncer {
+    debouncer: Debouncer<AutocmdEvent>,
+    augroup: i64,
+}
+
+impl AutocmdDebouncer {
+    /// The underlying debouncer, e.g. to cancel a pending event
+    pub fn debouncer(&self) -> &Debouncer<AutocmdEvent> {
+        &self.debouncer
+    }
+
+    /// Delete the autocommands and discard any pending event
+    pub fn stop(self, nvim: &mut Nvim) -> Result<(), Error> {
+        nvim.del_augroup_by_id(self.augroup)
+    }
+}
+
+impl Nvim {
+    /// Run `callback` once the given autocommand `events` pause for `delay`
+    ///
+    /// Autocommands are created which notify this client, with each
+    /// notification restarting the delay. Typical events are `CursorHold`,
+    /// `TextChanged` and `TextChangedI`. The event loop must be running.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::api::Nvim;
+    /// use std::time::Duration;
+    ///
+    /// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+    /// nvim.start_event_loop(None, None);
+    ///
+    /// let debouncer = nvim
+    ///     .debounce_autocmd(
+    ///         &["TextChanged", "TextChangedI"],
+    ///         Duration::from_millis(300),
+    ///         |event| println!("{} in buffer {}", event.event, event.buf),
+    ///     )
+    ///     .unwrap();
+    /// ```
+    pub fn debounce_autocmd<F>(
+        &mut self,
+        events: &[&str],
+        delay: Duration,
+        callback: F,
+    ) -> Result<AutocmdDebouncer, Error>
+    where
+        F: FnMut(AutocmdEvent) + Send + 'static,
+    {
+        self.autocmd_debouncer(events, Debouncer::new(delay, callback))
+    }
+
+    /// Run `callback` at most once every `interval` while the given
+    /// autocommand `events` are triggered
+    ///
+    /// See `Nvim::debounce_autocmd` for details.
+    pub fn throttle_autocmd<F>(
+        &mut self,
+        events: &[&str],
+        interval: Duration,
+        callback: F,
+    ) -> Result<AutocmdDebouncer, Error>
+    where
+        F: FnMut(AutocmdEvent) + Send + 'static,
+    {
+        self.autocmd_debouncer(events, Debouncer::throttle(interval, callback))
+    }
+
+    fn autocmd_debouncer(
+        &mut self,
+        events: &[&str],
+        debouncer: Debouncer<AutocmdEvent>,
+    ) -> Result<AutocmdDebouncer, Error> {
+        let channel = self
+            .get_api_info()?
+            .first()
+            .and_then(|id| id.as_i64())
+            .ok_or_else(|| Error::DecodingError("API info is missing the channel".to_string()))?;
+
+        let name = format!(
+            "rsnvim_debounce_{}",
+            AUGROUP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let augroup = self.create_augroup(
+            name.as_str(),
+            vec![(Value::from("clear"), Value::from(true))],
+        )?;
+
+        // Subscribe before creating the autocommands so no events are missed
+        let notifications = self.subscribe_events(&[&name]);
+        let inner = debouncer.inner.clone();
+        thread::spawn(move || {
+            for notification in notifications {
+                if let Some(event) = AutocmdEvent::from_params(&notification.params) {
+                    if !inner.trigger(event) {
+                        return;
+                    }
+                }
+            }
+        });
+
+        for event in events {
+            let command = format!(
+                "call rpcnotify({}, '{}', '{}', str2nr(expand('<abuf>')))",
+                channel, name, event
+            );
+            self.create_autocmd(
+                Value::from(*event),
+                vec![
+                    (Value::from("group"), Value::from(augroup)),
+                    (Value::from("command"), Value::from(command)),
+                ],
+            )?;
+        }
+
+        Ok(AutocmdDebouncer { debouncer, augroup })
+    }
+}
@@ -0,0 +1,146 @@
+//! Warnings for calls to API functions the connected Neovim has deprecated
+//!
+//! Once the API metadata has been fetched, calls to functions it marks
+//! deprecated are reported the first time each is called, even if these
+//! bindings were generated before the deprecation. The warning is logged
+//! with the `log` feature and passed to a callback set with
+//! `Session::on_deprecated`, along with the function to use instead when
+//! it is known. `Session::set_deprecation_policy` can instead silence the
+//! warnings, or reject the calls outright to find them in tests.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rsnvim::deprecation::DeprecationPolicy;
+//! use rsnvim::session::Session;
+//!
+//! let session = Session::from_tcp("127.0.0.1:6666").unwrap();
+//! session.start_event_loop(None, None);
+//! session.on_deprecated(|deprecation| {
+//!     eprintln!("{}", deprecation);
+//! });
+//!
+//! // In tests, fail on any deprecated call
+//! session.set_deprecation_policy(DeprecationPolicy::Deny);
+//! ```
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use crate::error::Error;
+
+type DeprecationCallback = Box<dyn Fn(&Deprecation) + Send>;
+
+/// The functions known to replace deprecated API functions
+const REPLACEMENTS: &[(&str, &str)] = &[
+    ("nvim_buf_get_option", "nvim_get_option_value"),
+    ("nvim_buf_set_option", "nvim_set_option_value"),
+    ("nvim_command_output", "nvim_exec2"),
+    ("nvim_exec", "nvim_exec2"),
+    ("nvim_execute_lua", "nvim_exec_lua"),
+    ("nvim_get_hl_by_id", "nvim_get_hl"),
+    ("nvim_get_hl_by_name", "nvim_get_hl"),
+    ("nvim_get_option", "nvim_get_option_value"),
+    ("nvim_get_option_info", "nvim_get_option_info2"),
+    ("nvim_set_option", "nvim_set_option_value"),
+    ("nvim_win_get_option", "nvim_get_option_value"),
+    ("nvim_win_set_option", "nvim_set_option_value"),
+];
+
+/// Deprecated functions used by the crate itself, e.g. by `CallBatch`,
+/// which aren't reported as the caller can't avoid them
+const INTERNAL: &[&str] = &["nvim_call_atomic"];
+
+/// What to do when a deprecated API function is called
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DeprecationPolicy {
+    /// Warn the first time each deprecated function is called
+    #[default]
+    Warn,
+    /// Call deprecated functions without warning
+    Ignore,
+    /// Reject calls to deprecated functions with
+    /// `Error::UnsupportedVersion`, without sending them
+    Deny,
+}
+
+/// A call to a deprecated API function, as passed to the callback set with
+/// `Session::on_deprecated`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Deprecation {
+    pub method: String,
+    /// The API level the function was deprecated in
+    pub deprecated_since: u64,
+    /// The function to use instead, if known
+    pub replacement: Option<&'static str>,
+}
+
+impl std::fmt::Display for Deprecation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} is deprecated since API level {}",
+            self.method, self.deprecated_since
+        )?;
+        match self.replacement {
+            Some(replacement) => write!(f, ", use {} instead", replacement),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The function known to replace the deprecated API function `method`
+pub fn replacement(method: &str) -> Option<&'static str> {
+    REPLACEMENTS
+        .iter()
+        .find(|(deprecated, _)| *deprecated == method)
+        .map(|(_, replacement)| *replacement)
+}
+
+/// The deprecation policy of a session, and the functions warned about
+#[derive(Default)]
+pub(crate) struct Deprecations {
+    policy: Mutex<DeprecationPolicy>,
+    warned: Mutex<HashSet<String>>,
+    on_deprecated: Mutex<Option<DeprecationCallback>>,
+}
+
+impl Deprecations {
+    /// Apply the policy to a call to `method`, deprecated since API level
+    /// `since`
+    pub(crate) fn check(&self, method: &str, since: u64) -> Result<(), Error> {
+        if INTERNAL.contains(&method) {
+            return Ok(());
+        }
+        let deprecation = Deprecation {
+            method: method.to_string(),
+            deprecated_since: since,
+            replacement: replacement(method),
+        };
+        match *self.policy.lock().unwrap() {
+            DeprecationPolicy::Ignore => return Ok(()),
+            DeprecationPolicy::Deny => {
+                return Err(Error::UnsupportedVersion(deprecation.to_string()))
+            }
+            DeprecationPolicy::Warn => {}
+        }
+        if !self.warned.lock().unwrap().insert(method.to_string()) {
+            return Ok(());
+        }
+
+        #[cfg(feature = "log")]
+        log::warn!(target: "rsnvim::deprecation", "{}", deprecation);
+
+        if let Some(callback) = &*self.on_deprecated.lock().unwrap() {
+            callback(&deprecation);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn set_policy(&self, policy: DeprecationPolicy) {
+        *self.policy.lock().unwrap() = policy;
+    }
+
+    pub(crate) fn on_deprecated(&self, callback: DeprecationCallback) {
+        *self.on_deprecated.lock().unwrap() = Some(callback);
+    }
+}
@@ -15,22 +15,172 @@ pub enum Error {
     MpscError(String),
     /// An error when an RPC method is not implemented
     NotImplemented(String),
+    /// An error when a decoded RPC message exceeds the configured limits
+    LimitExceeded(String),
+    /// An error when a response is received for an unknown request
+    UnexpectedResponse(String),
+    /// An error when the connected Neovim is too old for the requested API
+    UnsupportedVersion(String),
+    /// An error returned by Neovim when calling an API method
+    RpcError(String),
+    /// An error when the arguments to an API function don't match its
+    /// parameters
+    InvalidArgument(String),
+    /// An error when the arguments of a call don't match the parameters of
+    /// the API function `method`
+    InvalidArgs {
+        method: String,
+        /// The parameter, or number of arguments, the function expects
+        expected: String,
+        /// The argument, or number of arguments, received
+        got: String,
+    },
+    /// An error when a blocking call is made from a thread marked with
+    /// `blocking::mark_ui_thread`
+    BlockingCall(String),
+    /// An error when reading or writing a file with `storage::Storage`
+    StorageError(String),
+    /// An error when a sandboxed expression breaks the sandbox's
+    /// restrictions
+    SandboxViolation(String),
+    /// An error when the Neovim process being connected to exits before
+    /// its socket accepts a connection
+    ProcessExited(String),
+    /// An error when the incoming stream no longer holds valid RPC messages,
+    /// e.g. after a corrupt or partial message, so the connection can't be
+    /// used
+    StreamCorrupted(String),
+    /// An error when a request or notification handler panicked
+    HandlerPanicked(String),
+    /// An error when a `ReadOnlySession` is asked to call a method which
+    /// isn't allowed, or may change Neovim's state
+    ReadOnly(String),
+    /// An error connecting, or reading from or writing to the connection
+    ///
+    /// Like the other wrapped errors, it is displayed as the original error
+    /// and its `source` is the original error's source.
+    IoError(std::io::Error),
+    /// An error when reading MessagePack
+    MsgpackDecodeError(rmpv::decode::Error),
+    /// An error when writing MessagePack
+    MsgpackEncodeError(rmpv::encode::Error),
+}
+
+impl Error {
+    /// Whether the error is a call, connection or I/O timing out
+    pub fn is_timeout(&self) -> bool {
+        match self {
+            Error::TimeoutError(_) => true,
+            Error::IoError(error) => matches!(
+                error.kind(),
+                std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+            ),
+            _ => false,
+        }
+    }
+
+    /// Whether the error means the connection to Neovim is closed or can't
+    /// be used, so reconnecting is the only way to continue
+    pub fn is_disconnected(&self) -> bool {
+        match self {
+            Error::ConnectionError(_) | Error::StreamCorrupted(_) | Error::ProcessExited(_) => true,
+            Error::IoError(error) => !matches!(
+                error.kind(),
+                std::io::ErrorKind::TimedOut
+                    | std::io::ErrorKind::WouldBlock
+                    | std::io::ErrorKind::Interrupted
+            ),
+            _ => false,
+        }
+    }
+}
+
+/// The kind of an asynchronous error reported by Neovim
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AsyncErrorKind {
+    /// An error raised while running a method
+    Exception,
+    /// An invalid argument or call
+    Validation,
+    /// An error type unknown to these bindings
+    Other(i64),
+}
+
+/// An error Neovim reported using an `nvim_error_event` notification
+///
+/// These are sent for errors which can't be returned in a response, e.g.
+/// when a notification sent to Neovim fails.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AsyncError {
+    pub kind: AsyncErrorKind,
+    pub message: String,
+}
+
+impl AsyncError {
+    /// Decode the parameters of an `nvim_error_event` notification, which
+    /// have the form [type, message]
+    pub fn from_params(params: &[Value]) -> Option<Self> {
+        let kind = match params.first()?.as_i64()? {
+            0 => AsyncErrorKind::Exception,
+            1 => AsyncErrorKind::Validation,
+            other => AsyncErrorKind::Other(other),
+        };
+        Some(AsyncError {
+            kind,
+            message: params.get(1)?.as_str()?.to_string(),
+        })
+    }
+}
+
+impl std::fmt::Display for AsyncError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
 }
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match &self {
-                Error::ConnectionError(err) => err,
-                Error::DecodingError(err) => err,
-                Error::EncodingError(err) => err,
-                Error::TimeoutError(err) => err,
-                Error::MpscError(err) => err,
-                Error::NotImplemented(err) => err,
-            }
-        )
+        match &self {
+            Error::ConnectionError(err)
+            | Error::DecodingError(err)
+            | Error::EncodingError(err)
+            | Error::TimeoutError(err)
+            | Error::MpscError(err)
+            | Error::NotImplemented(err)
+            | Error::LimitExceeded(err)
+            | Error::UnexpectedResponse(err)
+            | Error::UnsupportedVersion(err)
+            | Error::RpcError(err)
+            | Error::InvalidArgument(err)
+            | Error::BlockingCall(err)
+            | Error::StorageError(err)
+            | Error::SandboxViolation(err)
+            | Error::ProcessExited(err)
+            | Error::StreamCorrupted(err)
+            | Error::HandlerPanicked(err)
+            | Error::ReadOnly(err) => write!(f, "{}", err),
+            Error::InvalidArgs {
+                method,
+                expected,
+                got,
+            } => write!(f, "{} expects {} but received {}", method, expected, got),
+            // Wrapped errors are transparent, giving their own message and
+            // source
+            Error::IoError(err) => write!(f, "{}", err),
+            Error::MsgpackDecodeError(err) => write!(f, "{}", err),
+            Error::MsgpackEncodeError(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::IoError(err) => err.source(),
+            Error::MsgpackDecodeError(err) => err.source(),
+            Error::MsgpackEncodeError(err) => err.source(),
+            _ => None,
+        }
     }
 }
 
@@ -42,18 +192,18 @@ impl From<Error> for Value {
 
 impl From<std::io::Error> for Error {
     fn from(value: std::io::Error) -> Self {
-        Self::ConnectionError(value.to_string())
+        Self::IoError(value)
     }
 }
 
 impl From<rmpv::decode::Error> for Error {
     fn from(value: rmpv::decode::Error) -> Self {
-        Self::DecodingError(value.to_string())
+        Self::MsgpackDecodeError(value)
     }
 }
 
 impl From<rmpv::encode::Error> for Error {
     fn from(value: rmpv::encode::Error) -> Self {
-        Self::EncodingError(value.to_string())
+        Self::MsgpackEncodeError(value)
     }
 }
@@ -1,14 +1,35 @@
 use rmpv::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
 
+use crate::api::{AsValue, FromValue, Nvim};
 use crate::error::Error;
 
+/// An RPC notification received from Neovim
+///
+/// Delivered to each subscriber registered with `Session::subscribe`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Notification {
+    pub method: String,
+    pub params: Vec<Value>,
+}
+
 /// A trait for handling incoming RPC requests
 ///
 /// Implementing this trait allows users to define custom RPC methods that can be
 /// called from Neovim (e.g. as part a custom user command).
+///
+/// Handlers are given an `Nvim` handle sharing the session's connection, which
+/// can be used to call back into Neovim while handling the request.
 pub trait RequestHandler {
     fn handle_request(
         &self,
+        _nvim: &mut Nvim,
         _msgid: u64,
         method: String,
         _params: Vec<Value>,
@@ -21,8 +42,11 @@ pub trait RequestHandler {
 ///
 /// Implementing this trait allows users to respond to notifications that are sent
 /// from Neovim (e.g. as part of a custom user command).
+///
+/// Handlers are given an `Nvim` handle sharing the session's connection, which
+/// can be used to call back into Neovim while handling the notification.
 pub trait NotificationHandler {
-    fn handle_notification(&self, _method: String, _params: Vec<Value>) {}
+    fn handle_notification(&self, _nvim: &mut Nvim, _method: String, _params: Vec<Value>) {}
 }
 
 #[derive(Default)]
@@ -37,3 +61,526 @@ impl DefaultHandler {
 impl RequestHandler for DefaultHandler {}
 
 impl NotificationHandler for DefaultHandler {}
+
+/// A trait for handling incoming RPC requests with mutable state
+///
+/// Handlers are shared with the event loop, so take `&self`. Wrapping a type
+/// implementing this trait in a `Mutex`, or an `Arc<Mutex<_>>` to keep access
+/// to its state, allows it to update its fields with `&mut self` instead.
+/// The lock is held while each message is handled, so when notifications
+/// run on several threads, e.g. with a `NotificationPool`, they are handled
+/// one at a time. A handler which panics doesn't poison the lock for later
+/// messages.
+pub trait RequestHandlerMut {
+    fn handle_request(
+        &mut self,
+        _nvim: &mut Nvim,
+        _msgid: u64,
+        method: String,
+        _params: Vec<Value>,
+    ) -> Result<Value, Error> {
+        Err(Error::NotImplemented(method))
+    }
+}
+
+/// A trait for handling incoming RPC notifications with mutable state
+///
+/// See `RequestHandlerMut` for how these are registered.
+///
+/// # Example
+///
+/// ```no_run
+/// use rmpv::Value;
+/// use rsnvim::api::Nvim;
+/// use rsnvim::handler::NotificationHandlerMut;
+/// use std::sync::{Arc, Mutex};
+///
+/// #[derive(Default)]
+/// struct Recorder {
+///     events: Vec<String>,
+/// }
+///
+/// impl NotificationHandlerMut for Recorder {
+///     fn handle_notification(
+///         &mut self,
+///         _nvim: &mut Nvim,
+///         method: String,
+///         _params: Vec<Value>,
+///     ) {
+///         self.events.push(method);
+///     }
+/// }
+///
+/// let recorder = Arc::new(Mutex::new(Recorder::default()));
+/// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+/// nvim.start_event_loop(None, Some(Box::new(recorder.clone())));
+///
+/// // Later, e.g. once the session ends
+/// println!("{:?}", recorder.lock().unwrap().events);
+/// ```
+pub trait NotificationHandlerMut {
+    fn handle_notification(&mut self, _nvim: &mut Nvim, _method: String, _params: Vec<Value>) {}
+}
+
+impl<H: RequestHandlerMut> RequestHandler for Mutex<H> {
+    fn handle_request(
+        &self,
+        nvim: &mut Nvim,
+        msgid: u64,
+        method: String,
+        params: Vec<Value>,
+    ) -> Result<Value, Error> {
+        self.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .handle_request(nvim, msgid, method, params)
+    }
+}
+
+impl<H: RequestHandlerMut> RequestHandler for Arc<Mutex<H>> {
+    fn handle_request(
+        &self,
+        nvim: &mut Nvim,
+        msgid: u64,
+        method: String,
+        params: Vec<Value>,
+    ) -> Result<Value, Error> {
+        RequestHandler::handle_request(&**self, nvim, msgid, method, params)
+    }
+}
+
+impl<H: NotificationHandlerMut> NotificationHandler for Mutex<H> {
+    fn handle_notification(&self, nvim: &mut Nvim, method: String, params: Vec<Value>) {
+        self.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .handle_notification(nvim, method, params)
+    }
+}
+
+impl<H: NotificationHandlerMut> NotificationHandler for Arc<Mutex<H>> {
+    fn handle_notification(&self, nvim: &mut Nvim, method: String, params: Vec<Value>) {
+        NotificationHandler::handle_notification(&**self, nvim, method, params)
+    }
+}
+
+/// Trait to convert the parameters of an RPC message to a Rust type
+///
+/// Implemented for `Vec<Value>`, which takes the parameters as they are, and
+/// for tuples of `FromValue` types, which require an exact parameter count.
+pub trait FromArgs: Sized {
+    fn from_args(params: Vec<Value>) -> Result<Self, Error>;
+}
+
+impl FromArgs for Vec<Value> {
+    fn from_args(params: Vec<Value>) -> Result<Self, Error> {
+        Ok(params)
+    }
+}
+
+/// Macro to implement FromArgs for a tuple of FromValue types
+macro_rules! impl_fromargs_tuple {
+    ($count:expr; $($arg:ident),*) => {
+        impl<$($arg: FromValue),*> FromArgs for ($($arg,)*) {
+            #[allow(unused_mut, unused_variables)]
+            fn from_args(params: Vec<Value>) -> Result<Self, Error> {
+                if params.len() != $count {
+                    return Err(Error::DecodingError(format!(
+                        "Expected {} parameters but received {}",
+                        $count,
+                        params.len()
+                    )));
+                }
+                let mut params = params.into_iter();
+                Ok(($($arg::from_value(params.next().unwrap())?,)*))
+            }
+        }
+    };
+}
+
+impl_fromargs_tuple!(0;);
+impl_fromargs_tuple!(1; A);
+impl_fromargs_tuple!(2; A, B);
+impl_fromargs_tuple!(3; A, B, C);
+impl_fromargs_tuple!(4; A, B, C, D);
+impl_fromargs_tuple!(5; A, B, C, D, E);
+impl_fromargs_tuple!(6; A, B, C, D, E, F);
+
+/// A function exported as an RPC method with `#[rsnvim::export]`
+///
+/// Implemented by the attribute for a type sharing the function's name.
+pub trait Export {
+    /// The name of the RPC method
+    const METHOD: &'static str;
+
+    /// Register the function's handler with `router`
+    fn register(router: &mut HandlerRouter);
+}
+
+type RequestFn = Box<dyn Fn(Vec<Value>, &mut Nvim) -> Result<Value, Error> + Send>;
+type NotificationFn = Box<dyn Fn(Vec<Value>, &mut Nvim) -> Result<(), Error> + Send + Sync>;
+type KeyFn = Box<dyn Fn(&[Value]) -> Option<Value> + Send + Sync>;
+type Job = (String, Vec<Value>);
+
+/// How long an idle worker waits before checking the connection is open
+const WORKER_IDLE: Duration = Duration::from_secs(1);
+
+/// Dispatches incoming requests to the closure registered for their method
+///
+/// Created by `HandlerRouter::into_handlers`.
+#[derive(Default)]
+pub struct RequestRouter {
+    routes: HashMap<String, RequestFn>,
+}
+
+impl RequestHandler for RequestRouter {
+    fn handle_request(
+        &self,
+        nvim: &mut Nvim,
+        _msgid: u64,
+        method: String,
+        params: Vec<Value>,
+    ) -> Result<Value, Error> {
+        match self.routes.get(&method) {
+            Some(route) => route(params, nvim),
+            None => Err(Error::NotImplemented(method)),
+        }
+    }
+}
+
+/// Dispatches incoming notifications to the closures registered for their
+/// method
+///
+/// Created by `HandlerRouter::into_handlers`. Errors, including parameters
+/// which couldn't be converted, are passed to the error handler if one was
+/// registered with `HandlerRouter::on_notification_error`.
+#[derive(Default)]
+pub struct NotificationRouter {
+    routes: HashMap<String, Vec<NotificationFn>>,
+    on_error: Option<Box<dyn Fn(String, Error) + Send + Sync>>,
+}
+
+impl NotificationHandler for NotificationRouter {
+    fn handle_notification(&self, nvim: &mut Nvim, method: String, params: Vec<Value>) {
+        for route in self.routes.get(&method).into_iter().flatten() {
+            if let Err(error) = route(params.clone(), nvim) {
+                if let Some(on_error) = &self.on_error {
+                    on_error(method.clone(), error);
+                }
+            }
+        }
+    }
+}
+
+/// Runs a notification handler on a pool of worker threads
+///
+/// Each notification has an ordering key, which defaults to its method.
+/// Notifications with the same key are handled in the order they were
+/// received, while those with different keys may be handled in parallel.
+/// Keys are assigned to workers by their hash, so unrelated keys can share
+/// a worker and wait on each other.
+///
+/// Workers are started on the first notification for them, each with its
+/// own `Nvim` handle sharing the session's connection, and exit once the
+/// connection is closed.
+///
+/// # Example
+///
+/// ```no_run
+/// use rmpv::Value;
+/// use rsnvim::api::Nvim;
+/// use rsnvim::handler::{NotificationHandler, NotificationPool};
+///
+/// struct Highlighter {}
+///
+/// impl NotificationHandler for Highlighter {
+///     fn handle_notification(&self, nvim: &mut Nvim, method: String, params: Vec<Value>) {
+///         // Update the highlights of the buffer in `params[0]`
+///     }
+/// }
+///
+/// // Each buffer's events are handled in order, different buffers in parallel
+/// let pool = NotificationPool::new(4, Highlighter {})
+///     .ordering_key("nvim_buf_lines_event", |params| params.first().cloned())
+///     .ordering_key("nvim_buf_changedtick_event", |params| params.first().cloned());
+///
+/// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+/// nvim.start_event_loop(None, Some(Box::new(pool)));
+/// ```
+pub struct NotificationPool<H> {
+    handler: Arc<H>,
+    keys: HashMap<String, KeyFn>,
+    /// The number of workers, so choosing one needn't lock `workers`
+    threads: usize,
+    workers: Arc<Mutex<Vec<Option<mpsc::Sender<Job>>>>>,
+}
+
+impl<H> NotificationPool<H>
+where
+    H: NotificationHandler + Send + Sync + 'static,
+{
+    /// Run `handler` on up to `threads` worker threads
+    pub fn new(threads: usize, handler: H) -> Self {
+        let threads = threads.max(1);
+        NotificationPool {
+            handler: Arc::new(handler),
+            keys: HashMap::new(),
+            threads,
+            workers: Arc::new(Mutex::new(vec![None; threads])),
+        }
+    }
+
+    /// Order notifications for `method` by the key `key` returns for their
+    /// parameters, e.g. the buffer of a buffer event
+    ///
+    /// Notifications whose key is `None` are ordered by their method. Keys
+    /// are compared across methods, so events for the same buffer are
+    /// handled in order even if they are different methods.
+    pub fn ordering_key<F>(mut self, method: &str, key: F) -> Self
+    where
+        F: Fn(&[Value]) -> Option<Value> + Send + Sync + 'static,
+    {
+        self.keys.insert(method.to_string(), Box::new(key));
+        self
+    }
+
+    /// Get the index of the worker handling notifications for `method`
+    fn worker(&self, method: &str, params: &[Value]) -> usize {
+        let mut hasher = DefaultHasher::new();
+        match self.keys.get(method).and_then(|key| key(params)) {
+            // Keys are hashed by their encoding, as different values can
+            // display the same, e.g. `[1]` as an array and as binary
+            Some(key) => {
+                let mut bytes = Vec::new();
+                // Writing to a Vec can't fail
+                rmpv::encode::write_value(&mut bytes, &key).unwrap();
+                bytes.hash(&mut hasher)
+            }
+            None => method.hash(&mut hasher),
+        }
+        (hasher.finish() % self.threads as u64) as usize
+    }
+
+    /// Start a worker thread with `job` as its first job
+    fn spawn_worker(&self, index: usize, nvim: Nvim, job: Job) -> mpsc::Sender<Job> {
+        let (sender, receiver) = mpsc::channel();
+        sender.send(job).unwrap();
+        let handler = self.handler.clone();
+        let workers = self.workers.clone();
+        let mut nvim = nvim;
+        thread::spawn(move || loop {
+            let (method, params) = match receiver.recv_timeout(WORKER_IDLE) {
+                Ok(job) => job,
+                Err(RecvTimeoutError::Timeout) if nvim.is_alive() => continue,
+                Err(RecvTimeoutError::Timeout) => {
+                    // Jobs are only sent while the lock is held, so none can
+                    // be lost between checking the queue and exiting
+                    let mut workers = workers.lock().unwrap();
+                    match receiver.try_recv() {
+                        Ok(job) => job,
+                        Err(_) => {
+                            workers[index] = None;
+                            return;
+                        }
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => return,
+            };
+            handler.handle_notification(&mut nvim, method, params);
+        });
+        sender
+    }
+}
+
+impl<H> NotificationHandler for NotificationPool<H>
+where
+    H: NotificationHandler + Send + Sync + 'static,
+{
+    fn handle_notification(&self, nvim: &mut Nvim, method: String, params: Vec<Value>) {
+        let index = self.worker(&method, &params);
+        let mut workers = self.workers.lock().unwrap();
+        let mut job = (method, params);
+        if let Some(sender) = &workers[index] {
+            match sender.send(job) {
+                Ok(()) => return,
+                // The worker exited, e.g. because a handler panicked
+                Err(mpsc::SendError(unsent)) => job = unsent,
+            }
+        }
+        workers[index] = Some(self.spawn_worker(index, nvim.clone(), job));
+    }
+}
+
+/// Routes requests and notifications to closures registered per method
+///
+/// Parameters are converted to the closure's argument type using `FromArgs`,
+/// and request results are converted to a `Value` using `AsValue`.
+/// Conversion and handler errors are returned to Neovim as the request's
+/// error.
+///
+/// # Example
+///
+/// ```no_run
+/// use rsnvim::api::Nvim;
+/// use rsnvim::handler::HandlerRouter;
+///
+/// let mut router = HandlerRouter::new();
+/// router
+///     .on_request("add", |(a, b): (i64, i64), _nvim| Ok(a + b))
+///     .on_request("open_file", |(path,): (String,), nvim| {
+///         nvim.command(format!("edit {}", path))
+///     })
+///     .on_notification("log", |(message,): (String,), _nvim| {
+///         println!("{}", message);
+///         Ok(())
+///     });
+///
+/// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+/// let (requests, notifications) = router.into_handlers();
+/// nvim.start_event_loop(requests, notifications);
+/// ```
+#[derive(Default)]
+pub struct HandlerRouter {
+    requests: RequestRouter,
+    notifications: NotificationRouter,
+    threads: usize,
+    keys: HashMap<String, KeyFn>,
+}
+
+impl HandlerRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle requests for `method` with `handler`
+    ///
+    /// Registering a method again replaces the previous handler.
+    pub fn on_request<P, T, F>(&mut self, method: &str, handler: F) -> &mut Self
+    where
+        P: FromArgs,
+        T: AsValue,
+        F: Fn(P, &mut Nvim) -> Result<T, Error> + Send + 'static,
+    {
+        let route: RequestFn = Box::new(move |params, nvim| {
+            let params = P::from_args(params)?;
+            handler(params, nvim).map(|result| result.convert())
+        });
+        self.requests.routes.insert(method.to_string(), route);
+        self
+    }
+
+    /// Handle notifications for `method` with `handler`
+    ///
+    /// Several handlers may be registered for the same method, and are run in
+    /// the order they were registered.
+    pub fn on_notification<P, F>(&mut self, method: &str, handler: F) -> &mut Self
+    where
+        P: FromArgs,
+        F: Fn(P, &mut Nvim) -> Result<(), Error> + Send + Sync + 'static,
+    {
+        let route: NotificationFn = Box::new(move |params, nvim| {
+            let params = P::from_args(params)?;
+            handler(params, nvim)
+        });
+        self.notifications
+            .routes
+            .entry(method.to_string())
+            .or_default()
+            .push(route);
+        self
+    }
+
+    /// Register a function exported with `#[rsnvim::export]`
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::handler::HandlerRouter;
+    ///
+    /// #[rsnvim::export]
+    /// fn add(a: i64, b: i64) -> i64 {
+    ///     a + b
+    /// }
+    ///
+    /// let mut router = HandlerRouter::new();
+    /// router.export::<add>();
+    /// ```
+    pub fn export<E: Export>(&mut self) -> &mut Self {
+        E::register(self);
+        self
+    }
+
+    /// Handle errors returned by notification handlers
+    ///
+    /// Neovim doesn't wait for a response to notifications, so these errors
+    /// are otherwise ignored.
+    pub fn on_notification_error<F>(&mut self, handler: F) -> &mut Self
+    where
+        F: Fn(String, Error) + Send + Sync + 'static,
+    {
+        self.notifications.on_error = Some(Box::new(handler));
+        self
+    }
+
+    /// Run notification handlers on `threads` worker threads rather than the
+    /// event loop's dispatch thread
+    ///
+    /// Notifications are ordered by their method unless an ordering key is
+    /// registered with `ordering_key`. See `NotificationPool` for details.
+    pub fn worker_threads(&mut self, threads: usize) -> &mut Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Order notifications for `method` by the key `key` returns for their
+    /// parameters when running on worker threads
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rmpv::Value;
+    /// use rsnvim::handler::HandlerRouter;
+    ///
+    /// let mut router = HandlerRouter::new();
+    /// router
+    ///     .on_notification("nvim_buf_lines_event", |params: Vec<Value>, _nvim| {
+    ///         // Lines of the same buffer are always handled in order
+    ///         Ok(())
+    ///     })
+    ///     .ordering_key("nvim_buf_lines_event", |params| params.first().cloned())
+    ///     .worker_threads(4);
+    /// ```
+    pub fn ordering_key<F>(&mut self, method: &str, key: F) -> &mut Self
+    where
+        F: Fn(&[Value]) -> Option<Value> + Send + Sync + 'static,
+    {
+        self.keys.insert(method.to_string(), Box::new(key));
+        self
+    }
+
+    /// The methods with a request handler
+    pub fn request_methods(&self) -> impl Iterator<Item = &str> {
+        self.requests.routes.keys().map(String::as_str)
+    }
+
+    /// The methods with a notification handler
+    pub fn notification_methods(&self) -> impl Iterator<Item = &str> {
+        self.notifications.routes.keys().map(String::as_str)
+    }
+
+    /// Split the router into handlers for `Nvim::start_event_loop`
+    #[allow(clippy::type_complexity)]
+    pub fn into_handlers(
+        self,
+    ) -> (
+        Option<Box<dyn RequestHandler + Send>>,
+        Option<Box<dyn NotificationHandler + Send>>,
+    ) {
+        let notifications: Box<dyn NotificationHandler + Send> = if self.threads > 0 {
+            let mut pool = NotificationPool::new(self.threads, self.notifications);
+            pool.keys = self.keys;
+            Box::new(pool)
+        } else {
+            Box::new(self.notifications)
+        };
+        (Some(Box::new(self.requests)), Some(notifications))
+    }
+}
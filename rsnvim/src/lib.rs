@@ -2,11 +2,82 @@
 //!
 //! `rsnvim` is a crate used to interact with Neovim's API via Rust.
 pub mod api;
+pub mod autocmd;
+pub mod blocking;
+pub mod bridge;
+mod build_info;
 pub mod client;
+mod coalesce;
+pub mod debounce;
+pub mod deprecation;
 pub mod error;
 pub mod handler;
+pub mod metrics;
+pub mod params;
+pub mod read_only;
+pub mod report;
 pub mod rpc;
+pub mod rplugin;
+#[cfg(feature = "serde")]
+pub mod serde;
 pub mod session;
+pub mod shutdown;
+pub mod storage;
+pub mod value;
+#[cfg(all(target_arch = "wasm32", feature = "websocket"))]
+pub mod websocket;
+pub mod yank_sync;
+
+/// Export a function as an RPC method which can be called from Neovim
+///
+/// The function's parameters are decoded from the RPC parameters using
+/// `FromValue`, and its result is returned using `AsValue`. A `&mut Nvim`
+/// parameter is passed the handler's `Nvim` handle instead. Functions may
+/// return a plain value or a `Result` whose error converts into
+/// `rsnvim::error::Error`.
+///
+/// The method is named after the function unless given with `name`, and is
+/// handled as a notification rather than a request with `notification`.
+/// Exported functions are registered with `HandlerRouter::export`.
+///
+/// # Example
+///
+/// ```no_run
+/// use rsnvim::api::Nvim;
+/// use rsnvim::error::Error;
+/// use rsnvim::handler::HandlerRouter;
+///
+/// #[rsnvim::export]
+/// fn add(a: i64, b: i64) -> i64 {
+///     a + b
+/// }
+///
+/// #[rsnvim::export(name = "open")]
+/// fn open_file(nvim: &mut Nvim, path: String) -> Result<(), Error> {
+///     nvim.command(format!("edit {}", path))
+/// }
+///
+/// #[rsnvim::export(notification)]
+/// fn log(message: String) {
+///     println!("{}", message);
+/// }
+///
+/// let mut router = HandlerRouter::new();
+/// router.export::<add>().export::<open_file>().export::<log>();
+///
+/// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+/// let (requests, notifications) = router.into_handlers();
+/// nvim.start_event_loop(requests, notifications);
+/// ```
+pub use rsnvim_macros::export;
+
+pub use build_info::{build_info, BuildInfo};
+#[cfg(all(unix, feature = "signals"))]
+pub use shutdown::{run_until_shutdown, ShutdownCause};
+
+// Used by the `lua!` macro
+#[doc(hidden)]
+pub use rmpv;
 
 #[cfg(test)]
 mod tests {}
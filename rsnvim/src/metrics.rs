@@ -0,0 +1,181 @@
+//! Latency and error metrics of the RPC calls made to Neovim
+//!
+//! Every request sent with `Session::call` or `Session::call_async` is
+//! timed from when it is sent until its response arrives, and counted per
+//! method. Calls slower than the session's threshold, and calls which fail,
+//! are logged with the `log` feature, and every call can be passed to a
+//! callback set with `Session::on_call`, e.g. to record it with `tracing`.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rsnvim::session::Session;
+//! use std::time::Duration;
+//!
+//! let session = Session::from_tcp("127.0.0.1:6666").unwrap();
+//! session.start_event_loop(None, None);
+//! session.set_slow_call_threshold(Duration::from_millis(50));
+//! session.on_call(|call| {
+//!     if call.slow {
+//!         eprintln!("{} took {:?}", call.method, call.latency);
+//!     }
+//! });
+//!
+//! // ... after running for a while
+//! let mut metrics: Vec<_> = session.metrics().into_iter().collect();
+//! metrics.sort_by_key(|(_, m)| std::cmp::Reverse(m.total_latency));
+//! for (method, m) in metrics.iter().take(5) {
+//!     println!(
+//!         "{}: {} calls, {:?} mean, {:.1}% failed",
+//!         method,
+//!         m.calls,
+//!         m.mean_latency(),
+//!         m.error_rate() * 100.0
+//!     );
+//! }
+//! ```
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How long a call takes before it is considered slow, by default
+pub const DEFAULT_SLOW_CALL_THRESHOLD: Duration = Duration::from_millis(100);
+
+type CallCallback = Box<dyn Fn(&CallRecord) + Send>;
+
+/// The calls made to one method
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MethodMetrics {
+    /// The number of calls which completed, successfully or not
+    pub calls: u64,
+    /// The number of calls which failed, including those still waiting for
+    /// a response when the connection closed
+    pub errors: u64,
+    /// The number of calls slower than the slow call threshold
+    pub slow: u64,
+    pub total_latency: Duration,
+    pub max_latency: Duration,
+}
+
+impl MethodMetrics {
+    /// The mean time from sending a call until its response arrived
+    pub fn mean_latency(&self) -> Duration {
+        match self.calls {
+            0 => Duration::ZERO,
+            calls => Duration::from_nanos((self.total_latency.as_nanos() / calls as u128) as u64),
+        }
+    }
+
+    /// The fraction of calls which failed, from `0.0` to `1.0`
+    pub fn error_rate(&self) -> f64 {
+        match self.calls {
+            0 => 0.0,
+            calls => self.errors as f64 / calls as f64,
+        }
+    }
+}
+
+/// A completed call, as passed to the callback set with `Session::on_call`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CallRecord {
+    pub method: String,
+    pub msgid: u64,
+    /// The time from sending the call until its response arrived, or the
+    /// connection closed
+    pub latency: Duration,
+    /// Whether the call was slower than the slow call threshold
+    pub slow: bool,
+    /// The error the call failed with, if any
+    pub error: Option<String>,
+}
+
+/// The metrics recorded by a client
+pub(crate) struct Metrics {
+    methods: Mutex<HashMap<String, MethodMetrics>>,
+    slow_threshold: Mutex<Duration>,
+    on_call: Mutex<Option<CallCallback>>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics {
+            methods: Mutex::new(HashMap::new()),
+            slow_threshold: Mutex::new(DEFAULT_SLOW_CALL_THRESHOLD),
+            on_call: Mutex::new(None),
+        }
+    }
+}
+
+impl Metrics {
+    /// Record a completed call to `method`
+    pub(crate) fn record(
+        &self,
+        method: &str,
+        msgid: u64,
+        latency: Duration,
+        error: Option<String>,
+    ) {
+        let slow = latency >= *self.slow_threshold.lock().unwrap();
+        {
+            let mut methods = self.methods.lock().unwrap();
+            let metrics = methods.entry(method.to_string()).or_default();
+            metrics.calls += 1;
+            metrics.errors += u64::from(error.is_some());
+            metrics.slow += u64::from(slow);
+            metrics.total_latency += latency;
+            metrics.max_latency = metrics.max_latency.max(latency);
+        }
+
+        #[cfg(feature = "log")]
+        match &error {
+            Some(error) => log::warn!(
+                target: "rsnvim::metrics",
+                "{} (request {}) failed after {:?}: {}",
+                method,
+                msgid,
+                latency,
+                error
+            ),
+            None if slow => log::warn!(
+                target: "rsnvim::metrics",
+                "{} (request {}) was slow, taking {:?}",
+                method,
+                msgid,
+                latency
+            ),
+            None => log::debug!(
+                target: "rsnvim::metrics",
+                "{} (request {}) took {:?}",
+                method,
+                msgid,
+                latency
+            ),
+        }
+
+        if let Some(callback) = &*self.on_call.lock().unwrap() {
+            callback(&CallRecord {
+                method: method.to_string(),
+                msgid,
+                latency,
+                slow,
+                error,
+            });
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> HashMap<String, MethodMetrics> {
+        self.methods.lock().unwrap().clone()
+    }
+
+    pub(crate) fn reset(&self) {
+        self.methods.lock().unwrap().clear();
+    }
+
+    pub(crate) fn set_slow_threshold(&self, threshold: Duration) {
+        *self.slow_threshold.lock().unwrap() = threshold;
+    }
+
+    pub(crate) fn on_call(&self, callback: CallCallback) {
+        *self.on_call.lock().unwrap() = Some(callback);
+    }
+}
@@ -0,0 +1,344 @@
+//! Validation of the parameters passed to RPC handlers
+//!
+//! Handlers given a `Vec<Value>` can describe the parameters they expect
+//! and destructure them in one go, rather than indexing and unwrapping each
+//! one. Errors name the offending parameter, so they make sense to the Lua
+//! code calling the handler.
+//!
+//! # Example
+//!
+//! ```
+//! use rmpv::Value;
+//! use rsnvim::params::ExpectParams;
+//!
+//! let params = vec![Value::from("main.rs"), Value::from(42)];
+//! let (path, line, opts) = params.expect().string().int().optional_map().done().unwrap();
+//! assert_eq!(path, "main.rs");
+//! assert_eq!(line, 42);
+//! assert_eq!(opts, None);
+//!
+//! let params = vec![Value::from("main.rs"), Value::from("42")];
+//! let error = params.expect().string().int().done().unwrap_err();
+//! assert_eq!(
+//!     error.to_string(),
+//!     "Parameter 2: expected an integer but received \"42\""
+//! );
+//! ```
+use rmpv::Value;
+
+use crate::api::FromValue;
+use crate::error::Error;
+
+/// Trait to start validating a list of parameters
+pub trait ExpectParams {
+    /// Describe the expected parameters, in order
+    fn expect(&self) -> Expect<'_, ()>;
+}
+
+impl ExpectParams for [Value] {
+    fn expect(&self) -> Expect<'_, ()> {
+        Expect {
+            params: self,
+            index: 0,
+            result: Ok(()),
+        }
+    }
+}
+
+/// The parameters validated so far, collected into a tuple
+///
+/// Each method checks the next parameter and appends it to the tuple. The
+/// first error is kept and returned by `done`.
+#[must_use = "call `done` to get the parameters"]
+pub struct Expect<'a, T> {
+    params: &'a [Value],
+    index: usize,
+    result: Result<T, Error>,
+}
+
+impl<'a, T> Expect<'a, T> {
+    /// Expect a parameter of any type which can be converted with `FromValue`
+    pub fn arg<X: FromValue>(self) -> Expect<'a, T::Output>
+    where
+        T: Append<X>,
+    {
+        let position = self.index + 1;
+        let value = self.params.get(self.index);
+        self.next(|| match value {
+            Some(value) => X::from_value(value.clone()).map_err(|error| invalid(position, error)),
+            None => Err(Error::InvalidArgument(format!(
+                "Missing parameter {}",
+                position
+            ))),
+        })
+    }
+
+    /// Expect an optional parameter, which may be nil or left out
+    ///
+    /// Only the last parameters may be left out.
+    pub fn optional_arg<X: FromValue>(self) -> Expect<'a, T::Output>
+    where
+        T: Append<Option<X>>,
+    {
+        let position = self.index + 1;
+        let value = self.params.get(self.index);
+        self.next(|| match value {
+            Some(value) => {
+                Option::<X>::from_value(value.clone()).map_err(|error| invalid(position, error))
+            }
+            None => Ok(None),
+        })
+    }
+
+    /// Expect a parameter of any type
+    pub fn value(self) -> Expect<'a, T::Output>
+    where
+        T: Append<Value>,
+    {
+        self.arg()
+    }
+
+    pub fn string(self) -> Expect<'a, T::Output>
+    where
+        T: Append<String>,
+    {
+        self.arg()
+    }
+
+    pub fn int(self) -> Expect<'a, T::Output>
+    where
+        T: Append<i64>,
+    {
+        self.arg()
+    }
+
+    /// Expect a number, which may be an integer as Lua sends whole floats
+    /// as integers
+    pub fn float(self) -> Expect<'a, T::Output>
+    where
+        T: Append<f64>,
+    {
+        self.arg()
+    }
+
+    pub fn bool(self) -> Expect<'a, T::Output>
+    where
+        T: Append<bool>,
+    {
+        self.arg()
+    }
+
+    pub fn array(self) -> Expect<'a, T::Output>
+    where
+        T: Append<Vec<Value>>,
+    {
+        self.arg()
+    }
+
+    /// Expect a dictionary
+    ///
+    /// An empty array is accepted as an empty dictionary, as Neovim sends
+    /// an empty Lua table as an array.
+    pub fn map(self) -> Expect<'a, T::Output>
+    where
+        T: Append<Vec<(Value, Value)>>,
+    {
+        let position = self.index + 1;
+        let value = self.params.get(self.index);
+        self.next(|| match value {
+            Some(value) => to_map(position, value),
+            None => Err(Error::InvalidArgument(format!(
+                "Missing parameter {}",
+                position
+            ))),
+        })
+    }
+
+    pub fn optional_string(self) -> Expect<'a, T::Output>
+    where
+        T: Append<Option<String>>,
+    {
+        self.optional_arg()
+    }
+
+    pub fn optional_int(self) -> Expect<'a, T::Output>
+    where
+        T: Append<Option<i64>>,
+    {
+        self.optional_arg()
+    }
+
+    pub fn optional_float(self) -> Expect<'a, T::Output>
+    where
+        T: Append<Option<f64>>,
+    {
+        self.optional_arg()
+    }
+
+    pub fn optional_bool(self) -> Expect<'a, T::Output>
+    where
+        T: Append<Option<bool>>,
+    {
+        self.optional_arg()
+    }
+
+    pub fn optional_array(self) -> Expect<'a, T::Output>
+    where
+        T: Append<Option<Vec<Value>>>,
+    {
+        self.optional_arg()
+    }
+
+    /// Expect an optional dictionary, like the `opts` of many API functions
+    pub fn optional_map(self) -> Expect<'a, T::Output>
+    where
+        T: Append<Option<Vec<(Value, Value)>>>,
+    {
+        let position = self.index + 1;
+        let value = self.params.get(self.index);
+        self.next(|| match value {
+            None | Some(Value::Nil) => Ok(None),
+            Some(value) => to_map(position, value).map(Some),
+        })
+    }
+
+    /// Finish validating, returning the parameters
+    ///
+    /// Returns `Error::InvalidArgument` if a parameter was invalid or there
+    /// were more parameters than expected.
+    pub fn done(self) -> Result<T, Error> {
+        let values = self.result?;
+        if self.params.len() > self.index {
+            return Err(Error::InvalidArgument(format!(
+                "Expected at most {} parameters but received {}",
+                self.index,
+                self.params.len()
+            )));
+        }
+        Ok(values)
+    }
+
+    /// Move on to the next parameter, appending the result of `check`
+    /// unless an earlier parameter was invalid
+    fn next<X, F>(self, check: F) -> Expect<'a, T::Output>
+    where
+        T: Append<X>,
+        F: FnOnce() -> Result<X, Error>,
+    {
+        Expect {
+            params: self.params,
+            index: self.index + 1,
+            result: self.result.and_then(|values| Ok(values.append(check()?))),
+        }
+    }
+}
+
+/// Convert a dictionary parameter, accepting an empty array
+fn to_map(position: usize, value: &Value) -> Result<Vec<(Value, Value)>, Error> {
+    match value {
+        Value::Array(array) if array.is_empty() => Ok(Vec::new()),
+        value => Vec::<(Value, Value)>::from_value(value.clone())
+            .map_err(|error| invalid(position, error)),
+    }
+}
+
+/// Build the error for the parameter at `position`, counting from 1
+fn invalid(position: usize, error: Error) -> Error {
+    let message = error.to_string();
+    let mut chars = message.chars();
+    let message = match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => message,
+    };
+    Error::InvalidArgument(format!("Parameter {}: {}", position, message))
+}
+
+/// Trait to append a value to a tuple
+pub trait Append<X> {
+    type Output;
+
+    fn append(self, value: X) -> Self::Output;
+}
+
+/// Macro to implement Append for a tuple
+macro_rules! impl_append_tuple {
+    ($($arg:ident),*) => {
+        impl<$($arg,)* X> Append<X> for ($($arg,)*) {
+            type Output = ($($arg,)* X,);
+
+            #[allow(non_snake_case)]
+            fn append(self, value: X) -> Self::Output {
+                let ($($arg,)*) = self;
+                ($($arg,)* value,)
+            }
+        }
+    };
+}
+
+impl_append_tuple!();
+impl_append_tuple!(A);
+impl_append_tuple!(A, B);
+impl_append_tuple!(A, B, C);
+impl_append_tuple!(A, B, C, D);
+impl_append_tuple!(A, B, C, D, E);
+impl_append_tuple!(A, B, C, D, E, F);
+impl_append_tuple!(A, B, C, D, E, F, G);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message<T: std::fmt::Debug>(result: Result<T, Error>) -> String {
+        match result.unwrap_err() {
+            Error::InvalidArgument(message) => message,
+            other => panic!("Expected an invalid argument, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn valid_params() {
+        let params = [Value::from("hi"), Value::from(2), Value::from(0.5)];
+        let (name, count, scale, flag) = params
+            .expect()
+            .string()
+            .int()
+            .float()
+            .optional_bool()
+            .done()
+            .unwrap();
+        assert_eq!((name.as_str(), count, scale, flag), ("hi", 2, 0.5, None));
+
+        // Lua sends an empty table as an array
+        let params = [Value::Array(Vec::new()), Value::Nil];
+        let (map, opts) = params.expect().map().optional_map().done().unwrap();
+        assert!(map.is_empty());
+        assert_eq!(opts, None);
+    }
+
+    #[test]
+    fn invalid_params() {
+        let params = [Value::from("hi"), Value::from("two")];
+        assert_eq!(
+            message(params.expect().string().int().done()),
+            "Parameter 2: expected an integer but received \"two\""
+        );
+        assert_eq!(
+            message(params.expect().string().string().map().done()),
+            "Missing parameter 3"
+        );
+        assert_eq!(
+            message(params.expect().string().done()),
+            "Expected at most 1 parameters but received 2"
+        );
+        // The first invalid parameter is reported
+        assert_eq!(
+            message(params.expect().int().int().done()),
+            "Parameter 1: expected an integer but received \"hi\""
+        );
+        let params = [Value::from(1)];
+        assert_eq!(
+            message(params.expect().optional_map().done()),
+            "Parameter 1: expected a dictionary but received 1"
+        );
+    }
+}
@@ -0,0 +1,333 @@
+//! Sessions which can only read Neovim's state
+//!
+//! A `ReadOnlySession` only sends methods on its allowlist, rejecting any
+//! other with `Error::ReadOnly` before it reaches Neovim, so a dashboard or
+//! monitor given one can attach to an editing session without being able
+//! to change it. By default the `get` and `list` methods and the other
+//! queries like `nvim_buf_line_count` are allowed, along with
+//! `nvim_eval` and `nvim_call_function` of side-effect-free Vimscript.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rsnvim::read_only::ReadOnlySession;
+//! use rsnvim::session::Session;
+//!
+//! let session = Session::from_tcp("127.0.0.1:6666").unwrap();
+//! session.start_event_loop(None, None);
+//!
+//! let monitor = ReadOnlySession::new(session);
+//! let mode = monitor.call("nvim_get_mode", vec![]).unwrap();
+//! let line = monitor.eval("line('.')").unwrap();
+//! assert!(monitor.call("nvim_command", vec!["quit!".into()]).is_err());
+//! ```
+use rmpv::Value;
+use std::sync::{mpsc, Arc};
+
+use crate::api::{ApiInfo, FromValue};
+use crate::client::ResponseHandle;
+use crate::error::Error;
+use crate::handler::Notification;
+use crate::session::Session;
+
+/// The methods allowed by default, where a trailing `*` matches any suffix
+const DEFAULT_ALLOWED: &[&str] = &[
+    "nvim_get_*",
+    "nvim_list_*",
+    "nvim_buf_get_*",
+    "nvim_buf_is_*",
+    "nvim_buf_line_count",
+    "nvim_win_get_*",
+    "nvim_win_is_valid",
+    "nvim_tabpage_get_*",
+    "nvim_tabpage_is_valid",
+    "nvim_tabpage_list_wins",
+    // Checked further by `ReadOnlySession::check`
+    "nvim_eval",
+    "nvim_call_function",
+    "nvim_call_atomic",
+];
+
+/// Methods matching the default allowlist which do change Neovim's state
+const EXCEPTIONS: &[&str] = &[
+    // Creates the highlight group if it doesn't exist
+    "nvim_get_hl_id_by_name",
+];
+
+/// Vimscript functions with no side effects, which may be called by
+/// `nvim_eval` and `nvim_call_function`
+///
+/// Functions which can run commands, like `expand()` with backticks, or
+/// move the cursor, like `search()`, aren't included.
+const PURE_FUNCTIONS: &[&str] = &[
+    "abs",
+    "and",
+    "bufexists",
+    "buflisted",
+    "bufloaded",
+    "bufname",
+    "bufnr",
+    "bufwinid",
+    "bufwinnr",
+    "byteidx",
+    "col",
+    "count",
+    "empty",
+    "escape",
+    "exists",
+    "filereadable",
+    "float2nr",
+    "fnamemodify",
+    "get",
+    "getbufinfo",
+    "getbufline",
+    "getbufvar",
+    "getcharpos",
+    "getcurpos",
+    "getcwd",
+    "getline",
+    "getloclist",
+    "getpos",
+    "getqflist",
+    "getreg",
+    "getreginfo",
+    "getregtype",
+    "gettabinfo",
+    "gettabvar",
+    "gettabwinvar",
+    "getwininfo",
+    "getwinvar",
+    "has",
+    "has_key",
+    "hlexists",
+    "index",
+    "indent",
+    "isdirectory",
+    "join",
+    "json_encode",
+    "keys",
+    "len",
+    "line",
+    "line2byte",
+    "localtime",
+    "max",
+    "min",
+    "mode",
+    "or",
+    "printf",
+    "range",
+    "repeat",
+    "str2float",
+    "str2nr",
+    "strcharpart",
+    "strchars",
+    "strftime",
+    "string",
+    "strlen",
+    "strpart",
+    "strtrans",
+    "strwidth",
+    "substitute",
+    "synID",
+    "synIDattr",
+    "tabpagebuflist",
+    "tabpagenr",
+    "tabpagewinnr",
+    "tolower",
+    "toupper",
+    "trim",
+    "type",
+    "values",
+    "virtcol",
+    "win_getid",
+    "win_id2win",
+    "winbufnr",
+    "wincol",
+    "winheight",
+    "winlayout",
+    "winline",
+    "winnr",
+    "winsaveview",
+    "winwidth",
+    "wordcount",
+    "xor",
+];
+
+/// A session which only sends methods on its allowlist
+///
+/// See the module documentation for an example. Notifications and the
+/// event loop are shared with the wrapped `Session`, which isn't limited,
+/// so only hand the `ReadOnlySession` to the code to be restricted.
+#[derive(Clone)]
+pub struct ReadOnlySession {
+    session: Session,
+    allowed: Arc<Vec<String>>,
+}
+
+impl ReadOnlySession {
+    /// Wrap `session`, allowing the default side-effect-free methods
+    pub fn new(session: Session) -> Self {
+        Self::with_allowlist(session, DEFAULT_ALLOWED)
+    }
+
+    /// Wrap `session`, allowing only `methods`
+    ///
+    /// A trailing `*` matches any suffix, e.g. `nvim_buf_get_*`. Allowing
+    /// `nvim_eval` or `nvim_call_function` still only allows side-effect-free
+    /// Vimscript.
+    pub fn with_allowlist(session: Session, methods: &[&str]) -> Self {
+        ReadOnlySession {
+            session,
+            allowed: Arc::new(methods.iter().map(|m| m.to_string()).collect()),
+        }
+    }
+
+    /// Also allow `method`, which may end with `*` to match any suffix
+    ///
+    /// Only allow methods without side effects, as the session can't tell.
+    pub fn allow(mut self, method: &str) -> Self {
+        Arc::make_mut(&mut self.allowed).push(method.to_string());
+        self
+    }
+
+    /// Whether `method` is on the allowlist
+    pub fn is_allowed(&self, method: &str) -> bool {
+        !EXCEPTIONS.contains(&method)
+            && self
+                .allowed
+                .iter()
+                .any(|pattern| match pattern.strip_suffix('*') {
+                    Some(prefix) => method.starts_with(prefix),
+                    None => method == pattern,
+                })
+    }
+
+    /// Check `method` and `args` would be allowed, without sending them
+    ///
+    /// Returns `Error::ReadOnly` for methods not on the allowlist, and for
+    /// Vimscript which may have side effects. An `nvim_call_atomic` batch
+    /// is allowed if every call in it is.
+    pub fn check(&self, method: &str, args: &[Value]) -> Result<(), Error> {
+        let denied = |reason: String| Err(Error::ReadOnly(reason));
+        if !self.is_allowed(method) {
+            return denied(format!("{} isn't allowed by a read-only session", method));
+        }
+        match method {
+            "nvim_eval" => match args.first().and_then(Value::as_str) {
+                Some(expr) if is_pure_expr(expr) => Ok(()),
+                Some(expr) => denied(format!("The expression '{}' may change state", expr)),
+                None => denied("nvim_eval expects an expression".to_string()),
+            },
+            "nvim_call_function" => match args.first().and_then(Value::as_str) {
+                Some(function) if PURE_FUNCTIONS.contains(&function) => Ok(()),
+                Some(function) => denied(format!("The function {}() may change state", function)),
+                None => denied("nvim_call_function expects a function name".to_string()),
+            },
+            "nvim_call_atomic" => {
+                let calls = args.first().and_then(Value::as_array);
+                for call in calls.into_iter().flatten() {
+                    let method = call[0].as_str().unwrap_or_default();
+                    let args = call[1].as_array().map(Vec::as_slice).unwrap_or_default();
+                    self.check(method, args)?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Call `method` if it is allowed, see `Session::call`
+    pub fn call(&self, method: &str, args: Vec<Value>) -> Result<Value, Error> {
+        self.check(method, &args)?;
+        self.session.call(method, args)
+    }
+
+    /// Call `method` if it is allowed, converting the result to `T`
+    pub fn call_typed<T: FromValue>(&self, method: &str, args: Vec<Value>) -> Result<T, Error> {
+        T::from_value(self.call(method, args)?)
+    }
+
+    /// Call `method` if it is allowed, without waiting for the response,
+    /// see `Session::call_async`
+    pub fn call_async(&self, method: &str, args: Vec<Value>) -> Result<ResponseHandle, Error> {
+        self.check(method, &args)?;
+        self.session.call_async(method, args)
+    }
+
+    /// Evaluate the Vimscript expression `expr`, if it has no side effects
+    ///
+    /// Expressions may only call the functions considered pure, e.g.
+    /// `line()`, `getline()` and `winsaveview()`.
+    pub fn eval(&self, expr: &str) -> Result<Value, Error> {
+        self.call("nvim_eval", vec![Value::from(expr)])
+    }
+
+    /// Subscribe to notifications for the given methods, see
+    /// `Session::subscribe`
+    pub fn subscribe(&self, methods: &[&str]) -> mpsc::Receiver<Notification> {
+        self.session.subscribe(methods)
+    }
+
+    /// The API metadata of the connected Neovim, see `Session::api_info`
+    pub fn api_info(&self) -> Result<Arc<ApiInfo>, Error> {
+        self.session.api_info()
+    }
+}
+
+/// Whether the Vimscript expression `expr` only calls pure functions
+///
+/// Any call of a function outside `PURE_FUNCTIONS`, and any call of a
+/// funcref or lambda, may have side effects. Assignments aren't
+/// expressions, so calls are the only way to change state.
+fn is_pure_expr(expr: &str) -> bool {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | ':' | '#' | '.');
+    let mut chars = expr.char_indices().peekable();
+    let mut name_start = None;
+    // The last name and character read, ignoring whitespace
+    let mut name = None;
+    let mut previous = ' ';
+    while let Some((i, c)) = chars.next() {
+        if is_name_char(c) {
+            name_start.get_or_insert(i);
+            previous = c;
+            continue;
+        }
+        if let Some(start) = name_start.take() {
+            name = Some(&expr[start..i]);
+        }
+        match c {
+            '\'' => {
+                // Literal strings escape quotes by doubling them
+                while let Some((_, c)) = chars.next() {
+                    if c == '\'' && chars.next_if(|&(_, c)| c == '\'').is_none() {
+                        break;
+                    }
+                }
+            }
+            '"' => {
+                while let Some((_, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '(' if is_name_char(previous)
+                && !name.is_some_and(|name| PURE_FUNCTIONS.contains(&name)) =>
+            {
+                return false
+            }
+            // Calling the result of an expression, e.g. a lambda
+            '(' if matches!(previous, ')' | ']' | '}') => return false,
+            _ => {}
+        }
+        if !c.is_whitespace() {
+            name = None;
+            previous = c;
+        }
+    }
+    true
+}
@@ -0,0 +1,212 @@
+//! Reporting errors to the user
+//!
+//! A `Report` shows an error, the chain of errors which caused it and any
+//! suggestions in a floating window, rather than a one-line echo which is
+//! easily missed. Reports can also be added to the quickfix list.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rsnvim::api::Nvim;
+//! use rsnvim::report::{show_error, Report};
+//!
+//! let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+//! nvim.start_event_loop(None, None);
+//!
+//! if let Err(error) = std::fs::read_to_string("config.toml") {
+//!     Report::new("Failed to load config", &error)
+//!         .suggestion("Create config.toml in the project root")
+//!         .show(&mut nvim)
+//!         .unwrap();
+//! }
+//!
+//! let error = nvim.command("not_a_command").unwrap_err();
+//! show_error(&mut nvim, "Command failed", &error).unwrap();
+//! ```
+use rmpv::Value;
+
+use crate::api::{Nvim, Window};
+use crate::error::Error;
+
+/// Lua run to show a report in a floating window
+///
+/// `q` and `<Esc>` close the window, and `y` copies the report.
+const SHOW_REPORT_LUA: &str = r#"
+local title, lines = ...
+local text = table.concat(lines, '\n')
+local buf = vim.api.nvim_create_buf(false, true)
+vim.api.nvim_buf_set_lines(buf, 0, -1, false, lines)
+vim.bo[buf].modifiable = false
+vim.bo[buf].bufhidden = 'wipe'
+
+local width = vim.fn.strdisplaywidth(title) + 4
+for _, line in ipairs(lines) do
+    width = math.max(width, vim.fn.strdisplaywidth(line))
+end
+width = math.min(width, math.floor(vim.o.columns * 0.8))
+local height = math.min(#lines, math.floor(vim.o.lines * 0.6))
+local win = vim.api.nvim_open_win(buf, true, {
+    relative = 'editor',
+    width = width,
+    height = height,
+    row = math.floor((vim.o.lines - height) / 2),
+    col = math.floor((vim.o.columns - width) / 2),
+    style = 'minimal',
+    border = 'rounded',
+    title = ' ' .. title .. ' ',
+    title_pos = 'center',
+})
+vim.wo[win].wrap = true
+vim.wo[win].winhighlight = 'FloatBorder:ErrorMsg,FloatTitle:ErrorMsg'
+
+local function close()
+    if vim.api.nvim_win_is_valid(win) then
+        vim.api.nvim_win_close(win, true)
+    end
+end
+local function copy()
+    vim.fn.setreg('"', text)
+    pcall(vim.fn.setreg, '+', text)
+    vim.notify('Copied the error report')
+end
+local opts = { buffer = buf, nowait = true, silent = true }
+vim.keymap.set('n', 'q', close, opts)
+vim.keymap.set('n', '<Esc>', close, opts)
+vim.keymap.set('n', 'y', copy, opts)
+"#;
+
+/// An error to show to the user, with its causes and suggested fixes
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    title: String,
+    /// The error followed by each error which caused it
+    chain: Vec<String>,
+    suggestions: Vec<String>,
+}
+
+impl Report {
+    /// Describe `error` and the errors which caused it
+    ///
+    /// Errors from this crate come with a suggestion where one applies.
+    pub fn new(title: &str, error: &(dyn std::error::Error + 'static)) -> Self {
+        let mut chain = Vec::new();
+        let mut suggestions = Vec::new();
+        let mut source = Some(error);
+        while let Some(error) = source {
+            chain.push(error.to_string());
+            if let Some(suggestion) = error.downcast_ref::<Error>().and_then(suggest) {
+                suggestions.push(suggestion.to_string());
+            }
+            source = error.source();
+        }
+        Report {
+            title: title.to_string(),
+            chain,
+            suggestions,
+        }
+    }
+
+    /// Suggest how the user could fix the error
+    pub fn suggestion(mut self, suggestion: &str) -> Self {
+        self.suggestions.push(suggestion.to_string());
+        self
+    }
+
+    /// The report as lines of text
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut chain = self.chain.iter();
+        if let Some(error) = chain.next() {
+            lines.extend(error.lines().map(str::to_string));
+        }
+        if chain.len() > 0 {
+            lines.push(String::new());
+            lines.push("Caused by:".to_string());
+            for (i, cause) in chain.enumerate() {
+                let mut cause = cause.lines();
+                lines.push(format!("  {}: {}", i + 1, cause.next().unwrap_or("")));
+                lines.extend(cause.map(|line| format!("     {}", line)));
+            }
+        }
+        if !self.suggestions.is_empty() {
+            lines.push(String::new());
+            lines.push("Suggestions:".to_string());
+            for suggestion in &self.suggestions {
+                lines.push(format!("  - {}", suggestion));
+            }
+        }
+        lines
+    }
+
+    /// Show the report in a floating window, returning the window
+    ///
+    /// The window is focused, and can be closed with `q` or `<Esc>`. Pressing
+    /// `y` copies the report to the unnamed and clipboard registers.
+    pub fn show(&self, nvim: &mut Nvim) -> Result<Window, Error> {
+        let mut lines = self.lines();
+        lines.push(String::new());
+        lines.push("y: copy  q: close".to_string());
+        let lines: Vec<Value> = lines.into_iter().map(Value::from).collect();
+        nvim.exec_lua(
+            SHOW_REPORT_LUA,
+            vec![Value::from(self.title.as_str()), Value::from(lines)],
+        )?;
+        // The window is entered when it's opened
+        nvim.get_current_win()
+    }
+
+    /// Replace the quickfix list with an entry for each line of the report
+    pub fn to_quickfix(&self, nvim: &mut Nvim) -> Result<(), Error> {
+        let items: Vec<Value> = self
+            .lines()
+            .into_iter()
+            .map(|line| {
+                Value::Map(vec![
+                    (Value::from("text"), Value::from(line)),
+                    (Value::from("type"), Value::from("E")),
+                ])
+            })
+            .collect();
+        let what = vec![
+            (Value::from("title"), Value::from(self.title.as_str())),
+            (Value::from("items"), Value::from(items)),
+        ];
+        nvim.call_function(
+            "setqflist",
+            vec![Value::Array(Vec::new()), Value::from(" "), Value::Map(what)],
+        )?;
+        Ok(())
+    }
+}
+
+/// Show `error` to the user in a floating window
+///
+/// See `Report` for adding suggestions or using the quickfix list instead.
+pub fn show_error(
+    nvim: &mut Nvim,
+    title: &str,
+    error: &(dyn std::error::Error + 'static),
+) -> Result<Window, Error> {
+    Report::new(title, error).show(nvim)
+}
+
+/// A suggestion for fixing an error from this crate, if one applies
+fn suggest(error: &Error) -> Option<&'static str> {
+    match error {
+        Error::ConnectionError(_) | Error::IoError(_) | Error::ProcessExited(_) => {
+            Some("Check that Neovim is still running and listening on the expected address")
+        }
+        Error::TimeoutError(_) => {
+            Some("Neovim may be waiting for input, e.g. at a prompt; try pressing <Esc>")
+        }
+        Error::UnsupportedVersion(_) => Some("Update Neovim to a newer version"),
+        Error::StreamCorrupted(_) => {
+            Some("Reconnect, and check nothing else writes to the connection, e.g. stdout")
+        }
+        Error::LimitExceeded(_) => {
+            Some("Raise the decode limits with `Session::set_decode_limits`")
+        }
+        Error::HandlerPanicked(_) => Some("Return an error from the handler instead of panicking"),
+        _ => None,
+    }
+}
@@ -1,5 +1,14 @@
+//! MessagePack-RPC message encoding and decoding
+//!
+//! The protocol core, `FrameDecoder`, `encode_message` and `decode_message`,
+//! works on byte buffers rather than `std::io` streams, so it can be driven
+//! by any transport, e.g. WebSocket messages in a browser. `read_frame`,
+//! `decode` and `encode` wrap it for `Read` and `Write` streams.
 use rmpv::{decode, encode, Value};
+use std::fmt;
 use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
 
 use crate::error::Error;
 
@@ -29,6 +38,76 @@ pub enum RpcMessage {
     RpcNotification { method: String, params: Vec<Value> },
 }
 
+/// Whether a traced message was received or sent
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// Decoded from the peer
+    Incoming,
+    /// Encoded to send to the peer
+    Outgoing,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Direction::Incoming => write!(f, "<-"),
+            Direction::Outgoing => write!(f, "->"),
+        }
+    }
+}
+
+type TraceHook = Box<dyn Fn(&RpcMessage, Direction) + Send + Sync>;
+
+/// The hook set by `set_trace_hook`
+static TRACE_HOOK: RwLock<Option<TraceHook>> = RwLock::new(None);
+
+/// Whether a trace hook is set, so untraced messages skip the lock
+static TRACING: AtomicBool = AtomicBool::new(false);
+
+/// Call `hook` with every message decoded by `decode_message` or encoded by
+/// `encode_message`, replacing any previous hook
+///
+/// The hook applies to every session in the process and is called on the
+/// thread encoding or decoding the message, so it should be quick. With the
+/// `log` feature, messages are also logged at the trace level with the
+/// target `rsnvim::rpc`.
+///
+/// # Example
+///
+/// ```
+/// use rsnvim::rpc::{self, RpcMessage};
+///
+/// rpc::set_trace_hook(|msg, direction| {
+///     if let RpcMessage::RpcRequest { method, .. } = msg {
+///         eprintln!("{} {}", direction, method);
+///     }
+/// });
+/// ```
+pub fn set_trace_hook<F>(hook: F)
+where
+    F: Fn(&RpcMessage, Direction) + Send + Sync + 'static,
+{
+    *TRACE_HOOK.write().unwrap() = Some(Box::new(hook));
+    TRACING.store(true, Ordering::Release);
+}
+
+/// Remove the hook set by `set_trace_hook`
+pub fn clear_trace_hook() {
+    TRACING.store(false, Ordering::Release);
+    *TRACE_HOOK.write().unwrap() = None;
+}
+
+/// Pass `msg` to the trace hook and logger, if any
+fn trace(msg: &RpcMessage, direction: Direction) {
+    #[cfg(feature = "log")]
+    log::trace!(target: "rsnvim::rpc", "{} {:?}", direction, msg);
+    if TRACING.load(Ordering::Acquire) {
+        if let Some(hook) = TRACE_HOOK.read().unwrap().as_ref() {
+            hook(msg, direction);
+        }
+    }
+}
+
 /// Iterate through Rust types, converting them to a rmpv::Value,
 /// concatenating them into a Vec and converting the Vec to a
 /// rmpv::Value::Array.
@@ -62,106 +141,613 @@ macro_rules! try_arr {
     };
 }
 
-/// Returns a &str if the input is a rmpv::Value::String,
+/// Returns a &str if the input is a valid UTF-8 rmpv::Value::String,
 /// otherwise return an error.
 macro_rules! try_str {
     ($exp:expr) => {
-        match $exp {
-            Value::String(_) => $exp.as_str().unwrap(),
-            _ => return Err(Error::DecodingError("RPC element not a string".to_string())),
+        match $exp.as_str() {
+            Some(s) => s,
+            None => return Err(Error::DecodingError("RPC element not a string".to_string())),
         }
     };
 }
 
-/// Returns a u64 if the input is a rmpv::Value::Integer,
+/// Returns a u64 if the input is a non-negative rmpv::Value::Integer,
 /// otherwise return an error.
 macro_rules! try_int {
     ($exp:expr) => {
-        match $exp {
-            Value::Integer(_) => $exp.as_u64().unwrap(),
-            _ => return Err(Error::DecodingError("RPC element not a string".to_string())),
+        match $exp.as_u64() {
+            Some(n) => n,
+            None => {
+                return Err(Error::DecodingError(format!(
+                    "RPC element {} not an unsigned integer",
+                    $exp
+                )))
+            }
+        }
+    };
+}
+
+/// Get the message of an error returned by Neovim as [type, message]
+pub(crate) fn error_message(error: Value) -> String {
+    match error
+        .as_array()
+        .and_then(|e| e.get(1))
+        .and_then(Value::as_str)
+    {
+        Some(message) => message.to_string(),
+        None => error.to_string(),
+    }
+}
+
+/// Limits enforced when decoding an incoming RPC message
+///
+/// `rmpv` will decode arbitrarily nested or large structures, so these limits
+/// protect against a buggy or hostile peer exhausting the stack or memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// Maximum nesting depth of arrays and maps
+    pub max_depth: usize,
+    /// Maximum number of elements in an array or entries in a map
+    pub max_collection_len: usize,
+    /// Maximum length in bytes of a string, binary or extension payload
+    pub max_string_len: usize,
+}
+
+impl DecodeLimits {
+    /// Limits suitable for bulk operations such as reading very large buffers
+    pub fn bulk() -> Self {
+        DecodeLimits {
+            max_depth: decode::MAX_DEPTH,
+            max_collection_len: u32::MAX as usize,
+            max_string_len: u32::MAX as usize,
+        }
+    }
+}
+
+impl Default for DecodeLimits {
+    /// Limits which comfortably fit regular Neovim traffic
+    fn default() -> Self {
+        DecodeLimits {
+            max_depth: 64,
+            max_collection_len: 1 << 20,
+            max_string_len: 64 << 20,
+        }
+    }
+}
+
+/// The shape of a single MessagePack item, as described by its marker
+enum Item {
+    /// A value followed by `len` bytes of payload
+    Scalar(usize),
+    /// A string, binary or extension value with a `len` byte payload
+    Bytes(usize),
+    /// An array with `len` elements
+    Array(usize),
+    /// A map with `len` key-value pairs
+    Map(usize),
+}
+
+/// Get the length of the header of an item starting with `marker`, i.e.
+/// the marker and any length fields
+fn header_len(marker: u8) -> usize {
+    match marker {
+        0xc4 | 0xc7 | 0xd9 => 2,
+        0xc5 | 0xc8 | 0xda | 0xdc | 0xde => 3,
+        0xc6 | 0xc9 | 0xdb | 0xdd | 0xdf => 5,
+        _ => 1,
+    }
+}
+
+/// Read the big-endian length field of a header
+fn read_len(bytes: &[u8]) -> usize {
+    let mut buf = [0u8; 4];
+    buf[4 - bytes.len()..].copy_from_slice(bytes);
+    u32::from_be_bytes(buf) as usize
+}
+
+/// Parse the complete header of a MessagePack item
+fn parse_item(header: &[u8]) -> Item {
+    let len = &header[1..];
+    match header[0] {
+        0x00..=0x7f | 0xc0..=0xc3 | 0xe0..=0xff => Item::Scalar(0),
+        m @ 0x80..=0x8f => Item::Map((m & 0x0f) as usize),
+        m @ 0x90..=0x9f => Item::Array((m & 0x0f) as usize),
+        m @ 0xa0..=0xbf => Item::Bytes((m & 0x1f) as usize),
+        0xc4..=0xc6 | 0xd9..=0xdb => Item::Bytes(read_len(len)),
+        // Extensions have a type byte before their data
+        0xc7..=0xc9 => Item::Bytes(read_len(len) + 1),
+        0xcc | 0xd0 => Item::Scalar(1),
+        0xcd | 0xd1 => Item::Scalar(2),
+        0xca | 0xce | 0xd2 => Item::Scalar(4),
+        0xcb | 0xcf | 0xd3 => Item::Scalar(8),
+        m @ 0xd4..=0xd8 => Item::Scalar(1 + (1 << (m - 0xd4))),
+        0xdc | 0xdd => Item::Array(read_len(len)),
+        0xde | 0xdf => Item::Map(read_len(len)),
+    }
+}
+
+/// Check a single MessagePack item at nesting `depth` against `limits`
+fn check_limits(item: &Item, depth: usize, limits: &DecodeLimits) -> Result<(), String> {
+    let len = match item {
+        Item::Scalar(_) => return Ok(()),
+        Item::Bytes(len) => {
+            if *len > limits.max_string_len {
+                return Err(format!(
+                    "RPC message contains a {} byte value, exceeding the limit of {}",
+                    len, limits.max_string_len
+                ));
+            }
+            return Ok(());
         }
+        Item::Array(len) | Item::Map(len) => *len,
     };
+
+    if depth > limits.max_depth {
+        Err(format!(
+            "RPC message nesting exceeds the maximum depth of {}",
+            limits.max_depth
+        ))
+    } else if len > limits.max_collection_len {
+        Err(format!(
+            "RPC message contains a collection of {} elements, exceeding the limit of {}",
+            len, limits.max_collection_len
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Splits a stream of bytes into the frames of MessagePack values,
+/// enforcing `DecodeLimits`
+///
+/// The decoder doesn't do any I/O, so it can be fed from any source, e.g.
+/// WebSocket messages. Bytes are given to `push` as they arrive, and
+/// `wanted` gives the most bytes which can be pushed without going past the
+/// end of the current value, for readers which mustn't consume any more.
+///
+/// If a limit is exceeded the rest of the value is still consumed (but
+/// discarded) so the stream remains in sync for the next message.
+///
+/// # Example
+///
+/// ```
+/// use rsnvim::rpc::{self, DecodeLimits, FrameDecoder, RpcMessage};
+///
+/// let message = RpcMessage::RpcNotification {
+///     method: "redraw".to_string(),
+///     params: Vec::new(),
+/// };
+/// let bytes = rpc::encode_message(message.clone());
+///
+/// let mut decoder = FrameDecoder::new(DecodeLimits::default());
+/// let (consumed, frame) = decoder.push(&bytes[..3]);
+/// assert_eq!((consumed, frame.is_none()), (3, true));
+/// let (_, frame) = decoder.push(&bytes[3..]);
+/// let frame = frame.unwrap().unwrap();
+/// assert_eq!(rpc::decode_message(&frame).unwrap(), message);
+/// ```
+pub struct FrameDecoder {
+    limits: DecodeLimits,
+    /// The bytes of the current value
+    frame: Vec<u8>,
+    /// The bytes of the current item's header read so far
+    header: Vec<u8>,
+    /// The number of payload bytes left in the current item
+    payload: usize,
+    /// The number of items left to read at each level of nesting
+    pending: Vec<usize>,
+    violation: Option<String>,
+}
+
+impl FrameDecoder {
+    pub fn new(limits: DecodeLimits) -> Self {
+        FrameDecoder {
+            limits,
+            frame: Vec::new(),
+            header: Vec::new(),
+            payload: 0,
+            pending: vec![1],
+            violation: None,
+        }
+    }
+
+    /// The most bytes which can be pushed without going past the end of the
+    /// current value, which is always at least one
+    pub fn wanted(&self) -> usize {
+        match self.header.first() {
+            _ if self.payload > 0 => self.payload,
+            Some(marker) => header_len(*marker) - self.header.len(),
+            None => 1,
+        }
+    }
+
+    /// Push bytes from the stream, stopping at the end of a value
+    ///
+    /// Returns the number of bytes consumed and, if a value was completed,
+    /// its frame or `Error::LimitExceeded`. Bytes which weren't consumed
+    /// should be pushed again.
+    pub fn push(&mut self, input: &[u8]) -> (usize, Option<Result<Vec<u8>, Error>>) {
+        let mut consumed = 0;
+        while consumed < input.len() {
+            if self.payload > 0 {
+                let len = self.payload.min(input.len() - consumed);
+                if self.violation.is_none() {
+                    self.frame
+                        .extend_from_slice(&input[consumed..consumed + len]);
+                }
+                consumed += len;
+                self.payload -= len;
+            } else {
+                self.header.push(input[consumed]);
+                consumed += 1;
+                if self.header.len() < header_len(self.header[0]) {
+                    continue;
+                }
+                self.start_item();
+            }
+
+            if self.payload == 0 && self.header.is_empty() {
+                if let Some(frame) = self.finish_item() {
+                    return (consumed, Some(frame));
+                }
+            }
+        }
+        (consumed, None)
+    }
+
+    /// Handle the item whose header has been read
+    fn start_item(&mut self) {
+        let item = parse_item(&self.header);
+        // Finished levels are popped, so there's always an item left
+        *self.pending.last_mut().unwrap() -= 1;
+        if self.violation.is_none() {
+            self.violation = check_limits(&item, self.pending.len(), &self.limits).err();
+        }
+        if self.violation.is_none() {
+            self.frame.extend_from_slice(&self.header);
+        }
+        self.header.clear();
+
+        match item {
+            Item::Scalar(len) | Item::Bytes(len) => self.payload = len,
+            Item::Array(len) => self.pending.push(len),
+            Item::Map(len) => self.pending.push(2 * len),
+        }
+    }
+
+    /// Pop finished levels of nesting, returning the value's frame if it is
+    /// complete
+    fn finish_item(&mut self) -> Option<Result<Vec<u8>, Error>> {
+        while self.pending.last() == Some(&0) {
+            self.pending.pop();
+        }
+        if !self.pending.is_empty() {
+            return None;
+        }
+
+        self.pending.push(1);
+        let frame = std::mem::take(&mut self.frame);
+        Some(match self.violation.take() {
+            Some(err) => Err(Error::LimitExceeded(err)),
+            None => Ok(frame),
+        })
+    }
+}
+
+/// Read the raw bytes of a single MessagePack value, enforcing `limits`
+///
+/// Bytes past the end of the value aren't read. See `FrameDecoder` for
+/// details.
+pub fn read_frame<R: Read>(reader: &mut R, limits: &DecodeLimits) -> Result<Vec<u8>, Error> {
+    let mut decoder = FrameDecoder::new(*limits);
+    let mut buf = [0u8; 8192];
+    loop {
+        let len = decoder.wanted().min(buf.len());
+        reader.read_exact(&mut buf[..len])?;
+        if let (_, Some(frame)) = decoder.push(&buf[..len]) {
+            return frame;
+        }
+    }
 }
 
-/// Decode MessagePack RPC message
+/// Decode MessagePack RPC message using the default `DecodeLimits`
 ///
 /// Returns an `RpcMessage` containing one of
 /// - `RpcRequest`: RPC call for this server to respond to
 /// - `RpcResponse`: response to an RPC call made from this server
 /// - `RpcNotification`: RPC notification from the Neovim instance
 pub fn decode<R: Read>(reader: &mut R) -> Result<RpcMessage, Error> {
-    let arr = decode::read_value(reader).unwrap();
-    match arr {
-        Value::Array(_) => (),
+    decode_with_limits(reader, &DecodeLimits::default())
+}
+
+/// Decode MessagePack RPC message, returning `Error::LimitExceeded` if the
+/// message exceeds any of the given `limits`
+pub fn decode_with_limits<R: Read>(
+    reader: &mut R,
+    limits: &DecodeLimits,
+) -> Result<RpcMessage, Error> {
+    decode_message(&read_frame(reader, limits)?)
+}
+
+/// Decode the RPC message in `frame`, e.g. as returned by `FrameDecoder`
+///
+/// Returns `Error::StreamCorrupted` if the frame isn't valid MessagePack or
+/// isn't shaped like an RPC message, which means the stream is out of sync,
+/// and `Error::DecodingError` if an element of the message has the wrong
+/// type.
+pub fn decode_message(frame: &[u8]) -> Result<RpcMessage, Error> {
+    let arr = decode::read_value(&mut &frame[..])
+        .map_err(|error| Error::StreamCorrupted(format!("Invalid MessagePack: {}", error)))?;
+    let len = match &arr {
+        Value::Array(arr) => arr.len(),
         _ => {
-            return Err(Error::DecodingError(
-                "RPC message must be an array".to_string(),
-            ))
+            return Err(Error::StreamCorrupted(format!(
+                "RPC message must be an array, got {}",
+                arr
+            )))
         }
-    }
-
-    match arr[0].as_u64() {
-        Some(0) => {
+    };
+    let msg = match arr[0].as_u64() {
+        Some(0) if len == 4 => {
             let msgid = try_int!(&arr[1]);
             let method = try_str!(&arr[2]).to_string();
             let params = try_arr!(&arr[3]).to_vec();
 
-            Ok(RpcMessage::RpcRequest {
+            RpcMessage::RpcRequest {
                 msgid,
                 method,
                 params,
-            })
+            }
         }
-        Some(1) => {
+        Some(1) if len == 4 => {
             let msgid = try_int!(&arr[1]);
             let error = arr[2].clone();
             let result = arr[3].clone();
 
-            Ok(RpcMessage::RpcResponse {
+            RpcMessage::RpcResponse {
                 msgid,
                 error,
                 result,
-            })
+            }
         }
-        Some(2) => {
+        Some(2) if len == 3 => {
             let method = try_str!(&arr[1]).to_string();
             let params = try_arr!(&arr[2]).to_vec();
 
-            Ok(RpcMessage::RpcNotification { method, params })
+            RpcMessage::RpcNotification { method, params }
         }
-        _ => Err(Error::DecodingError(
-            "RPC message does not contain type".to_string(),
-        )),
-    }
+        _ => {
+            return Err(Error::StreamCorrupted(format!(
+                "RPC message {} has an unknown type or the wrong length",
+                arr
+            )))
+        }
+    };
+    trace(&msg, Direction::Incoming);
+
+    Ok(msg)
 }
 
 /// Encode MessagePack RPC message and send to Neovim instance.
 pub fn encode<W: Write>(writer: &mut W, msg: RpcMessage) -> Result<(), Error> {
-    match msg {
+    writer.write_all(&encode_message(msg))?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Encode MessagePack RPC message as bytes
+pub fn encode_message(msg: RpcMessage) -> Vec<u8> {
+    trace(&msg, Direction::Outgoing);
+    let val = match msg {
         RpcMessage::RpcRequest {
             msgid,
             method,
             params,
-        } => {
-            let val = args_as_value!(0, msgid, method, params);
-            encode::write_value(writer, &val)?;
-        }
+        } => args_as_value!(0, msgid, method, params),
         RpcMessage::RpcResponse {
             msgid,
             error,
             result,
-        } => {
-            let val = args_as_value!(1, msgid, error, result);
-            encode::write_value(writer, &val)?;
+        } => args_as_value!(1, msgid, error, result),
+        RpcMessage::RpcNotification { method, params } => args_as_value!(2, method, params),
+    };
+
+    let mut bytes = Vec::new();
+    // Writing to a Vec can't fail
+    encode::write_value(&mut bytes, &val).unwrap();
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Encode `value` as MessagePack
+    fn bytes(value: &Value) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        encode::write_value(&mut bytes, value).unwrap();
+        bytes
+    }
+
+    /// Decode the single value in `bytes` as a frame with `limits`
+    fn frame(bytes: &[u8], limits: DecodeLimits) -> Result<Vec<u8>, Error> {
+        read_frame(&mut &bytes[..], &limits)
+    }
+
+    fn nested(depth: usize) -> Value {
+        (0..depth).fold(Value::from(1), |value, _| Value::Array(vec![value]))
+    }
+
+    #[test]
+    fn nesting_depth() {
+        let limits = DecodeLimits {
+            max_depth: 3,
+            ..DecodeLimits::default()
+        };
+        assert_eq!(
+            frame(&bytes(&nested(3)), limits).unwrap(),
+            bytes(&nested(3))
+        );
+        assert!(matches!(
+            frame(&bytes(&nested(4)), limits),
+            Err(Error::LimitExceeded(_))
+        ));
+        let map = Value::Map(vec![(Value::from("key"), nested(3))]);
+        assert!(matches!(
+            frame(&bytes(&map), limits),
+            Err(Error::LimitExceeded(_))
+        ));
+    }
+
+    #[test]
+    fn collection_len() {
+        let limits = DecodeLimits {
+            max_collection_len: 4,
+            ..DecodeLimits::default()
+        };
+        let array = |len| Value::Array(vec![Value::Nil; len]);
+        assert!(frame(&bytes(&array(4)), limits).is_ok());
+        assert!(matches!(
+            frame(&bytes(&array(5)), limits),
+            Err(Error::LimitExceeded(_))
+        ));
+        let map = Value::Map((0..5).map(|i| (Value::from(i), Value::Nil)).collect());
+        assert!(matches!(
+            frame(&bytes(&map), limits),
+            Err(Error::LimitExceeded(_))
+        ));
+        // The limit applies to nested collections too
+        let inner = Value::Array(vec![array(2), array(5)]);
+        assert!(matches!(
+            frame(&bytes(&inner), limits),
+            Err(Error::LimitExceeded(_))
+        ));
+    }
+
+    #[test]
+    fn string_len() {
+        let limits = DecodeLimits {
+            max_string_len: 8,
+            ..DecodeLimits::default()
+        };
+        assert!(frame(&bytes(&Value::from("12345678")), limits).is_ok());
+        for value in [
+            Value::from("123456789"),
+            Value::Binary(vec![0; 9]),
+            Value::Ext(0, vec![0; 9]),
+            Value::Array(vec![Value::from("123456789")]),
+        ] {
+            assert!(
+                matches!(frame(&bytes(&value), limits), Err(Error::LimitExceeded(_))),
+                "{}",
+                value
+            );
         }
-        RpcMessage::RpcNotification { method, params } => {
-            let val = args_as_value!(2, method, params);
-            encode::write_value(writer, &val)?;
+    }
+
+    #[test]
+    fn bulk_limits() {
+        let default = DecodeLimits::default();
+        let bulk = DecodeLimits::bulk();
+        assert!(bulk.max_depth >= default.max_depth);
+        assert!(bulk.max_collection_len > default.max_collection_len);
+        assert!(bulk.max_string_len > default.max_string_len);
+
+        // A buffer with more lines than the default allows
+        let lines = bytes(&Value::Array(vec![
+            Value::Nil;
+            default.max_collection_len + 1
+        ]));
+        assert!(matches!(
+            frame(&lines, default),
+            Err(Error::LimitExceeded(_))
+        ));
+        assert_eq!(frame(&lines, bulk).unwrap(), lines);
+    }
+
+    #[test]
+    fn stream_continues_after_rejected_message() {
+        let limits = DecodeLimits {
+            max_string_len: 8,
+            ..DecodeLimits::default()
+        };
+        let rejected = RpcMessage::RpcNotification {
+            method: "too_long".to_string(),
+            params: vec![Value::from("a long parameter")],
+        };
+        let accepted = RpcMessage::RpcResponse {
+            msgid: 3,
+            error: Value::Nil,
+            result: Value::Array(vec![Value::from(1), Value::from("short")]),
+        };
+        let mut stream = encode_message(rejected);
+        stream.extend(encode_message(accepted.clone()));
+
+        let mut reader = &stream[..];
+        assert!(matches!(
+            decode_with_limits(&mut reader, &limits),
+            Err(Error::LimitExceeded(_))
+        ));
+        assert_eq!(decode_with_limits(&mut reader, &limits).unwrap(), accepted);
+        assert!(reader.is_empty());
+
+        // The same holds when the stream arrives a byte at a time
+        let mut decoder = FrameDecoder::new(limits);
+        let mut frames = Vec::new();
+        for byte in &stream {
+            if let (1, Some(frame)) = decoder.push(std::slice::from_ref(byte)) {
+                frames.push(frame);
+            }
         }
-    };
+        assert_eq!(frames.len(), 2);
+        assert!(matches!(frames[0], Err(Error::LimitExceeded(_))));
+        let frame = frames.pop().unwrap().unwrap();
+        assert_eq!(decode_message(&frame).unwrap(), accepted);
+    }
 
-    writer.flush()?;
+    #[test]
+    fn negative_msgid() {
+        let response = Value::from(vec![
+            Value::from(1),
+            Value::from(-1),
+            Value::Nil,
+            Value::Nil,
+        ]);
+        assert!(matches!(
+            decode_message(&bytes(&response)),
+            Err(Error::DecodingError(_))
+        ));
+    }
 
-    Ok(())
+    #[test]
+    fn trace_hook() {
+        let traced = Arc::new(Mutex::new(Vec::new()));
+        let hook = traced.clone();
+        set_trace_hook(move |msg, direction| {
+            // Other tests encode and decode messages, so only record this one
+            if let RpcMessage::RpcRequest { method, .. } = msg {
+                if method == "rsnvim_trace" {
+                    hook.lock()
+                        .unwrap()
+                        .push(format!("{} {}", direction, method));
+                }
+            }
+        });
+        let request = RpcMessage::RpcRequest {
+            msgid: 1,
+            method: "rsnvim_trace".to_string(),
+            params: Vec::new(),
+        };
+        let frame = encode_message(request);
+        decode_message(&frame).unwrap();
+        clear_trace_hook();
+        decode_message(&frame).unwrap();
+        assert_eq!(
+            *traced.lock().unwrap(),
+            vec!["-> rsnvim_trace", "<- rsnvim_trace"]
+        );
+    }
 }
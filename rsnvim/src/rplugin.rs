@@ -0,0 +1,386 @@
+//! Neovim's remote plugin host protocol
+//!
+//! A `RemotePlugin` declares commands, functions and autocommands which are
+//! handled by this client, and answers the `poll` and `specs` requests
+//! Neovim sends to a remote plugin host. The plugin can be registered by
+//! `:UpdateRemotePlugins` like a Python or Node plugin, or defined directly
+//! on an existing connection with `RemotePlugin::define`.
+//!
+//! Neovim calls each handler with positional parameters, in this order:
+//!
+//! - commands: the arguments as a list of strings if `nargs` is set, the
+//!   `[line1, line2]` range or count if `range` or `count` is set, whether
+//!   `!` was given if `bang` is set, the register if `register` is set and
+//!   the result of `eval` if set
+//! - functions: the arguments as a list, the `[firstline, lastline]` range if
+//!   `range` is set and the result of `eval` if set
+//! - autocommands: the result of `eval` if set
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rsnvim::api::Nvim;
+//! use rsnvim::handler::HandlerRouter;
+//! use rsnvim::rplugin::{CommandSpec, FunctionSpec, RemotePlugin};
+//!
+//! let mut plugin = RemotePlugin::new("/path/to/rplugin/rsnvim/my-plugin");
+//! plugin
+//!     .command(
+//!         "Greet",
+//!         CommandSpec::new().nargs("1").bang(),
+//!         |(args, bang): (Vec<String>, bool), nvim| {
+//!             let greeting = if bang { "Hello!" } else { "Hello" };
+//!             nvim.out_write(format!("{} {}\n", greeting, args[0]))
+//!         },
+//!     )
+//!     .function(
+//!         "Double",
+//!         FunctionSpec::new().sync(),
+//!         |(args,): (Vec<i64>,), _nvim| Ok(args[0] * 2),
+//!     );
+//!
+//! let mut router = HandlerRouter::new();
+//! plugin.register(&mut router);
+//!
+//! // Run as a host started by Neovim over stdin/stdout
+//! let mut nvim = Nvim::from_parent().unwrap();
+//! let (requests, notifications) = router.into_handlers();
+//! nvim.start_event_loop(requests, notifications);
+//! nvim.join().unwrap();
+//! ```
+use rmpv::Value;
+use std::sync::Arc;
+
+use crate::api::{AsValue, Nvim};
+use crate::error::Error;
+use crate::handler::{FromArgs, HandlerRouter};
+
+type PluginFn = Arc<dyn Fn(Vec<Value>, &mut Nvim) -> Result<Value, Error> + Send + Sync>;
+
+/// Set `key` of a dictionary to `value`, replacing any previous value
+fn set(map: &mut Vec<(Value, Value)>, key: &str, value: Value) {
+    map.retain(|(k, _)| k.as_str() != Some(key));
+    map.push((Value::from(key), value));
+}
+
+/// Options for a command declared with `RemotePlugin::command`
+///
+/// Commands are asynchronous unless `sync` is used.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CommandSpec {
+    sync: bool,
+    opts: Vec<(Value, Value)>,
+}
+
+impl CommandSpec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wait for the handler to finish before the command returns
+    pub fn sync(mut self) -> Self {
+        self.sync = true;
+        self
+    }
+
+    /// Set the number of arguments, like `-nargs`
+    pub fn nargs(mut self, nargs: &str) -> Self {
+        set(&mut self.opts, "nargs", Value::from(nargs));
+        self
+    }
+
+    /// Accept a range, like `-range`, e.g. `""` for the current line or `"%"`
+    /// for the whole file
+    pub fn range(mut self, range: &str) -> Self {
+        set(&mut self.opts, "range", Value::from(range));
+        self
+    }
+
+    /// Accept a count defaulting to `count`, like `-count`
+    pub fn count(mut self, count: i64) -> Self {
+        set(&mut self.opts, "count", Value::from(count));
+        self
+    }
+
+    /// Accept a `!`, like `-bang`
+    pub fn bang(mut self) -> Self {
+        set(&mut self.opts, "bang", Value::from(""));
+        self
+    }
+
+    /// Accept a register, like `-register`
+    pub fn register(mut self) -> Self {
+        set(&mut self.opts, "register", Value::from(""));
+        self
+    }
+
+    /// Complete arguments with `complete`, like `-complete`
+    pub fn complete(mut self, complete: &str) -> Self {
+        set(&mut self.opts, "complete", Value::from(complete));
+        self
+    }
+
+    /// Pass the result of evaluating the Vimscript `expr` to the handler
+    pub fn eval(mut self, expr: &str) -> Self {
+        set(&mut self.opts, "eval", Value::from(expr));
+        self
+    }
+}
+
+/// Options for a function declared with `RemotePlugin::function`
+///
+/// Functions are asynchronous, returning `0` immediately, unless `sync` is
+/// used.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FunctionSpec {
+    sync: bool,
+    opts: Vec<(Value, Value)>,
+}
+
+impl FunctionSpec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wait for the handler and return its result
+    pub fn sync(mut self) -> Self {
+        self.sync = true;
+        self
+    }
+
+    /// Call the function once for a range, like `:function-range`
+    pub fn range(mut self) -> Self {
+        set(&mut self.opts, "range", Value::from(""));
+        self
+    }
+
+    /// Pass the result of evaluating the Vimscript `expr` to the handler
+    pub fn eval(mut self, expr: &str) -> Self {
+        set(&mut self.opts, "eval", Value::from(expr));
+        self
+    }
+}
+
+/// Options for an autocommand declared with `RemotePlugin::autocmd`
+///
+/// Autocommands are asynchronous unless `sync` is used, and match every
+/// file unless given a `pattern`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AutocmdSpec {
+    sync: bool,
+    pattern: String,
+    opts: Vec<(Value, Value)>,
+}
+
+impl Default for AutocmdSpec {
+    fn default() -> Self {
+        AutocmdSpec {
+            sync: false,
+            pattern: "*".to_string(),
+            opts: Vec::new(),
+        }
+    }
+}
+
+impl AutocmdSpec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wait for the handler to finish before continuing
+    pub fn sync(mut self) -> Self {
+        self.sync = true;
+        self
+    }
+
+    /// Only run for files matching `pattern`, e.g. `*.rs`
+    pub fn pattern(mut self, pattern: &str) -> Self {
+        self.pattern = pattern.to_string();
+        self
+    }
+
+    /// Pass the result of evaluating the Vimscript `expr` to the handler
+    pub fn eval(mut self, expr: &str) -> Self {
+        set(&mut self.opts, "eval", Value::from(expr));
+        self
+    }
+}
+
+/// A command, function or autocommand declared by a plugin
+struct Spec {
+    /// `command`, `function` or `autocmd`
+    kind: &'static str,
+    name: String,
+    sync: bool,
+    opts: Vec<(Value, Value)>,
+    handler: PluginFn,
+}
+
+/// A remote plugin whose commands, functions and autocommands are handled
+/// by this client
+///
+/// See the module documentation for an example.
+pub struct RemotePlugin {
+    path: String,
+    specs: Vec<Spec>,
+}
+
+impl RemotePlugin {
+    /// Create a plugin at `path`, the path Neovim registers it with
+    ///
+    /// For plugins registered by `:UpdateRemotePlugins`, this is the path of
+    /// the file in the `rplugin/{host}/` directory. Neovim names the methods
+    /// it calls after the path, so it must match exactly.
+    pub fn new(path: &str) -> Self {
+        RemotePlugin {
+            path: path.to_string(),
+            specs: Vec::new(),
+        }
+    }
+
+    /// Declare the command `name`, handled by `handler`
+    pub fn command<P, T, F>(&mut self, name: &str, spec: CommandSpec, handler: F) -> &mut Self
+    where
+        P: FromArgs,
+        T: AsValue,
+        F: Fn(P, &mut Nvim) -> Result<T, Error> + Send + Sync + 'static,
+    {
+        self.add("command", name, spec.sync, spec.opts, handler)
+    }
+
+    /// Declare the function `name`, which must start with a capital letter,
+    /// handled by `handler`
+    pub fn function<P, T, F>(&mut self, name: &str, spec: FunctionSpec, handler: F) -> &mut Self
+    where
+        P: FromArgs,
+        T: AsValue,
+        F: Fn(P, &mut Nvim) -> Result<T, Error> + Send + Sync + 'static,
+    {
+        self.add("function", name, spec.sync, spec.opts, handler)
+    }
+
+    /// Declare an autocommand for `event`, e.g. `BufEnter`, handled by
+    /// `handler`
+    pub fn autocmd<P, T, F>(&mut self, event: &str, spec: AutocmdSpec, handler: F) -> &mut Self
+    where
+        P: FromArgs,
+        T: AsValue,
+        F: Fn(P, &mut Nvim) -> Result<T, Error> + Send + Sync + 'static,
+    {
+        let mut opts = spec.opts;
+        set(&mut opts, "pattern", Value::from(spec.pattern.as_str()));
+        self.add("autocmd", event, spec.sync, opts, handler)
+    }
+
+    fn add<P, T, F>(
+        &mut self,
+        kind: &'static str,
+        name: &str,
+        sync: bool,
+        opts: Vec<(Value, Value)>,
+        handler: F,
+    ) -> &mut Self
+    where
+        P: FromArgs,
+        T: AsValue,
+        F: Fn(P, &mut Nvim) -> Result<T, Error> + Send + Sync + 'static,
+    {
+        self.specs.push(Spec {
+            kind,
+            name: name.to_string(),
+            sync,
+            opts,
+            handler: Arc::new(move |params, nvim| {
+                let params = P::from_args(params)?;
+                handler(params, nvim).map(|result| result.convert())
+            }),
+        });
+        self
+    }
+
+    /// The manifest of the plugin, returned for Neovim's `specs` request
+    pub fn specs(&self) -> Value {
+        let specs = self
+            .specs
+            .iter()
+            .map(|spec| {
+                Value::Map(vec![
+                    (Value::from("type"), Value::from(spec.kind)),
+                    (Value::from("name"), Value::from(spec.name.as_str())),
+                    (Value::from("sync"), Value::from(spec.sync)),
+                    (Value::from("opts"), Value::Map(spec.opts.clone())),
+                ])
+            })
+            .collect();
+        Value::Array(specs)
+    }
+
+    /// The method Neovim calls for `spec`
+    fn method(&self, spec: &Spec) -> String {
+        match spec.kind {
+            "autocmd" => {
+                let pattern = spec
+                    .opts
+                    .iter()
+                    .find(|(key, _)| key.as_str() == Some("pattern"))
+                    .and_then(|(_, pattern)| pattern.as_str())
+                    .unwrap_or("*");
+                format!("{}:autocmd:{}:{}", self.path, spec.name, pattern)
+            }
+            kind => format!("{}:{}:{}", self.path, kind, spec.name),
+        }
+    }
+
+    /// Handle the host protocol and each declared command, function and
+    /// autocommand with `router`
+    ///
+    /// Synchronous declarations are handled as requests and asynchronous ones
+    /// as notifications, whose errors go to the router's notification error
+    /// handler.
+    pub fn register(&self, router: &mut HandlerRouter) {
+        let specs = self.specs();
+        router
+            .on_request("poll", |_: Vec<Value>, _nvim| Ok(Value::from("ok")))
+            .on_request("specs", move |_: Vec<Value>, _nvim| Ok(specs.clone()));
+        for spec in &self.specs {
+            let handler = spec.handler.clone();
+            if spec.sync {
+                router.on_request(&self.method(spec), move |params: Vec<Value>, nvim| {
+                    handler(params, nvim)
+                });
+            } else {
+                router.on_notification(&self.method(spec), move |params: Vec<Value>, nvim| {
+                    handler(params, nvim).map(|_| ())
+                });
+            }
+        }
+    }
+
+    /// Define each command, function and autocommand in the connected Neovim,
+    /// calling this client's channel
+    ///
+    /// This skips `:UpdateRemotePlugins`, for a client which connected to
+    /// Neovim itself. The plugin must also be registered with the client's
+    /// router.
+    pub fn define(&self, nvim: &mut Nvim) -> Result<(), Error> {
+        let channel = nvim.channel_id()?;
+        for spec in &self.specs {
+            let define = match spec.kind {
+                "command" => "remote#define#CommandOnChannel",
+                "function" => "remote#define#FunctionOnChannel",
+                _ => "remote#define#AutocmdOnChannel",
+            };
+            nvim.call_function(
+                define,
+                vec![
+                    Value::from(channel),
+                    Value::from(self.method(spec)),
+                    Value::from(spec.sync),
+                    Value::from(spec.name.as_str()),
+                    Value::Map(spec.opts.clone()),
+                ],
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,641 @@
+//! Conversion between `rmpv::Value` and user types implementing serde's
+//! `Serialize`/`Deserialize` traits.
+//!
+//! This module requires the `serde` feature.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rsnvim::api::Nvim;
+//! use rsnvim::serde::DeserializeValue;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize)]
+//! struct ContextOpts {
+//!     types: Vec<String>,
+//! }
+//!
+//! #[derive(Deserialize)]
+//! struct Context {
+//!     jumps: Vec<String>,
+//! }
+//!
+//! let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+//! nvim.start_event_loop(None, None);
+//!
+//! let opts = rsnvim::serde::to_dict(&ContextOpts {
+//!     types: vec!["jumps".to_string()],
+//! })
+//! .unwrap();
+//! let context: Context = nvim.get_context(opts).unwrap().deserialize().unwrap();
+//! ```
+use ::serde::de::value::{MapAccessDeserializer, MapDeserializer, SeqDeserializer};
+use ::serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use ::serde::ser::{self, Serialize};
+use rmpv::ext::Error as ExtError;
+use rmpv::Value;
+
+use crate::api::Dict;
+use crate::error::Error;
+
+/// Deserialize a Neovim value into a user type
+pub trait DeserializeValue {
+    /// Deserialize `self` into any type implementing `serde::Deserialize`
+    fn deserialize<T: DeserializeOwned>(self) -> Result<T, Error>;
+}
+
+impl DeserializeValue for Value {
+    fn deserialize<T: DeserializeOwned>(self) -> Result<T, Error> {
+        from_value(self)
+    }
+}
+
+impl DeserializeValue for Vec<Value> {
+    fn deserialize<T: DeserializeOwned>(self) -> Result<T, Error> {
+        from_value(Value::Array(self))
+    }
+}
+
+impl DeserializeValue for Vec<(Value, Value)> {
+    fn deserialize<T: DeserializeOwned>(self) -> Result<T, Error> {
+        from_value(Value::Map(self))
+    }
+}
+
+impl DeserializeValue for Dict {
+    fn deserialize<T: DeserializeOwned>(self) -> Result<T, Error> {
+        Vec::<(Value, Value)>::from(self).deserialize()
+    }
+}
+
+/// Deserialize a `Value` into any type implementing `serde::Deserialize`
+///
+/// Unlike `rmpv::ext::from_value`, enums may be represented either by their
+/// variant name or as a single entry map of `{ variant: data }`, matching
+/// the values returned by the Neovim API.
+pub fn from_value<T: DeserializeOwned>(value: Value) -> Result<T, Error> {
+    T::deserialize(Deserializer(value)).map_err(|e| Error::DecodingError(e.to_string()))
+}
+
+/// Serialize any type implementing `serde::Serialize` into a `Value`
+///
+/// Unlike `rmpv::ext::to_value`, structs are serialized as maps keyed by
+/// their field names and unit enum variants as their name, matching the
+/// dictionaries expected by the Neovim API.
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value, Error> {
+    value
+        .serialize(Serializer)
+        .map_err(|e| Error::EncodingError(e.to_string()))
+}
+
+/// Serialize any type implementing `serde::Serialize` into a dictionary
+///
+/// This is useful for building the `opts` parameter accepted by many API
+/// functions from a `#[derive(Serialize)]` struct.
+pub fn to_dict<T: Serialize + ?Sized>(value: &T) -> Result<Vec<(Value, Value)>, Error> {
+    match to_value(value)? {
+        Value::Map(map) => Ok(map),
+        Value::Array(arr) => arr
+            .into_iter()
+            .map(|pair| match pair {
+                Value::Array(mut kv) if kv.len() == 2 => {
+                    let v = kv.pop().unwrap();
+                    let k = kv.pop().unwrap();
+                    Ok((k, v))
+                }
+                _ => Err(Error::EncodingError(
+                    "Value does not serialize to a dictionary".to_string(),
+                )),
+            })
+            .collect(),
+        _ => Err(Error::EncodingError(
+            "Value does not serialize to a dictionary".to_string(),
+        )),
+    }
+}
+
+/// A serde `Serializer` producing Neovim compatible `rmpv::Value`s
+struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = Value;
+    type Error = ExtError;
+
+    type SerializeSeq = SerializeVec;
+    type SerializeTuple = SerializeVec;
+    type SerializeTupleStruct = SerializeVec;
+    type SerializeTupleVariant = SerializeVariant<SerializeVec>;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeMap;
+    type SerializeStructVariant = SerializeVariant<SerializeMap>;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, ExtError> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, ExtError> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, ExtError> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, ExtError> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, ExtError> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, ExtError> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, ExtError> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, ExtError> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, ExtError> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, ExtError> {
+        Ok(Value::from(v as f64))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, ExtError> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, ExtError> {
+        Ok(Value::from(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, ExtError> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, ExtError> {
+        Ok(Value::from(v))
+    }
+
+    fn serialize_none(self) -> Result<Value, ExtError> {
+        Ok(Value::Nil)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value, ExtError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, ExtError> {
+        Ok(Value::Nil)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, ExtError> {
+        Ok(Value::Nil)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Value, ExtError> {
+        Ok(Value::from(variant))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value, ExtError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, ExtError> {
+        Ok(Value::Map(vec![(
+            Value::from(variant),
+            value.serialize(self)?,
+        )]))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeVec, ExtError> {
+        Ok(SerializeVec {
+            vec: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeVec, ExtError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeVec, ExtError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, ExtError> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<SerializeMap, ExtError> {
+        Ok(SerializeMap {
+            map: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeMap, ExtError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, ExtError> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+/// Serializes sequences and tuples into a `Value::Array`
+struct SerializeVec {
+    vec: Vec<Value>,
+}
+
+impl ser::SerializeSeq for SerializeVec {
+    type Ok = Value;
+    type Error = ExtError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ExtError> {
+        self.vec.push(value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, ExtError> {
+        Ok(Value::Array(self.vec))
+    }
+}
+
+impl ser::SerializeTuple for SerializeVec {
+    type Ok = Value;
+    type Error = ExtError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ExtError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value, ExtError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeVec {
+    type Ok = Value;
+    type Error = ExtError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ExtError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value, ExtError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+/// Serializes maps and structs into a `Value::Map`
+struct SerializeMap {
+    map: Vec<(Value, Value)>,
+    key: Option<Value>,
+}
+
+impl ser::SerializeMap for SerializeMap {
+    type Ok = Value;
+    type Error = ExtError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), ExtError> {
+        self.key = Some(key.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ExtError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| <ExtError as ser::Error>::custom("map value without a key"))?;
+        self.map.push((key, value.serialize(Serializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, ExtError> {
+        Ok(Value::Map(self.map))
+    }
+}
+
+impl ser::SerializeStruct for SerializeMap {
+    type Ok = Value;
+    type Error = ExtError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ExtError> {
+        self.map
+            .push((Value::from(key), value.serialize(Serializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, ExtError> {
+        Ok(Value::Map(self.map))
+    }
+}
+
+/// Serializes enum variants with data as a single entry map of
+/// `{ variant: data }`
+struct SerializeVariant<S> {
+    variant: &'static str,
+    inner: S,
+}
+
+impl ser::SerializeTupleVariant for SerializeVariant<SerializeVec> {
+    type Ok = Value;
+    type Error = ExtError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ExtError> {
+        ser::SerializeSeq::serialize_element(&mut self.inner, value)
+    }
+
+    fn end(self) -> Result<Value, ExtError> {
+        let inner = ser::SerializeSeq::end(self.inner)?;
+        Ok(Value::Map(vec![(Value::from(self.variant), inner)]))
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeVariant<SerializeMap> {
+    type Ok = Value;
+    type Error = ExtError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ExtError> {
+        ser::SerializeStruct::serialize_field(&mut self.inner, key, value)
+    }
+
+    fn end(self) -> Result<Value, ExtError> {
+        let inner = ser::SerializeStruct::end(self.inner)?;
+        Ok(Value::Map(vec![(Value::from(self.variant), inner)]))
+    }
+}
+
+/// A serde `Deserializer` reading Neovim compatible `rmpv::Value`s
+struct Deserializer(Value);
+
+impl<'de> IntoDeserializer<'de, ExtError> for Deserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer {
+    type Error = ExtError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ExtError> {
+        match self.0 {
+            Value::Nil => visitor.visit_unit(),
+            Value::Boolean(v) => visitor.visit_bool(v),
+            Value::Integer(v) => match v.as_i64() {
+                Some(v) => visitor.visit_i64(v),
+                None => visitor.visit_u64(v.as_u64().unwrap()),
+            },
+            Value::F32(v) => visitor.visit_f32(v),
+            Value::F64(v) => visitor.visit_f64(v),
+            Value::String(v) => match v.into_str() {
+                Some(v) => visitor.visit_string(v),
+                None => Err(<ExtError as de::Error>::custom("string is not valid UTF-8")),
+            },
+            Value::Binary(v) => visitor.visit_byte_buf(v),
+            Value::Array(v) => {
+                let mut seq = SeqDeserializer::new(v.into_iter().map(Deserializer));
+                let ret = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(ret)
+            }
+            Value::Map(v) => {
+                let mut map = MapDeserializer::new(
+                    v.into_iter()
+                        .map(|(k, v)| (Deserializer(k), Deserializer(v))),
+                );
+                let ret = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(ret)
+            }
+            Value::Ext(_, v) => visitor.visit_byte_buf(v),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ExtError> {
+        match self.0 {
+            Value::Nil => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ExtError> {
+        match self.0 {
+            Value::String(v) => match v.into_str() {
+                Some(v) => visitor.visit_enum(v.into_deserializer()),
+                None => Err(<ExtError as de::Error>::custom(
+                    "enum variant is not valid UTF-8",
+                )),
+            },
+            Value::Map(v) if v.len() == 1 => {
+                let map = MapDeserializer::new(
+                    v.into_iter()
+                        .map(|(k, v)| (Deserializer(k), Deserializer(v))),
+                );
+                visitor.visit_enum(MapAccessDeserializer::new(map))
+            }
+            other => Err(<ExtError as de::Error>::custom(format!(
+                "expected an enum variant, found {}",
+                other
+            ))),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ExtError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    ::serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Opts {
+        name: String,
+        count: u64,
+        tag: Option<String>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Kind {
+        Plain,
+        Sized(u8),
+        Split { row: i64, col: i64 },
+    }
+
+    fn map(entries: &[(&str, Value)]) -> Value {
+        let entries = entries.iter().map(|(k, v)| (Value::from(*k), v.clone()));
+        Value::Map(entries.collect())
+    }
+
+    #[test]
+    fn structs() {
+        let opts = Opts {
+            name: "rsnvim".to_string(),
+            count: 3,
+            tag: None,
+        };
+        let value = map(&[
+            ("name", Value::from("rsnvim")),
+            ("count", Value::from(3)),
+            ("tag", Value::Nil),
+        ]);
+        assert_eq!(to_value(&opts).unwrap(), value);
+        assert_eq!(from_value::<Opts>(value).unwrap(), opts);
+
+        let tagged = map(&[
+            ("count", Value::from(1)),
+            ("name", Value::from("a")),
+            ("tag", Value::from("b")),
+        ]);
+        assert_eq!(
+            from_value::<Opts>(tagged).unwrap().tag,
+            Some("b".to_string())
+        );
+        assert!(matches!(
+            from_value::<Opts>(Value::from(1)),
+            Err(Error::DecodingError(_))
+        ));
+    }
+
+    #[test]
+    fn enums() {
+        assert_eq!(to_value(&Kind::Plain).unwrap(), Value::from("Plain"));
+        let sized = map(&[("Sized", Value::from(2))]);
+        assert_eq!(to_value(&Kind::Sized(2)).unwrap(), sized);
+        let split = map(&[(
+            "Split",
+            map(&[("row", Value::from(1)), ("col", Value::from(-1))]),
+        )]);
+        assert_eq!(to_value(&Kind::Split { row: 1, col: -1 }).unwrap(), split);
+
+        assert_eq!(
+            from_value::<Kind>(Value::from("Plain")).unwrap(),
+            Kind::Plain
+        );
+        assert_eq!(from_value::<Kind>(sized).unwrap(), Kind::Sized(2));
+        assert_eq!(
+            from_value::<Kind>(split).unwrap(),
+            Kind::Split { row: 1, col: -1 }
+        );
+        assert!(from_value::<Kind>(Value::from("Other")).is_err());
+        assert!(from_value::<Kind>(Value::from(1)).is_err());
+    }
+
+    #[test]
+    fn options() {
+        assert_eq!(to_value(&None::<i64>).unwrap(), Value::Nil);
+        assert_eq!(to_value(&Some(1)).unwrap(), Value::from(1));
+        assert_eq!(from_value::<Option<i64>>(Value::Nil).unwrap(), None);
+        assert_eq!(from_value::<Option<i64>>(Value::from(1)).unwrap(), Some(1));
+    }
+
+    #[test]
+    fn large_integers() {
+        let large = i64::MAX as u64 + 1;
+        assert_eq!(to_value(&large).unwrap(), Value::from(large));
+        assert_eq!(from_value::<u64>(Value::from(large)).unwrap(), large);
+        assert_eq!(from_value::<u64>(Value::from(u64::MAX)).unwrap(), u64::MAX);
+        assert!(from_value::<i64>(Value::from(large)).is_err());
+    }
+
+    #[test]
+    fn dicts() {
+        let dict = to_dict(&[("a", 1), ("b", 2)]).unwrap();
+        assert_eq!(
+            dict,
+            vec![
+                (Value::from("a"), Value::from(1)),
+                (Value::from("b"), Value::from(2)),
+            ]
+        );
+        assert!(matches!(to_dict(&1), Err(Error::EncodingError(_))));
+        assert!(matches!(to_dict(&[1, 2]), Err(Error::EncodingError(_))));
+
+        let dict = Dict::from(vec![
+            (Value::from("name"), Value::from("a")),
+            (Value::from("count"), Value::from(1)),
+        ]);
+        let opts: Opts = dict.deserialize().unwrap();
+        assert_eq!(
+            opts,
+            Opts {
+                name: "a".to_string(),
+                count: 1,
+                tag: None,
+            }
+        );
+    }
+}
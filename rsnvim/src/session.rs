@@ -1,19 +1,266 @@
 use rmpv::Value;
-use std::net::TcpStream;
+use std::collections::HashMap;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{Shutdown, TcpStream};
 #[cfg(unix)]
 use std::os::unix::net::UnixStream;
+use std::process::Child;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::{
-    client::{Client, Connection},
-    error::Error,
-    handler::{NotificationHandler, RequestHandler},
+    api::{ApiInfo, FromValue, Nvim, RedrawMode, RedrawReceiver},
+    blocking::check_blocking,
+    client::{CancelPolicy, Client, ClientStats, Connection, ResponseHandle},
+    coalesce::{ExtmarkCoalescer, SET_EXTMARK},
+    deprecation::{Deprecation, DeprecationPolicy, Deprecations},
+    error::{AsyncError, Error},
+    handler::{Notification, NotificationHandler, RequestHandler},
+    metrics::{CallRecord, MethodMetrics},
+    rpc::DecodeLimits,
+    shutdown::ShutdownState,
 };
 
+/// How long `Session::call` waits for a response
+pub(crate) const CALL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How often `Session::shutdown` checks whether calls in flight finished
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
 /// The current Neovim session
 ///
-/// Used to send and receive messages to the Neovim session
+/// Used to send and receive messages to the Neovim session. Cloning a
+/// `Session` is cheap and the clone shares the same underlying connection.
+#[derive(Clone)]
 pub struct Session {
-    client: Connection,
+    client: Arc<Connection>,
+    /// The socket to reconnect to, if the session is connected to one
+    endpoint: Option<Endpoint>,
+    /// Shuts down the current socket so the read thread's read returns
+    close: Arc<Mutex<Option<CloseFn>>>,
+    api_info: Arc<Mutex<Metadata>>,
+    /// The arguments to `nvim_set_client_info`, sent again on reconnecting
+    client_info: Arc<Mutex<Option<Vec<Value>>>>,
+    pub(crate) extmarks: Arc<ExtmarkCoalescer>,
+    deprecations: Arc<Deprecations>,
+    shutdown: Arc<ShutdownState>,
+}
+
+type CloseFn = Box<dyn Fn() -> std::io::Result<()> + Send>;
+
+/// The API metadata of the connection, as cached by `Session::api_info`
+enum Metadata {
+    /// Not fetched yet, or Neovim couldn't provide it
+    Unknown,
+    /// Requested when the event loop started, with the response still to be
+    /// read
+    Requested(ResponseHandle),
+    Fetched(Arc<ApiInfo>),
+}
+
+impl Metadata {
+    /// Read the response to the request, if there is one, returning the
+    /// metadata once fetched
+    fn resolve(&mut self) -> Result<Option<Arc<ApiInfo>>, Error> {
+        match std::mem::replace(self, Metadata::Unknown) {
+            Metadata::Unknown => Ok(None),
+            Metadata::Requested(handle) => {
+                let response = handle.wait_unchecked(CALL_TIMEOUT)?;
+                let info = Arc::new(ApiInfo::from_value(response)?);
+                *self = Metadata::Fetched(info.clone());
+                Ok(Some(info))
+            }
+            Metadata::Fetched(info) => {
+                *self = Metadata::Fetched(info.clone());
+                Ok(Some(info))
+            }
+        }
+    }
+}
+
+/// The reader and writer of a socket, and how to shut it down
+type Socket = (Box<dyn Read + Send>, Box<dyn Write + Send>, CloseFn);
+
+/// A socket a Neovim instance listens on, as given to `nvim --listen`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Endpoint {
+    /// A TCP address, e.g. "127.0.0.1:6666"
+    Tcp(String),
+    /// The path of a Unix socket
+    #[cfg(unix)]
+    Unix(String),
+}
+
+impl std::fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Endpoint::Tcp(addr) => write!(f, "{}", addr),
+            #[cfg(unix)]
+            Endpoint::Unix(path) => write!(f, "{}", path),
+        }
+    }
+}
+
+impl std::str::FromStr for Endpoint {
+    type Err = Error;
+
+    /// Parse an address as given to `nvim --listen`
+    ///
+    /// Addresses containing a path separator are Unix sockets, as are bare
+    /// names without a port. A `host:port` address with a numeric port,
+    /// including `[::1]:port`, is a TCP address. Windows named pipes, e.g.
+    /// `\\.\pipe\nvim.1234.0`, are recognised but not supported.
+    fn from_str(addr: &str) -> Result<Self, Error> {
+        if addr.is_empty() {
+            return Err(Error::InvalidArgument("The address is empty".to_string()));
+        }
+        let lower = addr.to_ascii_lowercase();
+        if lower.starts_with(r"\\.\pipe\") || lower.starts_with(r"\\?\pipe\") {
+            return Err(Error::InvalidArgument(format!(
+                "Named pipes such as {} aren't supported",
+                addr
+            )));
+        }
+        let is_path = addr.contains('/') || addr.contains('\\');
+        let is_tcp = !is_path
+            && match addr.rsplit_once(':') {
+                Some((host, port)) => !host.is_empty() && port.parse::<u16>().is_ok(),
+                None => false,
+            };
+        if is_tcp {
+            return Ok(Endpoint::Tcp(addr.to_string()));
+        }
+        #[cfg(unix)]
+        return Ok(Endpoint::Unix(addr.to_string()));
+        #[cfg(not(unix))]
+        Err(Error::InvalidArgument(format!(
+            "{} isn't a TCP address, and Unix sockets aren't supported",
+            addr
+        )))
+    }
+}
+
+impl Endpoint {
+    /// The address of the Neovim this process was started from, taken from
+    /// `$NVIM`, or `$NVIM_LISTEN_ADDRESS` as set by older versions
+    pub fn from_env() -> Result<Self, Error> {
+        ["NVIM", "NVIM_LISTEN_ADDRESS"]
+            .iter()
+            .find_map(|name| std::env::var(name).ok().filter(|addr| !addr.is_empty()))
+            .ok_or_else(|| {
+                Error::InvalidArgument("Neither $NVIM nor $NVIM_LISTEN_ADDRESS is set".to_string())
+            })?
+            .parse()
+    }
+}
+
+/// How `Session::connect_with_retry` retries a connection
+///
+/// By default the first retry is after 10 milliseconds, doubling each time
+/// up to half a second, until 5 seconds have passed.
+#[derive(Debug)]
+pub struct RetrySpec<'a> {
+    initial_delay: Duration,
+    max_delay: Duration,
+    multiplier: f64,
+    deadline: Duration,
+    process: Option<&'a mut Child>,
+}
+
+impl Default for RetrySpec<'_> {
+    fn default() -> Self {
+        RetrySpec {
+            initial_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(500),
+            multiplier: 2.0,
+            deadline: Duration::from_secs(5),
+            process: None,
+        }
+    }
+}
+
+impl<'a> RetrySpec<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wait `delay` before the first retry
+    pub fn initial_delay(mut self, delay: Duration) -> Self {
+        self.initial_delay = delay;
+        self
+    }
+
+    /// Wait at most `delay` between retries
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Multiply the delay by `multiplier` after each retry
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// Give up once `deadline` has passed since the first attempt
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Stop retrying if `process`, the Neovim being connected to, exits
+    pub fn process(mut self, process: &'a mut Child) -> Self {
+        self.process = Some(process);
+        self
+    }
+}
+
+/// Waits for a notification, which is received from when the waiter is
+/// created
+///
+/// Created with `Session::expect_notification` before making the call which
+/// causes the notification, so it isn't missed if it arrives before
+/// `wait` is called.
+pub struct NotificationWaiter {
+    method: String,
+    notifications: mpsc::Receiver<Notification>,
+}
+
+impl NotificationWaiter {
+    /// Wait for the first notification whose parameters match `predicate`,
+    /// returning its parameters
+    ///
+    /// Notifications which don't match are skipped, and aren't seen by
+    /// later waits. Returns `Error::TimeoutError` if none matches within
+    /// `timeout`.
+    pub fn wait<P>(&self, mut predicate: P, timeout: Duration) -> Result<Vec<Value>, Error>
+    where
+        P: FnMut(&[Value]) -> bool,
+    {
+        check_blocking("Waiting for a notification")?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.notifications.recv_timeout(remaining) {
+                Ok(notification) if predicate(&notification.params) => {
+                    return Ok(notification.params)
+                }
+                Ok(_) => {}
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    return Err(Error::TimeoutError(format!(
+                        "No matching '{}' notification within {:?}",
+                        self.method, timeout
+                    )))
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    return Err(Error::ConnectionError(
+                        "The session stopped receiving notifications".to_string(),
+                    ))
+                }
+            }
+        }
+    }
 }
 
 impl Session {
@@ -39,13 +286,8 @@ impl Session {
     /// };
     /// ```
     pub fn from_tcp(addr: &str) -> Result<Session, Error> {
-        let reader = TcpStream::connect(addr)?;
-        let writer = reader.try_clone()?;
-        let client = Client::new(reader, writer);
-
-        Ok(Session {
-            client: Connection::TCP(client),
-        })
+        let endpoint = Endpoint::Tcp(addr.to_string());
+        Ok(Session::from_socket(connect(&endpoint)?, endpoint))
     }
 
     /// Create a Neovim connection using stdin/stdout
@@ -64,13 +306,64 @@ impl Session {
     /// };
     /// ```
     pub fn from_parent() -> Result<Session, Error> {
-        let client = Client::new(std::io::stdin(), std::io::stdout());
+        Ok(Session::from_transport(std::io::stdin(), std::io::stdout()))
+    }
 
-        Ok(Session {
-            client: Connection::STDIO(client)
-        })
+    /// Create a session over any transport, e.g. a TLS stream, an SSH
+    /// tunnel or an in-memory pipe for tests
+    ///
+    /// Messages are read from `reader` and written to `writer`, which are
+    /// used from separate threads. A custom transport can't be shut down by
+    /// `stop_event_loop` or reconnected, so the event loop runs until the
+    /// other end closes the connection.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::session::Session;
+    /// use std::process::{Command, Stdio};
+    ///
+    /// // Run Neovim on another machine, talking to it over SSH
+    /// let mut child = Command::new("ssh")
+    ///     .args(["host", "nvim", "--embed", "--headless"])
+    ///     .stdin(Stdio::piped())
+    ///     .stdout(Stdio::piped())
+    ///     .spawn()
+    ///     .unwrap();
+    /// let reader = child.stdout.take().unwrap();
+    /// let writer = child.stdin.take().unwrap();
+    /// let session = Session::from_transport(reader, writer);
+    /// session.start_event_loop(None, None);
+    /// ```
+    pub fn from_transport<R, W>(reader: R, writer: W) -> Session
+    where
+        R: Read + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        Session::new(Box::new(reader), Box::new(writer), None, None)
     }
 
+    fn from_socket((reader, writer, close): Socket, endpoint: Endpoint) -> Session {
+        Session::new(reader, writer, Some(endpoint), Some(close))
+    }
+
+    fn new(
+        reader: Box<dyn Read + Send>,
+        writer: Box<dyn Write + Send>,
+        endpoint: Option<Endpoint>,
+        close: Option<CloseFn>,
+    ) -> Session {
+        Session {
+            client: Arc::new(Client::new(reader, writer)),
+            endpoint,
+            close: Arc::new(Mutex::new(close)),
+            api_info: Arc::new(Mutex::new(Metadata::Unknown)),
+            client_info: Arc::new(Mutex::new(None)),
+            extmarks: Arc::new(ExtmarkCoalescer::default()),
+            deprecations: Arc::new(Deprecations::default()),
+            shutdown: Arc::new(ShutdownState::default()),
+        }
+    }
 
     /// Create a session using a Unix socket
     ///
@@ -94,13 +387,95 @@ impl Session {
     /// ```
     #[cfg(unix)]
     pub fn from_unix(path: &str) -> Result<Session, Error> {
-        let reader = UnixStream::connect(path)?;
-        let writer = reader.try_clone()?;
-        let client = Client::new(reader, writer);
+        let endpoint = Endpoint::Unix(path.to_string());
+        Ok(Session::from_socket(connect(&endpoint)?, endpoint))
+    }
 
-        Ok(Session {
-            client: Connection::UNIX(client),
-        })
+    /// Connect to the Neovim this process was started from, e.g. by
+    /// `:terminal` or `jobstart()`
+    ///
+    /// The address is read from `$NVIM`, falling back to
+    /// `$NVIM_LISTEN_ADDRESS`, and connected to over TCP or a Unix socket
+    /// depending on its form. See `Endpoint::from_env`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::session::Session;
+    ///
+    /// let mut session = match Session::connect_auto() {
+    ///     Ok(session) => session,
+    ///     Err(error) => panic!("Couldn't connect to $NVIM: {}", error)
+    /// };
+    /// ```
+    pub fn connect_auto() -> Result<Session, Error> {
+        let endpoint = Endpoint::from_env()?;
+        Ok(Session::from_socket(connect(&endpoint)?, endpoint))
+    }
+
+    /// Connect to `endpoint`, retrying until its socket accepts connections
+    ///
+    /// This is useful when attaching to a Neovim which has just been
+    /// spawned, as it may not have created its socket yet. Connections which
+    /// are refused or whose socket doesn't exist yet are retried with
+    /// exponential backoff until the deadline, after which
+    /// `Error::TimeoutError` is returned. Other errors are returned
+    /// immediately.
+    ///
+    /// If a process is given with `RetrySpec::process` and it exits before
+    /// connecting, `Error::ProcessExited` is returned rather than waiting for
+    /// the deadline.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::session::{Endpoint, RetrySpec, Session};
+    /// use std::process::Command;
+    /// use std::time::Duration;
+    ///
+    /// let mut child = Command::new("nvim")
+    ///     .args(["--headless", "--listen", "127.0.0.1:6666"])
+    ///     .spawn()
+    ///     .unwrap();
+    /// let spec = RetrySpec::new()
+    ///     .deadline(Duration::from_secs(10))
+    ///     .process(&mut child);
+    /// let session = Session::connect_with_retry(Endpoint::Tcp("127.0.0.1:6666".into()), spec);
+    /// ```
+    pub fn connect_with_retry(endpoint: Endpoint, mut spec: RetrySpec) -> Result<Session, Error> {
+        let start = Instant::now();
+        let mut delay = spec.initial_delay;
+        loop {
+            let error = match connect(&endpoint) {
+                Ok(socket) => return Ok(Session::from_socket(socket, endpoint)),
+                Err(error) => error,
+            };
+            if !matches!(
+                error.kind(),
+                ErrorKind::ConnectionRefused | ErrorKind::NotFound
+            ) {
+                return Err(error.into());
+            }
+
+            if let Some(process) = spec.process.as_mut() {
+                if let Some(status) = process.try_wait()? {
+                    return Err(Error::ProcessExited(format!(
+                        "Neovim exited with {} before {} accepted connections",
+                        status, endpoint
+                    )));
+                }
+            }
+
+            let elapsed = start.elapsed();
+            if elapsed >= spec.deadline {
+                return Err(Error::TimeoutError(format!(
+                    "Couldn't connect to {} within {:?}: {}",
+                    endpoint, spec.deadline, error
+                )));
+            }
+            thread::sleep(delay.min(spec.deadline - elapsed));
+            delay = delay.mul_f64(spec.multiplier).min(spec.max_delay);
+        }
     }
 
     /// Begin the RPC event loop
@@ -122,34 +497,712 @@ impl Session {
     /// trait which then allows it to process incoming RPC notifications from Neovim.
     /// If 'None' is passed the `DefaultHandler` will be used which ignores all
     /// RPC notifications.
+    ///
+    /// Handlers run on a dedicated thread and receive an `Nvim` sharing this
+    /// session's connection, so they may call back into Neovim.
+    ///
+    /// The API metadata, including the channel ID, is then fetched in the
+    /// background and cached for `api_info` and `channel_id`. Calls made
+    /// before it arrives wait for it, so they can be checked against it.
     pub fn start_event_loop(
-        &mut self,
+        &self,
         request_handler: Option<Box<dyn RequestHandler + Send>>,
         notification_handler: Option<Box<dyn NotificationHandler + Send>>,
     ) {
-        match self.client {
-            Connection::TCP(ref mut client) => {
-                client.start_event_loop(request_handler, notification_handler)
+        let nvim = Nvim::from_session(self.clone());
+        self.client
+            .start_event_loop(nvim, request_handler, notification_handler);
+        self.request_api_info();
+    }
+
+    /// Stop the event loop, closing the connection to Neovim
+    ///
+    /// Pending output is flushed and the socket is shut down, so the read
+    /// thread exits and calls waiting for a response fail. Use `join` to wait
+    /// for the event loop to finish. Stdin can't be interrupted, so for
+    /// `from_parent` sessions, and those using `from_transport`, the read
+    /// thread only exits once Neovim closes the connection.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::session::Session;
+    ///
+    /// let session = Session::from_tcp("127.0.0.1:6666").unwrap();
+    /// session.start_event_loop(None, None);
+    ///
+    /// session.stop_event_loop().unwrap();
+    /// session.join().unwrap();
+    /// ```
+    pub fn stop_event_loop(&self) -> Result<(), Error> {
+        self.client
+            .stop_event_loop(|_| match &*self.close.lock().unwrap() {
+                Some(close) => close(),
+                None => Ok(()),
+            })
+    }
+
+    /// Wait for the event loop to exit
+    ///
+    /// Returns the error which ended the event loop, e.g. a
+    /// `ConnectionError` if Neovim closed the connection, or `Ok(())` if it
+    /// was stopped with `stop_event_loop`. Returns immediately if the event
+    /// loop isn't running or has already been joined.
+    ///
+    /// This must not be called from a request or notification handler, which
+    /// run on the event loop's dispatch thread.
+    pub fn join(&self) -> Result<(), Error> {
+        self.client.join()
+    }
+
+    /// Whether the event loop is running and the connection is open
+    ///
+    /// This becomes `false` once Neovim closes the connection, e.g. because
+    /// it exited or restarted.
+    pub fn is_alive(&self) -> bool {
+        self.client.is_alive()
+    }
+
+    /// Register a callback for when Neovim closes the connection
+    ///
+    /// Calls waiting for a response fail immediately when this happens. The
+    /// callback runs on the event loop's read thread.
+    pub fn on_disconnect<F>(&self, callback: F)
+    where
+        F: Fn() + Send + 'static,
+    {
+        self.client.on_disconnect(callback)
+    }
+
+    /// Register a cleanup hook run by `shutdown`
+    ///
+    /// Hooks run in the reverse of the order they were registered, after
+    /// calls in flight have finished, and may still call Neovim from the
+    /// thread running them.
+    pub fn on_shutdown<F>(&self, hook: F)
+    where
+        F: FnOnce() -> Result<(), Error> + Send + 'static,
+    {
+        self.shutdown.on_shutdown(Box::new(hook))
+    }
+
+    /// Shut the session down gracefully, closing the connection
+    ///
+    /// New calls and notifications fail with `Error::ConnectionError` from
+    /// every clone of the session, while calls already sent and requests
+    /// from Neovim being handled are given until `deadline` to finish.
+    /// Calls still waiting after that fail with `Error::ConnectionError`.
+    /// The hooks registered with `on_shutdown` then run, and the event loop
+    /// is stopped. Returns the first error from a hook or from closing the
+    /// connection, and `Ok(())` if the session is already shutting down.
+    ///
+    /// See the `shutdown` module for an example.
+    pub fn shutdown(&self, deadline: Duration) -> Result<(), Error> {
+        if !self.shutdown.start() {
+            return Ok(());
+        }
+        let start = Instant::now();
+        loop {
+            let stats = self.stats();
+            if stats.pending_calls == 0 && stats.pending_requests == 0 {
+                break;
             }
-            Connection::STDIO(ref mut client) => {
-                client.start_event_loop(request_handler, notification_handler)
+            if start.elapsed() >= deadline || !self.is_alive() {
+                self.client
+                    .cancel_pending("The session shut down before Neovim responded");
+                break;
             }
-            #[cfg(unix)]
-            Connection::UNIX(ref mut client) => {
-                client.start_event_loop(request_handler, notification_handler)
+            thread::sleep(SHUTDOWN_POLL_INTERVAL);
+        }
+        let hooks = self.shutdown.run_hooks();
+        let stopped = self.stop_event_loop();
+        hooks.and(stopped)
+    }
+
+    /// Re-establish a closed connection to the same address
+    ///
+    /// If the event loop had been started it is restarted with the same
+    /// handlers, while callbacks and notification subscribers are kept.
+    /// Sessions connected over stdin/stdout can't be reconnected.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::session::Session;
+    /// use std::{thread, time::Duration};
+    ///
+    /// let session = Session::from_tcp("127.0.0.1:6666").unwrap();
+    /// session.start_event_loop(None, None);
+    ///
+    /// loop {
+    ///     if !session.is_alive() && session.reconnect().is_err() {
+    ///         eprintln!("Waiting for Neovim to restart");
+    ///     }
+    ///     thread::sleep(Duration::from_secs(1));
+    /// }
+    /// ```
+    pub fn reconnect(&self) -> Result<(), Error> {
+        // The new connection may be to a different version of Neovim
+        *self.api_info.lock().unwrap() = Metadata::Unknown;
+        let nvim = Nvim::from_session(self.clone());
+        let (reader, writer, close) = match &self.endpoint {
+            Some(endpoint) => connect(endpoint)?,
+            None => {
+                return Err(Error::ConnectionError(
+                    "Only socket connections can be reconnected".to_string(),
+                ))
             }
+        };
+        self.client.reconnect(reader, writer, nvim)?;
+        *self.close.lock().unwrap() = Some(close);
+        self.request_api_info();
+        if let Some(info) = self.client_info.lock().unwrap().clone() {
+            self.notify("nvim_set_client_info", info)?;
+        }
+        Ok(())
+    }
+
+    /// Get the API metadata of the connected Neovim
+    ///
+    /// The metadata is fetched once per connection and then cached.
+    pub fn api_info(&self) -> Result<Arc<ApiInfo>, Error> {
+        let mut cache = self.api_info.lock().unwrap();
+        if let Some(info) = cache.resolve()? {
+            return Ok(info);
+        }
+        // Sent directly, as `call` would check its arguments against the
+        // metadata being fetched
+        self.shutdown.check()?;
+        check_blocking("nvim_get_api_info")?;
+        let response = self
+            .send("nvim_get_api_info", Vec::new())?
+            .wait_timeout(CALL_TIMEOUT)?;
+        let info = Arc::new(ApiInfo::from_value(response)?);
+        *cache = Metadata::Fetched(info.clone());
+        Ok(info)
+    }
+
+    /// Request the API metadata as the event loop starts, and read the
+    /// response in the background
+    ///
+    /// Calls made in the meantime wait for it in `check_args`, so they are
+    /// always checked against the metadata when Neovim provides it.
+    fn request_api_info(&self) {
+        let mut cache = self.api_info.lock().unwrap();
+        if let Ok(handle) = self.send("nvim_get_api_info", Vec::new()) {
+            *cache = Metadata::Requested(handle);
+            // Only the response is read, so a failed request isn't repeated
+            let cache = self.api_info.clone();
+            thread::spawn(move || {
+                let _ = cache.lock().unwrap().resolve();
+            });
+        }
+    }
+
+    /// Describe this client to Neovim using `nvim_set_client_info`
+    ///
+    /// `info` is kept and sent again whenever the session reconnects.
+    pub fn announce(&self, info: Vec<Value>) -> Result<(), Error> {
+        self.call("nvim_set_client_info", info.clone())?;
+        *self.client_info.lock().unwrap() = Some(info);
+        Ok(())
+    }
+
+    /// Get the ID of this client's channel, e.g. for `rpcnotify`
+    ///
+    /// Fetched with the rest of the API metadata and then cached.
+    pub fn channel_id(&self) -> Result<i64, Error> {
+        Ok(self.api_info()?.channel)
+    }
+
+    /// Set the limits applied when decoding incoming RPC messages
+    ///
+    /// Messages exceeding these limits are skipped and never reach a handler
+    /// or caller. The defaults suit regular Neovim traffic, while
+    /// `DecodeLimits::bulk()` can be used for very large buffer transfers.
+    pub fn set_decode_limits(&self, limits: DecodeLimits) {
+        self.client.set_decode_limits(limits)
+    }
+
+    /// Set how many notifications can be queued by `notify` before it blocks
+    ///
+    /// This takes effect the next time the event loop is started. The
+    /// default is `client::DEFAULT_WRITE_QUEUE_CAPACITY`.
+    pub fn set_write_queue_capacity(&self, capacity: usize) {
+        self.client.set_write_queue_capacity(capacity)
+    }
+
+    /// Set how many requests from Neovim can be handled or waiting to be
+    /// handled at once
+    ///
+    /// Requests beyond this are answered straight away with an error asking
+    /// Neovim to retry later, and reported to the `on_error` callback as
+    /// `Error::LimitExceeded`. This stops a flood of requests, e.g. from an
+    /// autocommand in a loop, from queueing without bound. The default is
+    /// `client::DEFAULT_MAX_PENDING_REQUESTS`.
+    pub fn set_max_pending_requests(&self, max: usize) {
+        self.client.set_max_pending_requests(max)
+    }
+
+    /// Register a callback for errors encountered by the event loop
+    ///
+    /// This includes messages which couldn't be decoded or exceeded the
+    /// decoding limits, and responses to unknown requests (e.g. ones which
+    /// arrived after the call timed out). These errors are otherwise ignored
+    /// and the event loop keeps running until the connection is closed.
+    ///
+    /// If the incoming stream is corrupted, e.g. by something else writing
+    /// to Neovim's stdout, `Error::StreamCorrupted` is passed to the callback
+    /// and every pending request, and the event loop exits.
+    pub fn on_error<F>(&self, callback: F)
+    where
+        F: Fn(Error) + Send + 'static,
+    {
+        self.client.on_error(callback)
+    }
+
+    /// Register a callback for asynchronous errors reported by Neovim
+    ///
+    /// Neovim sends an `nvim_error_event` notification for errors it can't
+    /// return in a response, such as an API method called using a
+    /// notification failing. Without a callback they are
+    /// passed to the `on_error` callback as an `RpcError`. The notifications
+    /// are still delivered to the notification handler and subscribers.
+    pub fn on_async_error<F>(&self, callback: F)
+    where
+        F: Fn(AsyncError) + Send + 'static,
+    {
+        self.client.on_async_error(callback)
+    }
+
+    /// The number of asynchronous errors reported by Neovim on this session
+    pub fn async_error_count(&self) -> u64 {
+        self.client.async_error_count()
+    }
+
+    /// A snapshot of the session's pending requests and error counts
+    ///
+    /// Once every call has returned and Neovim has no requests in flight,
+    /// both pending counts are zero, so a long-running plugin can check
+    /// nothing is leaked.
+    pub fn stats(&self) -> ClientStats {
+        self.client.stats()
+    }
+
+    /// The latency and error metrics of the calls made on this session, by
+    /// method
+    ///
+    /// See the `metrics` module for an example.
+    pub fn metrics(&self) -> HashMap<String, MethodMetrics> {
+        self.client.metrics()
+    }
+
+    /// Clear the session's call metrics, e.g. between benchmark runs
+    pub fn reset_metrics(&self) {
+        self.client.reset_metrics()
+    }
+
+    /// Set what `ResponseHandle::cancel` does besides abandoning a request
+    ///
+    /// Defaults to `CancelPolicy::Abandon`.
+    pub fn set_cancel_policy(&self, policy: CancelPolicy) {
+        self.client.set_cancel_policy(policy)
+    }
+
+    /// Set how long a call takes before it is logged and counted as slow
+    ///
+    /// Defaults to `metrics::DEFAULT_SLOW_CALL_THRESHOLD`.
+    pub fn set_slow_call_threshold(&self, threshold: Duration) {
+        self.client.set_slow_call_threshold(threshold)
+    }
+
+    /// Set a callback run as each call completes, e.g. to log slow calls or
+    /// record them as `tracing` spans
+    ///
+    /// The callback runs on the event loop's read thread, so must not make
+    /// calls on this session.
+    pub fn on_call<F>(&self, callback: F)
+    where
+        F: Fn(&CallRecord) + Send + 'static,
+    {
+        self.client.on_call(callback)
+    }
+
+    /// Set what happens when a function the connected Neovim has deprecated
+    /// is called
+    ///
+    /// By default each deprecated function is warned about once. See the
+    /// `deprecation` module for details.
+    pub fn set_deprecation_policy(&self, policy: DeprecationPolicy) {
+        self.deprecations.set_policy(policy)
+    }
+
+    /// Set a callback run the first time each deprecated function is
+    /// called, e.g. to show the warning in Neovim
+    ///
+    /// The callback runs before the call is sent, on the calling thread.
+    pub fn on_deprecated<F>(&self, callback: F)
+    where
+        F: Fn(&Deprecation) + Send + 'static,
+    {
+        self.deprecations.on_deprecated(Box::new(callback))
+    }
+
+    /// Subscribe to notifications for the given methods
+    ///
+    /// Any number of subscribers may be registered, each receiving its own
+    /// copy of every matching notification alongside the notification
+    /// handler. An empty `methods` slice subscribes to all notifications.
+    /// Dropping the receiver unsubscribes.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::session::Session;
+    ///
+    /// let session = Session::from_tcp("127.0.0.1:6666").unwrap();
+    /// let events = session.subscribe(&["buf_changed"]);
+    /// session.start_event_loop(None, None);
+    ///
+    /// for notification in events {
+    ///     println!("{}: {:?}", notification.method, notification.params);
+    /// }
+    /// ```
+    pub fn subscribe(&self, methods: &[&str]) -> mpsc::Receiver<Notification> {
+        self.client.subscribe(methods)
+    }
+
+    /// Subscribe to the parameters of notifications for `method`
+    ///
+    /// Like `subscribe`, but only the parameters of each notification are
+    /// sent. The subscription lasts until the receiver is dropped or
+    /// `unsubscribe_notifications` is called.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rsnvim::session::Session;
+    /// use std::thread;
+    ///
+    /// let session = Session::from_tcp("127.0.0.1:6666").unwrap();
+    /// let events = session.subscribe_notifications("my_plugin_event");
+    /// session.start_event_loop(None, None);
+    ///
+    /// thread::spawn(move || {
+    ///     for params in events {
+    ///         println!("{:?}", params);
+    ///     }
+    /// });
+    /// ```
+    pub fn subscribe_notifications(&self, method: &str) -> mpsc::Receiver<Vec<Value>> {
+        self.client.subscribe_notifications(method)
+    }
+
+    /// Receive the `redraw` notifications of an attached UI a batch at a
+    /// time, each ending with a `flush` event
+    ///
+    /// See `RedrawReceiver` for details.
+    pub fn subscribe_redraw(&self, mode: RedrawMode) -> RedrawReceiver {
+        self.client.subscribe_redraw(mode)
+    }
+
+    /// Remove every subscription made with `subscribe_notifications` for
+    /// `method`
+    ///
+    /// Their receivers are closed, ending any iteration over them.
+    pub fn unsubscribe_notifications(&self, method: &str) {
+        self.client.unsubscribe_notifications(method)
+    }
+
+    /// Wait for the first notification for `method` whose parameters match
+    /// `predicate`, returning its parameters
+    ///
+    /// Notifications received before this is called are missed, so use
+    /// `expect_notification` to wait for one caused by a call. The
+    /// notification handler and other subscribers still receive every
+    /// notification. Returns `Error::TimeoutError` if none matches within
+    /// `timeout`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rmpv::Value;
+    /// use rsnvim::session::Session;
+    /// use std::time::Duration;
+    ///
+    /// let session = Session::from_tcp("127.0.0.1:6666").unwrap();
+    /// session.start_event_loop(None, None);
+    ///
+    /// // Sent by `autocmd BufWritePost * call rpcnotify(1, 'written', expand('<afile>'))`
+    /// let params = session
+    ///     .wait_for_notification(
+    ///         "written",
+    ///         |params| params.first().and_then(Value::as_str) == Some("init.lua"),
+    ///         Duration::from_secs(30),
+    ///     )
+    ///     .unwrap();
+    /// ```
+    pub fn wait_for_notification<P>(
+        &self,
+        method: &str,
+        predicate: P,
+        timeout: Duration,
+    ) -> Result<Vec<Value>, Error>
+    where
+        P: FnMut(&[Value]) -> bool,
+    {
+        self.expect_notification(method).wait(predicate, timeout)
+    }
+
+    /// Start receiving notifications for `method`, to wait for one with the
+    /// returned `NotificationWaiter`
+    ///
+    /// The subscription ends when the waiter is dropped.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rmpv::Value;
+    /// use rsnvim::session::Session;
+    /// use std::time::Duration;
+    ///
+    /// let session = Session::from_tcp("127.0.0.1:6666").unwrap();
+    /// session.start_event_loop(None, None);
+    ///
+    /// // Subscribed before writing, so the notification can't be missed
+    /// let written = session.expect_notification("written");
+    /// session.call("nvim_command", vec![Value::from("write")]).unwrap();
+    /// written.wait(|_| true, Duration::from_secs(5)).unwrap();
+    /// ```
+    pub fn expect_notification(&self, method: &str) -> NotificationWaiter {
+        NotificationWaiter {
+            method: method.to_string(),
+            notifications: self.subscribe(&[method]),
         }
     }
 
     /// Call a RPC function
     ///
-    /// This function allows for arbitrary Neovim function calls
-    pub fn call(&mut self, method: &str, args: Vec<Value>) -> Result<Value, Error> {
-        match self.client {
-            Connection::TCP(ref mut client) => Ok(client.call(method, args)?),
-            Connection::STDIO(ref mut client) => Ok(client.call(method, args)?),
-            #[cfg(unix)]
-            Connection::UNIX(ref mut client) => Ok(client.call(method, args)?),
+    /// This function allows for arbitrary Neovim function calls, waiting up
+    /// to one second for the response.
+    ///
+    /// In debug builds, when Neovim provides the API metadata, arguments
+    /// which don't match the function's parameters are rejected with
+    /// `Error::InvalidArgs` without sending anything.
+    pub fn call(&self, method: &str, args: Vec<Value>) -> Result<Value, Error> {
+        // Check before sending, so the request isn't run when it would fail
+        check_blocking(method)?;
+        self.call_async(method, args)?.wait_timeout(CALL_TIMEOUT)
+    }
+
+    /// Call a RPC function without waiting for the response
+    ///
+    /// This allows many requests to be issued at once, with the responses
+    /// collected later from the returned handles.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rmpv::Value;
+    /// use rsnvim::session::Session;
+    ///
+    /// let session = Session::from_tcp("127.0.0.1:6666").unwrap();
+    /// session.start_event_loop(None, None);
+    ///
+    /// let handles: Vec<_> = ["&tabstop", "&shiftwidth"]
+    ///     .iter()
+    ///     .map(|expr| session.call_async("nvim_eval", vec![Value::from(*expr)]))
+    ///     .collect::<Result<_, _>>()
+    ///     .unwrap();
+    /// for handle in handles {
+    ///     println!("{}", handle.wait().unwrap());
+    /// }
+    /// ```
+    pub fn call_async(&self, method: &str, args: Vec<Value>) -> Result<ResponseHandle, Error> {
+        self.shutdown.check()?;
+        self.check_args(method, &args)?;
+        let args = if method == SET_EXTMARK {
+            match self.extmarks.push(self, self.reserve_msgid(), args) {
+                Ok(handle) => return Ok(handle),
+                Err(args) => args,
+            }
+        } else {
+            args
+        };
+        // Send queued extmark updates first so calls stay in order
+        if self.extmarks.has_pending() {
+            self.flush_extmarks()?;
+        }
+        self.send(method, args)
+    }
+
+    /// Send a notification to Neovim, which has no response
+    ///
+    /// Notifications are queued and written by a dedicated thread, so bulk
+    /// sends don't hold up requests. This blocks while the queue is full.
+    /// Requests wait for earlier queued notifications to be written, so
+    /// Neovim receives them in order.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rmpv::Value;
+    /// use rsnvim::session::Session;
+    ///
+    /// let session = Session::from_tcp("127.0.0.1:6666").unwrap();
+    /// session.start_event_loop(None, None);
+    ///
+    /// for key in ["i", "h", "i", "<Esc>"] {
+    ///     session.notify("nvim_input", vec![Value::from(key)]).unwrap();
+    /// }
+    /// let mode = session.call("nvim_get_mode", Vec::new()).unwrap();
+    /// ```
+    pub fn notify(&self, method: &str, args: Vec<Value>) -> Result<(), Error> {
+        self.shutdown.check()?;
+        self.check_args(method, &args)?;
+        // Send queued extmark updates first so calls stay in order
+        if self.extmarks.has_pending() {
+            self.flush_extmarks()?;
+        }
+        self.client.notify(method, args)
+    }
+
+    /// Send a notification to Neovim without blocking
+    ///
+    /// Returns `Error::LimitExceeded` if the write queue is full.
+    pub fn try_notify(&self, method: &str, args: Vec<Value>) -> Result<(), Error> {
+        self.shutdown.check()?;
+        self.check_args(method, &args)?;
+        if self.extmarks.has_pending() {
+            self.flush_extmarks()?;
+        }
+        self.client.try_notify(method, args)
+    }
+
+    /// Block until every queued notification has been written
+    pub fn flush_notifications(&self) -> Result<(), Error> {
+        self.client.flush_notifications()
+    }
+
+    /// Coalesce asynchronous `nvim_buf_set_extmark` calls in the namespace
+    /// `ns_id` into atomic batches
+    ///
+    /// Calls made with `call_async` are queued and sent together once
+    /// `window` has elapsed since the first was queued, or on the next
+    /// `flush_extmarks` if `window` is `None`. Any other call sends the queue
+    /// first, so calls are still run in the order they were made. The
+    /// returned handles receive each call's result as usual.
+    ///
+    /// Synchronous calls, like `Buffer::set_extmark`, wait for the queue to be
+    /// sent, so with a `None` window they time out unless another thread
+    /// flushes it.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rmpv::Value;
+    /// use rsnvim::session::Session;
+    /// use std::time::Duration;
+    ///
+    /// let session = Session::from_tcp("127.0.0.1:6666").unwrap();
+    /// session.start_event_loop(None, None);
+    ///
+    /// let ns_id = 1;
+    /// session.coalesce_extmarks(ns_id, Some(Duration::from_millis(1)));
+    /// let handles: Vec<_> = (0..100)
+    ///     .map(|line| {
+    ///         let args = vec![
+    ///             Value::from(0),
+    ///             Value::from(ns_id),
+    ///             Value::from(line),
+    ///             Value::from(0),
+    ///             Value::Map(Vec::new()),
+    ///         ];
+    ///         session.call_async("nvim_buf_set_extmark", args).unwrap()
+    ///     })
+    ///     .collect();
+    /// ```
+    pub fn coalesce_extmarks(&self, ns_id: i64, window: Option<Duration>) {
+        self.extmarks.enable(ns_id, window);
+    }
+
+    /// Stop coalescing extmark updates in the namespace `ns_id`, sending any
+    /// which are queued
+    pub fn stop_coalescing_extmarks(&self, ns_id: i64) -> Result<(), Error> {
+        self.extmarks.disable(ns_id);
+        self.flush_extmarks()
+    }
+
+    /// Send any queued extmark updates now
+    pub fn flush_extmarks(&self) -> Result<(), Error> {
+        self.extmarks
+            .flush(|args| self.send("nvim_call_atomic", args))
+    }
+
+    /// Check a call against the API metadata, waiting for it if it was
+    /// requested when the event loop started
+    ///
+    /// API methods the connected Neovim doesn't provide, e.g. those added in
+    /// a newer version than it, return an `UnsupportedVersion` error rather
+    /// than being sent. In debug builds `args` are also checked against the
+    /// parameters of `method`, so mistakes are reported here rather than as
+    /// an exception from Neovim.
+    ///
+    /// Calls to deprecated functions are then handled by the deprecation
+    /// policy.
+    fn check_args(&self, method: &str, args: &[Value]) -> Result<(), Error> {
+        // Calls can't be checked if Neovim didn't provide the metadata
+        let info = match self.api_info.lock().unwrap().resolve() {
+            Ok(Some(info)) => info,
+            _ => return Ok(()),
+        };
+        match info.function(method) {
+            Some(function) => {
+                if cfg!(debug_assertions) {
+                    function.check_args(args)?;
+                }
+                match function.deprecated_since {
+                    Some(since) => self.deprecations.check(method, since),
+                    None => Ok(()),
+                }
+            }
+            // Other methods may be handled by a plugin, e.g. a remote host
+            None if method.starts_with("nvim_") => Err(Error::UnsupportedVersion(format!(
+                "Neovim {} (API level {}) doesn't provide {}",
+                info.version, info.version.api_level, method
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Send a request directly to the client
+    fn send(&self, method: &str, args: Vec<Value>) -> Result<ResponseHandle, Error> {
+        self.client.call(method, args)
+    }
+
+    /// Reserve a request ID without sending a request
+    fn reserve_msgid(&self) -> u64 {
+        self.client.reserve_msgid()
+    }
+}
+
+/// Connect to the socket at `endpoint`
+fn connect(endpoint: &Endpoint) -> std::io::Result<Socket> {
+    match endpoint {
+        Endpoint::Tcp(addr) => {
+            let reader = TcpStream::connect(addr)?;
+            let writer = reader.try_clone()?;
+            let socket = reader.try_clone()?;
+            let close = move || socket.shutdown(Shutdown::Both);
+            Ok((Box::new(reader), Box::new(writer), Box::new(close)))
+        }
+        #[cfg(unix)]
+        Endpoint::Unix(path) => {
+            let reader = UnixStream::connect(path)?;
+            let writer = reader.try_clone()?;
+            let socket = reader.try_clone()?;
+            let close = move || socket.shutdown(Shutdown::Both);
+            Ok((Box::new(reader), Box::new(writer), Box::new(close)))
         }
     }
 }
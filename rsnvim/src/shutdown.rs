@@ -0,0 +1,192 @@
+//! Graceful shutdown of a session
+//!
+//! `Session::shutdown` stops a session in order: new calls and
+//! notifications are rejected, calls already sent and requests from Neovim
+//! being handled are given until a deadline to finish, the cleanup hooks
+//! registered with `Session::on_shutdown` run, and the connection is
+//! closed. Cleanup hooks may still call Neovim, e.g. to delete autocommands
+//! or clear extmarks, as calls from the thread running them are allowed.
+//!
+//! With the `signals` feature on Unix, `run_until_shutdown` waits for
+//! SIGTERM or SIGINT and then shuts the session down, so a daemon built on
+//! this crate exits cleanly when stopped.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use rsnvim::api::Nvim;
+//!
+//! let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+//! nvim.start_event_loop(None, None);
+//!
+//! let cleanup = nvim.clone();
+//! nvim.on_shutdown(move || cleanup.command("echo 'Goodbye'"));
+//!
+//! // Wait up to a second for calls in flight, then clean up and close
+//! nvim.shutdown(Duration::from_secs(1)).unwrap();
+//! ```
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::error::Error;
+
+type ShutdownHook = Box<dyn FnOnce() -> Result<(), Error> + Send>;
+
+thread_local! {
+    /// Whether the current thread is running cleanup hooks, so its calls
+    /// are allowed during shutdown
+    static RUNNING_HOOKS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// The shutdown state of a session, shared by its clones
+#[derive(Default)]
+pub(crate) struct ShutdownState {
+    draining: AtomicBool,
+    hooks: Mutex<Vec<ShutdownHook>>,
+}
+
+impl ShutdownState {
+    /// Fail if the session is shutting down and the current thread isn't
+    /// running its cleanup hooks
+    pub(crate) fn check(&self) -> Result<(), Error> {
+        if self.draining.load(Ordering::SeqCst) && !RUNNING_HOOKS.with(Cell::get) {
+            return Err(Error::ConnectionError(
+                "The session is shutting down".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Reject new calls, returning `false` if they already were
+    pub(crate) fn start(&self) -> bool {
+        !self.draining.swap(true, Ordering::SeqCst)
+    }
+
+    pub(crate) fn on_shutdown(&self, hook: ShutdownHook) {
+        self.hooks.lock().unwrap().push(hook);
+    }
+
+    /// Run every cleanup hook, most recently registered first, returning
+    /// the first error
+    ///
+    /// Every hook runs even if one fails.
+    pub(crate) fn run_hooks(&self) -> Result<(), Error> {
+        let hooks = std::mem::take(&mut *self.hooks.lock().unwrap());
+        RUNNING_HOOKS.with(|running| running.set(true));
+        let result = hooks
+            .into_iter()
+            .rev()
+            .map(|hook| hook())
+            .fold(Ok(()), Result::and);
+        RUNNING_HOOKS.with(|running| running.set(false));
+        result
+    }
+}
+
+/// Why `run_until_shutdown` shut the session down
+#[cfg(all(unix, feature = "signals"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShutdownCause {
+    /// The process received this signal, e.g. `libc::SIGTERM`
+    Signal(i32),
+    /// Neovim closed the connection
+    Disconnected,
+}
+
+#[cfg(all(unix, feature = "signals"))]
+mod signals {
+    use std::sync::atomic::{AtomicI32, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    use super::ShutdownCause;
+    use crate::api::Nvim;
+    use crate::error::Error;
+
+    /// The signals which shut the session down
+    const SIGNALS: [libc::c_int; 2] = [libc::SIGTERM, libc::SIGINT];
+
+    /// How often the wait for a signal checks the connection is open
+    const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+    /// The last signal received, or `0`
+    static RECEIVED: AtomicI32 = AtomicI32::new(0);
+
+    extern "C" fn on_signal(signal: libc::c_int) {
+        RECEIVED.store(signal, Ordering::SeqCst);
+    }
+
+    /// Replace the action of `signal`, returning the previous action
+    fn set_action(
+        signal: libc::c_int,
+        handler: libc::sighandler_t,
+    ) -> Result<libc::sigaction, Error> {
+        // SAFETY: the structs are zeroed plain data, and `on_signal` only
+        // stores to an atomic, which is async-signal-safe
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = handler;
+            libc::sigemptyset(&mut action.sa_mask);
+            let mut previous: libc::sigaction = std::mem::zeroed();
+            if libc::sigaction(signal, &action, &mut previous) != 0 {
+                return Err(Error::IoError(std::io::Error::last_os_error()));
+            }
+            Ok(previous)
+        }
+    }
+
+    /// Run until the process receives SIGTERM or SIGINT, or Neovim closes
+    /// the connection, then shut `nvim`'s session down with `deadline`
+    ///
+    /// The previous handlers of the signals are restored once one arrives,
+    /// so a second SIGINT during the shutdown stops the process as usual.
+    /// See `Session::shutdown` for how the session is shut down.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// use rsnvim::api::Nvim;
+    ///
+    /// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+    /// nvim.start_event_loop(None, None);
+    ///
+    /// let cause = rsnvim::run_until_shutdown(&nvim, Duration::from_secs(2)).unwrap();
+    /// eprintln!("Stopped by {:?}", cause);
+    /// ```
+    pub fn run_until_shutdown(nvim: &Nvim, deadline: Duration) -> Result<ShutdownCause, Error> {
+        RECEIVED.store(0, Ordering::SeqCst);
+        let mut previous = Vec::new();
+        for signal in SIGNALS {
+            previous.push((
+                signal,
+                set_action(
+                    signal,
+                    on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t,
+                )?,
+            ));
+        }
+        let cause = loop {
+            match RECEIVED.load(Ordering::SeqCst) {
+                0 if nvim.is_alive() => thread::sleep(POLL_INTERVAL),
+                0 => break ShutdownCause::Disconnected,
+                signal => break ShutdownCause::Signal(signal),
+            }
+        };
+        for (signal, action) in previous {
+            // SAFETY: `action` was returned by `sigaction` for `signal`
+            unsafe {
+                libc::sigaction(signal, &action, std::ptr::null_mut());
+            }
+        }
+        nvim.shutdown(deadline)?;
+        Ok(cause)
+    }
+}
+
+#[cfg(all(unix, feature = "signals"))]
+pub use signals::run_until_shutdown;
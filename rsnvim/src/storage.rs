@@ -0,0 +1,236 @@
+//! Persistent state files stored alongside Neovim's own data
+//!
+//! A `Storage` resolves `stdpath('data')` and `stdpath('state')` once, then
+//! reads and writes small files in a directory named after the plugin. Files
+//! are written atomically, so a crash never leaves a partially written file.
+//!
+//! Values are stored as MessagePack. Other formats like JSON can be stored
+//! by encoding them with `write_bytes`.
+use rmpv::Value;
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::api::{FromValue, Nvim};
+use crate::error::Error;
+
+/// Counter used to give each temporary file a unique name
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Where a file is stored
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Location {
+    /// `stdpath('data')`, for data the user would want to keep like
+    /// bookmarks
+    Data,
+    /// `stdpath('state')`, for state which can be lost like caches or
+    /// recently used files
+    State,
+}
+
+/// Namespaced state files for a plugin
+///
+/// # Example
+///
+/// ```no_run
+/// use rmpv::Value;
+/// use rsnvim::api::Nvim;
+/// use rsnvim::storage::{Location, Storage};
+///
+/// let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+/// nvim.start_event_loop(None, None);
+///
+/// // Files are stored in e.g. ~/.local/state/nvim/my-plugin/
+/// let storage = Storage::new(&mut nvim, "my-plugin").unwrap();
+/// storage
+///     .write_value(Location::State, "recent", &Value::from(vec![Value::from("a.rs")]))
+///     .unwrap();
+/// let recent = storage.read_value(Location::State, "recent").unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Storage {
+    namespace: String,
+    data_dir: PathBuf,
+    state_dir: PathBuf,
+}
+
+impl Storage {
+    /// Store files in the `namespace` directory of the connected Neovim's
+    /// data and state directories
+    pub fn new(nvim: &mut Nvim, namespace: impl Into<String>) -> Result<Self, Error> {
+        let data_dir = stdpath(nvim, "data")?;
+        let state_dir = stdpath(nvim, "state")?;
+        Self::with_dirs(namespace, data_dir, state_dir)
+    }
+
+    /// Store files in the `namespace` directory of the given directories
+    ///
+    /// Useful when Neovim isn't connected, e.g. in tests.
+    pub fn with_dirs(
+        namespace: impl Into<String>,
+        data_dir: impl Into<PathBuf>,
+        state_dir: impl Into<PathBuf>,
+    ) -> Result<Self, Error> {
+        let namespace = namespace.into();
+        validate_name("namespace", &namespace)?;
+        Ok(Storage {
+            namespace,
+            data_dir: data_dir.into(),
+            state_dir: state_dir.into(),
+        })
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Get the directory holding this namespace's files in `location`
+    pub fn dir(&self, location: Location) -> PathBuf {
+        match location {
+            Location::Data => self.data_dir.join(&self.namespace),
+            Location::State => self.state_dir.join(&self.namespace),
+        }
+    }
+
+    /// Get the path of the file `name` in `location`
+    ///
+    /// Names must be a single path component, so files can't escape the
+    /// namespace's directory.
+    pub fn path(&self, location: Location, name: &str) -> Result<PathBuf, Error> {
+        validate_name("file name", name)?;
+        Ok(self.dir(location).join(name))
+    }
+
+    /// Read the file `name`, or `None` if it doesn't exist
+    pub fn read_bytes(&self, location: Location, name: &str) -> Result<Option<Vec<u8>>, Error> {
+        let path = self.path(location, name)?;
+        match fs::read(&path) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(storage_error("read", &path, error)),
+        }
+    }
+
+    /// Atomically replace the file `name` with `bytes`
+    ///
+    /// The contents are written to a temporary file which is then renamed,
+    /// so readers only ever see the old or the new contents.
+    pub fn write_bytes(&self, location: Location, name: &str, bytes: &[u8]) -> Result<(), Error> {
+        let path = self.path(location, name)?;
+        let dir = self.dir(location);
+        fs::create_dir_all(&dir).map_err(|error| storage_error("create", &dir, error))?;
+
+        let temp = dir.join(format!(
+            ".{}.{}-{}.tmp",
+            name,
+            process::id(),
+            TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let result = write_synced(&temp, bytes)
+            .and_then(|_| fs::rename(&temp, &path))
+            .map_err(|error| storage_error("write", &path, error));
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        result
+    }
+
+    /// Read the MessagePack file `name`, or `None` if it doesn't exist
+    pub fn read_value(&self, location: Location, name: &str) -> Result<Option<Value>, Error> {
+        match self.read_bytes(location, name)? {
+            Some(bytes) => Ok(Some(rmpv::decode::read_value(&mut bytes.as_slice())?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Atomically replace the file `name` with `value` encoded as MessagePack
+    pub fn write_value(&self, location: Location, name: &str, value: &Value) -> Result<(), Error> {
+        let mut bytes = Vec::new();
+        rmpv::encode::write_value(&mut bytes, value)?;
+        self.write_bytes(location, name, &bytes)
+    }
+
+    /// Read the MessagePack file `name` as a `T`, or `None` if it doesn't
+    /// exist
+    pub fn read<T: FromValue>(&self, location: Location, name: &str) -> Result<Option<T>, Error> {
+        self.read_value(location, name)?
+            .map(T::from_value)
+            .transpose()
+    }
+
+    /// Read the MessagePack file `name` as any type implementing
+    /// `serde::Deserialize`, or `None` if it doesn't exist
+    ///
+    /// This requires the `serde` feature.
+    #[cfg(feature = "serde")]
+    pub fn read_serde<T: ::serde::de::DeserializeOwned>(
+        &self,
+        location: Location,
+        name: &str,
+    ) -> Result<Option<T>, Error> {
+        self.read_value(location, name)?
+            .map(crate::serde::from_value)
+            .transpose()
+    }
+
+    /// Atomically replace the file `name` with any type implementing
+    /// `serde::Serialize`, encoded as MessagePack
+    ///
+    /// This requires the `serde` feature.
+    #[cfg(feature = "serde")]
+    pub fn write_serde<T: ::serde::Serialize + ?Sized>(
+        &self,
+        location: Location,
+        name: &str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.write_value(location, name, &crate::serde::to_value(value)?)
+    }
+
+    /// Remove the file `name`, returning whether it existed
+    pub fn remove(&self, location: Location, name: &str) -> Result<bool, Error> {
+        let path = self.path(location, name)?;
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(true),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(false),
+            Err(error) => Err(storage_error("remove", &path, error)),
+        }
+    }
+}
+
+/// Resolve the standard path `what` of the connected Neovim
+fn stdpath(nvim: &mut Nvim, what: &str) -> Result<PathBuf, Error> {
+    let path = nvim.call_function("stdpath", vec![Value::from(what)])?;
+    Ok(PathBuf::from(String::from_value(path)?))
+}
+
+/// Check that `name` is a single, normal path component
+fn validate_name(what: &str, name: &str) -> Result<(), Error> {
+    let invalid =
+        name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\', '\0']);
+    if invalid {
+        return Err(Error::InvalidArgument(format!(
+            "Invalid {} \"{}\"",
+            what, name
+        )));
+    }
+    Ok(())
+}
+
+/// Write `bytes` to `path` and wait for them to reach the disk
+fn write_synced(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let mut file = fs::File::create(path)?;
+    file.write_all(bytes)?;
+    file.sync_all()
+}
+
+fn storage_error(action: &str, path: &Path, error: std::io::Error) -> Error {
+    Error::StorageError(format!(
+        "Failed to {} {}: {}",
+        action,
+        path.display(),
+        error
+    ))
+}
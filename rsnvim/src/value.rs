@@ -0,0 +1,290 @@
+//! Utilities for inspecting `rmpv::Value`s
+//!
+//! Values returned by Neovim are often deeply nested dictionaries, which are
+//! hard to read when debug printed and verbose to navigate by hand.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rsnvim::api::Nvim;
+//! use rsnvim::value::{get_path, pretty};
+//!
+//! let mut nvim = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+//! nvim.start_event_loop(None, None);
+//!
+//! let info = nvim.get_api_info().unwrap();
+//! let metadata = &info[1];
+//! println!("{}", pretty(metadata));
+//! let name = get_path(metadata, "functions[0].name").and_then(|name| name.as_str());
+//! println!("The first function is {:?}", name);
+//! ```
+use rmpv::Value;
+
+use crate::api::{BUFFER_EXT, TABPAGE_EXT, WINDOW_EXT};
+
+/// The width `pretty` keeps values within before splitting them over lines
+const PRETTY_WIDTH: usize = 80;
+
+/// Format `value` like Vimscript, e.g. `{'name': 'main.rs', 'lines': [1, 2]}`
+///
+/// Arrays and dictionaries too wide to fit on one line are split with an
+/// item per line. Buffers, windows and tabpages are shown as
+/// `<buffer 1>` etc., and binary as a blob, e.g. `0z00FF`.
+pub fn pretty(value: &Value) -> String {
+    let mut out = String::new();
+    write_pretty(value, 0, &mut out);
+    out
+}
+
+fn write_pretty(value: &Value, indent: usize, out: &mut String) {
+    let line = compact(value);
+    if indent + line.len() <= PRETTY_WIDTH {
+        out.push_str(&line);
+        return;
+    }
+    let pad = " ".repeat(indent + 2);
+    match value {
+        Value::Array(items) => {
+            out.push_str("[\n");
+            for item in items {
+                out.push_str(&pad);
+                write_pretty(item, indent + 2, out);
+                out.push_str(",\n");
+            }
+            out.push_str(&" ".repeat(indent));
+            out.push(']');
+        }
+        Value::Map(entries) => {
+            out.push_str("{\n");
+            for (key, item) in entries {
+                out.push_str(&pad);
+                out.push_str(&compact(key));
+                out.push_str(": ");
+                write_pretty(item, indent + 2, out);
+                out.push_str(",\n");
+            }
+            out.push_str(&" ".repeat(indent));
+            out.push('}');
+        }
+        _ => out.push_str(&line),
+    }
+}
+
+/// Format `value` on one line
+fn compact(value: &Value) -> String {
+    match value {
+        Value::Nil => "v:null".to_string(),
+        Value::Boolean(true) => "v:true".to_string(),
+        Value::Boolean(false) => "v:false".to_string(),
+        Value::Integer(n) => n.to_string(),
+        Value::F32(f) => float(*f as f64),
+        Value::F64(f) => float(*f),
+        Value::String(s) => {
+            let s = String::from_utf8_lossy(s.as_bytes());
+            format!("'{}'", s.replace('\'', "''"))
+        }
+        Value::Binary(bytes) => {
+            let hex: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+            format!("0z{}", hex)
+        }
+        Value::Array(items) => {
+            let items: Vec<_> = items.iter().map(compact).collect();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Map(entries) => {
+            let entries: Vec<_> = entries
+                .iter()
+                .map(|(key, item)| format!("{}: {}", compact(key), compact(item)))
+                .collect();
+            format!("{{{}}}", entries.join(", "))
+        }
+        Value::Ext(kind, data) => {
+            let name = match *kind {
+                BUFFER_EXT => "buffer",
+                WINDOW_EXT => "window",
+                TABPAGE_EXT => "tabpage",
+                _ => "ext",
+            };
+            match ext_id(data) {
+                Some(id) if name != "ext" => format!("<{} {}>", name, id),
+                _ => format!("<ext {} {}>", kind, compact(&Value::Binary(data.clone()))),
+            }
+        }
+    }
+}
+
+/// Format a float so it reads as one, e.g. `1.0` rather than `1`
+fn float(f: f64) -> String {
+    if f.is_finite() && f.fract() == 0.0 {
+        format!("{:.1}", f)
+    } else {
+        f.to_string()
+    }
+}
+
+/// Decode the integer ID Neovim sends as the data of a handle
+fn ext_id(data: &[u8]) -> Option<i64> {
+    rmpv::decode::read_value(&mut &data[..]).ok()?.as_i64()
+}
+
+/// A part of a path given to `get_path`
+enum Segment<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+/// Split a path like `a.b[2].c` into its parts, or `None` if it is invalid
+fn parse_path(path: &str) -> Option<Vec<Segment<'_>>> {
+    let mut segments = Vec::new();
+    for part in path.split('.') {
+        let (key, mut indexes) = match part.find('[') {
+            Some(start) => part.split_at(start),
+            None => (part, ""),
+        };
+        // Only the first part of a path may have no key, e.g. `[0].name`
+        if !key.is_empty() {
+            segments.push(Segment::Key(key));
+        } else if !segments.is_empty() || indexes.is_empty() {
+            return None;
+        }
+        while !indexes.is_empty() {
+            let end = indexes.find(']')?;
+            segments.push(Segment::Index(indexes.get(1..end)?.parse().ok()?));
+            indexes = &indexes[end + 1..];
+            if !indexes.is_empty() && !indexes.starts_with('[') {
+                return None;
+            }
+        }
+    }
+    Some(segments)
+}
+
+/// Get the value at `path` within `value`, e.g. `"a.b[2].c"`
+///
+/// Keys select an entry of a dictionary and `[n]` an item of an array.
+/// Returns `None` if a part of the path is missing or the path is invalid,
+/// while an empty path returns `value` itself.
+pub fn get_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    if path.is_empty() {
+        return Some(value);
+    }
+    parse_path(path)?
+        .into_iter()
+        .try_fold(value, |value, segment| match segment {
+            Segment::Key(key) => value
+                .as_map()?
+                .iter()
+                .find(|(k, _)| k.as_str() == Some(key))
+                .map(|(_, v)| v),
+            Segment::Index(index) => value.as_array()?.get(index),
+        })
+}
+
+/// Convert `value` to JSON
+///
+/// Dictionary keys which aren't strings are formatted with `pretty`,
+/// binary becomes an array of bytes, and buffers, windows and tabpages
+/// become their IDs. Strings which aren't valid UTF-8 and floats which
+/// aren't finite can't be represented exactly, so are replaced with U+FFFD
+/// and `null` respectively.
+///
+/// This requires the `json` feature.
+#[cfg(feature = "json")]
+pub fn to_json(value: &Value) -> serde_json::Value {
+    use serde_json::Value as Json;
+
+    match value {
+        Value::Nil => Json::Null,
+        Value::Boolean(b) => Json::Bool(*b),
+        Value::Integer(n) => match n.as_i64() {
+            Some(n) => Json::from(n),
+            None => Json::from(n.as_u64().unwrap()),
+        },
+        Value::F32(f) => Json::from(*f as f64),
+        Value::F64(f) => Json::from(*f),
+        Value::String(s) => Json::from(String::from_utf8_lossy(s.as_bytes())),
+        Value::Binary(bytes) => Json::from(bytes.clone()),
+        Value::Array(items) => Json::Array(items.iter().map(to_json).collect()),
+        Value::Map(entries) => Json::Object(
+            entries
+                .iter()
+                .map(|(key, item)| {
+                    let key = match key.as_str() {
+                        Some(key) => key.to_string(),
+                        None => compact(key),
+                    };
+                    (key, to_json(item))
+                })
+                .collect(),
+        ),
+        Value::Ext(_, data) => match ext_id(data) {
+            Some(id) => Json::from(id),
+            None => Json::from(data.clone()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encode a handle like Neovim sends it
+    fn to_handle(ext: i8, id: i64) -> Value {
+        let mut bytes = Vec::new();
+        rmpv::encode::write_value(&mut bytes, &Value::from(id)).unwrap();
+        Value::Ext(ext, bytes)
+    }
+
+    fn sample() -> Value {
+        Value::Map(vec![
+            (Value::from("name"), Value::from("it's")),
+            (
+                Value::from("items"),
+                Value::Array(vec![Value::Nil, Value::from(1.0), Value::from(true)]),
+            ),
+        ])
+    }
+
+    #[test]
+    fn pretty_values() {
+        assert_eq!(
+            pretty(&sample()),
+            "{'name': 'it''s', 'items': [v:null, 1.0, v:true]}"
+        );
+        assert_eq!(pretty(&to_handle(BUFFER_EXT, 1)), "<buffer 1>");
+        assert_eq!(pretty(&to_handle(WINDOW_EXT, 1000)), "<window 1000>");
+        assert_eq!(pretty(&Value::Binary(vec![0, 255])), "0z00FF");
+        let long = Value::Array((0..40).map(Value::from).collect());
+        assert!(pretty(&long).starts_with("[\n  0,\n  1,\n"));
+        assert!(pretty(&long).ends_with("  39,\n]"));
+    }
+
+    #[test]
+    fn paths() {
+        let value = sample();
+        assert_eq!(get_path(&value, "items[1]"), Some(&Value::from(1.0)));
+        assert_eq!(get_path(&value, "name"), Some(&Value::from("it's")));
+        assert_eq!(get_path(&value, ""), Some(&value));
+        let nested = Value::Array(vec![value.clone()]);
+        assert_eq!(get_path(&nested, "[0].items[2]"), Some(&Value::from(true)));
+        assert_eq!(get_path(&value, "items[3]"), None);
+        assert_eq!(get_path(&value, "missing"), None);
+        assert_eq!(get_path(&value, "items.1"), None);
+        assert_eq!(get_path(&value, "items[1"), None);
+        assert_eq!(get_path(&value, "name[0]"), None);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json() {
+        let value = Value::Map(vec![
+            (Value::from(1), Value::Binary(vec![0, 255])),
+            (Value::from("nan"), Value::from(f64::NAN)),
+            (Value::from("buffer"), to_handle(BUFFER_EXT, 3)),
+        ]);
+        assert_eq!(
+            to_json(&value).to_string(),
+            r#"{"1":[0,255],"buffer":3,"nan":null}"#
+        );
+    }
+}
@@ -0,0 +1,343 @@
+//! A client for browsers, talking to Neovim over a WebSocket
+//!
+//! Browsers don't allow blocking or spawning threads, so `Session` and
+//! `Nvim` can't be used on `wasm32-unknown-unknown`. `WebSocketClient`
+//! instead drives the protocol core in `rpc` from the WebSocket's events
+//! and delivers responses to callbacks.
+//!
+//! Neovim doesn't accept WebSocket connections itself, so a small proxy
+//! forwarding binary messages to its socket is needed, e.g.
+//! ```shell
+//! websocat --binary ws-l:127.0.0.1:8080 tcp:127.0.0.1:6666
+//! ```
+use js_sys::{ArrayBuffer, Uint8Array};
+use rmpv::Value;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{BinaryType, CloseEvent, Event, MessageEvent, WebSocket};
+
+use crate::error::Error;
+use crate::handler::Notification;
+use crate::rpc::{self, DecodeLimits, FrameDecoder, RpcMessage};
+
+type ResponseCallback = Box<dyn FnOnce(Result<Value, Error>)>;
+type NotificationCallback = Box<dyn FnMut(Notification)>;
+type RequestCallback = Box<dyn FnMut(String, Vec<Value>) -> Result<Value, Error>>;
+type ErrorCallback = Box<dyn FnMut(Error)>;
+type CloseCallback = Box<dyn FnMut()>;
+
+/// State shared between a `WebSocketClient` and its event handlers
+struct State {
+    decoder: FrameDecoder,
+    /// Messages sent before the socket opened
+    queue: Vec<Vec<u8>>,
+    pending: HashMap<u64, ResponseCallback>,
+    on_notification: Option<NotificationCallback>,
+    on_request: Option<RequestCallback>,
+    on_error: Option<ErrorCallback>,
+    on_close: Option<CloseCallback>,
+}
+
+/// A Neovim client using a browser WebSocket
+///
+/// Requests are answered through callbacks, which run on the browser's
+/// event loop once the response arrives. Messages sent before the socket
+/// opens are queued.
+///
+/// # Example
+///
+/// ```no_run
+/// use rmpv::Value;
+/// use rsnvim::websocket::WebSocketClient;
+///
+/// let client = WebSocketClient::connect("ws://127.0.0.1:8080").unwrap();
+/// client.on_notification(|notification| {
+///     web_sys::console::log_1(&notification.method.into());
+/// });
+/// client
+///     .call("nvim_eval", vec![Value::from("&columns")], |result| {
+///         if let Ok(columns) = result {
+///             web_sys::console::log_1(&columns.to_string().into());
+///         }
+///     })
+///     .unwrap();
+/// ```
+pub struct WebSocketClient {
+    socket: WebSocket,
+    state: Rc<RefCell<State>>,
+    msg_counter: Cell<u64>,
+    // The handlers must live as long as the socket uses them
+    _on_open: Closure<dyn FnMut(Event)>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_close: Closure<dyn FnMut(CloseEvent)>,
+}
+
+impl WebSocketClient {
+    /// Open a WebSocket to `url`
+    pub fn connect(url: &str) -> Result<Self, Error> {
+        let socket = WebSocket::new(url).map_err(js_error)?;
+        socket.set_binary_type(BinaryType::Arraybuffer);
+        let state = Rc::new(RefCell::new(State {
+            decoder: FrameDecoder::new(DecodeLimits::default()),
+            queue: Vec::new(),
+            pending: HashMap::new(),
+            on_notification: None,
+            on_request: None,
+            on_error: None,
+            on_close: None,
+        }));
+
+        let on_open = {
+            let (socket, state) = (socket.clone(), state.clone());
+            Closure::<dyn FnMut(Event)>::new(move |_: Event| {
+                let queue = std::mem::take(&mut state.borrow_mut().queue);
+                for bytes in queue {
+                    if let Err(error) = socket.send_with_u8_array(&bytes) {
+                        report_error(&state, js_error(error));
+                    }
+                }
+            })
+        };
+        let on_message = {
+            let (socket, state) = (socket.clone(), state.clone());
+            Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+                match event.data().dyn_into::<ArrayBuffer>() {
+                    Ok(buffer) => receive(&socket, &state, &Uint8Array::new(&buffer).to_vec()),
+                    Err(_) => report_error(
+                        &state,
+                        Error::DecodingError("Received a text WebSocket message".to_string()),
+                    ),
+                }
+            })
+        };
+        let on_close = {
+            let state = state.clone();
+            Closure::<dyn FnMut(CloseEvent)>::new(move |_: CloseEvent| {
+                let pending = std::mem::take(&mut state.borrow_mut().pending);
+                for (_, callback) in pending {
+                    callback(Err(Error::ConnectionError(
+                        "The WebSocket was closed".to_string(),
+                    )));
+                }
+                with_callback(&state, |s| &mut s.on_close, |callback| callback());
+            })
+        };
+        socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+
+        Ok(WebSocketClient {
+            socket,
+            state,
+            msg_counter: Cell::new(0),
+            _on_open: on_open,
+            _on_message: on_message,
+            _on_close: on_close,
+        })
+    }
+
+    /// Send a request, passing the response to `callback` once it arrives
+    ///
+    /// If the socket closes first, `callback` is given a `ConnectionError`.
+    pub fn call<F>(&self, method: &str, args: Vec<Value>, callback: F) -> Result<(), Error>
+    where
+        F: FnOnce(Result<Value, Error>) + 'static,
+    {
+        let msgid = self.msg_counter.get();
+        self.msg_counter.set(msgid + 1);
+        self.state
+            .borrow_mut()
+            .pending
+            .insert(msgid, Box::new(callback));
+        let result = self.send(RpcMessage::RpcRequest {
+            msgid,
+            method: method.to_string(),
+            params: args,
+        });
+        if result.is_err() {
+            self.state.borrow_mut().pending.remove(&msgid);
+        }
+        result
+    }
+
+    /// Send a notification to Neovim, which has no response
+    pub fn notify(&self, method: &str, args: Vec<Value>) -> Result<(), Error> {
+        self.send(RpcMessage::RpcNotification {
+            method: method.to_string(),
+            params: args,
+        })
+    }
+
+    /// Handle notifications from Neovim with `callback`
+    pub fn on_notification<F>(&self, callback: F)
+    where
+        F: FnMut(Notification) + 'static,
+    {
+        self.state.borrow_mut().on_notification = Some(Box::new(callback));
+    }
+
+    /// Handle requests from Neovim with `callback`, which is given the
+    /// method and parameters
+    ///
+    /// Without a handler, requests are answered with `NotImplemented`.
+    pub fn on_request<F>(&self, callback: F)
+    where
+        F: FnMut(String, Vec<Value>) -> Result<Value, Error> + 'static,
+    {
+        self.state.borrow_mut().on_request = Some(Box::new(callback));
+    }
+
+    /// Register a callback for errors in messages received from Neovim,
+    /// which are otherwise ignored
+    pub fn on_error<F>(&self, callback: F)
+    where
+        F: FnMut(Error) + 'static,
+    {
+        self.state.borrow_mut().on_error = Some(Box::new(callback));
+    }
+
+    /// Register a callback for when the socket is closed
+    pub fn on_close<F>(&self, callback: F)
+    where
+        F: FnMut() + 'static,
+    {
+        self.state.borrow_mut().on_close = Some(Box::new(callback));
+    }
+
+    /// Set the limits applied when decoding incoming messages
+    ///
+    /// Any partially received message is discarded.
+    pub fn set_decode_limits(&self, limits: DecodeLimits) {
+        self.state.borrow_mut().decoder = FrameDecoder::new(limits);
+    }
+
+    /// Close the socket
+    pub fn close(&self) -> Result<(), Error> {
+        self.socket.close().map_err(js_error)
+    }
+
+    fn send(&self, message: RpcMessage) -> Result<(), Error> {
+        let bytes = rpc::encode_message(message);
+        if self.socket.ready_state() == WebSocket::CONNECTING {
+            self.state.borrow_mut().queue.push(bytes);
+            return Ok(());
+        }
+        self.socket.send_with_u8_array(&bytes).map_err(js_error)
+    }
+}
+
+impl Drop for WebSocketClient {
+    fn drop(&mut self) {
+        // The handlers are freed with the client, so mustn't be called
+        self.socket.set_onopen(None);
+        self.socket.set_onmessage(None);
+        self.socket.set_onclose(None);
+        let _ = self.socket.close();
+    }
+}
+
+/// Decode the messages in `bytes` and dispatch them
+fn receive(socket: &WebSocket, state: &Rc<RefCell<State>>, bytes: &[u8]) {
+    let mut messages = Vec::new();
+    {
+        let decoder = &mut state.borrow_mut().decoder;
+        let mut input = bytes;
+        while !input.is_empty() {
+            let (consumed, frame) = decoder.push(input);
+            input = &input[consumed..];
+            if let Some(frame) = frame {
+                messages.push(frame.and_then(|frame| rpc::decode_message(&frame)));
+            }
+        }
+    }
+
+    // Callbacks run without the state borrowed, so they may use the client
+    for message in messages {
+        match message {
+            Ok(RpcMessage::RpcResponse {
+                msgid,
+                error,
+                result,
+            }) => {
+                let callback = state.borrow_mut().pending.remove(&msgid);
+                match callback {
+                    Some(callback) if error == Value::Nil => callback(Ok(result)),
+                    Some(callback) => callback(Err(Error::RpcError(rpc::error_message(error)))),
+                    None => report_error(
+                        state,
+                        Error::UnexpectedResponse(format!(
+                            "Received response to unknown request {}",
+                            msgid
+                        )),
+                    ),
+                }
+            }
+            Ok(RpcMessage::RpcNotification { method, params }) => {
+                let notification = Notification { method, params };
+                with_callback(
+                    state,
+                    |s| &mut s.on_notification,
+                    |callback| callback(notification),
+                );
+            }
+            Ok(RpcMessage::RpcRequest {
+                msgid,
+                method,
+                params,
+            }) => {
+                let name = method.clone();
+                let result = with_callback(
+                    state,
+                    |s| &mut s.on_request,
+                    |callback| callback(method, params),
+                )
+                .unwrap_or(Err(Error::NotImplemented(name)));
+                let response = match result {
+                    Ok(result) => RpcMessage::RpcResponse {
+                        msgid,
+                        error: Value::Nil,
+                        result,
+                    },
+                    Err(error) => RpcMessage::RpcResponse {
+                        msgid,
+                        error: Value::from(error),
+                        result: Value::Nil,
+                    },
+                };
+                if let Err(error) = socket.send_with_u8_array(&rpc::encode_message(response)) {
+                    report_error(state, js_error(error));
+                }
+            }
+            Err(error) => report_error(state, error),
+        }
+    }
+}
+
+/// Run the callback in `slot` without holding the borrow of `state`
+///
+/// The callback is put back afterwards unless it was replaced while running.
+fn with_callback<C, R>(
+    state: &Rc<RefCell<State>>,
+    slot: fn(&mut State) -> &mut Option<C>,
+    run: impl FnOnce(&mut C) -> R,
+) -> Option<R> {
+    let mut callback = slot(&mut state.borrow_mut()).take()?;
+    let result = run(&mut callback);
+    let mut state = state.borrow_mut();
+    let slot = slot(&mut state);
+    if slot.is_none() {
+        *slot = Some(callback);
+    }
+    Some(result)
+}
+
+fn report_error(state: &Rc<RefCell<State>>, error: Error) {
+    with_callback(state, |s| &mut s.on_error, |callback| callback(error));
+}
+
+fn js_error(error: JsValue) -> Error {
+    Error::ConnectionError(format!("{:?}", error))
+}
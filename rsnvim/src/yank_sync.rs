@@ -0,0 +1,193 @@
+//! Mirror the unnamed register between several Neovim instances
+//!
+//! A `YankSync` watches each Neovim added to it for `TextYankPost`, and
+//! copies the yanked or deleted text into the unnamed register of every
+//! other one, so text yanked in one editor can be put in the rest. Syncing
+//! is opt-in: only Neovims added to a `YankSync` take part, and only while
+//! it is running.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rsnvim::api::Nvim;
+//! use rsnvim::yank_sync::YankSync;
+//!
+//! let mut left = Nvim::from_tcp("127.0.0.1:6666").unwrap();
+//! left.start_event_loop(None, None);
+//! let mut right = Nvim::from_tcp("127.0.0.1:6667").unwrap();
+//! right.start_event_loop(None, None);
+//!
+//! let sync = YankSync::new();
+//! sync.add(&mut left).unwrap();
+//! sync.add(&mut right).unwrap();
+//!
+//! // `yy` in the left Neovim can now be put with `p` in the right one
+//! ```
+use rmpv::Value;
+use std::sync::{Arc, Mutex, Weak};
+
+use crate::api::{AutocmdOpts, Dict, Nvim};
+use crate::autocmd::AutocmdManager;
+use crate::error::Error;
+
+/// The contents and type of a register, as given to `setreg()`
+#[derive(Clone, Debug, PartialEq)]
+struct Register {
+    contents: Value,
+    regtype: Value,
+}
+
+impl Register {
+    /// Read the unnamed register of `nvim`
+    fn read(nvim: &Nvim) -> Result<Self, Error> {
+        let info = Dict::try_from(nvim.call_function("getreginfo", vec![Value::from("\"")])?)?;
+        Ok(Register {
+            contents: info["regcontents"].clone(),
+            regtype: info["regtype"].clone(),
+        })
+    }
+
+    /// Replace the unnamed register of `nvim`
+    fn write(&self, nvim: &Nvim) -> Result<(), Error> {
+        let info = Dict::new()
+            .with("regcontents", self.contents.clone())
+            .with("regtype", self.regtype.clone());
+        nvim.call_function("setreg", vec![Value::from("\""), info.into()])?;
+        Ok(())
+    }
+}
+
+/// A Neovim taking part in a `YankSync`
+struct Member {
+    id: usize,
+    nvim: Nvim,
+    autocmds: Option<AutocmdManager>,
+    /// The register last copied into this Neovim, so a yank reporting it
+    /// back isn't copied around again
+    synced: Option<Register>,
+}
+
+/// Copies yanks from each added Neovim into the unnamed register of the
+/// others
+///
+/// See the module documentation for an example. Cloning a `YankSync` is
+/// cheap and the clone shares the same Neovims.
+#[derive(Clone, Default)]
+pub struct YankSync {
+    members: Arc<Mutex<Vec<Member>>>,
+    next_id: Arc<Mutex<usize>>,
+}
+
+impl YankSync {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start syncing yanks to and from `nvim`, returning an ID which can be
+    /// given to `remove`
+    ///
+    /// The event loop of `nvim` must be running. The unnamed register of
+    /// `nvim` is left unchanged until the next yank in another Neovim.
+    pub fn add(&self, nvim: &mut Nvim) -> Result<usize, Error> {
+        let id = {
+            let mut next_id = self.next_id.lock().unwrap();
+            *next_id += 1;
+            *next_id
+        };
+        let mut autocmds = AutocmdManager::new(nvim)?;
+        // Weak, as the members own the autocommand and so this closure
+        let members = Arc::downgrade(&self.members);
+        self.members.lock().unwrap().push(Member {
+            id,
+            nvim: nvim.clone(),
+            autocmds: None,
+            synced: None,
+        });
+
+        let created = autocmds.on(&["TextYankPost"], AutocmdOpts::new(), move |_, nvim| {
+            // Errors reading or writing a register, e.g. from a Neovim
+            // which has exited, skip that Neovim
+            if let Ok(register) = Register::read(nvim) {
+                propagate(&members, id, register);
+            }
+        });
+        if let Err(error) = created {
+            let _ = autocmds.stop();
+            self.members
+                .lock()
+                .unwrap()
+                .retain(|member| member.id != id);
+            return Err(error);
+        }
+
+        let mut members = self.members.lock().unwrap();
+        match members.iter_mut().find(|member| member.id == id) {
+            Some(member) => member.autocmds = Some(autocmds),
+            // Removed while its autocommand was being created
+            None => {
+                let _ = autocmds.stop();
+            }
+        }
+        Ok(id)
+    }
+
+    /// Stop syncing yanks to and from the Neovim added with ID `id`
+    pub fn remove(&self, id: usize) -> Result<(), Error> {
+        let member = {
+            let mut members = self.members.lock().unwrap();
+            match members.iter().position(|member| member.id == id) {
+                Some(index) => members.remove(index),
+                None => return Ok(()),
+            }
+        };
+        match member.autocmds {
+            Some(autocmds) => autocmds.stop(),
+            None => Ok(()),
+        }
+    }
+
+    /// The number of Neovims being synced
+    pub fn len(&self) -> usize {
+        self.members.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Stop syncing every Neovim, deleting their autocommands
+    ///
+    /// Every Neovim is stopped even if one fails, and the first error is
+    /// returned.
+    pub fn stop(&self) -> Result<(), Error> {
+        let members = std::mem::take(&mut *self.members.lock().unwrap());
+        members
+            .into_iter()
+            .filter_map(|member| member.autocmds)
+            .map(AutocmdManager::stop)
+            .fold(Ok(()), Result::and)
+    }
+}
+
+/// Copy `register`, yanked in the Neovim `from`, into every other Neovim
+fn propagate(members: &Weak<Mutex<Vec<Member>>>, from: usize, register: Register) {
+    let members = match members.upgrade() {
+        Some(members) => members,
+        None => return,
+    };
+    let mut members = members.lock().unwrap();
+    let source = match members.iter_mut().find(|member| member.id == from) {
+        Some(source) => source,
+        None => return,
+    };
+    // Setting a register doesn't trigger `TextYankPost`, but a plugin
+    // re-yanking what was just copied in must not bounce it back
+    if source.synced.take().as_ref() == Some(&register) {
+        return;
+    }
+    for member in members.iter_mut().filter(|member| member.id != from) {
+        if register.write(&member.nvim).is_ok() {
+            member.synced = Some(register.clone());
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! A fake Neovim on the other end of a socket pair
+//!
+//! Tests of how a session handles particular messages, e.g. out of order
+//! responses or malformed data, play Neovim themselves rather than starting
+//! a real instance.
+#![cfg(unix)]
+
+use rmpv::Value;
+use std::os::unix::net::UnixStream;
+use std::thread;
+
+use rsnvim::rpc::{self, RpcMessage};
+use rsnvim::session::Session;
+
+/// A session connected to a socket pair, and the other end of the pair to
+/// play Neovim with
+///
+/// The event loop isn't started, so handlers can be set first. Once it is,
+/// the session requests `nvim_get_api_info` in the background.
+pub fn socket_session() -> (Session, UnixStream) {
+    let (ours, theirs) = UnixStream::pair().unwrap();
+    let session = Session::from_transport(ours.try_clone().unwrap(), ours);
+    (session, theirs)
+}
+
+/// Read the next request, panicking if anything else arrives
+pub fn next_request(peer: &mut UnixStream) -> (u64, String, Vec<Value>) {
+    match rpc::decode(peer).unwrap() {
+        RpcMessage::RpcRequest {
+            msgid,
+            method,
+            params,
+        } => (msgid, method, params),
+        other => panic!("Expected a request, got {:?}", other),
+    }
+}
+
+/// Answer the request `msgid`
+pub fn respond(peer: &mut UnixStream, msgid: u64, error: Value, result: Value) {
+    rpc::encode(
+        peer,
+        RpcMessage::RpcResponse {
+            msgid,
+            error,
+            result,
+        },
+    )
+    .unwrap();
+}
+
+pub fn notify(peer: &mut UnixStream, method: &str, params: Vec<Value>) {
+    let method = method.to_string();
+    rpc::encode(peer, RpcMessage::RpcNotification { method, params }).unwrap();
+}
+
+/// The error Neovim sends for a failed call
+pub fn rpc_error(message: &str) -> Value {
+    Value::Array(vec![Value::from(0), Value::from(message)])
+}
+
+/// Answer each request with `answer` on a background thread, until the
+/// session closes the connection
+///
+/// `nvim_get_api_info` fails with "Unavailable", so tests needn't answer it.
+pub fn serve<F>(mut peer: UnixStream, mut answer: F)
+where
+    F: FnMut(&str, Vec<Value>) -> Result<Value, Value> + Send + 'static,
+{
+    thread::spawn(move || {
+        while let Ok(message) = rpc::decode(&mut peer) {
+            let RpcMessage::RpcRequest {
+                msgid,
+                method,
+                params,
+            } = message
+            else {
+                continue;
+            };
+            let (error, result) = match method.as_str() {
+                "nvim_get_api_info" => (rpc_error("Unavailable"), Value::Nil),
+                method => match answer(method, params) {
+                    Ok(result) => (Value::Nil, result),
+                    Err(error) => (error, Value::Nil),
+                },
+            };
+            let response = RpcMessage::RpcResponse {
+                msgid,
+                error,
+                result,
+            };
+            if rpc::encode(&mut peer, response).is_err() {
+                return;
+            }
+        }
+    });
+}
+
+/// The response to `nvim_get_api_info` for channel 1 of a Neovim providing
+/// `functions`, at API level 0
+pub fn api_info(functions: Vec<Value>) -> Value {
+    let version = Value::Map(
+        ["major", "minor", "patch", "api_level", "api_compatible"]
+            .iter()
+            .map(|key| (Value::from(*key), Value::from(0)))
+            .collect(),
+    );
+    let metadata = Value::Map(vec![
+        (Value::from("version"), version),
+        (Value::from("functions"), Value::Array(functions)),
+    ]);
+    Value::Array(vec![Value::from(1), metadata])
+}
@@ -0,0 +1,3045 @@
+//! Integration tests run against a real Neovim instance
+//!
+//! These are skipped unless `RSNVIM_TEST_NVIM` points to a Neovim binary,
+//! which `cargo xtask conformance` sets for each supported version.
+use rmpv::Value;
+use std::net::{TcpListener, TcpStream};
+use std::num::Wrapping;
+use std::process::{Child, Command, Stdio};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use rsnvim::api::{
+    AsValue, AutocmdOpts, CallBatch, ChannelMode, ChannelStream, ClientInfoBuilder, ClientType,
+    Color, CompletionKind, Dict, DynamicApi, EditOpts, ExtmarkOpts, FileFormat, FloatConfigBuilder,
+    FromValue, HighlightAttrs, HighlightRange, HlAttrTable, Indexing, InputOpts, KeymapOpts, Keys,
+    Layout, LayoutNode, Macro, MatchRange, Nvim, Pacing, PlaybackOpts, Point, Position, Range,
+    RedrawEvent, Relative, SandboxOpts, SearchOpts, SnapshotSpec, TextEdit, UiOptions, WinConfig,
+    WindowLayout, WorkspaceEdit,
+};
+use rsnvim::autocmd::AutocmdManager;
+use rsnvim::blocking::{mark_ui_thread, unmark_ui_thread, BlockingPolicy};
+use rsnvim::bridge::LuaBridge;
+use rsnvim::client::Client;
+use rsnvim::error::Error;
+use rsnvim::handler::{HandlerRouter, NotificationHandlerMut, RequestHandler, RequestHandlerMut};
+use rsnvim::params::ExpectParams;
+use rsnvim::report::{show_error, Report};
+use rsnvim::rpc::{self, RpcMessage};
+use rsnvim::rplugin::{CommandSpec, FunctionSpec, RemotePlugin};
+use rsnvim::session::{Endpoint, RetrySpec, Session};
+use rsnvim::storage::{Location, Storage};
+use rsnvim::value::{get_path, pretty};
+use rsnvim::yank_sync::YankSync;
+#[cfg(unix)]
+use rsnvim::{
+    api::{Buffer, NvimString, RedrawMode},
+    client::CancelPolicy,
+    deprecation::DeprecationPolicy,
+    read_only::ReadOnlySession,
+};
+
+mod common;
+
+/// A headless Neovim which is killed when dropped
+struct Instance {
+    child: Child,
+}
+
+impl Drop for Instance {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Start a headless Neovim listening on a free TCP port
+fn spawn() -> Option<(Instance, String)> {
+    let binary = std::env::var("RSNVIM_TEST_NVIM").ok()?;
+    let addr = {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().to_string()
+    };
+    let child = Command::new(binary)
+        .args(["--headless", "--clean", "--listen", &addr])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn()
+        .expect("Couldn't start Neovim");
+    let instance = Instance { child };
+
+    for _ in 0..100 {
+        if TcpStream::connect(&addr).is_ok() {
+            return Some((instance, addr));
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    panic!("Neovim didn't start listening on {}", addr);
+}
+
+/// Connect to a fresh Neovim, or skip the test if none is configured
+macro_rules! nvim {
+    ($requests:expr, $notifications:expr) => {
+        match spawn() {
+            Some((instance, addr)) => {
+                let mut nvim = Nvim::from_tcp(&addr).unwrap();
+                nvim.start_event_loop($requests, $notifications);
+                (instance, nvim)
+            }
+            None => {
+                eprintln!("RSNVIM_TEST_NVIM is not set, skipping");
+                return;
+            }
+        }
+    };
+    ($requests:expr) => {
+        nvim!($requests, None)
+    };
+    () => {
+        nvim!(None)
+    };
+}
+
+#[test]
+fn api_info() {
+    let (_instance, mut nvim) = nvim!();
+    let info = nvim.get_api_info().unwrap();
+    assert!(info[0].as_i64().unwrap() > 0);
+    assert!(info[1]["version"]["api_level"].as_u64().unwrap() >= 11);
+    nvim.require_api_level(1).unwrap();
+    assert!(matches!(
+        nvim.require_api_level(u64::MAX),
+        Err(Error::UnsupportedVersion(_))
+    ));
+}
+
+#[test]
+fn supports() {
+    let (_instance, mut nvim) = nvim!();
+    let info = nvim.api_info().unwrap();
+    assert!(info.channel > 0);
+    assert!(info.version.api_level >= info.version.api_compatible);
+    let function = info.function("nvim_buf_set_extmark").unwrap();
+    assert_eq!(function.parameters[0].0, "Buffer");
+    assert!(nvim.supports("nvim_get_api_info").unwrap());
+    assert!(!nvim.supports("nvim_does_not_exist").unwrap());
+}
+
+#[test]
+fn eval_and_command() {
+    let (_instance, nvim) = nvim!();
+    assert_eq!(nvim.eval("1 + 2").unwrap(), Value::from(3));
+    nvim.command("let g:rsnvim = 'conformance'").unwrap();
+    assert_eq!(nvim.get_var("rsnvim").unwrap(), Value::from("conformance"));
+}
+
+#[test]
+fn buffer_lines() {
+    let (_instance, nvim) = nvim!();
+    let buffer = nvim.get_current_buf().unwrap();
+    buffer.set_lines(0, -1, true, ["first", "second"]).unwrap();
+    assert_eq!(
+        buffer.get_lines(0, -1, true).unwrap(),
+        vec!["first".to_string(), "second".to_string()]
+    );
+    assert_eq!(nvim.list_bufs().unwrap().len(), 1);
+}
+
+#[test]
+fn integer_boundaries() {
+    let (_instance, mut nvim) = nvim!();
+    let max = nvim.eval("v:numbermax").unwrap();
+    assert_eq!(i64::from_value(max).unwrap(), i64::MAX);
+    assert!(matches!(
+        nvim.exec_lua_typed::<u64>("return -1", Vec::new()),
+        Err(Error::DecodingError(_))
+    ));
+    let Wrapping(bits): Wrapping<u64> = nvim.exec_lua_typed("return -1", Vec::new()).unwrap();
+    assert_eq!(bits, u64::MAX);
+    // Sent with the same bits, so Neovim receives -1
+    let sent: i64 = nvim
+        .exec_lua_typed("return ...", vec![Wrapping(u64::MAX).convert()])
+        .unwrap();
+    assert_eq!(sent, -1);
+}
+
+#[test]
+fn text_ranges() {
+    let (_instance, nvim) = nvim!();
+    let mut buffer = nvim.get_current_buf().unwrap();
+    buffer
+        .set_lines(0, -1, true, ["let foo = 1;", "foo"])
+        .unwrap();
+
+    let range = Range::new((0, 4), (0, 7));
+    assert_eq!(buffer.get_range(&range).unwrap(), ["foo"]);
+    buffer.set_range(&range, ["bar"]).unwrap();
+    buffer
+        .insert_text(Position::new(1, 0), ["// ", ""])
+        .unwrap();
+    assert_eq!(
+        buffer.get_range(&Range::new((0, 0), (2, 3))).unwrap(),
+        ["let bar = 1;", "// ", "foo"]
+    );
+    assert!(range.contains(Position::new(0, 6)));
+    assert!(!range.contains(Position::new(0, 7)));
+
+    let backwards = Range::new((1, 0), (0, 3));
+    assert!(matches!(
+        buffer.get_range(&backwards),
+        Err(Error::InvalidArgument(_))
+    ));
+    assert!(matches!(
+        buffer.set_range(&Range::point((-1, 0)), ["x"]),
+        Err(Error::InvalidArgument(_))
+    ));
+}
+
+#[test]
+fn open_float() {
+    let (_instance, nvim) = nvim!();
+    let buffer = nvim.create_buf(false, true).unwrap();
+    assert!(matches!(
+        nvim.open_float(&buffer, FloatConfigBuilder::new(0, 5)),
+        Err(Error::InvalidArgument(_))
+    ));
+
+    let config = FloatConfigBuilder::new(20, 6)
+        .centered()
+        .title("Preview")
+        .zindex(60)
+        .enter(true);
+    let window = nvim.open_float(&buffer, config).unwrap();
+    assert_eq!(nvim.get_current_win().unwrap(), window);
+    let config = window.get_config().unwrap().unwrap();
+    assert_eq!(config.get_str("relative"), Some("editor"));
+    assert_eq!(config.get_i64("zindex"), Some(60));
+    // Centered in the default 80x24 screen, above the command line and
+    // with room for the border the title needs
+    assert_eq!(config.get_f64("row").map(|row| row as i64), Some(7));
+    assert_eq!(config.get_f64("col").map(|col| col as i64), Some(29));
+    assert!(config.get("border").is_some_and(|border| border.is_array()));
+
+    // Only the given keys change
+    window
+        .update_config(WinConfig::new().width(30).row(2.0))
+        .unwrap();
+    let config = window.get_config().unwrap().unwrap();
+    assert_eq!(config.get_i64("width"), Some(30));
+    assert_eq!(config.get_i64("height"), Some(6));
+    assert_eq!(config.get_f64("row").map(|row| row as i64), Some(2));
+    assert_eq!(config.get_f64("col").map(|col| col as i64), Some(29));
+
+    window.close(true).unwrap();
+    assert!(!window.is_valid().unwrap());
+}
+
+#[test]
+fn highlight_attrs() {
+    let color: Color = "#1E2a3b".parse().unwrap();
+    assert_eq!(color, Color::rgb(0x1e, 0x2a, 0x3b));
+    assert_eq!(color.to_string(), "#1e2a3b");
+    assert_eq!(Color::try_from(0x1e2a3b).unwrap(), color);
+    for invalid in ["1e2a3b", "#1e2a3", "#+e2a3b", "#1e2a3g"] {
+        assert!(invalid.parse::<Color>().is_err(), "{}", invalid);
+    }
+    assert!(Color::try_from(0x1000000).is_err());
+    assert!(Color::try_from(-1).is_err());
+
+    let (_instance, nvim) = nvim!();
+    let attrs = HighlightAttrs {
+        fg: Some(color),
+        bg: Some("#ffffff".parse().unwrap()),
+        ctermfg: Some(4),
+        bold: true,
+        undercurl: true,
+        ..Default::default()
+    };
+    nvim.set_highlight(0, "RsnvimTitle", &attrs).unwrap();
+    assert_eq!(nvim.get_highlight(0, "RsnvimTitle").unwrap(), attrs);
+    nvim.set_highlight(0, "RsnvimLink", &HighlightAttrs::link("RsnvimTitle"))
+        .unwrap();
+    assert_eq!(
+        nvim.get_highlight(0, "RsnvimLink").unwrap().link.as_deref(),
+        Some("RsnvimTitle")
+    );
+    let groups = nvim.get_highlights(0).unwrap();
+    assert_eq!(groups.get("RsnvimTitle"), Some(&attrs));
+
+    // Namespaced groups don't change the global ones
+    let dim = HighlightAttrs {
+        fg: Some(Color::rgb(128, 128, 128)),
+        ..Default::default()
+    };
+    let ns_id = nvim
+        .define_hl_namespace("rsnvim-dim", &[("RsnvimTitle", dim.clone())])
+        .unwrap();
+    assert_eq!(nvim.get_highlight(ns_id, "RsnvimTitle").unwrap(), dim);
+    assert_eq!(nvim.get_highlight(0, "RsnvimTitle").unwrap(), attrs);
+    nvim.set_hl_ns(ns_id).unwrap();
+}
+
+#[test]
+fn channels() {
+    let (_instance, mut nvim) = nvim!();
+    let own = nvim.channel_id().unwrap();
+    nvim.announce(
+        &ClientInfoBuilder::new("rsnvim-test")
+            .version(1, 2, 3)
+            .client_type(ClientType::Host)
+            .method("add")
+            .attribute("license", "MIT"),
+    )
+    .unwrap();
+
+    let info = nvim.channel(own).unwrap().unwrap();
+    assert_eq!(info.stream, ChannelStream::Socket);
+    assert_eq!(info.mode, ChannelMode::Rpc);
+    let client = info.client.unwrap();
+    assert_eq!(client.name, "rsnvim-test");
+    assert_eq!((client.version.major, client.version.patch), (1, 3));
+    assert_eq!(client.client_type, Some(ClientType::Host));
+    assert_eq!(client.methods, vec!["add".to_string()]);
+    assert_eq!(
+        client.attributes,
+        vec![("license".to_string(), Value::from("MIT"))]
+    );
+    assert_eq!(nvim.channel(9999).unwrap(), None);
+
+    let job: i64 = nvim.eval("jobstart(['cat'])").unwrap().as_i64().unwrap();
+    let channels = nvim.channels().unwrap();
+    assert!(channels.iter().any(|channel| channel.id == own));
+    let job_info = channels.iter().find(|channel| channel.id == job).unwrap();
+    assert_eq!(job_info.stream, ChannelStream::Job);
+    assert_eq!(job_info.mode, ChannelMode::Bytes);
+    // The program may be resolved to its full path
+    assert!(job_info.argv[0].ends_with("cat"));
+    assert!(job_info.client.is_none());
+
+    nvim.close_channel(job).unwrap();
+    assert!(nvim.close_channel(9999).is_err());
+}
+
+#[test]
+fn window_cursor() {
+    let (_instance, nvim) = nvim!();
+    let buffer = nvim.get_current_buf().unwrap();
+    buffer.set_lines(0, -1, true, ["abc", "def"]).unwrap();
+    nvim.command("call cursor(2, 2)").unwrap();
+    let window = nvim.get_current_win().unwrap();
+    assert_eq!(window.get_cursor().unwrap(), Point::new(2, 1));
+}
+
+#[test]
+fn cursor_points() {
+    let point = Point::new(2, 1);
+    assert_eq!(point.to_position(Indexing::OneZero), Position::new(1, 1));
+    assert_eq!(point.to_position(Indexing::ZeroZero), Position::new(2, 1));
+    assert_eq!(
+        Point::from_position(Position::new(1, 1), Indexing::OneZero),
+        point
+    );
+    assert_eq!(
+        point.convert(),
+        Value::from(vec![Value::from(2), Value::from(1)])
+    );
+    assert_eq!(Point::from_value(point.convert()).unwrap(), point);
+
+    let (_instance, nvim) = nvim!();
+    let buffer = nvim.get_current_buf().unwrap();
+    buffer.set_lines(0, -1, true, ["abc", "def"]).unwrap();
+    let window = nvim.get_current_win().unwrap();
+    window.set_cursor((2, 2)).unwrap();
+    assert_eq!(window.get_cursor().unwrap(), Point::new(2, 2));
+    nvim.command("mark a").unwrap();
+    assert_eq!(buffer.get_mark("a").unwrap(), Point::new(2, 0));
+    assert_eq!(window.get_position().unwrap(), Point::new(0, 0));
+}
+
+#[test]
+fn completion() {
+    let (_instance, mut nvim) = nvim!();
+    let commands = nvim
+        .get_completion("colo", CompletionKind::Command)
+        .unwrap();
+    assert_eq!(commands, ["colorscheme"]);
+    let options = nvim.get_completion("tabs", CompletionKind::Option).unwrap();
+    assert!(options.contains(&"tabstop".to_string()));
+
+    let typist = nvim.clone();
+    let typing = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        typist.input("answer<CR>").unwrap();
+    });
+    let opts = InputOpts::new()
+        .prompt("Name: ")
+        .default_text("an ")
+        .completion(CompletionKind::File);
+    assert_eq!(
+        nvim.prompt_input(opts).unwrap().as_deref(),
+        Some("an answer")
+    );
+    typing.join().unwrap();
+}
+
+#[test]
+fn exec_output() {
+    let (_instance, nvim) = nvim!();
+    let result = nvim.exec("set tabstop=4\nset tabstop?", true).unwrap();
+    assert_eq!(result.output.trim(), "tabstop=4");
+    let result = nvim.exec("echo 'hidden'", false).unwrap();
+    assert_eq!(result.output, "");
+    assert!(nvim.exec("notacommand", true).is_err());
+}
+
+#[test]
+fn dict_values() {
+    let mut dict = Dict::new().with("b", 1).with("a", "text");
+    dict.insert("b", true);
+    assert_eq!(dict.keys().collect::<Vec<_>>(), ["b", "a"]);
+    assert_eq!(dict.get_bool("b"), Some(true));
+    assert_eq!(dict.get_str("a"), Some("text"));
+    assert_eq!(dict.get_i64("a"), None);
+    assert_eq!(dict["missing"], Value::Nil);
+    assert_eq!(dict.remove("b"), Some(Value::from(true)));
+    let value = Value::from(dict.clone());
+    assert_eq!(Dict::try_from(value).unwrap(), dict);
+    assert!(Dict::try_from(Value::from(1)).is_err());
+
+    let (_instance, nvim) = nvim!();
+    let mode = nvim.get_mode().unwrap();
+    assert_eq!(mode.get_str("mode"), Some("n"));
+    assert_eq!(mode.get_bool("blocking"), Some(false));
+    let config = nvim
+        .get_current_win()
+        .unwrap()
+        .get_config()
+        .unwrap()
+        .unwrap();
+    assert_eq!(config.get_str("relative"), Some(""));
+}
+
+#[test]
+fn layout_restore() {
+    let window = |buffer: &str| {
+        LayoutNode::Window(WindowLayout {
+            buffer: buffer.to_string(),
+            bufnr: 0,
+            width: 80,
+            height: 24,
+            cursor: Point::new(1, 0),
+        })
+    };
+    let tree = LayoutNode::Row(vec![
+        LayoutNode::Column(vec![window("c"), window("b")]),
+        window("a"),
+    ]);
+    let order: Vec<_> = tree.windows().iter().map(|w| w.buffer.as_str()).collect();
+    assert_eq!(order, ["c", "b", "a"]);
+
+    let (_instance, nvim) = nvim!();
+    nvim.command("edit a.txt | vsplit b.txt | split c.txt | tabnew d.txt | tabprevious")
+        .unwrap();
+    let layout = Layout::capture(&nvim).unwrap();
+    assert_eq!(layout.tabpages.len(), 2);
+    assert_eq!(layout.current, 0);
+    let tab = &layout.tabpages[0];
+    assert!(matches!(&tab.root, LayoutNode::Row(children) if children.len() == 2));
+    let names: Vec<_> = tab
+        .root
+        .windows()
+        .iter()
+        .map(|w| w.buffer.rsplit('/').next().unwrap().to_string())
+        .collect();
+    assert_eq!(names, ["c.txt", "b.txt", "a.txt"]);
+    assert_eq!(tab.current, 0);
+
+    nvim.command("tabonly | only | enew").unwrap();
+    layout.restore(&nvim).unwrap();
+    assert_eq!(Layout::capture(&nvim).unwrap(), layout);
+}
+
+#[test]
+fn type_text() {
+    let (_instance, nvim) = nvim!();
+    nvim.input("i").unwrap();
+    let text = "a <b>\r\nc";
+    let accepted = nvim.type_text(text, &Pacing::new().chunk_size(3)).unwrap();
+    // `<` and the line break are escaped to `<LT>` and `<CR>`, which are
+    // never split even though they're larger than a chunk
+    assert_eq!(accepted.iter().sum::<usize>(), "a <LT>b><CR>c".len());
+    assert!(accepted.iter().all(|&bytes| bytes <= 4));
+    nvim.input("<Esc>").unwrap();
+    let buffer = nvim.get_current_buf().unwrap();
+    assert_eq!(buffer.get_lines(0, -1, true).unwrap(), ["a <b>", "c"]);
+}
+
+#[test]
+fn borrowed_handles() {
+    let (_instance, nvim) = nvim!();
+    let buffer = nvim.create_buf(true, false).unwrap();
+    let window = nvim.get_current_win().unwrap();
+    window.set_buf(&buffer).unwrap();
+    nvim.set_current_win(&window).unwrap();
+    // The handles are only borrowed, so are still usable
+    assert_eq!(window.get_buf().unwrap(), buffer);
+    assert_eq!(nvim.get_current_buf().unwrap(), buffer);
+}
+
+#[test]
+fn concurrent_calls() {
+    let (_instance, mut nvim) = nvim!();
+    let handles: Vec<_> = (0..10)
+        .map(|i| {
+            nvim.call_async("nvim_eval", vec![Value::from(format!("{} * 2", i))])
+                .unwrap()
+        })
+        .collect();
+    for (i, handle) in handles.into_iter().enumerate() {
+        assert_eq!(handle.wait().unwrap(), Value::from(i * 2));
+    }
+}
+
+#[test]
+fn call_batch() {
+    let (_instance, mut nvim) = nvim!();
+    let mut batch = CallBatch::new();
+    batch.call::<()>("nvim_set_current_line", vec![Value::from("batched")]);
+    let line = batch.call::<String>("nvim_get_current_line", Vec::new());
+    let failed = batch.call::<Value>("nvim_get_var", vec![Value::from("rsnvim_missing")]);
+    let skipped = batch.call::<i64>("nvim_eval", vec![Value::from("1")]);
+
+    let results = nvim.call_batch(batch).unwrap();
+    assert_eq!(results.get(&line).unwrap(), "batched");
+    assert_eq!(results.error().unwrap().index, failed.index());
+    assert!(matches!(results.get(&failed), Err(Error::RpcError(_))));
+    assert!(results.get(&skipped).is_err());
+}
+
+#[test]
+fn snapshot() {
+    let (_instance, mut nvim) = nvim!();
+    let buffer = nvim.get_current_buf().unwrap();
+    buffer.set_lines(0, -1, true, ["one", "two"]).unwrap();
+    nvim.command("call cursor(2, 1) | setlocal tabstop=4")
+        .unwrap();
+
+    let spec = SnapshotSpec::new().lines().cursor().option("tabstop");
+    let snapshot = nvim.snapshot(&spec).unwrap();
+    assert_eq!(
+        snapshot.to_string(),
+        "lines:\n   1 | one\n   2 | two\ncursor: 2:0\noptions:\n  tabstop = 4\n"
+    );
+
+    let golden = std::env::temp_dir().join(format!("rsnvim-{}.snap", std::process::id()));
+    snapshot.assert_matches(&golden);
+    snapshot.assert_matches(&golden);
+    let _ = std::fs::remove_file(golden);
+}
+
+#[test]
+fn navigation() {
+    let (_instance, mut nvim) = nvim!();
+    let buffer = nvim.get_current_buf().unwrap();
+    buffer
+        .set_lines(0, -1, true, ["one", "two", "three"])
+        .unwrap();
+    nvim.command("call cursor(2, 3) | mark a | normal! G")
+        .unwrap();
+
+    let navigation = nvim.get_navigation().unwrap();
+    let mark = navigation
+        .buffer_marks
+        .iter()
+        .find(|mark| mark.name == "'a")
+        .unwrap();
+    assert_eq!((mark.row, mark.col), (2, 0));
+    assert!(!navigation.jumps.entries.is_empty());
+    assert_eq!(
+        nvim.get_marks().unwrap().len(),
+        navigation.global_marks.len() + navigation.buffer_marks.len()
+    );
+}
+
+#[test]
+fn global_marks() {
+    let (_instance, mut nvim) = nvim!();
+    let buffer = nvim.get_current_buf().unwrap();
+    buffer
+        .set_lines(0, -1, true, ["one", "two", "three"])
+        .unwrap();
+    nvim.command("call cursor(2, 1) | mark A").unwrap();
+    let mark = nvim.get_global_mark('A').unwrap().unwrap();
+    assert_eq!((mark.name.as_str(), mark.row, mark.col), ("'A", 2, 0));
+    assert_eq!(nvim.get_global_mark('B').unwrap(), None);
+
+    let watcher = nvim.watch_global_marks(Duration::from_millis(20)).unwrap();
+    nvim.command("call cursor(3, 1) | mark B").unwrap();
+    let change = watcher
+        .changes()
+        .recv_timeout(Duration::from_secs(2))
+        .unwrap();
+    assert_eq!(change.name, "'B");
+    assert_eq!(change.previous, None);
+    assert_eq!(change.current.unwrap().row, 3);
+    nvim.command("delmarks B").unwrap();
+    let change = watcher
+        .changes()
+        .recv_timeout(Duration::from_secs(2))
+        .unwrap();
+    assert_eq!((change.name.as_str(), change.current), ("'B", None));
+}
+
+#[test]
+#[cfg(feature = "ropey")]
+fn buffer_rope() {
+    let (_instance, nvim) = nvim!();
+    let mut buffer = nvim.get_current_buf().unwrap();
+    buffer
+        .set_lines(0, -1, true, ["one", "two", "three"])
+        .unwrap();
+    assert_eq!(buffer.to_rope().unwrap().to_string(), "one\ntwo\nthree\n");
+
+    let mut sync = buffer.attach_rope().unwrap();
+    assert_eq!(sync.rope().to_string(), "one\ntwo\nthree\n");
+    buffer.set_lines(1, 2, true, ["2", "2.5"]).unwrap();
+    buffer.set_lines(-1, -1, true, ["four"]).unwrap();
+    let tick = nvim.eval("b:changedtick").unwrap().as_i64().unwrap();
+    while sync.changedtick() < tick {
+        assert!(sync.wait(Duration::from_secs(2)).unwrap());
+    }
+    assert_eq!(sync.rope().to_string(), "one\n2\n2.5\nthree\nfour\n");
+    assert_eq!(sync.detach().unwrap(), buffer.to_rope().unwrap());
+}
+
+#[cfg(all(unix, feature = "ropey"))]
+#[test]
+fn rope_short_events() {
+    let (session, mut theirs) = common::socket_session();
+    session.start_event_loop(None, None);
+    let mut buffer = Buffer::new(1, Arc::new(Mutex::new(session)));
+    let handle = Value::Ext(0, vec![1]);
+
+    // A changedtick event, which has no lines, arrives before the contents
+    let mut peer = theirs.try_clone().unwrap();
+    let event = handle.clone();
+    let server = thread::spawn(move || loop {
+        let (msgid, method, _) = common::next_request(&mut peer);
+        if method != "nvim_buf_attach" {
+            common::respond(
+                &mut peer,
+                msgid,
+                common::rpc_error("Unavailable"),
+                Value::Nil,
+            );
+            continue;
+        }
+        common::respond(&mut peer, msgid, Value::Nil, Value::from(true));
+        common::notify(
+            &mut peer,
+            "nvim_buf_changedtick_event",
+            vec![event.clone(), Value::from(1)],
+        );
+        let lines = Value::Array(vec![Value::from("one")]);
+        let params = vec![
+            event,
+            Value::from(2),
+            0.into(),
+            (-1).into(),
+            lines,
+            false.into(),
+        ];
+        common::notify(&mut peer, "nvim_buf_lines_event", params);
+        return;
+    });
+    let mut sync = buffer.attach_rope().unwrap();
+    server.join().unwrap();
+    assert_eq!(sync.rope().to_string(), "one\n");
+    assert_eq!(sync.changedtick(), 2);
+
+    // A truncated lines event is an error rather than a panic
+    common::notify(
+        &mut theirs,
+        "nvim_buf_lines_event",
+        vec![handle.clone(), 3.into()],
+    );
+    assert!(matches!(
+        sync.wait(Duration::from_secs(5)),
+        Err(Error::DecodingError(_))
+    ));
+    common::notify(&mut theirs, "nvim_buf_detach_event", vec![handle]);
+    assert!(!sync.wait(Duration::from_secs(5)).unwrap());
+    assert!(!sync.is_attached());
+}
+
+#[test]
+fn typed_opts() {
+    let (_instance, nvim) = nvim!();
+    let buffer = nvim.get_current_buf().unwrap();
+    let config = WinConfig::new()
+        .relative(Relative::Editor)
+        .row(1.0)
+        .col(1.0)
+        .width(10)
+        .height(2)
+        .minimal();
+    let window = nvim.open_win(&buffer, false, config).unwrap();
+    assert_eq!(window.get_width().unwrap(), 10);
+
+    let opts = KeymapOpts::new().noremap(true).desc("rsnvim");
+    nvim.set_keymap("n", "<F2>", ":echo<CR>", opts).unwrap();
+
+    let buffer = buffer;
+    buffer.set_lines(0, -1, true, ["text"]).unwrap();
+    let ns = nvim.create_namespace("rsnvim").unwrap();
+    let opts = ExtmarkOpts::new().end_col(2).hl_group("Search");
+    assert!(buffer.set_extmark(ns, 0, 0, opts).unwrap() > 0);
+}
+
+#[test]
+fn scan_lines() {
+    let (_instance, nvim) = nvim!();
+    let mut buffer = nvim.get_current_buf().unwrap();
+    let lines: Vec<String> = (0..25).map(|i| i.to_string()).collect();
+    buffer.set_lines(0, -1, true, lines.clone()).unwrap();
+
+    let chunks: Vec<Vec<String>> = buffer
+        .scan_lines(10)
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(chunks.len(), 3);
+    assert_eq!(chunks.concat(), lines);
+}
+
+#[test]
+fn get_lines_chunked() {
+    let (_instance, nvim) = nvim!();
+    let mut buffer = nvim.get_current_buf().unwrap();
+    let lines: Vec<String> = (0..10).map(|i| i.to_string()).collect();
+    buffer.set_lines(0, -1, true, lines.clone()).unwrap();
+
+    let chunks: Vec<Vec<String>> = buffer
+        .get_lines_chunked(2, -2, 3)
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(chunks.iter().map(Vec::len).collect::<Vec<_>>(), [3, 3, 1]);
+    assert_eq!(chunks.concat(), lines[2..9]);
+
+    let all: Vec<Vec<String>> = buffer
+        .get_lines_chunked(0, -1, 100)
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(all, [lines]);
+    assert_eq!(buffer.get_lines_chunked(5, 20, 3).count(), 2);
+}
+
+#[test]
+fn notifications() {
+    let (_instance, mut nvim) = nvim!();
+    let events = nvim.subscribe_events(&["rsnvim_event"]);
+    nvim.command("call rpcnotify(0, 'rsnvim_other')").unwrap();
+    nvim.command("call rpcnotify(0, 'rsnvim_event', 42)")
+        .unwrap();
+    let notification = events.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(notification.params, vec![Value::from(42)]);
+}
+
+struct Echo;
+
+impl RequestHandler for Echo {
+    fn handle_request(
+        &self,
+        nvim: &mut Nvim,
+        _msgid: u64,
+        method: String,
+        params: Vec<Value>,
+    ) -> Result<Value, Error> {
+        match method.as_str() {
+            // Calls back into Neovim while Neovim waits for the response
+            "echo" => {
+                let line = nvim.get_current_line()?;
+                Ok(Value::from(format!(
+                    "{}{}",
+                    line,
+                    params[0].as_str().unwrap()
+                )))
+            }
+            _ => Err(Error::NotImplemented(method)),
+        }
+    }
+}
+
+/// Counts requests and records notifications in its own fields
+#[derive(Default)]
+struct Tally {
+    requests: i64,
+    notifications: Vec<Value>,
+}
+
+impl RequestHandlerMut for Tally {
+    fn handle_request(
+        &mut self,
+        _nvim: &mut Nvim,
+        _msgid: u64,
+        _method: String,
+        _params: Vec<Value>,
+    ) -> Result<Value, Error> {
+        self.requests += 1;
+        Ok(Value::from(self.requests))
+    }
+}
+
+impl NotificationHandlerMut for Tally {
+    fn handle_notification(&mut self, _nvim: &mut Nvim, _method: String, params: Vec<Value>) {
+        self.notifications.extend(params);
+    }
+}
+
+#[test]
+fn stateful_handlers() {
+    let tally = Arc::new(Mutex::new(Tally::default()));
+    let (_instance, mut nvim) = nvim!(
+        Some(Box::new(Mutex::new(Tally::default()))),
+        Some(Box::new(tally.clone()))
+    );
+    let channel = nvim.channel_id().unwrap();
+    for n in 1..=3 {
+        let notify = format!("call rpcnotify({}, 'rsnvim_tally', {})", channel, n);
+        nvim.command(notify.as_str()).unwrap();
+    }
+    let request = format!(
+        "rpcrequest({}, 'next') + rpcrequest({}, 'next')",
+        channel, channel
+    );
+    // Handled after the notifications, which were received first
+    assert_eq!(nvim.eval(request.as_str()).unwrap(), Value::from(3));
+    assert_eq!(
+        tally.lock().unwrap().notifications,
+        vec![Value::from(1), Value::from(2), Value::from(3)]
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn stateful_handler_panics() {
+    use rsnvim::handler::NotificationHandler;
+    use std::panic::{self, AssertUnwindSafe};
+
+    struct Fragile(Vec<String>);
+
+    impl NotificationHandlerMut for Fragile {
+        fn handle_notification(&mut self, _nvim: &mut Nvim, method: String, _params: Vec<Value>) {
+            if method == "explode" {
+                panic!("exploded");
+            }
+            self.0.push(method);
+        }
+    }
+
+    let (session, _theirs) = common::socket_session();
+    let mut nvim = Nvim::from_session(session);
+    let handler = Arc::new(Mutex::new(Fragile(Vec::new())));
+    let mut notify = |method: &str| {
+        panic::catch_unwind(AssertUnwindSafe(|| {
+            NotificationHandler::handle_notification(
+                &handler,
+                &mut nvim,
+                method.to_string(),
+                vec![],
+            )
+        }))
+    };
+    assert!(notify("explode").is_err());
+    // Later notifications are still handled
+    assert!(notify("after").is_ok());
+    assert!(handler.is_poisoned());
+    assert_eq!(
+        handler.lock().unwrap_or_else(|error| error.into_inner()).0,
+        vec!["after".to_string()]
+    );
+}
+
+#[test]
+fn requests() {
+    let (_instance, nvim) = nvim!(Some(Box::new(Echo)));
+    nvim.set_current_line("hello ").unwrap();
+    let channel = nvim.get_api_info().unwrap()[0].as_i64().unwrap();
+    assert_eq!(
+        nvim.eval(format!("rpcrequest({}, 'echo', 'world')", channel))
+            .unwrap(),
+        Value::from("hello world")
+    );
+}
+
+#[test]
+fn router() {
+    let mut router = HandlerRouter::new();
+    router.on_request("add", |(a, b): (i64, i64), _nvim| Ok(a + b));
+    let (requests, _) = router.into_handlers();
+    let (_instance, nvim) = nvim!(requests);
+    let channel = nvim.get_api_info().unwrap()[0].as_i64().unwrap();
+
+    let sum = format!("rpcrequest({}, 'add', 1, 2)", channel);
+    assert_eq!(nvim.eval(sum).unwrap(), Value::from(3));
+    let invalid = format!("rpcrequest({}, 'add', 'one')", channel);
+    assert!(nvim.eval(invalid).is_err());
+}
+
+#[rsnvim::export]
+fn multiply(a: i64, b: i64) -> i64 {
+    a * b
+}
+
+#[rsnvim::export(name = "line_count")]
+fn current_line_count(nvim: &mut Nvim) -> Result<i64, Error> {
+    nvim.get_current_buf()?.line_count()
+}
+
+#[test]
+fn remote_plugin() {
+    let mut plugin = RemotePlugin::new("/rplugin/rsnvim/test");
+    plugin
+        .function(
+            "RsnvimAdd",
+            FunctionSpec::new().sync(),
+            |(args,): (Vec<i64>,), _nvim| Ok(args.iter().sum::<i64>()),
+        )
+        .command(
+            "RsnvimGreet",
+            CommandSpec::new().sync().nargs("*").bang(),
+            |(args, bang): (Vec<String>, bool), nvim| {
+                let greeting = format!("{}{}", args.join(" "), if bang { "!" } else { "" });
+                nvim.command(format!("let g:greeting = '{}'", greeting))
+            },
+        );
+    let mut router = HandlerRouter::new();
+    plugin.register(&mut router);
+    let (requests, notifications) = router.into_handlers();
+    let (_instance, mut nvim) = nvim!(requests, notifications);
+
+    let channel = nvim.channel_id().unwrap();
+    let poll = format!("rpcrequest({}, 'poll')", channel);
+    assert_eq!(nvim.eval(poll.as_str()).unwrap(), Value::from("ok"));
+    let specs = format!("rpcrequest({}, 'specs', '/rplugin/rsnvim/test')", channel);
+    let specs = nvim.eval(specs.as_str()).unwrap();
+    assert_eq!(specs[0]["name"], Value::from("RsnvimAdd"));
+    assert_eq!(specs[1]["type"], Value::from("command"));
+
+    plugin.define(&mut nvim).unwrap();
+    assert_eq!(nvim.eval("RsnvimAdd(1, 2, 3)").unwrap(), Value::from(6));
+    nvim.command("RsnvimGreet hello world").unwrap();
+    assert_eq!(
+        nvim.get_var("greeting").unwrap(),
+        Value::from("hello world")
+    );
+    nvim.command("RsnvimGreet! hi").unwrap();
+    assert_eq!(nvim.get_var("greeting").unwrap(), Value::from("hi!"));
+}
+
+#[test]
+fn export() {
+    let mut router = HandlerRouter::new();
+    router.export::<multiply>().export::<current_line_count>();
+    let (requests, _) = router.into_handlers();
+    let (_instance, mut nvim) = nvim!(requests);
+    let channel = nvim.api_info().unwrap().channel;
+
+    let product = format!("rpcrequest({}, 'multiply', 6, 7)", channel);
+    assert_eq!(nvim.eval(product).unwrap(), Value::from(42));
+    let lines = format!("rpcrequest({}, 'line_count')", channel);
+    assert_eq!(nvim.eval(lines).unwrap(), Value::from(1));
+}
+
+#[test]
+fn search() {
+    let (_instance, nvim) = nvim!();
+    let mut buffer = nvim.get_current_buf().unwrap();
+    let lines = vec!["foo bar foo", "Foo a.b", "bar"];
+    buffer.set_lines(0, -1, true, lines).unwrap();
+
+    let matches = buffer.search("^foo", &SearchOpts::new()).unwrap();
+    assert_eq!(
+        matches,
+        vec![MatchRange {
+            row: 0,
+            col: 0,
+            end_col: 3,
+            text: "foo".to_string()
+        }]
+    );
+
+    let opts = SearchOpts::new().ignore_case(true);
+    let rows: Vec<_> = buffer
+        .search("foo", &opts)
+        .unwrap()
+        .iter()
+        .map(|m| (m.row, m.col))
+        .collect();
+    assert_eq!(rows, vec![(0, 0), (0, 8), (1, 0)]);
+
+    let opts = SearchOpts::new().literal();
+    assert_eq!(buffer.search("a.b", &opts).unwrap().len(), 1);
+    let opts = SearchOpts::new().range(1, -1).max_matches(1);
+    assert_eq!(buffer.search("bar", &opts).unwrap()[0].row, 2);
+}
+
+#[test]
+fn handles() {
+    let (_instance, nvim) = nvim!();
+    let buffer = nvim.get_current_buf().unwrap();
+    let window = nvim.get_current_win().unwrap();
+    assert_eq!(buffer.id(), 1);
+    assert_eq!(window.get_buf().unwrap(), buffer);
+    assert_eq!(nvim.list_wins().unwrap(), vec![window.clone()]);
+
+    // Handles hash by ID only, so the session's interior mutability is fine
+    #[allow(clippy::mutable_key_type)]
+    let mut ids = std::collections::HashSet::new();
+    ids.insert(buffer.clone());
+    ids.insert(nvim.create_buf(false, true).unwrap());
+    ids.insert(nvim.get_current_buf().unwrap());
+    assert_eq!(ids.len(), 2);
+    assert_eq!(format!("{:?}", buffer), "Buffer(1)");
+}
+
+#[test]
+fn ui_thread_guard() {
+    let (_instance, mut nvim) = nvim!();
+    mark_ui_thread(BlockingPolicy::Error);
+    assert!(matches!(
+        nvim.get_current_line(),
+        Err(Error::BlockingCall(_))
+    ));
+    let handle = nvim
+        .call_async("nvim_get_current_line", Vec::new())
+        .unwrap();
+    assert!(matches!(handle.wait(), Err(Error::BlockingCall(_))));
+
+    unmark_ui_thread();
+    assert_eq!(nvim.get_current_line().unwrap(), "");
+}
+
+#[test]
+fn stop_and_join() {
+    let (_instance, mut nvim) = nvim!();
+    assert!(nvim.is_alive());
+    nvim.stop_event_loop().unwrap();
+    nvim.join().unwrap();
+    assert!(!nvim.is_alive());
+    assert!(nvim.get_current_line().is_err());
+}
+
+#[test]
+fn dynamic_api() {
+    let (_instance, mut nvim) = nvim!();
+    let api = DynamicApi::new(&mut nvim).unwrap();
+    api.invoke("nvim_set_current_line", vec![Value::from("dynamic")])
+        .unwrap();
+    assert_eq!(
+        api.invoke("nvim_get_current_line", Vec::new()).unwrap(),
+        Value::from("dynamic")
+    );
+    assert!(matches!(
+        api.invoke("nvim_set_current_line", Vec::new()),
+        Err(Error::InvalidArgs { .. })
+    ));
+    assert!(matches!(
+        api.invoke("nvim_set_current_line", vec![Value::from(1)]),
+        Err(Error::InvalidArgs { .. })
+    ));
+
+    let api = api.allow(["nvim_get_current_line"]);
+    assert_eq!(api.functions().count(), 1);
+    assert!(matches!(
+        api.invoke("nvim_command", vec![Value::from("quit")]),
+        Err(Error::NotImplemented(_))
+    ));
+}
+
+#[test]
+#[cfg(debug_assertions)]
+fn raw_call_validation() {
+    let (_instance, mut nvim) = nvim!();
+    nvim.api_info().unwrap();
+    assert!(matches!(
+        nvim.call_async("nvim_set_current_line", vec![Value::from(1)]),
+        Err(Error::InvalidArgs { .. })
+    ));
+    assert!(matches!(
+        nvim.send_notification("nvim_set_current_line", Vec::new()),
+        Err(Error::InvalidArgs { .. })
+    ));
+    // Methods unknown to the API aren't checked
+    nvim.send_notification("rsnvim_unknown", vec![Value::from(1)])
+        .unwrap();
+    nvim.set_current_line("valid").unwrap();
+    assert_eq!(nvim.get_current_line().unwrap(), "valid");
+}
+
+#[test]
+fn unsupported_methods() {
+    let (_instance, mut nvim) = nvim!();
+    nvim.api_info().unwrap();
+    // Methods missing from the server's API aren't sent
+    assert!(matches!(
+        nvim.call_async("nvim_does_not_exist", Vec::new()),
+        Err(Error::UnsupportedVersion(_))
+    ));
+    assert!(matches!(
+        nvim.send_notification("nvim_does_not_exist", Vec::new()),
+        Err(Error::UnsupportedVersion(_))
+    ));
+    #[cfg(feature = "nvim-0-10")]
+    if nvim.supports("nvim_win_text_height").unwrap() {
+        let window = nvim.get_current_win().unwrap();
+        assert!(window.text_height(Vec::new()).is_ok());
+    }
+}
+
+#[test]
+fn notification_channels() {
+    let (_instance, mut nvim) = nvim!();
+    let events = nvim.subscribe_notifications("rsnvim_params");
+    let channel = nvim.api_info().unwrap().channel;
+    let notify = format!("call rpcnotify({}, 'rsnvim_params', 1, 'two')", channel);
+    nvim.command(notify.as_str()).unwrap();
+    assert_eq!(
+        events.recv_timeout(Duration::from_secs(1)).unwrap(),
+        vec![Value::from(1), Value::from("two")]
+    );
+
+    nvim.unsubscribe_notifications("rsnvim_params");
+    assert!(events.recv_timeout(Duration::from_secs(1)).is_err());
+}
+
+#[test]
+fn coalesced_extmarks() {
+    let (_instance, mut nvim) = nvim!();
+    let buffer = nvim.get_current_buf().unwrap();
+    buffer.set_lines(0, -1, true, vec!["a", "b", "c"]).unwrap();
+    let ns_id = nvim.create_namespace("rsnvim_coalesce").unwrap();
+    nvim.coalesce_extmarks(ns_id, Some(Duration::from_millis(5)));
+
+    let handles: Vec<_> = (0..3)
+        .map(|line| {
+            let args = vec![
+                Value::from(buffer.clone()),
+                Value::from(ns_id),
+                Value::from(line),
+                Value::from(0),
+                Value::Map(Vec::new()),
+            ];
+            nvim.call_async("nvim_buf_set_extmark", args).unwrap()
+        })
+        .collect();
+    let ids: Vec<_> = handles.into_iter().map(|h| h.wait().unwrap()).collect();
+    assert_eq!(ids.len(), 3);
+    assert_ne!(ids[0], ids[1]);
+
+    nvim.stop_coalescing_extmarks(ns_id).unwrap();
+    let extmarks = buffer
+        .get_extmarks(ns_id, Value::from(0), Value::from(-1), Vec::new())
+        .unwrap();
+    assert_eq!(extmarks.len(), 3);
+}
+
+#[test]
+fn storage() {
+    let (_instance, mut nvim) = nvim!();
+    let storage = Storage::new(&mut nvim, "rsnvim-test").unwrap();
+    let state = nvim
+        .call_function("stdpath", vec![Value::from("state")])
+        .unwrap();
+    assert_eq!(
+        storage.dir(Location::State),
+        std::path::Path::new(state.as_str().unwrap()).join("rsnvim-test")
+    );
+    assert!(storage.path(Location::Data, "../escape").is_err());
+
+    // Write to a temporary directory rather than the user's data
+    let dir = std::env::temp_dir().join(format!("rsnvim-storage-{}", std::process::id()));
+    let storage = Storage::with_dirs("rsnvim-test", dir.join("data"), dir.join("state")).unwrap();
+    assert_eq!(storage.read_value(Location::Data, "counts").unwrap(), None);
+    storage
+        .write_value(Location::Data, "counts", &Value::from(vec![Value::from(1)]))
+        .unwrap();
+    assert_eq!(
+        storage.read::<Vec<i64>>(Location::Data, "counts").unwrap(),
+        Some(vec![1])
+    );
+    assert!(storage
+        .read_bytes(Location::State, "counts")
+        .unwrap()
+        .is_none());
+    assert!(storage.remove(Location::Data, "counts").unwrap());
+    assert!(!storage.remove(Location::Data, "counts").unwrap());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn exec_lua_typed() {
+    let (_instance, mut nvim) = nvim!();
+    let sum: i64 = rsnvim::lua!(nvim, "local a, b = ...; return a + b", 1, 2).unwrap();
+    assert_eq!(sum, 3);
+
+    let words: Vec<String> = rsnvim::lua!(nvim, "return vim.split(..., ' ')", "a b").unwrap();
+    assert_eq!(words, vec!["a", "b"]);
+
+    let result: Result<bool, Error> = nvim.exec_lua_typed("return 'yes'", vec![]);
+    assert!(matches!(result, Err(Error::DecodingError(_))));
+}
+
+#[test]
+fn eval_sandboxed() {
+    let (_instance, mut nvim) = nvim!();
+    let opts = SandboxOpts::new().var("width", 81);
+    let half: i64 = nvim.eval_sandboxed("math.floor(width / 2)", &opts).unwrap();
+    assert_eq!(half, 40);
+
+    assert!(matches!(
+        nvim.eval_sandboxed::<Value>("vim.fn.getcwd()", &opts),
+        Err(Error::SandboxViolation(_))
+    ));
+    assert!(matches!(
+        nvim.eval_sandboxed::<Value>("1 +", &opts),
+        Err(Error::InvalidArgument(_))
+    ));
+    assert!(matches!(
+        nvim.eval_sandboxed::<Value>("error('failed')", &opts.clone().allow("error")),
+        Err(Error::RpcError(_))
+    ));
+
+    let looping = "(function() while true do end end)()";
+    let opts = opts.timeout(Duration::from_millis(10));
+    assert!(matches!(
+        nvim.eval_sandboxed::<Value>(looping, &opts),
+        Err(Error::SandboxViolation(_))
+    ));
+}
+
+#[test]
+fn ui_options() {
+    let (_instance, mut nvim) = nvim!();
+    assert!(matches!(
+        nvim.attach_ui(80, 24, UiOptions::new().ext_multigrid(true)),
+        Err(Error::InvalidArgument(_))
+    ));
+
+    let mut ui = nvim
+        .attach_ui(80, 24, UiOptions::new().rgb(true).ext_linegrid(true))
+        .unwrap();
+    ui.set_options(UiOptions::new().ext_multigrid(true))
+        .unwrap();
+    assert!(matches!(
+        ui.set_options(UiOptions::new().ext_linegrid(false)),
+        Err(Error::InvalidArgument(_))
+    ));
+    assert_eq!(ui.options().get("ext_multigrid"), Some(&Value::from(true)));
+
+    assert!(ui.try_resize(0, 10).is_err());
+    ui.try_resize(100, 30).unwrap();
+    assert_eq!((ui.width(), ui.height()), (100, 30));
+    ui.detach().unwrap();
+}
+
+#[test]
+fn notification_queue() {
+    let (_instance, mut nvim) = nvim!();
+    for _ in 0..2000 {
+        nvim.send_notification("nvim_input", vec![Value::from("ia<Esc>")])
+            .unwrap();
+    }
+    nvim.flush_notifications().unwrap();
+    // The input is processed after the notifications have been read
+    for _ in 0..100 {
+        if nvim.get_current_line().unwrap().len() == 2000 {
+            return;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    panic!("The queued input wasn't processed");
+}
+
+#[test]
+fn macro_playback() {
+    let (_instance, mut nvim) = nvim!();
+    let keys = Keys::new().text("ia<b").key("Esc");
+    Macro::new(keys)
+        .play(&mut nvim, &PlaybackOpts::new().count(2))
+        .unwrap();
+    assert_eq!(nvim.get_current_line().unwrap(), "a<a<bb");
+
+    nvim.command("let @q = \"A!\\<Esc>\"").unwrap();
+    let recorded = Macro::from_register(&mut nvim, 'q').unwrap();
+    assert_eq!(recorded.keys().to_string(), "A!<Esc>");
+    let opts = PlaybackOpts::new().delay(Duration::from_millis(1));
+    recorded.play(&mut nvim, &opts).unwrap();
+    assert_eq!(nvim.get_current_line().unwrap(), "a<a<bb!");
+}
+
+#[test]
+fn notification_workers() {
+    let (sender, events) = mpsc::channel();
+    let sender = Mutex::new(sender);
+    let mut router = HandlerRouter::new();
+    router
+        .on_notification("rsnvim_buf", move |(buf, seq): (i64, i64), _nvim| {
+            // Delay the first buffer so the other overtakes it
+            if buf == 1 {
+                thread::sleep(Duration::from_millis(5));
+            }
+            sender.lock().unwrap().send((buf, seq)).unwrap();
+            Ok(())
+        })
+        .ordering_key("rsnvim_buf", |params| params.first().cloned())
+        .worker_threads(4);
+    let (_, notifications) = router.into_handlers();
+    let (_instance, mut nvim) = nvim!(None, notifications);
+
+    let channel = nvim.api_info().unwrap().channel;
+    let notify = format!(
+        "for i in range(20) | call rpcnotify({}, 'rsnvim_buf', i % 2 + 1, i) | endfor",
+        channel
+    );
+    nvim.command(notify.as_str()).unwrap();
+
+    let mut received: Vec<(i64, i64)> = (0..20)
+        .map(|_| events.recv_timeout(Duration::from_secs(1)).unwrap())
+        .collect();
+    for buf in [1, 2] {
+        let seqs: Vec<i64> = received
+            .iter()
+            .filter(|e| e.0 == buf)
+            .map(|e| e.1)
+            .collect();
+        let mut sorted = seqs.clone();
+        sorted.sort();
+        assert_eq!(seqs, sorted);
+        assert_eq!(seqs.len(), 10);
+    }
+    received.sort();
+    received.dedup();
+    assert_eq!(received.len(), 20);
+}
+
+#[test]
+fn connect_with_retry() {
+    let binary = match std::env::var("RSNVIM_TEST_NVIM") {
+        Ok(binary) => binary,
+        Err(_) => {
+            eprintln!("RSNVIM_TEST_NVIM is not set, skipping");
+            return;
+        }
+    };
+    let addr = {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().to_string()
+    };
+    let mut child = Command::new(&binary)
+        .args(["--headless", "--clean", "--listen", &addr])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    let spec = RetrySpec::new().process(&mut child);
+    let mut nvim = Nvim::connect_with_retry(Endpoint::Tcp(addr.clone()), spec).unwrap();
+    let _instance = Instance { child };
+    nvim.start_event_loop(None, None);
+    assert_eq!(nvim.eval("1 + 1").unwrap(), Value::from(2));
+
+    // A Neovim which exits without listening is reported as such
+    let mut child = Command::new(&binary)
+        .args(["--headless", "--clean", "+qa"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    let spec = RetrySpec::new().process(&mut child);
+    let addr = {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().to_string()
+    };
+    let result = Nvim::connect_with_retry(Endpoint::Tcp(addr), spec);
+    assert!(matches!(result, Err(Error::ProcessExited(_))));
+}
+
+#[test]
+fn connect_auto() {
+    let parse = |addr: &str| addr.parse::<Endpoint>();
+    assert_eq!(
+        parse("127.0.0.1:6666").unwrap(),
+        Endpoint::Tcp("127.0.0.1:6666".to_string())
+    );
+    assert_eq!(
+        parse("localhost:6666").unwrap(),
+        Endpoint::Tcp("localhost:6666".to_string())
+    );
+    assert_eq!(
+        parse("[::1]:6666").unwrap(),
+        Endpoint::Tcp("[::1]:6666".to_string())
+    );
+    assert_eq!(
+        parse("/run/user/1000/nvim.1234.0").unwrap(),
+        Endpoint::Unix("/run/user/1000/nvim.1234.0".to_string())
+    );
+    assert_eq!(
+        parse("/tmp/nvim:6666").unwrap(),
+        Endpoint::Unix("/tmp/nvim:6666".to_string())
+    );
+    assert_eq!(
+        parse("nvim.sock").unwrap(),
+        Endpoint::Unix("nvim.sock".to_string())
+    );
+    assert!(matches!(
+        parse(r"\\.\pipe\nvim.1234.0"),
+        Err(Error::InvalidArgument(_))
+    ));
+    assert!(matches!(parse(""), Err(Error::InvalidArgument(_))));
+
+    let (_instance, addr) = match spawn() {
+        Some(spawned) => spawned,
+        None => {
+            eprintln!("RSNVIM_TEST_NVIM is not set, skipping");
+            return;
+        }
+    };
+    std::env::set_var("NVIM", &addr);
+    let mut nvim = Nvim::connect_auto().unwrap();
+    nvim.start_event_loop(None, None);
+    assert_eq!(nvim.eval("1 + 1").unwrap(), Value::from(2));
+}
+
+#[test]
+fn custom_transport() {
+    let binary = match std::env::var("RSNVIM_TEST_NVIM") {
+        Ok(binary) => binary,
+        Err(_) => {
+            eprintln!("RSNVIM_TEST_NVIM is not set, skipping");
+            return;
+        }
+    };
+    let mut child = Command::new(binary)
+        .args(["--embed", "--headless", "--clean"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let reader = child.stdout.take().unwrap();
+    let writer = child.stdin.take().unwrap();
+    let _instance = Instance { child };
+
+    let mut nvim = Nvim::from_transport(reader, writer);
+    nvim.start_event_loop(None, None);
+    assert_eq!(nvim.eval("1 + 1").unwrap(), Value::from(2));
+    assert!(nvim.reconnect().is_err());
+}
+
+#[test]
+fn param_validation() {
+    let mut router = HandlerRouter::new();
+    router.on_request("greet", |params: Vec<Value>, _nvim| {
+        let (name, count, opts) = params.expect().string().int().optional_map().done()?;
+        let punctuation = opts
+            .and_then(|opts| {
+                opts.into_iter()
+                    .find(|(k, _)| k.as_str() == Some("punctuation"))
+            })
+            .and_then(|(_, v)| v.as_str().map(String::from))
+            .unwrap_or_else(|| "!".to_string());
+        Ok(format!("{}{}", name.repeat(count as usize), punctuation))
+    });
+    let (requests, _) = router.into_handlers();
+    let (_instance, mut nvim) = nvim!(requests);
+    let channel = nvim.api_info().unwrap().channel;
+
+    let mut greet = |args: &str| -> Result<String, Error> {
+        let code = format!("return vim.rpcrequest({}, 'greet', {})", channel, args);
+        nvim.exec_lua_typed(code, Vec::new())
+    };
+    assert_eq!(greet("'hi', 2").unwrap(), "hihi!");
+    assert_eq!(greet("'hi', 1, {}").unwrap(), "hi!");
+    assert_eq!(greet("'hi', 1, { punctuation = '?' }").unwrap(), "hi?");
+    let error = greet("'hi', 'two'").unwrap_err().to_string();
+    assert!(
+        error.contains("Parameter 2: expected an integer"),
+        "{}",
+        error
+    );
+    let error = greet("'hi'").unwrap_err().to_string();
+    assert!(error.contains("Missing parameter 2"), "{}", error);
+}
+
+#[test]
+fn autocmd_manager() {
+    let (_instance, mut nvim) = nvim!();
+    let mut autocmds = AutocmdManager::new(&mut nvim).unwrap();
+    let (sender, events) = mpsc::channel();
+    let id = autocmds
+        .on(
+            &["User"],
+            AutocmdOpts::new().pattern(&["RsnvimTest"]),
+            move |args, _nvim| sender.send(args).unwrap(),
+        )
+        .unwrap();
+
+    nvim.command("doautocmd User RsnvimTest").unwrap();
+    let args = events.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(args.id, id);
+    assert_eq!(args.event, "User");
+    assert_eq!(args.matched, "RsnvimTest");
+    assert_eq!(args.group, Some(autocmds.augroup()));
+
+    autocmds.remove(id).unwrap();
+    nvim.command("doautocmd User RsnvimTest").unwrap();
+    assert!(events.recv_timeout(Duration::from_millis(200)).is_err());
+    autocmds.stop().unwrap();
+}
+
+#[test]
+fn yank_sync() {
+    let (_left_instance, mut left) = nvim!();
+    let (_right_instance, mut right) = nvim!();
+    let sync = YankSync::new();
+    sync.add(&mut left).unwrap();
+    let right_id = sync.add(&mut right).unwrap();
+    assert_eq!(sync.len(), 2);
+
+    let wait_for = |nvim: &Nvim, expected: &str| {
+        for _ in 0..20 {
+            if nvim.eval("@\"").unwrap() == Value::from(expected) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        panic!("The unnamed register was never {:?}", expected);
+    };
+
+    left.set_current_line("first line").unwrap();
+    left.command("normal! yy").unwrap();
+    wait_for(&right, "first line\n");
+    assert_eq!(
+        right.call_function("getregtype", vec![]).unwrap(),
+        Value::from("V")
+    );
+
+    right.set_current_line("second line").unwrap();
+    right.command("normal! 0yiw").unwrap();
+    wait_for(&left, "second");
+
+    // Removed Neovims no longer receive yanks
+    sync.remove(right_id).unwrap();
+    left.command("normal! yy").unwrap();
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(right.eval("@\"").unwrap(), Value::from("second"));
+    sync.stop().unwrap();
+    assert!(sync.is_empty());
+}
+
+#[test]
+fn keymap_rpc() {
+    let (_instance, mut nvim) = nvim!();
+    let (first, first_calls) = mpsc::channel();
+    nvim.set_keymap_rpc("n", "<F2>", KeymapOpts::new(), move |_nvim| {
+        first.send(()).unwrap()
+    })
+    .unwrap();
+    nvim.input("<F2>").unwrap();
+    first_calls.recv_timeout(Duration::from_secs(1)).unwrap();
+
+    // Mapping the keys again replaces the callback
+    let (second, second_calls) = mpsc::channel();
+    nvim.set_keymap_rpc("n", "<F2>", KeymapOpts::new(), move |nvim| {
+        second.send(nvim.get_current_line().unwrap()).unwrap()
+    })
+    .unwrap();
+    nvim.set_current_line("mapped").unwrap();
+    nvim.input("<F2>").unwrap();
+    assert_eq!(
+        second_calls.recv_timeout(Duration::from_secs(1)).unwrap(),
+        "mapped"
+    );
+    assert!(first_calls
+        .recv_timeout(Duration::from_millis(200))
+        .is_err());
+
+    nvim.del_keymap_rpc("n", "<F2>").unwrap();
+    nvim.input("<F2>").unwrap();
+    assert!(second_calls
+        .recv_timeout(Duration::from_millis(200))
+        .is_err());
+}
+
+#[test]
+fn with_options() {
+    let (_instance, mut nvim) = nvim!();
+    nvim.command("set nohidden eventignore=").unwrap();
+    let options = [
+        ("hidden", Value::from(true)),
+        ("eventignore", Value::from("all")),
+    ];
+    let inside = nvim
+        .with_options(&options, |nvim| nvim.eval("[&hidden, &eventignore]"))
+        .unwrap();
+    assert_eq!(
+        inside,
+        Value::from(vec![Value::from(1), Value::from("all")])
+    );
+    let restored = nvim.eval("[&hidden, &eventignore]").unwrap();
+    assert_eq!(restored, Value::from(vec![Value::from(0), Value::from("")]));
+
+    // Options are restored when the closure fails
+    let result: Result<(), Error> =
+        nvim.with_options(&options, |nvim| nvim.command("rsnvim_not_a_command"));
+    assert!(result.is_err());
+    assert_eq!(nvim.eval("&hidden").unwrap(), Value::from(0));
+
+    // and when an option can't be set, without running the closure
+    let options = [("hidden", Value::from(true)), ("tabstop", Value::from(-1))];
+    let result = nvim.with_options(&options, |_nvim| -> Result<(), Error> {
+        panic!("closure should not run")
+    });
+    assert!(matches!(result, Err(Error::RpcError(_))));
+    assert_eq!(nvim.eval("&hidden").unwrap(), Value::from(0));
+}
+
+#[test]
+fn channel_id() {
+    let (_instance, mut nvim) = nvim!();
+    let channel = nvim.channel_id().unwrap();
+    assert_eq!(
+        nvim.eval("nvim_get_chan_info(0).id").unwrap(),
+        Value::from(channel)
+    );
+    assert_eq!(nvim.api_info().unwrap().channel, channel);
+}
+
+#[test]
+fn announce_client_info() {
+    let (_instance, mut nvim) = nvim!();
+    let mut router = HandlerRouter::new();
+    router
+        .on_request("rsnvim_add", |(a, b): (i64, i64), _nvim| Ok(a + b))
+        .on_notification("rsnvim_ping", |_: Vec<Value>, _nvim| Ok(()));
+    let info = ClientInfoBuilder::new("rsnvim-test")
+        .version(1, 2, 3)
+        .client_type(ClientType::Host)
+        .methods_from(&router)
+        .attribute("license", "MIT");
+    nvim.announce(&info).unwrap();
+
+    let client = nvim.eval("nvim_get_chan_info(0).client").unwrap();
+    assert_eq!(client["name"], Value::from("rsnvim-test"));
+    assert_eq!(client["type"], Value::from("host"));
+    assert_eq!(client["version"]["minor"], Value::from(2));
+    assert_eq!(client["attributes"]["license"], Value::from("MIT"));
+    assert!(client["methods"]["rsnvim_add"].is_map());
+    assert_eq!(client["methods"]["rsnvim_ping"]["async"], Value::from(true));
+}
+
+#[test]
+fn error_sources() {
+    use std::error::Error as _;
+
+    // Nothing listens on a port just released
+    let addr = {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().to_string()
+    };
+    let error = Session::from_tcp(&addr).err().unwrap();
+    match &error {
+        Error::IoError(io) => assert_eq!(io.kind(), std::io::ErrorKind::ConnectionRefused),
+        other => panic!("Expected an I/O error but received {:?}", other),
+    }
+    assert!(error.is_disconnected() && !error.is_timeout());
+
+    // The original error's source is kept
+    let truncated = [0xcd, 0x01];
+    let error = Error::from(rmpv::decode::read_value(&mut &truncated[..]).unwrap_err());
+    assert!(matches!(error, Error::MsgpackDecodeError(_)));
+    let source = error.source().unwrap();
+    assert_eq!(
+        source.downcast_ref::<std::io::Error>().unwrap().kind(),
+        std::io::ErrorKind::UnexpectedEof
+    );
+    let lines = Report::new("Decoding failed", &error).lines();
+    assert_eq!(lines[1..3], ["", "Caused by:"]);
+
+    let error = Error::from(std::io::Error::from(std::io::ErrorKind::TimedOut));
+    assert!(error.is_timeout() && !error.is_disconnected());
+    assert!(Error::TimeoutError("No response".to_string()).is_timeout());
+    assert!(Error::ConnectionError("Closed".to_string()).is_disconnected());
+    assert!(!Error::RpcError("E492".to_string()).is_disconnected());
+
+    // Composes with error handling libraries, which need these bounds
+    let boxed: Box<dyn std::error::Error + Send + Sync + 'static> = Box::new(error);
+    assert!(boxed.downcast_ref::<Error>().unwrap().is_timeout());
+}
+
+#[test]
+fn error_report() {
+    let (_instance, mut nvim) = nvim!();
+    let error = Error::TimeoutError("No response".to_string());
+    let report = Report::new("Request failed", &error).suggestion("Try again");
+    let lines = report.lines();
+    assert_eq!(lines[0], "No response");
+    assert!(lines.contains(&"  - Try again".to_string()));
+
+    let window = show_error(&mut nvim, "Request failed", &error).unwrap();
+    let config = window.get_config().unwrap().unwrap();
+    assert!(config
+        .iter()
+        .any(|(k, v)| k.as_str() == Some("relative") && v.as_str() == Some("editor")));
+    let text = nvim
+        .exec_lua("return vim.api.nvim_buf_get_lines(0, 0, -1, false)", vec![])
+        .unwrap();
+    assert_eq!(text[0], Value::from("No response"));
+    nvim.input("q").unwrap();
+    thread::sleep(Duration::from_millis(100));
+    assert!(!window.is_valid().unwrap());
+
+    report.to_quickfix(&mut nvim).unwrap();
+    let title = nvim.eval("getqflist({'title': 1}).title").unwrap();
+    assert_eq!(title, Value::from("Request failed"));
+}
+
+#[test]
+fn decorations() {
+    let (_instance, nvim) = nvim!();
+    let ns = nvim.create_namespace("rsnvim_decorations").unwrap();
+    let mut buffer = nvim.get_current_buf().unwrap();
+    buffer
+        .set_lines(0, -1, true, vec!["first", "second", "third"])
+        .unwrap();
+
+    let text_id = buffer
+        .set_virtual_text(ns, 0, &[("hint", "Comment")])
+        .unwrap();
+    let region = HighlightRange::new("Search", (1, 2), (2, 3)).priority(200);
+    let region_id = buffer.highlight_region(ns, &region).unwrap();
+    let line_id = buffer
+        .highlight_region(ns, &HighlightRange::line("Visual", 2))
+        .unwrap();
+
+    let extmarks = buffer.extmarks(ns).unwrap();
+    assert_eq!(extmarks.len(), 3);
+    assert_eq!(extmarks[0].id, text_id);
+    assert_eq!(
+        extmarks[0].virt_text,
+        [("hint".to_string(), "Comment".to_string())]
+    );
+    assert_eq!(extmarks[1].id, region_id);
+    assert_eq!((extmarks[1].row, extmarks[1].col), (1, 2));
+    assert_eq!(extmarks[1].end, Some(Point::new(2, 3)));
+    assert_eq!(extmarks[1].hl_group.as_deref(), Some("Search"));
+    assert_eq!(extmarks[2].id, line_id);
+    assert_eq!(extmarks[2].end, Some(Point::new(3, 0)));
+}
+
+#[test]
+fn edit_file() {
+    let (_instance, mut nvim) = nvim!();
+    let dir = std::env::temp_dir().join(format!("rsnvim-edit-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("dos file #1.txt");
+    std::fs::write(&path, "one\r\ntwo\r\n").unwrap();
+
+    let mut buffer = nvim.edit_file(&path, EditOpts::new()).unwrap();
+    assert_eq!(buffer, nvim.get_current_buf().unwrap());
+    assert_eq!(buffer.get_lines(0, -1, true).unwrap(), ["one", "two"]);
+    let info = buffer.file_info().unwrap();
+    assert_eq!(info.format, FileFormat::Dos);
+    assert_eq!(info.encoding, "utf-8");
+    assert!(!info.bom);
+
+    let forced = nvim
+        .edit_file(&path, EditOpts::new().force().format(FileFormat::Unix))
+        .unwrap();
+    assert_eq!(forced.clone().get_lines(0, 1, true).unwrap(), ["one\r"]);
+
+    let mut scratch = nvim.create_buf(true, false).unwrap();
+    let other = dir.join("other.txt");
+    std::fs::write(&other, "loaded\n").unwrap();
+    scratch.load_file(&other).unwrap();
+    assert_eq!(scratch.get_lines(0, -1, true).unwrap(), ["loaded"]);
+    assert_eq!(scratch.file_info().unwrap().format, FileFormat::Unix);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn workspace_edit() {
+    let (_instance, nvim) = nvim!();
+    // Overlapping edits are rejected before anything is sent
+    let overlapping = WorkspaceEdit::new().edit(
+        "unused.txt",
+        vec![
+            TextEdit::new(Range::new((0, 0), (0, 4)), "a"),
+            TextEdit::new(Range::new((0, 2), (0, 6)), "b"),
+        ],
+    );
+    assert!(matches!(
+        overlapping.apply(&nvim),
+        Err(Error::InvalidArgument(_))
+    ));
+
+    let dir = std::env::temp_dir().join(format!("rsnvim-workspace-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let lib = dir.join("lib.rs");
+    std::fs::write(&lib, "fn parse() {}\n").unwrap();
+    let main = dir.join("main.rs");
+    std::fs::write(&main, "use lib::parse;\nfn main() {\n    parse();\n}\n").unwrap();
+    let scratch = nvim.create_buf(false, true).unwrap();
+    scratch.set_lines(0, -1, true, ["one two"]).unwrap();
+
+    // A bad edit to one target leaves every target unchanged
+    let results = WorkspaceEdit::new()
+        .edit(
+            lib.as_path(),
+            vec![TextEdit::new(Range::new((0, 3), (0, 8)), "parse_config")],
+        )
+        .edit(&scratch, vec![TextEdit::insert((5, 0), "past the end")])
+        .apply(&nvim)
+        .unwrap();
+    assert!(results.iter().all(|result| !result.applied));
+    assert!(results[0].is_ok());
+    assert!(results[1].error.as_deref().unwrap().contains("Row 5"));
+    assert_eq!(scratch.get_lines(0, -1, true).unwrap(), ["one two"]);
+
+    // Edits are given against the original text, in any order
+    let results = WorkspaceEdit::new()
+        .edit(
+            lib.as_path(),
+            vec![TextEdit::new(Range::new((0, 3), (0, 8)), "parse_config")],
+        )
+        .edit(
+            main.as_path(),
+            vec![
+                TextEdit::new(Range::new((2, 4), (2, 9)), "parse_config"),
+                TextEdit::new(Range::new((0, 9), (0, 14)), "parse_config"),
+                TextEdit::insert((0, 0), "// renamed\n"),
+            ],
+        )
+        .edit(
+            &scratch,
+            vec![
+                TextEdit::delete(Range::new((0, 3), (0, 7))),
+                TextEdit::insert((0, 7), "!"),
+                TextEdit::insert((0, 0), "1"),
+                TextEdit::insert((0, 0), "2"),
+            ],
+        )
+        .save(true)
+        .apply(&nvim)
+        .unwrap();
+    assert!(results[0].is_ok() && results[0].applied && results[0].saved);
+    assert!(results[1].is_ok() && results[1].saved);
+    // Scratch buffers have no file to save to
+    assert!(results[2].applied && !results[2].saved);
+    assert!(results[2].error.is_some());
+    assert_eq!(results[2].buffer.as_ref(), Some(&scratch));
+    assert_eq!(scratch.get_lines(0, -1, true).unwrap(), ["12one!"]);
+    assert_eq!(
+        std::fs::read_to_string(&lib).unwrap(),
+        "fn parse_config() {}\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(&main).unwrap(),
+        "// renamed\nuse lib::parse_config;\nfn main() {\n    parse_config();\n}\n"
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn pipelined_calls() {
+    let (started, wait_started) = mpsc::channel();
+    let (release, wait_release) = mpsc::channel::<()>();
+    let (started, wait_release) = (Mutex::new(started), Mutex::new(wait_release));
+    let mut router = HandlerRouter::new();
+    router.on_request("slow", move |_: Vec<Value>, _nvim| {
+        started.lock().unwrap().send(()).unwrap();
+        let released = wait_release
+            .lock()
+            .unwrap()
+            .recv_timeout(Duration::from_millis(800))
+            .is_ok();
+        Ok(released)
+    });
+    let (requests, _) = router.into_handlers();
+    let (_instance, mut nvim) = nvim!(requests);
+    let channel = nvim.channel_id().unwrap();
+
+    let other = nvim.clone();
+    let slow = thread::spawn(move || other.eval(format!("rpcrequest({}, 'slow')", channel)));
+    wait_started.recv_timeout(Duration::from_secs(1)).unwrap();
+    // Answered while the other thread is still waiting for its response
+    assert_eq!(nvim.eval("1 + 1").unwrap(), Value::from(2));
+    release.send(()).unwrap();
+    assert_eq!(slow.join().unwrap().unwrap(), Value::from(true));
+}
+
+#[test]
+fn overloaded_requests() {
+    let (started, wait_started) = mpsc::channel();
+    let (release, wait_release) = mpsc::channel::<()>();
+    let (started, wait_release) = (Mutex::new(started), Mutex::new(wait_release));
+    let mut router = HandlerRouter::new();
+    router.on_request("slow", move |_: Vec<Value>, _nvim| {
+        started.lock().unwrap().send(()).unwrap();
+        let released = wait_release
+            .lock()
+            .unwrap()
+            .recv_timeout(Duration::from_millis(800))
+            .is_ok();
+        Ok(released)
+    });
+    let (requests, _) = router.into_handlers();
+    let (_instance, mut nvim) = nvim!(requests);
+    nvim.set_max_pending_requests(1);
+    let channel = nvim.channel_id().unwrap();
+
+    let other = nvim.clone();
+    let slow = thread::spawn(move || other.eval(format!("rpcrequest({}, 'slow')", channel)));
+    wait_started.recv_timeout(Duration::from_secs(1)).unwrap();
+    // Rejected without waiting for the first request to be handled
+    let rejected = nvim
+        .exec_lua(
+            "return { pcall(vim.rpcrequest, ...) }",
+            vec![Value::from(channel), Value::from("slow")],
+        )
+        .unwrap();
+    assert_eq!(rejected[0], Value::from(false));
+    assert!(rejected[1].as_str().unwrap().contains("overloaded"));
+    release.send(()).unwrap();
+    assert_eq!(slow.join().unwrap().unwrap(), Value::from(true));
+}
+
+/// Wait for a redraw event matching `find`, recording highlights in `table`
+fn next_redraw<T>(
+    redraws: &mpsc::Receiver<Vec<Value>>,
+    table: &mut HlAttrTable,
+    find: impl Fn(RedrawEvent) -> Option<T>,
+) -> T {
+    loop {
+        let params = redraws.recv_timeout(Duration::from_secs(1)).unwrap();
+        for event in RedrawEvent::parse(params).unwrap() {
+            table.update(&event);
+            if let Some(found) = find(event) {
+                return found;
+            }
+        }
+    }
+}
+
+#[test]
+fn redraw_events() {
+    let batch = vec![Value::Array(vec![
+        Value::from("cmdline_pos"),
+        Value::Array(vec![Value::from(1), Value::from(1)]),
+        Value::Array(vec![Value::from(2), Value::from(1)]),
+    ])];
+    let events = RedrawEvent::parse(batch).unwrap();
+    assert_eq!(events.len(), 2);
+    assert!(matches!(events[1], RedrawEvent::CmdlinePos(pos) if pos.pos == 2));
+
+    let (_instance, mut nvim) = nvim!();
+    let redraws = nvim.subscribe_notifications("redraw");
+    let options = UiOptions::new()
+        .rgb(true)
+        .ext_linegrid(true)
+        .ext_hlstate(true)
+        .ext_messages(true);
+    let ui = nvim.attach_ui(80, 24, options).unwrap();
+    let mut table = HlAttrTable::new();
+
+    nvim.command("echohl ErrorMsg | echomsg 'oops' | echohl None")
+        .unwrap();
+    let msg = next_redraw(&redraws, &mut table, |event| match event {
+        RedrawEvent::MsgShow(msg) => Some(msg),
+        _ => None,
+    });
+    assert_eq!(msg.kind, "echomsg");
+    let resolved = table.resolve(&msg.content);
+    assert_eq!(resolved.len(), 1);
+    assert_eq!(resolved[0].0, "oops");
+    assert!(resolved[0]
+        .1
+        .unwrap()
+        .groups
+        .contains(&"ErrorMsg".to_string()));
+
+    nvim.input(":abc").unwrap();
+    let cmdline = next_redraw(&redraws, &mut table, |event| match event {
+        RedrawEvent::CmdlineShow(cmdline) if cmdline.pos == 3 => Some(cmdline),
+        _ => None,
+    });
+    assert_eq!(cmdline.firstc, ":");
+    assert_eq!(
+        cmdline
+            .content
+            .iter()
+            .map(|c| c.text.as_str())
+            .collect::<String>(),
+        "abc"
+    );
+    nvim.input("<Esc>").unwrap();
+    ui.detach().unwrap();
+}
+
+#[test]
+fn lua_bridge() {
+    let mut bridge = LuaBridge::new("rsnvim_test");
+    bridge
+        .function("add", |(a, b): (i64, i64), _nvim| Ok(a + b))
+        .function("current_line", |(): (), nvim| nvim.get_current_line());
+    assert_eq!(bridge.method("add"), "rsnvim_test.add");
+    let mut router = HandlerRouter::new();
+    bridge.register(&mut router);
+    let (requests, _) = router.into_handlers();
+    let (_instance, mut nvim) = nvim!(requests);
+    bridge.install(&mut nvim).unwrap();
+
+    let sum: i64 = rsnvim::lua!(nvim, "return require('rsnvim_test').add(...)", 1, 2).unwrap();
+    assert_eq!(sum, 3);
+    nvim.set_current_line("from Rust").unwrap();
+    let line: String = rsnvim::lua!(nvim, "return require('rsnvim_test').current_line()").unwrap();
+    assert_eq!(line, "from Rust");
+    // Arguments which can't be decoded raise a Lua error
+    let failed: bool = rsnvim::lua!(
+        nvim,
+        "return not pcall(require('rsnvim_test').add, 'one', 2)"
+    )
+    .unwrap();
+    assert!(failed);
+}
+
+#[test]
+fn runtime_paths() {
+    let (_instance, mut nvim) = nvim!();
+    assert!(!nvim.runtime_paths().unwrap().is_empty());
+    let dir = std::env::temp_dir().join(format!("rsnvim-rtp,{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("colors")).unwrap();
+    std::fs::write(dir.join("colors/rsnvim_test.vim"), "").unwrap();
+
+    assert!(nvim.prepend_runtime_path(&dir).unwrap());
+    assert!(!nvim.prepend_runtime_path(&dir).unwrap());
+    assert!(!nvim.append_runtime_path(&dir).unwrap());
+    assert_eq!(nvim.runtime_paths().unwrap()[0], dir);
+    assert_eq!(
+        nvim.runtime_files("colors/rsnvim_test.vim", true).unwrap(),
+        [dir.join("colors/rsnvim_test.vim")]
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn split_client() {
+    let (_instance, addr) = match spawn() {
+        Some(spawned) => spawned,
+        None => {
+            eprintln!("RSNVIM_TEST_NVIM is not set, skipping");
+            return;
+        }
+    };
+    let stream = TcpStream::connect(&addr).unwrap();
+    let client = Client::new(stream.try_clone().unwrap(), stream);
+    let (mut reader, writer) = client.split().unwrap();
+    assert!(client.split().is_err());
+
+    let info = client.call("nvim_get_api_info", Vec::new()).unwrap();
+    assert!(reader.read().unwrap().is_none());
+    let channel = info.wait().unwrap()[0].as_i64().unwrap();
+
+    let ping = format!("rpcrequest({}, 'ping', 1)", channel);
+    let eval = client.call("nvim_eval", vec![Value::from(ping)]).unwrap();
+    match reader.read().unwrap() {
+        Some(RpcMessage::RpcRequest {
+            msgid,
+            method,
+            params,
+        }) => {
+            assert_eq!(method, "ping");
+            assert_eq!(params, [Value::from(1)]);
+            writer.respond(msgid, Ok(Value::from("pong"))).unwrap();
+        }
+        other => panic!("Expected a request, got {:?}", other),
+    }
+    assert!(reader.read().unwrap().is_none());
+    assert_eq!(eval.wait().unwrap(), Value::from("pong"));
+}
+
+#[test]
+fn value_inspection() {
+    let (_instance, mut nvim) = nvim!();
+    let buffer = nvim.get_current_buf().unwrap().convert();
+    assert_eq!(pretty(&buffer), "<buffer 1>");
+    let info = nvim.get_api_info().unwrap();
+    let version = get_path(&info[1], "version.api_level").unwrap();
+    assert_eq!(
+        version.as_u64(),
+        Some(nvim.api_info().unwrap().version.api_level)
+    );
+}
+
+#[test]
+fn build_info() {
+    let info = rsnvim::build_info();
+    assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(info.api_level, rsnvim::api::API_LEVEL);
+    assert!(!info.git_hash.is_empty());
+    // A UTC timestamp, e.g. 2024-06-01T12:00:00Z
+    assert_eq!(info.generated_at.len(), 20);
+    assert!(info.generated_at.ends_with('Z'));
+    let expected = format!("rsnvim {} (API level {},", info.version, info.api_level);
+    assert!(info.to_string().starts_with(&expected));
+}
+
+#[cfg(unix)]
+#[test]
+fn stream_corrupted() {
+    let mut frame = Vec::new();
+    rmpv::encode::write_value(&mut frame, &Value::from(5)).unwrap();
+    assert!(matches!(
+        rpc::decode_message(&frame),
+        Err(Error::StreamCorrupted(_))
+    ));
+    assert!(matches!(
+        rpc::decode_message(&[0xc1]),
+        Err(Error::StreamCorrupted(_))
+    ));
+
+    let (session, mut theirs) = common::socket_session();
+    let (sender, errors) = mpsc::channel();
+    session.on_error(move |error| {
+        let _ = sender.send(error);
+    });
+    session.start_event_loop(None, None);
+    let pending = session
+        .call_async("nvim_eval", vec![Value::from("1")])
+        .unwrap();
+    std::io::Write::write_all(&mut theirs, &[0xc1]).unwrap();
+    // Fails straight away rather than timing out
+    assert!(matches!(
+        pending.wait_timeout(Duration::from_millis(500)),
+        Err(Error::StreamCorrupted(_))
+    ));
+    assert!(matches!(
+        errors.recv_timeout(Duration::from_millis(500)),
+        Ok(Error::StreamCorrupted(_))
+    ));
+    assert!(!session.is_alive());
+}
+
+#[cfg(unix)]
+#[test]
+fn cancel_requests() {
+    let (session, mut theirs) = common::socket_session();
+    let (sender, errors) = mpsc::channel();
+    session.on_error(move |error| {
+        let _ = sender.send(error);
+    });
+    session.set_cancel_policy(CancelPolicy::Interrupt);
+    session.start_event_loop(None, None);
+
+    // The session fetches the API metadata once the event loop starts
+    let (api_info, _, _) = common::next_request(&mut theirs);
+    common::respond(
+        &mut theirs,
+        api_info,
+        common::rpc_error("Unavailable"),
+        Value::Nil,
+    );
+
+    let pending = session
+        .call_async("nvim_eval", vec![Value::from("slow")])
+        .unwrap();
+    let (msgid, method, _) = common::next_request(&mut theirs);
+    assert_eq!(method, "nvim_eval");
+    assert_eq!(session.stats().pending_calls, 1);
+    pending.cancel().unwrap();
+    assert_eq!(session.stats().pending_calls, 0);
+    assert_eq!(
+        rpc::decode(&mut theirs).unwrap(),
+        RpcMessage::RpcNotification {
+            method: "nvim_input".to_string(),
+            params: vec![Value::from("<C-c>")],
+        }
+    );
+
+    // The late response to the cancelled request isn't unexpected
+    common::respond(&mut theirs, msgid, Value::Nil, Value::Nil);
+    let pending = session.call_async("nvim_get_mode", vec![]).unwrap();
+    let (msgid, _, _) = common::next_request(&mut theirs);
+    common::respond(&mut theirs, msgid, Value::Nil, Value::from(1));
+    assert_eq!(
+        pending.wait_timeout(Duration::from_secs(1)).unwrap(),
+        Value::from(1)
+    );
+    assert!(errors.try_recv().is_err());
+    assert_eq!(session.stats().pending_calls, 0);
+    assert_eq!(session.metrics()["nvim_eval"].errors, 1);
+
+    // Timing out abandons the request without interrupting Neovim
+    session.set_cancel_policy(CancelPolicy::Abandon);
+    let pending = session
+        .call_async("nvim_eval", vec![Value::from("slow")])
+        .unwrap();
+    assert!(matches!(
+        pending.wait_timeout(Duration::from_millis(10)),
+        Err(Error::TimeoutError(_))
+    ));
+    assert_eq!(session.stats().pending_calls, 0);
+}
+
+#[cfg(unix)]
+#[test]
+fn redraw_batches() {
+    let (session, mut theirs) = common::socket_session();
+    let latest = session.subscribe_redraw(RedrawMode::DropStale);
+    let every = session.subscribe_redraw(RedrawMode::Every);
+    let done = session.subscribe_notifications("done");
+    session.start_event_loop(None, None);
+
+    let event =
+        |name: &str, args: Vec<Value>| Value::from(vec![Value::from(name), Value::from(args)]);
+    for i in 0..3 {
+        let resize = event(
+            "grid_resize",
+            vec![Value::from(1), Value::from(80 + i), Value::from(24)],
+        );
+        let line = event("grid_line", vec![Value::from(1), Value::from(i)]);
+        // A batch may span several notifications
+        common::notify(&mut theirs, "redraw", vec![resize]);
+        common::notify(&mut theirs, "redraw", vec![line, event("flush", vec![])]);
+    }
+    // Incomplete batches aren't received
+    let clear = event("grid_clear", vec![Value::from(1)]);
+    common::notify(&mut theirs, "redraw", vec![clear]);
+    common::notify(&mut theirs, "done", vec![]);
+    done.recv_timeout(Duration::from_secs(1)).unwrap();
+
+    assert_eq!(every.len(), 3);
+    let first = every.try_recv().unwrap();
+    assert_eq!(first.dropped, 0);
+    assert_eq!(first.params.len(), 3);
+    assert_eq!(every.len(), 2);
+
+    assert_eq!(latest.len(), 1);
+    let batch = latest.try_recv().unwrap();
+    assert_eq!(batch.dropped, 2);
+    let names: Vec<_> = batch
+        .events()
+        .unwrap()
+        .into_iter()
+        .map(|event| match event {
+            RedrawEvent::Other { name, .. } => name,
+            other => panic!("Unexpected event {:?}", other),
+        })
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "grid_resize",
+            "grid_resize",
+            "grid_resize",
+            "grid_line",
+            "flush"
+        ]
+    );
+    assert!(latest.try_recv().is_none());
+}
+
+#[cfg(unix)]
+#[test]
+fn call_metrics() {
+    let (session, mut theirs) = common::socket_session();
+    let (sender, calls) = mpsc::channel();
+    session.on_call(move |call| {
+        let _ = sender.send(call.clone());
+    });
+    session.set_slow_call_threshold(Duration::from_millis(50));
+    session.start_event_loop(None, None);
+
+    let mut respond = |delay: Duration, error: Value| {
+        let (msgid, _, _) = common::next_request(&mut theirs);
+        thread::sleep(delay);
+        common::respond(&mut theirs, msgid, error, Value::Nil);
+    };
+
+    // The session fetches the API metadata once the event loop starts
+    respond(Duration::ZERO, common::rpc_error("Unavailable"));
+    let call = calls.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(call.method, "nvim_get_api_info");
+    assert_eq!(call.error.as_deref(), Some("Unavailable"));
+
+    let pending = session
+        .call_async("nvim_eval", vec![Value::from("1")])
+        .unwrap();
+    respond(Duration::ZERO, Value::Nil);
+    pending.wait_timeout(Duration::from_secs(1)).unwrap();
+    let pending = session
+        .call_async("nvim_eval", vec![Value::from("2")])
+        .unwrap();
+    respond(Duration::from_millis(100), Value::Nil);
+    pending.wait_timeout(Duration::from_secs(1)).unwrap();
+    let pending = session
+        .call_async("nvim_command", vec![Value::from("bad")])
+        .unwrap();
+    respond(
+        Duration::ZERO,
+        common::rpc_error("E492: Not an editor command"),
+    );
+    assert!(pending.wait_timeout(Duration::from_secs(1)).is_err());
+
+    let call = calls.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(
+        (call.method.as_str(), call.slow, call.error),
+        ("nvim_eval", false, None)
+    );
+    let call = calls.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert!(call.slow && call.latency >= Duration::from_millis(100));
+    let call = calls.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(call.method, "nvim_command");
+    assert_eq!(call.error.as_deref(), Some("E492: Not an editor command"));
+
+    // Calls outstanding when the connection closes count as failed
+    let pending = session.call_async("nvim_get_mode", vec![]).unwrap();
+    drop(theirs);
+    assert!(pending.wait_timeout(Duration::from_secs(1)).is_err());
+
+    let metrics = session.metrics();
+    let eval = &metrics["nvim_eval"];
+    assert_eq!((eval.calls, eval.errors, eval.slow), (2, 0, 1));
+    assert!(eval.max_latency >= Duration::from_millis(100));
+    assert!(eval.mean_latency() <= eval.max_latency);
+    assert_eq!(metrics["nvim_command"].error_rate(), 1.0);
+    assert_eq!(metrics["nvim_get_mode"].errors, 1);
+
+    session.reset_metrics();
+    assert!(session.metrics().is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn read_only_session() {
+    let (session, _theirs) = common::socket_session();
+    let monitor = ReadOnlySession::new(session).allow("nvim_buf_attach");
+    let allowed = |method: &str, args: Vec<Value>| monitor.check(method, &args).is_ok();
+    let eval = |expr: &str| allowed("nvim_eval", vec![Value::from(expr)]);
+
+    assert!(allowed("nvim_get_mode", vec![]));
+    assert!(allowed("nvim_buf_get_lines", vec![]));
+    assert!(allowed("nvim_buf_attach", vec![]));
+    assert!(!allowed("nvim_buf_set_lines", vec![]));
+    assert!(!allowed("nvim_command", vec![Value::from("quit!")]));
+    assert!(!allowed("nvim_get_hl_id_by_name", vec![Value::from("Foo")]));
+    assert!(matches!(
+        monitor.check("nvim_exec_lua", &[]),
+        Err(Error::ReadOnly(_))
+    ));
+
+    assert!(eval("line('.') + len(getline(1, '$'))"));
+    assert!(eval("winsaveview().topline"));
+    assert!(eval("bufnr ('%')"));
+    assert!(eval("'system(1)' . \"execute(\\\"x\\\")\" . 'it''s'"));
+    assert!(eval("(1 + 2) * &tabstop"));
+    assert!(!eval("system('rm -rf ~')"));
+    assert!(!eval("getline(1)->setline(2)"));
+    assert!(!eval("{-> execute('quit')}()"));
+    assert!(!eval("g:Callback()"));
+    assert!(!eval("v:lua.require('x').run()"));
+    assert!(allowed(
+        "nvim_call_function",
+        vec![Value::from("getline"), Value::from(vec![Value::from(1)])]
+    ));
+    assert!(!allowed(
+        "nvim_call_function",
+        vec![
+            Value::from("delete"),
+            Value::from(vec![Value::from("file")])
+        ]
+    ));
+
+    // Batches are only allowed if every call is
+    let batch = |calls: Vec<(&str, Vec<Value>)>| {
+        let calls: Vec<Value> = calls
+            .into_iter()
+            .map(|(method, args)| Value::from(vec![Value::from(method), Value::from(args)]))
+            .collect();
+        allowed("nvim_call_atomic", vec![Value::from(calls)])
+    };
+    assert!(batch(vec![
+        ("nvim_get_mode", vec![]),
+        ("nvim_list_bufs", vec![])
+    ]));
+    assert!(!batch(vec![
+        ("nvim_get_mode", vec![]),
+        ("nvim_command", vec![Value::from("quit!")])
+    ]));
+
+    let (_instance, addr) = match spawn() {
+        Some(spawned) => spawned,
+        None => {
+            eprintln!("RSNVIM_TEST_NVIM is not set, skipping");
+            return;
+        }
+    };
+    let session = Session::from_tcp(&addr).unwrap();
+    session.start_event_loop(None, None);
+    let monitor = ReadOnlySession::new(session.clone());
+    assert_eq!(monitor.eval("1 + 1").unwrap(), Value::from(2));
+    let mode: Dict = monitor.call_typed("nvim_get_mode", vec![]).unwrap();
+    assert_eq!(mode.get_str("mode"), Some("n"));
+    assert!(monitor
+        .call("nvim_command", vec![Value::from("let g:x = 1")])
+        .is_err());
+    let exists = session.call("nvim_eval", vec![Value::from("exists('g:x')")]);
+    assert_eq!(exists.unwrap(), Value::from(0));
+}
+
+#[cfg(unix)]
+#[test]
+fn concurrent_clones() {
+    let (session, mut theirs) = common::socket_session();
+    session.start_event_loop(None, None);
+    let nvim = Nvim::from_session(session);
+
+    let callers: Vec<_> = (0..8)
+        .map(|i| {
+            let nvim = nvim.clone();
+            thread::spawn(move || {
+                let name = format!("var{}", i);
+                assert_eq!(nvim.get_var(name.as_str()).unwrap(), Value::from(name));
+            })
+        })
+        .collect();
+
+    // Every call is in flight at once, and is answered in reverse order
+    let mut requests = Vec::new();
+    while requests.len() < callers.len() {
+        let (msgid, method, params) = common::next_request(&mut theirs);
+        // Fetched in the background by the event loop
+        if method == "nvim_get_api_info" {
+            common::respond(
+                &mut theirs,
+                msgid,
+                common::rpc_error("Unavailable"),
+                Value::Nil,
+            );
+            continue;
+        }
+        requests.push((msgid, params[0].clone()));
+    }
+    for (msgid, result) in requests.into_iter().rev() {
+        common::respond(&mut theirs, msgid, Value::Nil, result);
+    }
+    for caller in callers {
+        caller.join().unwrap();
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn graceful_shutdown() {
+    let (session, mut theirs) = common::socket_session();
+    session.start_event_loop(None, None);
+
+    // Answers everything but `nvim_eval` until the cleanup hook's call,
+    // recording the methods called
+    let server = thread::spawn(move || {
+        let mut methods = Vec::new();
+        while methods.last().map(String::as_str) != Some("nvim_command") {
+            let (msgid, method, _) = common::next_request(&mut theirs);
+            methods.push(method.clone());
+            if method != "nvim_eval" {
+                common::respond(&mut theirs, msgid, Value::Nil, Value::Nil);
+            }
+        }
+        methods
+    });
+
+    let cleanup = session.clone();
+    session.on_shutdown(move || {
+        cleanup
+            .call("nvim_command", vec![Value::from("echo 'bye'")])
+            .map(drop)
+    });
+    let pending = session
+        .call_async("nvim_eval", vec![Value::from("getchar()")])
+        .unwrap();
+
+    session.shutdown(Duration::from_millis(50)).unwrap();
+    assert!(matches!(pending.wait(), Err(Error::ConnectionError(_))));
+    assert!(matches!(
+        session.call("nvim_get_mode", vec![]),
+        Err(Error::ConnectionError(_))
+    ));
+    assert!(session
+        .notify("nvim_input", vec![Value::from("i")])
+        .is_err());
+    // Shutting down again does nothing
+    session.shutdown(Duration::ZERO).unwrap();
+
+    let methods = server.join().unwrap();
+    assert!(methods.ends_with(&["nvim_eval".to_string(), "nvim_command".to_string()]));
+}
+
+#[cfg(unix)]
+#[test]
+fn requests_follow_notifications() {
+    let (session, theirs) = common::socket_session();
+    session.start_event_loop(None, None);
+
+    let (sender, received) = mpsc::channel();
+    thread::spawn(move || {
+        let mut theirs = theirs;
+        while let Ok(message) = rpc::decode(&mut theirs) {
+            match message {
+                RpcMessage::RpcNotification { params, .. } => {
+                    sender.send(params[0].clone()).unwrap();
+                }
+                RpcMessage::RpcRequest { msgid, method, .. } => {
+                    if method == "nvim_eval" {
+                        sender.send(Value::from("request")).unwrap();
+                    }
+                    common::respond(&mut theirs, msgid, Value::Nil, Value::Nil);
+                }
+                _ => (),
+            }
+        }
+    });
+
+    for i in 0..500 {
+        session.notify("nvim_input", vec![Value::from(i)]).unwrap();
+    }
+    session.call("nvim_eval", vec![Value::from("1")]).unwrap();
+    let order: Vec<Value> = received.iter().take(501).collect();
+    assert_eq!(
+        order[..500],
+        (0..500).map(Value::from).collect::<Vec<_>>()[..]
+    );
+    assert_eq!(order[500], Value::from("request"));
+}
+
+#[cfg(unix)]
+#[test]
+fn dispatch_write_errors() {
+    let (session, mut theirs) = common::socket_session();
+    let (sender, disconnected) = mpsc::channel();
+    session.on_disconnect(move || {
+        let _ = sender.send(());
+    });
+    session.start_event_loop(None, None);
+
+    // Neovim stops reading, so answering its request fails
+    theirs.shutdown(std::net::Shutdown::Read).unwrap();
+    let request = RpcMessage::RpcRequest {
+        msgid: 1,
+        method: "rsnvim_unknown".to_string(),
+        params: vec![],
+    };
+    rpc::encode(&mut theirs, request).unwrap();
+    // Once the dispatch thread has exited, the next message disconnects
+    let deadline = std::time::Instant::now() + Duration::from_secs(5);
+    theirs
+        .set_write_timeout(Some(Duration::from_millis(10)))
+        .unwrap();
+    while disconnected.try_recv().is_err() {
+        assert!(std::time::Instant::now() < deadline, "Never disconnected");
+        let ping = RpcMessage::RpcNotification {
+            method: "ping".to_string(),
+            params: vec![],
+        };
+        // Nothing reads these once the read thread has exited
+        let _ = rpc::encode(&mut theirs, ping);
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(!session.is_alive());
+}
+
+#[cfg(unix)]
+#[test]
+fn wait_for_notification() {
+    let (session, mut theirs) = common::socket_session();
+    session.start_event_loop(None, None);
+
+    let written = session.expect_notification("written");
+    for (method, file) in [
+        ("read", "a.txt"),
+        ("written", "b.txt"),
+        ("written", "a.txt"),
+    ] {
+        common::notify(&mut theirs, method, vec![Value::from(file)]);
+    }
+    let params = written
+        .wait(
+            |params| params[0].as_str() == Some("a.txt"),
+            Duration::from_secs(5),
+        )
+        .unwrap();
+    assert_eq!(params, vec![Value::from("a.txt")]);
+
+    // The skipped notification was consumed, and nothing else arrives
+    let start = std::time::Instant::now();
+    assert!(matches!(
+        written.wait(|_| true, Duration::from_millis(50)),
+        Err(Error::TimeoutError(_))
+    ));
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    assert!(matches!(
+        session.wait_for_notification("written", |_| true, Duration::ZERO),
+        Err(Error::TimeoutError(_))
+    ));
+}
+
+#[cfg(unix)]
+#[test]
+fn typed_handle_lists() {
+    let (session, theirs) = common::socket_session();
+    session.start_event_loop(None, None);
+    let nvim = Nvim::from_session(session);
+
+    // Handles are ext types holding their ID: 0 for buffers, 1 for windows
+    // and 2 for tabpages
+    let handle = |ext: i8, id: u8| Value::Ext(ext, vec![id]);
+    common::serve(theirs, move |method, params| {
+        Ok(match method {
+            "nvim_list_bufs" => Value::Array(vec![handle(0, 1), handle(0, 3)]),
+            "nvim_list_wins" => Value::Array(vec![handle(1, 5)]),
+            "nvim_list_tabpages" => Value::Array(vec![handle(2, 1), handle(2, 2)]),
+            "nvim_tabpage_list_wins" => Value::Array(vec![handle(1, 7)]),
+            "nvim_buf_get_name" => Value::from(format!("{}", params[0])),
+            _ => Value::Nil,
+        })
+    });
+
+    let buffers = nvim.list_bufs().unwrap();
+    assert_eq!(
+        buffers.iter().map(|buffer| buffer.id()).collect::<Vec<_>>(),
+        vec![1, 3]
+    );
+    // The handles share the session, and are sent back as ext types
+    assert_eq!(buffers[1].get_name().unwrap(), format!("{}", handle(0, 3)));
+    assert_eq!(nvim.list_wins().unwrap()[0].id(), 5);
+    let tabpages = nvim.list_tabpages().unwrap();
+    assert_eq!(tabpages.len(), 2);
+    assert_eq!(tabpages[1].list_wins().unwrap()[0].id(), 7);
+}
+
+#[cfg(unix)]
+#[test]
+fn binary_safe_lines() {
+    let latin1 = NvimString::from_bytes(b"caf\xe9".to_vec());
+    assert!(!latin1.is_utf8());
+    assert!(latin1.to_str().is_err());
+    assert_eq!(latin1.to_string_lossy(), "caf\u{fffd}");
+    assert_eq!(NvimString::from("café").into_string().unwrap(), "café");
+    assert_eq!(latin1.convert(), Value::Binary(b"caf\xe9".to_vec()));
+    assert_eq!(NvimString::from("café").convert(), Value::from("café"));
+
+    let (session, theirs) = common::socket_session();
+    session.start_event_loop(None, None);
+    let buffer = Buffer::new(1, Arc::new(Mutex::new(session)));
+
+    // Neovim sends lines as strings, whatever bytes they hold
+    let line = rmpv::decode::read_value(&mut &[0xa4, b'c', b'a', b'f', 0xe9][..]).unwrap();
+    let (sender, params) = mpsc::channel();
+    common::serve(theirs, move |method, params| match method {
+        "nvim_buf_get_lines" => Ok(Value::Array(vec![line.clone()])),
+        _ => {
+            sender.send(params).unwrap();
+            Ok(Value::Nil)
+        }
+    });
+
+    assert!(matches!(
+        buffer.get_lines(0, -1, false),
+        Err(Error::DecodingError(_))
+    ));
+    let lines = buffer.get_lines_raw(0, -1, false).unwrap();
+    assert_eq!(lines, vec![latin1.clone()]);
+
+    buffer.set_lines_raw(0, -1, false, lines).unwrap();
+    assert_eq!(
+        params.recv().unwrap()[4],
+        Value::Array(vec![Value::Binary(b"caf\xe9".to_vec())])
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn deprecation_warnings() {
+    let (session, mut theirs) = common::socket_session();
+    let (sender, warnings) = mpsc::channel();
+    session.on_deprecated(move |deprecation| {
+        let _ = sender.send(deprecation.clone());
+    });
+    session.start_event_loop(None, None);
+
+    let respond = |theirs: &mut std::os::unix::net::UnixStream, result: Value| {
+        let (msgid, method, _) = common::next_request(theirs);
+        common::respond(theirs, msgid, Value::Nil, result);
+        method
+    };
+
+    // Deprecated by the server, though not by these bindings
+    let function = |name: &str, deprecated_since: Option<u64>| {
+        let mut entries = vec![
+            (Value::from("name"), Value::from(name)),
+            (Value::from("since"), Value::from(1)),
+            (Value::from("parameters"), Value::Array(vec![])),
+            (Value::from("return_type"), Value::from("Object")),
+        ];
+        if let Some(level) = deprecated_since {
+            entries.push((Value::from("deprecated_since"), Value::from(level)));
+        }
+        Value::Map(entries)
+    };
+    let version = Value::Map(
+        ["major", "minor", "patch", "api_level", "api_compatible"]
+            .iter()
+            .map(|key| (Value::from(*key), Value::from(0)))
+            .collect(),
+    );
+    let metadata = Value::Map(vec![
+        (Value::from("version"), version),
+        (
+            Value::from("functions"),
+            Value::Array(vec![
+                function("nvim_get_mode", None),
+                function("nvim_exec", Some(11)),
+                function("nvim_frobnicate", Some(12)),
+            ]),
+        ),
+    ]);
+    let method = respond(&mut theirs, Value::Array(vec![Value::from(1), metadata]));
+    assert_eq!(method, "nvim_get_api_info");
+    assert_eq!(session.channel_id().unwrap(), 1);
+
+    // Each deprecated function is warned about once
+    for method in ["nvim_get_mode", "nvim_exec", "nvim_exec", "nvim_frobnicate"] {
+        let pending = session.call_async(method, vec![]).unwrap();
+        assert_eq!(respond(&mut theirs, Value::Nil), method);
+        pending.wait_timeout(Duration::from_secs(1)).unwrap();
+    }
+    let warning = warnings.try_recv().unwrap();
+    assert_eq!(warning.method, "nvim_exec");
+    assert_eq!(warning.deprecated_since, 11);
+    assert_eq!(warning.replacement, Some("nvim_exec2"));
+    assert_eq!(
+        warning.to_string(),
+        "nvim_exec is deprecated since API level 11, use nvim_exec2 instead"
+    );
+    let warning = warnings.try_recv().unwrap();
+    assert_eq!(
+        (warning.method.as_str(), warning.replacement),
+        ("nvim_frobnicate", None)
+    );
+    assert!(warnings.try_recv().is_err());
+
+    // Denied calls aren't sent
+    session.set_deprecation_policy(DeprecationPolicy::Deny);
+    assert!(matches!(
+        session.call_async("nvim_exec", vec![]),
+        Err(Error::UnsupportedVersion(_))
+    ));
+    let pending = session.call_async("nvim_get_mode", vec![]).unwrap();
+    assert_eq!(respond(&mut theirs, Value::Nil), "nvim_get_mode");
+    pending.wait_timeout(Duration::from_secs(1)).unwrap();
+}
+
+#[cfg(all(unix, debug_assertions))]
+#[test]
+fn early_validation() {
+    let (session, mut theirs) = common::socket_session();
+    session.start_event_loop(None, None);
+
+    // The metadata arrives after the first call is made
+    let server = thread::spawn(move || {
+        let (msgid, method, _) = common::next_request(&mut theirs);
+        assert_eq!(method, "nvim_get_api_info");
+        thread::sleep(Duration::from_millis(50));
+        let function = Value::Map(vec![
+            (Value::from("name"), Value::from("nvim_set_current_line")),
+            (Value::from("since"), Value::from(1)),
+            (
+                Value::from("parameters"),
+                Value::Array(vec![Value::Array(vec![
+                    Value::from("String"),
+                    Value::from("line"),
+                ])]),
+            ),
+            (Value::from("return_type"), Value::from("void")),
+        ]);
+        let result = common::api_info(vec![function]);
+        common::respond(&mut theirs, msgid, Value::Nil, result);
+        theirs
+    });
+
+    match session.call_async("nvim_set_current_line", vec![Value::from(1)]) {
+        Err(Error::InvalidArgs {
+            method,
+            expected,
+            got,
+        }) => {
+            assert_eq!(method, "nvim_set_current_line");
+            assert_eq!(expected, "line to be String");
+            assert_eq!(got, "1");
+        }
+        _ => panic!("The call wasn't checked"),
+    }
+    assert_eq!(
+        session
+            .notify("nvim_set_current_line", vec![])
+            .unwrap_err()
+            .to_string(),
+        "nvim_set_current_line expects 1 arguments but received 0"
+    );
+    server.join().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn early_unsupported_methods() {
+    let (session, mut theirs) = common::socket_session();
+    session.start_event_loop(None, None);
+
+    let server = thread::spawn(move || {
+        let (msgid, method, _) = common::next_request(&mut theirs);
+        assert_eq!(method, "nvim_get_api_info");
+        thread::sleep(Duration::from_millis(50));
+        common::respond(&mut theirs, msgid, Value::Nil, common::api_info(vec![]));
+        theirs
+    });
+
+    // Rejected even though the metadata hadn't arrived when it was made
+    assert!(matches!(
+        session.call_async("nvim_does_not_exist", vec![]),
+        Err(Error::UnsupportedVersion(_))
+    ));
+    let mut theirs = server.join().unwrap();
+    // Methods outside the API are left to plugins
+    session.notify("rsnvim_unknown", vec![]).unwrap();
+    assert!(matches!(
+        rpc::decode(&mut theirs).unwrap(),
+        RpcMessage::RpcNotification { method, .. } if method == "rsnvim_unknown"
+    ));
+}
+
+#[test]
+fn handler_panics() {
+    let mut router = HandlerRouter::new();
+    router.on_request("explode", |(): (), _nvim| -> Result<(), Error> {
+        panic!("exploded")
+    });
+    let (requests, notifications) = router.into_handlers();
+    let (_instance, mut nvim) = nvim!(requests, notifications);
+    let (sender, errors) = mpsc::channel();
+    nvim.on_error(move |error| {
+        let _ = sender.send(error);
+    });
+    let channel = nvim.channel_id().unwrap();
+    let result = nvim
+        .exec_lua(
+            "local ok, err = pcall(vim.rpcrequest, ..., 'explode') return {ok, err}",
+            vec![Value::from(channel)],
+        )
+        .unwrap();
+    assert_eq!(result[0], Value::from(false));
+    assert!(result[1].as_str().unwrap().contains("explode panicked"));
+    assert!(matches!(
+        errors.recv_timeout(Duration::from_secs(1)),
+        Ok(Error::HandlerPanicked(_))
+    ));
+    // The dispatch thread survives to handle later requests
+    assert_eq!(nvim.eval("1 + 1").unwrap(), Value::from(2));
+    let stats = nvim.stats();
+    assert_eq!(stats.handler_panics, 1);
+    assert_eq!(stats.pending_calls, 0);
+    assert_eq!(stats.pending_requests, 0);
+}
+
+/// The message of panics injected by `soak`, which aren't printed
+const INJECTED_PANIC: &str = "injected panic";
+
+/// A xorshift generator, so a failing soak run can be repeated from its seed
+struct Chaos {
+    state: u64,
+}
+
+impl Chaos {
+    fn new(seed: u64) -> Self {
+        Chaos { state: seed.max(1) }
+    }
+
+    /// A number from `0` up to but excluding `n`
+    fn below(&mut self, n: u64) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state % n
+    }
+}
+
+/// Run a session for `RSNVIM_SOAK_SECS` seconds while injecting disconnects,
+/// slow responses, handler panics and giant payloads, checking after each
+/// that nothing is leaked and the counts add up
+///
+/// This takes as long as it is told to, so is ignored unless run with
+/// `cargo xtask soak`. Set `RSNVIM_SOAK_SEED` to repeat a run.
+#[test]
+#[ignore]
+fn soak() {
+    let env = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+    let secs = env("RSNVIM_SOAK_SECS").unwrap_or(60);
+    let seed = env("RSNVIM_SOAK_SEED").unwrap_or_else(|| {
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH);
+        now.unwrap().as_nanos() as u64
+    });
+    eprintln!("soak: running for {}s with RSNVIM_SOAK_SEED={}", secs, seed);
+
+    let mut router = HandlerRouter::new();
+    router
+        .on_request("soak.slow", |(ms,): (u64,), _nvim| {
+            thread::sleep(Duration::from_millis(ms));
+            Ok(ms)
+        })
+        .on_request("soak.panic", |(): (), _nvim| -> Result<(), Error> {
+            std::panic::panic_any(INJECTED_PANIC)
+        });
+    let (requests, notifications) = router.into_handlers();
+    let (_instance, mut nvim) = nvim!(requests, notifications);
+
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if info.payload().downcast_ref::<&str>() != Some(&INJECTED_PANIC) {
+            hook(info);
+        }
+    }));
+
+    // Exit rather than fail, as a deadlocked test would never finish
+    let (heartbeat, beats) = mpsc::channel::<()>();
+    thread::spawn(move || loop {
+        match beats.recv_timeout(Duration::from_secs(30)) {
+            Ok(()) => continue,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                eprintln!("soak: no progress for 30s, RSNVIM_SOAK_SEED={}", seed);
+                std::process::exit(1);
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => return,
+        }
+    });
+
+    let buffer = nvim.get_current_buf().unwrap();
+    let mut chaos = Chaos::new(seed);
+    let mut rounds = [0u64; 5];
+    let mut panics = 0;
+    let start = std::time::Instant::now();
+    let mut reported = start;
+    while start.elapsed() < Duration::from_secs(secs) {
+        let fault = chaos.below(5) as usize;
+        rounds[fault] += 1;
+        match fault {
+            // Regular traffic, checking each response matches its request
+            0 => {
+                let handles: Vec<_> = (0..chaos.below(32) as i64)
+                    .map(|i| {
+                        nvim.call_async("nvim_eval", vec![Value::from(format!("{} * 3", i))])
+                            .unwrap()
+                    })
+                    .collect();
+                for (i, handle) in handles.into_iter().enumerate() {
+                    assert_eq!(handle.wait().unwrap(), Value::from(i * 3));
+                }
+            }
+            // Neovim closes the connection with a call in flight
+            1 => {
+                let channel = nvim.channel_id().unwrap();
+                nvim.send_notification(
+                    "nvim_command",
+                    vec![Value::from(format!("call chanclose({})", channel))],
+                )
+                .unwrap();
+                // Either answered or failed by the disconnect, never left waiting
+                if let Ok(handle) = nvim.call_async("nvim_eval", vec![Value::from("1")]) {
+                    let result = handle.wait_timeout(Duration::from_secs(5));
+                    assert!(!matches!(result, Err(Error::TimeoutError(_))));
+                }
+                for _ in 0..100 {
+                    if !nvim.is_alive() {
+                        break;
+                    }
+                    thread::sleep(Duration::from_millis(10));
+                }
+                assert!(!nvim.is_alive());
+                nvim.reconnect().unwrap();
+            }
+            // A slow handler, or a call timing out behind a slow command
+            2 => {
+                if chaos.below(2) == 0 {
+                    let channel = nvim.channel_id().unwrap();
+                    let ms = chaos.below(500);
+                    let slept = nvim
+                        .exec_lua(
+                            "local channel, ms = ... return vim.rpcrequest(channel, 'soak.slow', ms)",
+                            vec![Value::from(channel), Value::from(ms)],
+                        )
+                        .unwrap();
+                    assert_eq!(slept, Value::from(ms));
+                } else {
+                    let slow = nvim
+                        .call_async("nvim_command", vec![Value::from("sleep 1100m")])
+                        .unwrap();
+                    assert!(matches!(nvim.eval("1"), Err(Error::TimeoutError(_))));
+                    slow.wait().unwrap();
+                }
+            }
+            3 => {
+                let channel = nvim.channel_id().unwrap();
+                let ok = nvim
+                    .exec_lua(
+                        "return pcall(vim.rpcrequest, ..., 'soak.panic')",
+                        vec![Value::from(channel)],
+                    )
+                    .unwrap();
+                assert_eq!(ok, Value::from(false));
+                panics += 1;
+            }
+            // Either one huge line or many lines
+            4 => {
+                let lines: Vec<String> = if chaos.below(2) == 0 {
+                    vec!["x".repeat(1 + chaos.below(4 << 20) as usize)]
+                } else {
+                    let len = chaos.below(80) as usize;
+                    (0..1 + chaos.below(100_000))
+                        .map(|_| "y".repeat(len))
+                        .collect()
+                };
+                buffer.set_lines(0, -1, false, lines.clone()).unwrap();
+                assert_eq!(buffer.line_count().unwrap(), lines.len() as i64);
+                assert_eq!(buffer.get_lines(0, -1, false).unwrap(), lines);
+            }
+            _ => unreachable!(),
+        }
+
+        // Responses arrive in order, so once this returns every earlier
+        // response has been received
+        assert_eq!(nvim.eval("1").unwrap(), Value::from(1));
+        let stats = nvim.stats();
+        assert_eq!(stats.pending_calls, 0, "leaked pending calls");
+        assert_eq!(stats.pending_requests, 0, "leaked pending requests");
+        assert_eq!(stats.handler_panics, panics);
+        assert_eq!(stats.async_errors, 0);
+        heartbeat.send(()).unwrap();
+
+        if reported.elapsed() >= Duration::from_secs(60) {
+            eprintln!(
+                "soak: {}s, rounds (traffic, disconnect, slow, panic, payload) {:?}",
+                start.elapsed().as_secs(),
+                rounds
+            );
+            reported = std::time::Instant::now();
+        }
+    }
+    eprintln!("soak: passed, rounds {:?}", rounds);
+}
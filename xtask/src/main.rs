@@ -0,0 +1,383 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{exit, Command};
+
+/// The Neovim releases the bindings are tested against
+const VERSIONS: [&str; 3] = ["0.9.5", "0.10.4", "0.11.0"];
+
+const USAGE: &str = "\
+Usage: cargo xtask conformance [--offline] [VERSION...]
+       cargo xtask soak [--offline] [--secs SECS] [--seed SEED] [VERSION]
+       cargo xtask genapi [--offline] [--api-info FILE] [--docs FILE] [VERSION]
+
+`conformance` runs the rsnvim integration tests against each Neovim VERSION
+(by default 0.9.5, 0.10.4 and 0.11.0).
+
+`soak` runs a session against Neovim VERSION (by default 0.11.0) for SECS
+seconds (by default 3600) while injecting disconnects, slow responses,
+handler panics and giant payloads. A failing run prints its SEED, which
+repeats the run.
+
+`genapi` regenerates the API bindings checked in to rsnvim/src/api. The API
+is read from FILE, saved with `nvim --api-info > FILE`, or the `nvim` on
+PATH, and documented from Neovim's api.txt if found or given with --docs.
+Given a VERSION, e.g. 0.10.4, the API and api.txt are read from that
+release instead, unless given with --api-info or --docs.
+
+A binary is found by checking, in order:
+  - the RSNVIM_NVIM_<MAJOR>_<MINOR> environment variable, e.g. RSNVIM_NVIM_0_10
+  - an `nvim` on PATH with a matching major and minor version
+  - a release previously downloaded to target/nvim/<VERSION>
+  - downloading the release from GitHub using curl and tar, unless --offline";
+
+/// The files genapi writes to `build`, which are checked in to rsnvim/src/api
+const GENERATED_FILES: [&str; 4] = ["buffer.rs", "nvim.rs", "tabpage.rs", "window.rs"];
+
+/// The outcome of testing a single Neovim version
+enum Outcome {
+    Passed,
+    Failed,
+    Missing(String),
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(|arg| arg.as_str()) {
+        Some("conformance") => conformance(&args[1..]),
+        Some("soak") => soak(&args[1..]),
+        Some("genapi") => genapi(&args[1..]),
+        _ => {
+            eprintln!("{}", USAGE);
+            exit(2);
+        }
+    }
+}
+
+/// Run the integration tests against each requested Neovim version
+fn conformance(args: &[String]) {
+    let offline = args.iter().any(|arg| arg == "--offline");
+    let mut versions: Vec<&str> = args
+        .iter()
+        .filter(|arg| !arg.starts_with("--"))
+        .map(|arg| arg.trim_start_matches('v'))
+        .collect();
+    if versions.is_empty() {
+        versions = VERSIONS.to_vec();
+    }
+
+    let root = workspace_root();
+    let mut results = Vec::new();
+    for version in versions {
+        println!("==> Neovim {}", version);
+        let outcome = match find_nvim(&root, version, offline) {
+            Ok(nvim) => run_tests(&root, &nvim),
+            Err(reason) => Outcome::Missing(reason),
+        };
+        results.push((version, outcome));
+    }
+
+    println!();
+    println!("Conformance results:");
+    let mut failed = false;
+    for (version, outcome) in results {
+        let status = match outcome {
+            Outcome::Passed => "passed".to_string(),
+            Outcome::Failed => {
+                failed = true;
+                "FAILED".to_string()
+            }
+            Outcome::Missing(reason) => {
+                failed = true;
+                format!("MISSING ({})", reason)
+            }
+        };
+        println!("  {:<8} {}", version, status);
+    }
+
+    if failed {
+        exit(1);
+    }
+}
+
+/// Run the soak test against one Neovim version
+fn soak(args: &[String]) {
+    let mut offline = false;
+    let mut secs = "3600".to_string();
+    let mut seed = None;
+    let mut version = VERSIONS[VERSIONS.len() - 1].to_string();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| match args.next() {
+            Some(value) => value.clone(),
+            None => {
+                eprintln!("{} needs a value\n\n{}", name, USAGE);
+                exit(2);
+            }
+        };
+        match arg.as_str() {
+            "--offline" => offline = true,
+            "--secs" => secs = value("--secs"),
+            "--seed" => seed = Some(value("--seed")),
+            other => version = other.trim_start_matches('v').to_string(),
+        }
+    }
+
+    let root = workspace_root();
+    let nvim = match find_nvim(&root, &version, offline) {
+        Ok(nvim) => nvim,
+        Err(reason) => {
+            eprintln!("Neovim {} is missing: {}", version, reason);
+            exit(1);
+        }
+    };
+    println!("==> Soaking Neovim {} for {}s", version, secs);
+
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let mut command = Command::new(cargo);
+    command
+        .current_dir(root)
+        .args([
+            "test",
+            "--release",
+            "--package",
+            "rsnvim",
+            "--test",
+            "conformance",
+        ])
+        .args(["--", "--ignored", "--exact", "--nocapture", "soak"])
+        .env("RSNVIM_TEST_NVIM", nvim)
+        .env("RSNVIM_SOAK_SECS", secs);
+    if let Some(seed) = seed {
+        command.env("RSNVIM_SOAK_SEED", seed);
+    }
+    match command.status() {
+        Ok(status) if status.success() => (),
+        _ => exit(1),
+    }
+}
+
+/// Regenerate the API bindings with genapi and check them in
+fn genapi(args: &[String]) {
+    // genapi runs from the workspace root, so paths are made absolute first
+    let cwd = env::current_dir().unwrap();
+    let mut offline = false;
+    let mut version = None;
+    let mut genapi_args = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--offline" {
+            offline = true;
+            continue;
+        }
+        if !arg.starts_with("--") {
+            version = Some(arg.trim_start_matches('v').to_string());
+            continue;
+        }
+        genapi_args.push(arg.clone());
+        if arg == "--api-info" || arg == "--docs" {
+            match args.next() {
+                Some(path) => genapi_args.push(cwd.join(path).display().to_string()),
+                None => {
+                    eprintln!("{} needs a value\n\n{}", arg, USAGE);
+                    exit(2);
+                }
+            }
+        }
+    }
+
+    let root = workspace_root();
+    if let Some(version) = version {
+        if let Err(reason) = release_inputs(&root, &version, offline, &mut genapi_args) {
+            eprintln!("Neovim {} is missing: {}", version, reason);
+            exit(1);
+        }
+    }
+
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let status = Command::new(cargo)
+        .current_dir(&root)
+        .args(["run", "--package", "genapi", "--"])
+        .args(&genapi_args)
+        .status();
+    if !matches!(status, Ok(status) if status.success()) {
+        exit(1);
+    }
+
+    let build = root.join("build");
+    let api = root.join("rsnvim").join("src").join("api");
+    for name in GENERATED_FILES {
+        if let Err(err) = fs::copy(build.join(name), api.join(name)) {
+            eprintln!("Couldn't copy {}: {}", name, err);
+            exit(1);
+        }
+        println!("Generated rsnvim/src/api/{}", name);
+    }
+    let _ = fs::remove_dir_all(&build);
+}
+
+/// Add the API metadata and `api.txt` of the Neovim release `version` to
+/// `genapi_args`, unless they were given already
+fn release_inputs(
+    root: &Path,
+    version: &str,
+    offline: bool,
+    genapi_args: &mut Vec<String>,
+) -> Result<(), String> {
+    let nvim = find_nvim(root, version, offline)?;
+    if !genapi_args.iter().any(|arg| arg == "--api-info") {
+        let output = Command::new(&nvim)
+            .arg("--api-info")
+            .output()
+            .map_err(|err| format!("couldn't run {}: {}", nvim.display(), err))?;
+        let dir = root.join("target").join("nvim");
+        fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+        let path = dir.join(format!("api-info-{}.mpack", version));
+        fs::write(&path, output.stdout).map_err(|err| err.to_string())?;
+        genapi_args.push("--api-info".to_string());
+        genapi_args.push(path.display().to_string());
+    }
+    if !genapi_args.iter().any(|arg| arg == "--docs") {
+        let output = Command::new(&nvim)
+            .args(["--clean", "--headless", "--cmd"])
+            .arg("lua io.stdout:write(vim.env.VIMRUNTIME)")
+            .args(["--cmd", "quit"])
+            .output()
+            .map_err(|err| format!("couldn't run {}: {}", nvim.display(), err))?;
+        let runtime = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let path = Path::new(&runtime).join("doc").join("api.txt");
+        if !path.is_file() {
+            return Err(format!("{} doesn't exist", path.display()));
+        }
+        genapi_args.push("--docs".to_string());
+        genapi_args.push(path.display().to_string());
+    }
+    Ok(())
+}
+
+/// The directory containing the workspace Cargo.toml
+fn workspace_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .unwrap()
+        .to_path_buf()
+}
+
+/// Locate a Neovim binary for `version`, downloading it if necessary
+fn find_nvim(root: &Path, version: &str, offline: bool) -> Result<PathBuf, String> {
+    let series: Vec<&str> = version.split('.').take(2).collect();
+    let var = format!("RSNVIM_NVIM_{}", series.join("_"));
+    if let Ok(path) = env::var(&var) {
+        return Ok(PathBuf::from(path));
+    }
+
+    if let Some(installed) = installed_version() {
+        if installed.split('.').take(2).eq(series.iter().copied()) {
+            println!("Using nvim {} from PATH", installed);
+            return Ok(PathBuf::from("nvim"));
+        }
+    }
+
+    let dir = root.join("target").join("nvim").join(version);
+    if let Some(nvim) = find_binary(&dir) {
+        return Ok(nvim);
+    }
+
+    if offline {
+        return Err(format!("set {} or install Neovim {}", var, version));
+    }
+    download(version, &dir)?;
+    find_binary(&dir).ok_or_else(|| "release archive has no bin/nvim".to_string())
+}
+
+/// The version of the `nvim` on PATH, if any
+fn installed_version() -> Option<String> {
+    let output = Command::new("nvim").arg("--version").output().ok()?;
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    // The first line has the form "NVIM v0.10.4"
+    let version = stdout.lines().next()?.strip_prefix("NVIM v")?;
+    Some(version.split('-').next()?.to_string())
+}
+
+/// Search an extracted release for its `bin/nvim`
+fn find_binary(dir: &Path) -> Option<PathBuf> {
+    for entry in fs::read_dir(dir).ok()?.flatten() {
+        let nvim = entry.path().join("bin").join("nvim");
+        if nvim.is_file() {
+            return Some(nvim);
+        }
+    }
+    None
+}
+
+/// The release archives which may contain a binary for this platform
+///
+/// Release assets were renamed in 0.10.4, so both names are tried.
+fn asset_names() -> Result<&'static [&'static str], String> {
+    match (env::consts::OS, env::consts::ARCH) {
+        ("linux", "x86_64") => Ok(&["nvim-linux-x86_64.tar.gz", "nvim-linux64.tar.gz"]),
+        ("linux", "aarch64") => Ok(&["nvim-linux-arm64.tar.gz"]),
+        ("macos", "x86_64") => Ok(&["nvim-macos-x86_64.tar.gz", "nvim-macos.tar.gz"]),
+        ("macos", "aarch64") => Ok(&["nvim-macos-arm64.tar.gz", "nvim-macos.tar.gz"]),
+        (os, arch) => Err(format!("no release is available for {}-{}", os, arch)),
+    }
+}
+
+/// Download and extract the release of `version` into `dir`
+fn download(version: &str, dir: &Path) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+    let archive = dir.join("nvim.tar.gz");
+
+    for asset in asset_names()? {
+        let url = format!(
+            "https://github.com/neovim/neovim/releases/download/v{}/{}",
+            version, asset
+        );
+        println!("Downloading {}", url);
+        let downloaded = Command::new("curl")
+            .args([
+                "--fail",
+                "--location",
+                "--silent",
+                "--show-error",
+                "--output",
+            ])
+            .arg(&archive)
+            .arg(&url)
+            .status()
+            .map_err(|err| format!("couldn't run curl: {}", err))?;
+        if !downloaded.success() {
+            continue;
+        }
+
+        let extracted = Command::new("tar")
+            .arg("-xzf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(dir)
+            .status()
+            .map_err(|err| format!("couldn't run tar: {}", err))?;
+        let _ = fs::remove_file(&archive);
+        return match extracted.success() {
+            true => Ok(()),
+            false => Err(format!("couldn't extract {}", asset)),
+        };
+    }
+
+    Err(format!("couldn't download Neovim {}", version))
+}
+
+/// Run the rsnvim integration tests against the Neovim at `nvim`
+fn run_tests(root: &Path, nvim: &Path) -> Outcome {
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let status = Command::new(cargo)
+        .current_dir(root)
+        .args(["test", "--package", "rsnvim", "--test", "conformance"])
+        .env("RSNVIM_TEST_NVIM", nvim)
+        .status();
+
+    match status {
+        Ok(status) if status.success() => Outcome::Passed,
+        _ => Outcome::Failed,
+    }
+}